@@ -0,0 +1,307 @@
+//! # Human Approval for Tool Calls
+//!
+//! Sensitive tools can be gated behind a human decision. An [`ApprovalPolicy`] is set per
+//! tool with [`SwarmsAgentBuilder::require_approval`](super::SwarmsAgentBuilder::require_approval),
+//! and the agent asks its [`ApprovalHandler`] before calling a tool whose policy requires it.
+//!
+//! The handler can approve the call, deny it with a reason (which is returned to the model
+//! as the tool result), or edit the arguments before the tool runs. If approval is required
+//! but no handler is configured, or the handler fails, the call is denied.
+//!
+//! Requests for tools of the agent's
+//! [`ToolRegistry`](crate::structs::tool_registry::ToolRegistry) carry their
+//! [`ToolMetadata`], e.g. to decide based on the side effects of the tool.
+//!
+//! Three handlers are provided:
+//!
+//! - [`StdinApprovalHandler`]: asks on the terminal
+//! - [`ChannelApprovalHandler`]: sends requests over a channel to the application
+//! - [`HttpApprovalHandler`]: posts requests to an HTTP callback
+
+use std::{fmt::Debug, str::FromStr, sync::Arc, time::Duration};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::{Mutex, mpsc, oneshot},
+};
+
+use crate::structs::tool_registry::ToolMetadata;
+
+#[derive(Debug, Error)]
+pub enum ApprovalError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Http error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Approval channel closed")]
+    ChannelClosed,
+    #[error("Invalid approval response: {0}")]
+    InvalidResponse(String),
+}
+
+/// When a tool call needs approval.
+#[derive(Clone, Default)]
+pub enum ApprovalPolicy {
+    /// Every call needs approval.
+    Always,
+    /// Calls never need approval.
+    #[default]
+    Never,
+    /// Calls need approval when the predicate returns `true` for the call arguments.
+    When(Arc<dyn Fn(&serde_json::Value) -> bool + Send + Sync>),
+}
+
+impl ApprovalPolicy {
+    /// Require approval when `predicate` returns `true` for the call arguments.
+    pub fn when(predicate: impl Fn(&serde_json::Value) -> bool + Send + Sync + 'static) -> Self {
+        Self::When(Arc::new(predicate))
+    }
+
+    pub fn requires_approval(&self, args: &serde_json::Value) -> bool {
+        match self {
+            ApprovalPolicy::Always => true,
+            ApprovalPolicy::Never => false,
+            ApprovalPolicy::When(predicate) => predicate(args),
+        }
+    }
+}
+
+impl Debug for ApprovalPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalPolicy::Always => write!(f, "Always"),
+            ApprovalPolicy::Never => write!(f, "Never"),
+            ApprovalPolicy::When(_) => write!(f, "When(<predicate>)"),
+        }
+    }
+}
+
+/// A tool call waiting for approval.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub agent_name: String,
+    /// The task being run, or `None` for direct calls to `chat`.
+    pub task: Option<String>,
+    pub tool_name: String,
+    pub args: serde_json::Value,
+    /// Metadata of the tool in the tool registry of the agent, or `None` for the tools added
+    /// to the agent itself.
+    #[serde(default)]
+    pub metadata: Option<ToolMetadata>,
+}
+
+/// The decision of an [`ApprovalHandler`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// Run the tool with the requested arguments.
+    Approve,
+    /// Don't run the tool. The reason is returned to the model as the tool result.
+    Deny { reason: String },
+    /// Run the tool with these arguments instead.
+    Edit { args: serde_json::Value },
+}
+
+/// Parses a terminal answer: `y`/`yes`/`approve`, `n`/`no`/`deny` optionally followed by a
+/// reason, or `edit` followed by the new arguments as JSON.
+impl FromStr for ApprovalDecision {
+    type Err = ApprovalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (command, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let rest = rest.trim();
+        match command.to_lowercase().as_str() {
+            "y" | "yes" | "approve" => Ok(ApprovalDecision::Approve),
+            "n" | "no" | "deny" => Ok(ApprovalDecision::Deny {
+                reason: if rest.is_empty() {
+                    "Denied by user".to_owned()
+                } else {
+                    rest.to_owned()
+                },
+            }),
+            "e" | "edit" => serde_json::from_str(rest)
+                .map(|args| ApprovalDecision::Edit { args })
+                .map_err(|e| ApprovalError::InvalidResponse(e.to_string())),
+            _ => Err(ApprovalError::InvalidResponse(s.to_owned())),
+        }
+    }
+}
+
+/// Decides whether a tool call may run.
+pub trait ApprovalHandler: Send + Sync {
+    fn request_approval(
+        &self,
+        request: ApprovalRequest,
+    ) -> BoxFuture<'_, Result<ApprovalDecision, ApprovalError>>;
+}
+
+/// Asks for approval on the terminal.
+///
+/// Concurrent requests are asked one at a time. Unrecognized answers are asked again.
+#[derive(Default)]
+pub struct StdinApprovalHandler {
+    lock: Mutex<()>,
+}
+
+impl StdinApprovalHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ApprovalHandler for StdinApprovalHandler {
+    fn request_approval(
+        &self,
+        request: ApprovalRequest,
+    ) -> BoxFuture<'_, Result<ApprovalDecision, ApprovalError>> {
+        Box::pin(async move {
+            let _guard = self.lock.lock().await;
+            let mut stdout = tokio::io::stdout();
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+            let prompt = format!(
+                "\nAgent '{}' wants to call tool '{}' with arguments:\n{}\n",
+                request.agent_name,
+                request.tool_name,
+                serde_json::to_string_pretty(&request.args).unwrap_or_default()
+            );
+            stdout.write_all(prompt.as_bytes()).await?;
+
+            loop {
+                stdout
+                    .write_all(b"Approve? [y]es / [n]o <reason> / [e]dit <json>: ")
+                    .await?;
+                stdout.flush().await?;
+
+                let Some(line) = lines.next_line().await? else {
+                    return Ok(ApprovalDecision::Deny {
+                        reason: "No answer from user".to_owned(),
+                    });
+                };
+                match line.parse() {
+                    Ok(decision) => return Ok(decision),
+                    Err(e) => stdout.write_all(format!("{e}\n").as_bytes()).await?,
+                }
+            }
+        })
+    }
+}
+
+/// An approval request received from a [`ChannelApprovalHandler`].
+#[derive(Debug)]
+pub struct PendingApproval {
+    pub request: ApprovalRequest,
+    responder: oneshot::Sender<ApprovalDecision>,
+}
+
+impl PendingApproval {
+    pub fn respond(self, decision: ApprovalDecision) {
+        // the agent may have stopped waiting, nothing to do then
+        let _ = self.responder.send(decision);
+    }
+
+    pub fn approve(self) {
+        self.respond(ApprovalDecision::Approve);
+    }
+
+    pub fn deny(self, reason: impl Into<String>) {
+        self.respond(ApprovalDecision::Deny {
+            reason: reason.into(),
+        });
+    }
+
+    pub fn edit(self, args: serde_json::Value) {
+        self.respond(ApprovalDecision::Edit { args });
+    }
+}
+
+/// Sends approval requests to the application over a channel.
+///
+/// Dropping a [`PendingApproval`] without responding denies the call.
+#[derive(Clone)]
+pub struct ChannelApprovalHandler {
+    sender: mpsc::Sender<PendingApproval>,
+}
+
+impl ChannelApprovalHandler {
+    /// Create a handler and the receiver the application answers requests from.
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<PendingApproval>) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        (Self { sender }, receiver)
+    }
+}
+
+impl ApprovalHandler for ChannelApprovalHandler {
+    fn request_approval(
+        &self,
+        request: ApprovalRequest,
+    ) -> BoxFuture<'_, Result<ApprovalDecision, ApprovalError>> {
+        Box::pin(async move {
+            let (responder, response) = oneshot::channel();
+            self.sender
+                .send(PendingApproval { request, responder })
+                .await
+                .map_err(|_| ApprovalError::ChannelClosed)?;
+            response.await.map_err(|_| ApprovalError::ChannelClosed)
+        })
+    }
+}
+
+/// Posts approval requests as JSON to an HTTP callback.
+///
+/// The callback must answer with an [`ApprovalDecision`] as JSON, e.g.
+/// `{"decision": "approve"}`, `{"decision": "deny", "reason": "..."}` or
+/// `{"decision": "edit", "args": {...}}`.
+#[derive(Clone)]
+pub struct HttpApprovalHandler {
+    client: reqwest::Client,
+    url: String,
+    bearer_token: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl HttpApprovalHandler {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            bearer_token: None,
+            timeout: None,
+        }
+    }
+
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// How long to wait for a decision. Waits indefinitely by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApprovalHandler for HttpApprovalHandler {
+    fn request_approval(
+        &self,
+        request: ApprovalRequest,
+    ) -> BoxFuture<'_, Result<ApprovalDecision, ApprovalError>> {
+        Box::pin(async move {
+            let mut builder = self.client.post(&self.url).json(&request);
+            if let Some(token) = &self.bearer_token {
+                builder = builder.bearer_auth(token);
+            }
+            if let Some(timeout) = self.timeout {
+                builder = builder.timeout(timeout);
+            }
+
+            let response = builder.send().await?.error_for_status()?;
+            Ok(response.json::<ApprovalDecision>().await?)
+        })
+    }
+}
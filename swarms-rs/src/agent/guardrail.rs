@@ -0,0 +1,688 @@
+//! # Guardrails
+//!
+//! Guardrails check the task given to an agent and the final answer of its runs. They are
+//! added to an agent with
+//! [`SwarmsAgentBuilder::add_input_guardrail`](super::SwarmsAgentBuilder::add_input_guardrail)
+//! and [`SwarmsAgentBuilder::add_output_guardrail`](super::SwarmsAgentBuilder::add_output_guardrail),
+//! and run in order. A guardrail passes the text, redacts it, or trips:
+//!
+//! - a tripped input guardrail fails the run with [`AgentError::GuardrailTripped`] before
+//!   anything is sent to the model,
+//! - a tripped output guardrail either fails the run the same way
+//!   ([`GuardrailAction::Block`]), or asks the model for a revised answer
+//!   ([`GuardrailAction::Repair`]) and checks it again.
+//!
+//! A guardrail that fails to check the text trips. Every check is listed in
+//! [`RunResult::guardrails`](super::run_result::RunResult::guardrails).
+//!
+//! The provided guardrails are:
+//!
+//! - [`LengthLimit`]: trips on text longer than a number of characters
+//! - [`DenyList`]: trips on (or redacts) matches of regular expressions
+//! - [`JsonSchemaCheck`]: trips on output that isn't JSON conforming to a schema
+//! - [`PiiFilter`]: redacts (or trips on) emails, phone numbers, card numbers, SSNs and IP
+//!   addresses
+//! - [`LlmClassifier`]: asks an LLM whether the text complies with a policy
+//!
+//! ```rust
+//! use swarms_rs::agent::guardrail::{
+//!     DenyList, Guardrail, GuardrailVerdict, LengthLimit, PiiFilter,
+//! };
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let verdict = PiiFilter::new().check("Mail me at ada@example.com").await?;
+//! assert_eq!(verdict, GuardrailVerdict::Redact("Mail me at [EMAIL]".to_owned()));
+//!
+//! let verdict = DenyList::new([r"(?i)\bpassword\b"])?.check("My password is hunter2").await?;
+//! assert!(matches!(verdict, GuardrailVerdict::Trip(_)));
+//!
+//! assert_eq!(LengthLimit::new(100).check("Short").await?, GuardrailVerdict::Pass);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::Display,
+    sync::{Arc, LazyLock},
+};
+
+use futures::future::BoxFuture;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    llm::{
+        self, CompletionError,
+        completion::{AssistantContent, Message},
+        request::CompletionRequest,
+    },
+    structs::agent::AgentError,
+};
+
+/// Name of the role under which guardrails ask the agent to repair its answer in short
+/// memory.
+pub const GUARDRAIL_ROLE: &str = "Guardrail";
+
+#[derive(Debug, Error)]
+pub enum GuardrailError {
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    #[error("Invalid JSON schema: {0}")]
+    InvalidSchema(String),
+    #[error("Completion error: {0}")]
+    CompletionError(#[from] CompletionError),
+    #[error("Unreadable classification: {0}")]
+    InvalidClassification(String),
+}
+
+/// The result of a guardrail check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuardrailVerdict {
+    /// The text is fine.
+    Pass,
+    /// The text is fine once replaced with this one.
+    Redact(String),
+    /// The text is rejected, for this reason.
+    Trip(String),
+}
+
+/// A check of the task or final answer of an agent.
+pub trait Guardrail: Send + Sync {
+    /// Name of the guardrail, used in errors and run results.
+    fn name(&self) -> String;
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailStage {
+    /// The task, before the run.
+    Input,
+    /// The final answer, after the run.
+    Output,
+}
+
+impl Display for GuardrailStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuardrailStage::Input => f.write_str("input"),
+            GuardrailStage::Output => f.write_str("output"),
+        }
+    }
+}
+
+/// What a tripped output guardrail does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GuardrailAction {
+    /// Fail the run with [`AgentError::GuardrailTripped`].
+    #[default]
+    Block,
+    /// Tell the model why its answer was rejected and ask for a revised one, up to
+    /// `max_attempts` times, then block.
+    Repair { max_attempts: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailStatus {
+    Passed,
+    Redacted,
+    Tripped,
+}
+
+/// A guardrail check made during a run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GuardrailOutcome {
+    pub guardrail: String,
+    pub stage: GuardrailStage,
+    pub status: GuardrailStatus,
+    /// Why the guardrail tripped.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// The guardrails of an agent.
+#[derive(Clone, Default)]
+pub(crate) struct Guardrails {
+    input: Vec<Arc<dyn Guardrail>>,
+    output: Vec<(Arc<dyn Guardrail>, GuardrailAction)>,
+}
+
+/// The result of the output guardrails.
+pub(crate) enum OutputCheck {
+    /// The output, redacted if a guardrail asked for it
+    Passed(String),
+    Repair {
+        guardrail: String,
+        reason: String,
+    },
+    Blocked(AgentError),
+}
+
+impl Guardrails {
+    pub(crate) fn add_input(&mut self, guardrail: Arc<dyn Guardrail>) {
+        self.input.push(guardrail);
+    }
+
+    pub(crate) fn add_output(&mut self, guardrail: Arc<dyn Guardrail>, action: GuardrailAction) {
+        self.output.push((guardrail, action));
+    }
+
+    pub(crate) fn has_output(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    /// Checks the task, returning it redacted, or the error of the first tripped guardrail.
+    pub(crate) async fn check_input(
+        &self,
+        mut text: String,
+        outcomes: &mut Vec<GuardrailOutcome>,
+    ) -> Result<String, AgentError> {
+        for guardrail in &self.input {
+            let (outcome, verdict) = check(guardrail.as_ref(), GuardrailStage::Input, &text).await;
+            outcomes.push(outcome);
+            match verdict {
+                GuardrailVerdict::Pass => {},
+                GuardrailVerdict::Redact(redacted) => text = redacted,
+                GuardrailVerdict::Trip(reason) => {
+                    return Err(AgentError::GuardrailTripped {
+                        guardrail: guardrail.name(),
+                        stage: GuardrailStage::Input,
+                        reason,
+                    });
+                },
+            }
+        }
+        Ok(text)
+    }
+
+    /// Checks a final answer after `repairs` repair turns.
+    pub(crate) async fn check_output(
+        &self,
+        mut text: String,
+        repairs: u32,
+        outcomes: &mut Vec<GuardrailOutcome>,
+    ) -> OutputCheck {
+        for (guardrail, action) in &self.output {
+            let (outcome, verdict) = check(guardrail.as_ref(), GuardrailStage::Output, &text).await;
+            outcomes.push(outcome);
+            match verdict {
+                GuardrailVerdict::Pass => {},
+                GuardrailVerdict::Redact(redacted) => text = redacted,
+                GuardrailVerdict::Trip(reason) => {
+                    return match action {
+                        GuardrailAction::Repair { max_attempts } if repairs < *max_attempts => {
+                            OutputCheck::Repair {
+                                guardrail: guardrail.name(),
+                                reason,
+                            }
+                        },
+                        _ => OutputCheck::Blocked(AgentError::GuardrailTripped {
+                            guardrail: guardrail.name(),
+                            stage: GuardrailStage::Output,
+                            reason,
+                        }),
+                    };
+                },
+            }
+        }
+        OutputCheck::Passed(text)
+    }
+}
+
+/// Runs a guardrail, tripping it if the check fails.
+async fn check(
+    guardrail: &dyn Guardrail,
+    stage: GuardrailStage,
+    text: &str,
+) -> (GuardrailOutcome, GuardrailVerdict) {
+    let verdict = guardrail
+        .check(text)
+        .await
+        .unwrap_or_else(|e| GuardrailVerdict::Trip(format!("check failed: {e}")));
+    let (status, reason) = match &verdict {
+        GuardrailVerdict::Pass => (GuardrailStatus::Passed, None),
+        GuardrailVerdict::Redact(_) => (GuardrailStatus::Redacted, None),
+        GuardrailVerdict::Trip(reason) => (GuardrailStatus::Tripped, Some(reason.clone())),
+    };
+    let outcome = GuardrailOutcome {
+        guardrail: guardrail.name(),
+        stage,
+        status,
+        reason,
+    };
+    (outcome, verdict)
+}
+
+/// The prompt asking for a revised answer after `guardrail` tripped.
+pub(crate) fn repair_prompt(guardrail: &str, reason: &str) -> String {
+    format!(
+        "Your answer was rejected by the `{guardrail}` guardrail: {reason}. \
+        Give a revised final answer that complies."
+    )
+}
+
+/// Trips on text longer than a number of characters.
+pub struct LengthLimit {
+    max_chars: usize,
+}
+
+impl LengthLimit {
+    pub fn new(max_chars: usize) -> Self {
+        Self { max_chars }
+    }
+}
+
+impl Guardrail for LengthLimit {
+    fn name(&self) -> String {
+        "length_limit".to_owned()
+    }
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>> {
+        let chars = text.chars().count();
+        let verdict = if chars > self.max_chars {
+            GuardrailVerdict::Trip(format!(
+                "{chars} characters exceed the limit of {}",
+                self.max_chars
+            ))
+        } else {
+            GuardrailVerdict::Pass
+        };
+        Box::pin(async move { Ok(verdict) })
+    }
+}
+
+/// Trips on matches of regular expressions, or redacts them.
+pub struct DenyList {
+    patterns: Vec<Regex>,
+    replacement: Option<String>,
+}
+
+impl DenyList {
+    pub fn new<S: AsRef<str>>(
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Result<Self, GuardrailError> {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| Regex::new(pattern.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            patterns,
+            replacement: None,
+        })
+    }
+
+    /// Replaces the matches with `replacement` instead of tripping.
+    pub fn redact_with(mut self, replacement: impl Into<String>) -> Self {
+        self.replacement = Some(replacement.into());
+        self
+    }
+}
+
+impl Guardrail for DenyList {
+    fn name(&self) -> String {
+        "deny_list".to_owned()
+    }
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>> {
+        let verdict = match &self.replacement {
+            None => self
+                .patterns
+                .iter()
+                .find(|pattern| pattern.is_match(text))
+                .map_or(GuardrailVerdict::Pass, |pattern| {
+                    GuardrailVerdict::Trip(format!("matches denied pattern `{pattern}`"))
+                }),
+            Some(replacement) => {
+                let mut redacted = text.to_owned();
+                for pattern in &self.patterns {
+                    redacted = pattern
+                        .replace_all(&redacted, replacement.as_str())
+                        .into_owned();
+                }
+                if redacted == text {
+                    GuardrailVerdict::Pass
+                } else {
+                    GuardrailVerdict::Redact(redacted)
+                }
+            },
+        };
+        Box::pin(async move { Ok(verdict) })
+    }
+}
+
+/// Trips on output that isn't JSON conforming to a schema. The JSON may be surrounded by
+/// text, e.g. in a fenced code block.
+pub struct JsonSchemaCheck {
+    validator: jsonschema::Validator,
+}
+
+impl JsonSchemaCheck {
+    pub fn new(schema: &serde_json::Value) -> Result<Self, GuardrailError> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| GuardrailError::InvalidSchema(e.to_string()))?;
+        Ok(Self { validator })
+    }
+
+    /// Creates a check from the schema of a type deriving [`schemars::JsonSchema`].
+    pub fn for_type<T: schemars::JsonSchema>() -> Result<Self, GuardrailError> {
+        let schema = serde_json::to_value(schemars::schema_for!(T))
+            .map_err(|e| GuardrailError::InvalidSchema(e.to_string()))?;
+        Self::new(&schema)
+    }
+}
+
+impl Guardrail for JsonSchemaCheck {
+    fn name(&self) -> String {
+        "json_schema".to_owned()
+    }
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>> {
+        let verdict = match extract_json(text) {
+            Ok(value) => {
+                let errors = self
+                    .validator
+                    .iter_errors(&value)
+                    .map(|error| error.to_string())
+                    .collect::<Vec<_>>();
+                if errors.is_empty() {
+                    GuardrailVerdict::Pass
+                } else {
+                    GuardrailVerdict::Trip(format!(
+                        "the JSON does not match the schema: {}",
+                        errors.join("; ")
+                    ))
+                }
+            },
+            Err(e) => GuardrailVerdict::Trip(format!("the output is not JSON: {e}")),
+        };
+        Box::pin(async move { Ok(verdict) })
+    }
+}
+
+/// Parses the outermost JSON object or array of `text`.
+fn extract_json(text: &str) -> Result<serde_json::Value, serde_json::Error> {
+    let trimmed = text.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+    match (text.find(['{', '[']), text.rfind(['}', ']'])) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&text[start..=end]),
+        _ => serde_json::from_str(trimmed),
+    }
+}
+
+/// A kind of personally identifiable information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    /// Card numbers passing the Luhn check
+    CreditCard,
+    /// US social security numbers, e.g. `123-45-6789`
+    Ssn,
+    Phone,
+    IpAddress,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 5] = [
+        PiiKind::Email,
+        PiiKind::CreditCard,
+        PiiKind::Ssn,
+        PiiKind::Phone,
+        PiiKind::IpAddress,
+    ];
+
+    /// The text replacing the matches, e.g. `[EMAIL]`.
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::CreditCard => "[CREDIT_CARD]",
+            PiiKind::Ssn => "[SSN]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::IpAddress => "[IP_ADDRESS]",
+        }
+    }
+
+    fn pattern(&self) -> &'static Regex {
+        static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap()
+        });
+        static CREDIT_CARD: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap());
+        static SSN: LazyLock<Regex> =
+            LazyLock::new(|| Regex::new(r"\b\d{3}-\d{2}-\d{4}\b").unwrap());
+        static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\)|\b\d{3})[ .-]?\d{3}[ .-]?\d{4}\b")
+                .unwrap()
+        });
+        static IP_ADDRESS: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b")
+                .unwrap()
+        });
+        match self {
+            PiiKind::Email => &EMAIL,
+            PiiKind::CreditCard => &CREDIT_CARD,
+            PiiKind::Ssn => &SSN,
+            PiiKind::Phone => &PHONE,
+            PiiKind::IpAddress => &IP_ADDRESS,
+        }
+    }
+
+    fn is_match(&self, found: &str) -> bool {
+        match self {
+            PiiKind::CreditCard => luhn(found),
+            _ => true,
+        }
+    }
+}
+
+impl Display for PiiKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PiiKind::Email => "email address",
+            PiiKind::CreditCard => "credit card number",
+            PiiKind::Ssn => "social security number",
+            PiiKind::Phone => "phone number",
+            PiiKind::IpAddress => "IP address",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Whether the digits of `number` pass the Luhn checksum.
+fn luhn(number: &str) -> bool {
+    let digits = number
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect::<Vec<_>>();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match (i % 2 == 1, digit * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => digit,
+        })
+        .sum();
+    digits.len() >= 13 && sum.is_multiple_of(10)
+}
+
+/// Redacts personally identifiable information, or trips on it.
+pub struct PiiFilter {
+    kinds: Vec<PiiKind>,
+    block: bool,
+}
+
+impl PiiFilter {
+    /// Redacts all the [kinds](PiiKind::ALL) of PII.
+    pub fn new() -> Self {
+        Self {
+            kinds: PiiKind::ALL.to_vec(),
+            block: false,
+        }
+    }
+
+    /// Only looks for these kinds of PII.
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = PiiKind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    /// Trips on PII instead of redacting it.
+    pub fn block(mut self) -> Self {
+        self.block = true;
+        self
+    }
+}
+
+impl Default for PiiFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Guardrail for PiiFilter {
+    fn name(&self) -> String {
+        "pii_filter".to_owned()
+    }
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>> {
+        let mut redacted = text.to_owned();
+        let mut found = Vec::new();
+        // Card numbers and SSNs are redacted before they can be taken for phone numbers
+        for kind in PiiKind::ALL.iter().filter(|kind| self.kinds.contains(kind)) {
+            redacted = kind
+                .pattern()
+                .replace_all(&redacted, |captures: &Captures| {
+                    let matched = &captures[0];
+                    if kind.is_match(matched) {
+                        if !found.contains(kind) {
+                            found.push(*kind);
+                        }
+                        kind.placeholder().to_owned()
+                    } else {
+                        matched.to_owned()
+                    }
+                })
+                .into_owned();
+        }
+
+        let verdict = if found.is_empty() {
+            GuardrailVerdict::Pass
+        } else if self.block {
+            let kinds = found
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            GuardrailVerdict::Trip(format!("contains PII: {kinds}"))
+        } else {
+            GuardrailVerdict::Redact(redacted)
+        };
+        Box::pin(async move { Ok(verdict) })
+    }
+}
+
+const CLASSIFIER_FORMAT: &str = "Reply with JSON only: \
+    {\"allowed\": <true or false>, \"reason\": \"<why, in one sentence>\"}";
+
+#[derive(Deserialize)]
+struct Classification {
+    allowed: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Asks an LLM whether the text complies with a policy.
+pub struct LlmClassifier<M> {
+    model: M,
+    name: String,
+    policy: String,
+}
+
+impl<M> LlmClassifier<M> {
+    /// Creates a classifier of text against `policy`, e.g. "No medical or legal advice".
+    pub fn new(model: M, policy: impl Into<String>) -> Self {
+        Self {
+            model,
+            name: "llm_classifier".to_owned(),
+            policy: policy.into(),
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+}
+
+impl<M> Guardrail for LlmClassifier<M>
+where
+    M: llm::Model + Send + Sync,
+    M::RawCompletionResponse: Send,
+{
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn check<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<GuardrailVerdict, GuardrailError>> {
+        Box::pin(async move {
+            let request = CompletionRequest {
+                prompt: Message::user(format!("Text to classify:\n\n{text}")),
+                system_prompt: Some(format!(
+                    "You check whether texts comply with this policy:\n{}\n\n{CLASSIFIER_FORMAT}",
+                    self.policy
+                )),
+                chat_history: vec![],
+                tools: vec![],
+                temperature: Some(0.0),
+                max_tokens: None,
+            };
+            let response = self.model.completion(request).await?;
+            let reply = response
+                .choice
+                .into_iter()
+                .filter_map(|choice| match choice {
+                    AssistantContent::Text(text) => Some(text.text),
+                    AssistantContent::ToolCall(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let classification = extract_json(&reply)
+                .and_then(serde_json::from_value::<Classification>)
+                .map_err(|_| GuardrailError::InvalidClassification(reply.clone()))?;
+            if classification.allowed {
+                Ok(GuardrailVerdict::Pass)
+            } else {
+                Ok(GuardrailVerdict::Trip(
+                    classification
+                        .reason
+                        .unwrap_or_else(|| "the text does not comply with the policy".to_owned()),
+                ))
+            }
+        })
+    }
+}
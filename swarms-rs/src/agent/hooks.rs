@@ -0,0 +1,101 @@
+//! # Agent Lifecycle Hooks
+//!
+//! Hooks observe and intercept a [`SwarmsAgent`](super::SwarmsAgent) while it runs. They are
+//! registered with [`SwarmsAgentBuilder::add_hook`](super::SwarmsAgentBuilder::add_hook) and
+//! called in registration order.
+//!
+//! Every callback has a no-op default, so a hook only implements the events it cares about.
+//! Callbacks that receive `&mut` values can modify them (e.g. redact a request before it is
+//! sent), and the `before_*` callbacks can veto the operation by returning
+//! [`HookAction::Veto`].
+//!
+//! ```rust
+//! use swarms_rs::agent::hooks::{AgentHook, HookAction, HookContext};
+//! use swarms_rs::llm::completion::ToolFunction;
+//!
+//! struct NoDeletes;
+//!
+//! impl AgentHook for NoDeletes {
+//!     fn before_tool_call(&self, _ctx: &HookContext, call: &mut ToolFunction) -> HookAction {
+//!         if call.name.starts_with("delete") {
+//!             HookAction::Veto {
+//!                 reason: "deleting is not allowed".to_owned(),
+//!             }
+//!         } else {
+//!             HookAction::Continue
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::{
+    llm::{
+        completion::{AssistantContent, ToolFunction},
+        request::CompletionRequest,
+    },
+    structs::{agent::AgentError, budget::BudgetExceeded},
+};
+
+use super::{TaskStatus, ToolCallOutput};
+
+/// What the agent should do after a `before_*` hook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HookAction {
+    /// Proceed with the (possibly modified) operation.
+    Continue,
+    /// Skip the operation.
+    ///
+    /// A vetoed tool call is reported to the LLM as a tool result containing the reason;
+    /// a vetoed LLM call fails with [`AgentError::Vetoed`].
+    Veto { reason: String },
+}
+
+/// Information about the agent and task a hook is called for.
+#[derive(Clone, Copy, Debug)]
+pub struct HookContext<'a> {
+    pub agent_id: &'a str,
+    pub agent_name: &'a str,
+    /// The task being run, or `None` for direct calls to `chat` and `prompt`.
+    pub task: Option<&'a str>,
+}
+
+/// Callbacks for the lifecycle events of an agent run.
+#[allow(unused_variables)]
+pub trait AgentHook: Send + Sync {
+    /// Called at the start of each loop, with the zero-based loop index.
+    fn on_loop_start(&self, ctx: &HookContext, loop_count: u32) {}
+
+    /// Called at the end of each loop that got a response from the LLM.
+    fn on_loop_end(&self, ctx: &HookContext, loop_count: u32) {}
+
+    /// Called before a request is sent to the LLM.
+    fn before_llm_call(&self, ctx: &HookContext, request: &mut CompletionRequest) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called with the choices returned by the LLM, before they are handled.
+    fn after_llm_call(&self, ctx: &HookContext, choices: &mut Vec<AssistantContent>) {}
+
+    /// Called before a tool is executed. The name and arguments can be modified.
+    fn before_tool_call(&self, ctx: &HookContext, call: &mut ToolFunction) -> HookAction {
+        HookAction::Continue
+    }
+
+    /// Called with the output of a tool call, before it is added to the conversation.
+    fn after_tool_call(&self, ctx: &HookContext, output: &mut ToolCallOutput) {}
+
+    /// Called when an attempt fails and will be retried (if attempts are left).
+    fn on_retry(&self, ctx: &HookContext, attempt: u32, error: &AgentError) {}
+
+    /// Called with the verdict of the `task_evaluator` tool.
+    fn on_task_evaluation(&self, ctx: &HookContext, status: &TaskStatus) {}
+
+    /// Called when a run is stopped because its budget is exhausted, before the final
+    /// summary is requested.
+    fn on_budget_exhausted(&self, ctx: &HookContext, exceeded: &BudgetExceeded) {}
+
+    /// Called when the run finishes, with the output that will be returned: the rendered
+    /// conversation for `run`, or the final answer of the
+    /// [`RunResult`](super::run_result::RunResult) for `run_with_result`.
+    fn on_complete(&self, ctx: &HookContext, output: &mut String) {}
+}
@@ -1,3 +1,12 @@
+pub mod approval;
+pub mod guardrail;
+pub mod hooks;
+pub mod output_processor;
+pub mod plan;
+pub mod reflexion;
+pub mod run_result;
+pub mod session;
+pub mod spec;
 pub mod swarms_agent;
 
 pub use swarms_agent::*;
@@ -0,0 +1,313 @@
+//! # Output Processing
+//!
+//! Output processors clean up the final answer of a run. They are added to an agent with
+//! [`SwarmsAgentBuilder::add_output_processor`](super::SwarmsAgentBuilder::add_output_processor)
+//! and run in order once the agent loop finishes. The processed answer is added to the
+//! conversation under the [`OUTPUT_CLEANER_ROLE`] assistant role, so it is the final answer of
+//! the [`RunResult`](super::run_result::RunResult).
+//!
+//! The provided processors are:
+//!
+//! - [`StripTimestamps`]: removes the `Timestamp(millis)` prefixes of conversation messages
+//! - [`ExtractFencedBlock`]: keeps the content of the first fenced code block
+//! - [`RegexReplace`]: replaces the matches of a regular expression
+//! - [`MaxLength`]: truncates the output to a number of characters
+//! - [`LlmOutputCleaner`]: asks an LLM to clean the output
+//!
+//! Closures taking and returning a `String` are processors too. An [`OutputPipeline`] chains
+//! processors and can also be used on its own, e.g. on the output of
+//! [`Agent::run`](crate::structs::agent::Agent::run).
+//!
+//! ```rust
+//! use swarms_rs::agent::output_processor::{ExtractFencedBlock, MaxLength, OutputPipeline};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let pipeline = OutputPipeline::new()
+//!     .then(ExtractFencedBlock::new().language("json"))
+//!     .then(|output: String| output.trim().to_owned())
+//!     .then(MaxLength::new(1000));
+//!
+//! let output = pipeline
+//!     .process("Here it is:\n```json\n{\"ok\": true}\n```".to_owned())
+//!     .await?;
+//! assert_eq!(output, "{\"ok\": true}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, LazyLock};
+
+use futures::future::BoxFuture;
+use regex::Regex;
+use thiserror::Error;
+
+use crate::llm::{
+    self, CompletionError,
+    completion::{AssistantContent, Message},
+    request::CompletionRequest,
+};
+
+/// Name of the assistant role under which processed output is stored in short memory.
+pub const OUTPUT_CLEANER_ROLE: &str = "Output Cleaner";
+
+#[derive(Debug, Error)]
+pub enum OutputProcessorError {
+    #[error("Completion error: {0}")]
+    CompletionError(#[from] CompletionError),
+    #[error("No fenced block found in output")]
+    NoFencedBlock,
+    #[error("Output cleaner returned no text")]
+    EmptyResponse,
+}
+
+/// A step of output post-processing.
+pub trait OutputProcessor: Send + Sync {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>>;
+}
+
+impl<F> OutputProcessor for F
+where
+    F: Fn(String) -> String + Send + Sync,
+{
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        let output = self(output);
+        Box::pin(async move { Ok(output) })
+    }
+}
+
+/// Processors applied in order, each to the output of the previous one.
+#[derive(Clone, Default)]
+pub struct OutputPipeline {
+    processors: Vec<Arc<dyn OutputProcessor>>,
+}
+
+impl OutputPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, processor: impl OutputProcessor + 'static) -> Self {
+        self.push(processor);
+        self
+    }
+
+    pub fn push(&mut self, processor: impl OutputProcessor + 'static) {
+        self.processors.push(Arc::new(processor));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.processors.len()
+    }
+
+    /// Runs every processor, stopping at the first error.
+    pub async fn process(&self, mut output: String) -> Result<String, OutputProcessorError> {
+        for processor in &self.processors {
+            output = processor.process(output).await?;
+        }
+        Ok(output)
+    }
+}
+
+impl OutputProcessor for OutputPipeline {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        Box::pin(OutputPipeline::process(self, output))
+    }
+}
+
+static TIMESTAMP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"Timestamp\(millis\): -?\d+ \n").unwrap());
+
+static FENCED_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```([\w+#.-]*)[^\n]*\n(.*?)\n?```").unwrap());
+
+/// Removes the `Timestamp(millis): <millis>` prefixes that short memory adds to messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StripTimestamps;
+
+impl OutputProcessor for StripTimestamps {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        let output = TIMESTAMP.replace_all(&output, "").into_owned();
+        Box::pin(async move { Ok(output) })
+    }
+}
+
+/// Keeps the content of the first fenced (` ``` `) block, optionally of a given language.
+///
+/// Output without a matching block is left unchanged, unless the block is
+/// [`required`](Self::required).
+#[derive(Clone, Debug, Default)]
+pub struct ExtractFencedBlock {
+    language: Option<String>,
+    required: bool,
+}
+
+impl ExtractFencedBlock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only extract blocks tagged with `language` (case insensitive), e.g. `json`.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Fail with [`OutputProcessorError::NoFencedBlock`] if there is no matching block.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn extract(&self, output: &str) -> Option<String> {
+        FENCED_BLOCK
+            .captures_iter(output)
+            .find(|captures| match &self.language {
+                Some(language) => captures[1].eq_ignore_ascii_case(language),
+                None => true,
+            })
+            .map(|captures| captures[2].to_owned())
+    }
+}
+
+impl OutputProcessor for ExtractFencedBlock {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        let result = match self.extract(&output) {
+            Some(block) => Ok(block),
+            None if self.required => Err(OutputProcessorError::NoFencedBlock),
+            None => Ok(output),
+        };
+        Box::pin(async move { result })
+    }
+}
+
+/// Replaces every match of a regular expression. The replacement can refer to capture
+/// groups, e.g. `$1` or `${name}`.
+#[derive(Clone, Debug)]
+pub struct RegexReplace {
+    regex: Regex,
+    replacement: String,
+}
+
+impl RegexReplace {
+    pub fn new(pattern: &str, replacement: impl Into<String>) -> Result<Self, regex::Error> {
+        Ok(Self {
+            regex: Regex::new(pattern)?,
+            replacement: replacement.into(),
+        })
+    }
+}
+
+impl OutputProcessor for RegexReplace {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        let output = self
+            .regex
+            .replace_all(&output, self.replacement.as_str())
+            .into_owned();
+        Box::pin(async move { Ok(output) })
+    }
+}
+
+/// Truncates the output to at most `max_chars` characters, including the ellipsis
+/// (`...` by default) marking the cut.
+#[derive(Clone, Debug)]
+pub struct MaxLength {
+    max_chars: usize,
+    ellipsis: String,
+}
+
+impl MaxLength {
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            max_chars,
+            ellipsis: "...".to_owned(),
+        }
+    }
+
+    pub fn ellipsis(mut self, ellipsis: impl Into<String>) -> Self {
+        self.ellipsis = ellipsis.into();
+        self
+    }
+}
+
+impl OutputProcessor for MaxLength {
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        let output = if output.chars().count() <= self.max_chars {
+            output
+        } else {
+            let ellipsis_chars = self.ellipsis.chars().count().min(self.max_chars);
+            let mut truncated = output
+                .chars()
+                .take(self.max_chars - ellipsis_chars)
+                .collect::<String>();
+            truncated.extend(self.ellipsis.chars().take(ellipsis_chars));
+            truncated
+        };
+        Box::pin(async move { Ok(output) })
+    }
+}
+
+const DEFAULT_CLEANER_INSTRUCTIONS: &str = "You clean up the output of an AI agent. \
+    Remove conversational filler, meta commentary about the task and formatting artifacts, \
+    and keep the content itself unchanged. Reply with the cleaned output only.";
+
+/// Asks an LLM to clean the output.
+///
+/// The cleaner calls its model directly, so its usage is not counted in the run budget of
+/// the agent.
+#[derive(Clone)]
+pub struct LlmOutputCleaner<M> {
+    model: M,
+    instructions: String,
+}
+
+impl<M> LlmOutputCleaner<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            instructions: DEFAULT_CLEANER_INSTRUCTIONS.to_owned(),
+        }
+    }
+
+    /// Replaces the default system prompt of the cleaner.
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = instructions.into();
+        self
+    }
+}
+
+impl<M> OutputProcessor for LlmOutputCleaner<M>
+where
+    M: llm::Model + Send + Sync,
+    M::RawCompletionResponse: Send,
+{
+    fn process(&self, output: String) -> BoxFuture<'_, Result<String, OutputProcessorError>> {
+        Box::pin(async move {
+            let request = CompletionRequest {
+                prompt: Message::user(output),
+                system_prompt: Some(self.instructions.clone()),
+                chat_history: vec![],
+                tools: vec![],
+                temperature: Some(0.0),
+                max_tokens: None,
+            };
+            let response = self.model.completion(request).await?;
+            let text = response
+                .choice
+                .into_iter()
+                .filter_map(|choice| match choice {
+                    AssistantContent::Text(text) => Some(text.text),
+                    AssistantContent::ToolCall(_) => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            if text.trim().is_empty() {
+                return Err(OutputProcessorError::EmptyResponse);
+            }
+            Ok(text)
+        })
+    }
+}
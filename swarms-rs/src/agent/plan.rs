@@ -0,0 +1,314 @@
+//! # Plans
+//!
+//! With planning enabled ([`SwarmsAgentBuilder::enable_plan`](super::SwarmsAgentBuilder::enable_plan)),
+//! the agent asks its model for a [`Plan`] before the first loop. Each loop, the prompt shows the
+//! plan and the current step, and the agent reports the outcome of a step with the built-in
+//! `update_plan` tool. When a step fails, the agent re-plans the remaining work, up to
+//! `max_replans` times per run.
+//!
+//! The plan is saved with the task state, so a resumed run continues from the current step, and
+//! returned in [`RunResult::plan`](super::run_result::RunResult::plan).
+//!
+//! ```rust
+//! use swarms_rs::agent::plan::{Plan, StepStatus, StepUpdate};
+//!
+//! let mut plan = Plan::parse(r#"{"steps": ["Find sources", "Write the summary"]}"#).unwrap();
+//! plan.update(&StepUpdate {
+//!     step: 1,
+//!     status: StepStatus::Done,
+//!     note: Some("Found 3 sources".to_owned()),
+//! })
+//! .unwrap();
+//! assert_eq!(plan.current_step().unwrap().description, "Write the summary");
+//! ```
+
+use std::fmt::Display;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    llm::request::ToolDefinition,
+    structs::tool::{ToolDyn, ToolError},
+};
+
+/// Name of the built-in tool that updates the plan.
+pub const UPDATE_PLAN_TOOL_NAME: &str = "update_plan";
+
+/// Planning prompt used when planning is enabled without one.
+pub(crate) const DEFAULT_PLANNING_PROMPT: &str = "Create a step-by-step plan for the task:";
+
+const PLAN_FORMAT: &str = "Reply with JSON only: \
+    {\"steps\": [\"<first step>\", \"<second step>\", ...]}";
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error("Unknown plan step: {0}")]
+    UnknownStep(u32),
+    #[error("Step {0} is already finished")]
+    StepFinished(u32),
+    #[error("A step can only be marked done or failed")]
+    InvalidStatus,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    #[default]
+    Pending,
+    Done,
+    Failed,
+}
+
+/// A step of a [`Plan`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// Number of the step, unique within its plan.
+    pub id: u32,
+    pub description: String,
+    #[serde(default)]
+    pub status: StepStatus,
+    /// What the agent reported when finishing the step.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// The steps of a task, worked through in order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+    /// Number of times the remaining steps were re-planned after a failure.
+    #[serde(default)]
+    pub replans: u32,
+}
+
+/// The outcome of a step, reported with the `update_plan` tool.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StepUpdate {
+    pub step: u32,
+    pub status: StepStatus,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawStep {
+    Text(String),
+    Object { description: String },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawPlan {
+    Object { steps: Vec<RawStep> },
+    List(Vec<RawStep>),
+}
+
+impl Plan {
+    /// Creates a plan with pending steps numbered from 1.
+    pub fn new<S: Into<String>>(steps: impl IntoIterator<Item = S>) -> Self {
+        let mut plan = Self::default();
+        plan.extend(steps);
+        plan
+    }
+
+    /// Parses a plan from a model response: JSON (`{"steps": [...]}` or a list), or else a
+    /// numbered or bulleted list. Returns `None` if no steps are found.
+    pub fn parse(text: &str) -> Option<Self> {
+        let steps = Self::parse_json(text).unwrap_or_else(|| Self::parse_list(text));
+        (!steps.is_empty()).then(|| Self::new(steps))
+    }
+
+    fn parse_json(text: &str) -> Option<Vec<String>> {
+        let start = text.find(['{', '['])?;
+        let end = text.rfind(['}', ']'])?;
+        let raw = serde_json::from_str::<RawPlan>(text.get(start..=end)?).ok()?;
+        let steps = match raw {
+            RawPlan::Object { steps } | RawPlan::List(steps) => steps,
+        };
+        Some(
+            steps
+                .into_iter()
+                .map(|step| match step {
+                    RawStep::Text(description) | RawStep::Object { description } => description,
+                })
+                .map(|description| description.trim().to_owned())
+                .filter(|description| !description.is_empty())
+                .collect(),
+        )
+    }
+
+    fn parse_list(text: &str) -> Vec<String> {
+        text.lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                let rest = match line.strip_prefix(['-', '*']) {
+                    Some(rest) => rest,
+                    None => {
+                        let digits = line.len() - line.trim_start_matches(char::is_numeric).len();
+                        if digits == 0 {
+                            return None;
+                        }
+                        line[digits..].strip_prefix(['.', ')'])?
+                    },
+                };
+                let description = rest.trim();
+                (!description.is_empty()).then(|| description.to_owned())
+            })
+            .collect()
+    }
+
+    /// The first pending step.
+    pub fn current_step(&self) -> Option<&PlanStep> {
+        self.steps
+            .iter()
+            .find(|step| step.status == StepStatus::Pending)
+    }
+
+    /// Whether no step is pending.
+    pub fn is_finished(&self) -> bool {
+        self.current_step().is_none()
+    }
+
+    /// Marks a pending step done or failed.
+    pub fn update(&mut self, update: &StepUpdate) -> Result<(), PlanError> {
+        if update.status == StepStatus::Pending {
+            return Err(PlanError::InvalidStatus);
+        }
+        let step = self
+            .steps
+            .iter_mut()
+            .find(|step| step.id == update.step)
+            .ok_or(PlanError::UnknownStep(update.step))?;
+        if step.status != StepStatus::Pending {
+            return Err(PlanError::StepFinished(update.step));
+        }
+        step.status = update.status;
+        step.note = update.note.clone();
+        Ok(())
+    }
+
+    /// Replaces the pending steps with `steps`, keeping the finished ones.
+    pub fn replan<S: Into<String>>(&mut self, steps: impl IntoIterator<Item = S>) {
+        self.steps.retain(|step| step.status != StepStatus::Pending);
+        self.extend(steps);
+        self.replans += 1;
+    }
+
+    fn extend<S: Into<String>>(&mut self, steps: impl IntoIterator<Item = S>) {
+        let last_id = self.steps.iter().map(|step| step.id).max().unwrap_or(0);
+        for (id, description) in (last_id + 1..).zip(steps) {
+            self.steps.push(PlanStep {
+                id,
+                description: description.into(),
+                status: StepStatus::Pending,
+                note: None,
+            });
+        }
+    }
+}
+
+impl Display for Plan {
+    /// Renders the plan as a checklist, e.g. `2. [x] Find sources (Found 3 sources)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let mark = match step.status {
+                StepStatus::Pending => " ",
+                StepStatus::Done => "x",
+                StepStatus::Failed => "!",
+            };
+            write!(f, "{}. [{}] {}", step.id, mark, step.description)?;
+            if let Some(note) = &step.note {
+                write!(f, " ({note})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The prompt asking for a plan of `task`.
+pub(crate) fn planning_prompt(planning_prompt: Option<&str>, task: &str) -> String {
+    let planning_prompt = planning_prompt.unwrap_or(DEFAULT_PLANNING_PROMPT);
+    format!("{planning_prompt} {task}\n\n{PLAN_FORMAT}")
+}
+
+/// The prompt asking for new remaining steps after `failed` failed.
+pub(crate) fn replanning_prompt(
+    planning_prompt: Option<&str>,
+    task: &str,
+    plan: &Plan,
+    failed: &StepUpdate,
+) -> String {
+    let planning_prompt = planning_prompt.unwrap_or(DEFAULT_PLANNING_PROMPT);
+    let reason = failed.note.as_deref().unwrap_or("no reason given");
+    format!(
+        "{planning_prompt} {task}\n\nThe current plan is:\n{plan}\n\nStep {} failed: {reason}. \
+        Plan the remaining work, without the steps already done.\n\n{PLAN_FORMAT}",
+        failed.step
+    )
+}
+
+/// The part of a loop prompt that tells the agent where it is in the plan.
+pub(crate) fn step_prompt(plan: &Plan) -> String {
+    match plan.current_step() {
+        Some(step) => format!(
+            "Plan:\n{plan}\n\nCurrent step {}: {}\nWork on this step only. When it is done or \
+            cannot be done, call `{UPDATE_PLAN_TOOL_NAME}` with its status.",
+            step.id, step.description
+        ),
+        None => {
+            format!("Plan:\n{plan}\n\nAll steps of the plan are finished. Give your final answer.")
+        },
+    }
+}
+
+/// The `update_plan` tool. It only validates its arguments; the agent loop applies them.
+pub(crate) struct UpdatePlan;
+
+impl ToolDyn for UpdatePlan {
+    fn name(&self) -> String {
+        UPDATE_PLAN_TOOL_NAME.to_owned()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: UPDATE_PLAN_TOOL_NAME.to_owned(),
+            description: "Report that a step of the plan is done or failed. A failed step makes \
+                you re-plan the remaining work."
+                .to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "step": {
+                        "type": "integer",
+                        "description": "Number of the step"
+                    },
+                    "status": {
+                        "type": "string",
+                        "enum": ["done", "failed"]
+                    },
+                    "note": {
+                        "type": "string",
+                        "description": "Result of the step, or why it failed"
+                    }
+                },
+                "required": ["step", "status"]
+            }),
+        }
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move {
+            let update = serde_json::from_str::<StepUpdate>(&args)?;
+            if update.status == StepStatus::Pending {
+                return Err(ToolError::ToolCallError(Box::new(PlanError::InvalidStatus)));
+            }
+            Ok(serde_json::to_string(&update)?)
+        })
+    }
+}
@@ -0,0 +1,252 @@
+//! # Reflexion
+//!
+//! In reflexion mode, enabled with
+//! [`SwarmsAgentBuilder::enable_reflexion`](super::SwarmsAgentBuilder::enable_reflexion), a
+//! critic scores every draft answer of the agent against the task and an optional rubric.
+//! A draft is a plain text response, or the latest answer when `task_evaluator` reports the
+//! task complete. If the score is below the threshold, the critique is added to the
+//! conversation and the agent revises its answer in the next loop. The run stops once a
+//! draft passes, after `max_revisions` revisions, or when the loops run out.
+//!
+//! By default the critic uses the agent's own model. A different model can be used with
+//! [`ModelCritic`], another agent with [`AgentCritic`], or any [`Critic`] implementation.
+//!
+//! ```rust,no_run
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::agent::reflexion::Reflexion;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let model = OpenAI::from_env_with_model("gpt-4o-mini");
+//! let agent = SwarmsAgentBuilder::new_with_model(model)
+//!     .max_loops(5)
+//!     .enable_reflexion(
+//!         Reflexion::new()
+//!             .rubric("Cites at least two sources and ends with a one-line summary")
+//!             .threshold(8.0),
+//!     )
+//!     .build();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    llm::{self, CompletionError, completion::AssistantContent, request::CompletionRequest},
+    structs::agent::{Agent, AgentError, CancellationToken},
+};
+
+/// Name of the user role under which critiques are added to the conversation.
+pub const CRITIC_ROLE: &str = "Critic";
+
+#[derive(Debug, Error)]
+pub enum CritiqueError {
+    #[error("Completion error: {0}")]
+    CompletionError(#[from] CompletionError),
+    #[error("Agent error: {0}")]
+    AgentError(#[from] AgentError),
+    #[error("Invalid critique: {0}")]
+    InvalidCritique(String),
+}
+
+/// The verdict of a critic on a draft answer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Critique {
+    /// Score from 0 (unusable) to 10 (perfect).
+    pub score: f64,
+    /// What should be improved.
+    #[serde(default)]
+    pub feedback: String,
+}
+
+impl Critique {
+    /// Parses a critique from the first JSON object in `text`.
+    pub fn parse(text: &str) -> Result<Self, CritiqueError> {
+        let json = text
+            .find('{')
+            .zip(text.rfind('}'))
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| &text[start..=end])
+            .ok_or_else(|| CritiqueError::InvalidCritique(text.to_owned()))?;
+        let critique = serde_json::from_str::<Critique>(json)
+            .map_err(|e| CritiqueError::InvalidCritique(format!("{e}: {text}")))?;
+        if !(0.0..=10.0).contains(&critique.score) {
+            return Err(CritiqueError::InvalidCritique(format!(
+                "score {} is not between 0 and 10",
+                critique.score
+            )));
+        }
+        Ok(critique)
+    }
+}
+
+/// Scores draft answers.
+pub trait Critic: Send + Sync {
+    fn critique<'a>(
+        &'a self,
+        task: &'a str,
+        rubric: Option<&'a str>,
+        draft: &'a str,
+    ) -> BoxFuture<'a, Result<Critique, CritiqueError>>;
+}
+
+/// Settings of reflexion mode.
+#[derive(Clone)]
+pub struct Reflexion {
+    pub(crate) rubric: Option<String>,
+    pub(crate) threshold: f64,
+    pub(crate) max_revisions: u32,
+    pub(crate) critic: Option<Arc<dyn Critic>>,
+}
+
+impl Default for Reflexion {
+    fn default() -> Self {
+        Self {
+            rubric: None,
+            threshold: 7.0,
+            max_revisions: 3,
+            critic: None,
+        }
+    }
+}
+
+impl Reflexion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Criteria the critic checks in addition to the task.
+    pub fn rubric(mut self, rubric: impl Into<String>) -> Self {
+        self.rubric = Some(rubric.into());
+        self
+    }
+
+    /// Minimum score, from 0 to 10, for a draft to pass (default: 7).
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Maximum number of revisions before the latest draft is accepted (default: 3).
+    pub fn max_revisions(mut self, max_revisions: u32) -> Self {
+        self.max_revisions = max_revisions;
+        self
+    }
+
+    /// Uses `critic` instead of the agent's own model.
+    pub fn critic(mut self, critic: impl Critic + 'static) -> Self {
+        self.critic = Some(Arc::new(critic));
+        self
+    }
+}
+
+const CRITIC_INSTRUCTIONS: &str = "You are a strict reviewer. Score how well the answer \
+    completes the task (and meets the rubric, if there is one) from 0 (unusable) to 10 \
+    (perfect), and explain concisely what must be improved. Reply with JSON only: \
+    {\"score\": <number from 0 to 10>, \"feedback\": \"<what to improve>\"}";
+
+/// The prompt asking a critic to score `draft`.
+pub(crate) fn critique_prompt(task: &str, rubric: Option<&str>, draft: &str) -> String {
+    match rubric {
+        Some(rubric) => format!("Task:\n{task}\n\nRubric:\n{rubric}\n\nAnswer:\n{draft}"),
+        None => format!("Task:\n{task}\n\nAnswer:\n{draft}"),
+    }
+}
+
+/// The completion request asking a model to score `draft`.
+pub(crate) fn critique_request(task: &str, rubric: Option<&str>, draft: &str) -> CompletionRequest {
+    CompletionRequest {
+        prompt: llm::completion::Message::user(critique_prompt(task, rubric, draft)),
+        system_prompt: Some(CRITIC_INSTRUCTIONS.to_owned()),
+        chat_history: vec![],
+        tools: vec![],
+        temperature: Some(0.0),
+        max_tokens: None,
+    }
+}
+
+/// Parses the critique in the text choices of a completion.
+pub(crate) fn parse_choices(choices: Vec<AssistantContent>) -> Result<Critique, CritiqueError> {
+    let text = choices
+        .into_iter()
+        .filter_map(|choice| match choice {
+            AssistantContent::Text(text) => Some(text.text),
+            AssistantContent::ToolCall(_) => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Critique::parse(&text)
+}
+
+/// A critic that asks a model, e.g. a stronger or cheaper one than the agent's.
+///
+/// Unlike the default critic, its calls are not counted in the run budget of the agent.
+#[derive(Clone)]
+pub struct ModelCritic<M> {
+    model: M,
+}
+
+impl<M> ModelCritic<M> {
+    pub fn new(model: M) -> Self {
+        Self { model }
+    }
+}
+
+impl<M> Critic for ModelCritic<M>
+where
+    M: llm::Model + Send + Sync,
+    M::RawCompletionResponse: Send,
+{
+    fn critique<'a>(
+        &'a self,
+        task: &'a str,
+        rubric: Option<&'a str>,
+        draft: &'a str,
+    ) -> BoxFuture<'a, Result<Critique, CritiqueError>> {
+        Box::pin(async move {
+            let response = self
+                .model
+                .completion(critique_request(task, rubric, draft))
+                .await?;
+            parse_choices(response.choice)
+        })
+    }
+}
+
+/// A critic that runs another agent on the critique prompt. The agent should reply with
+/// the JSON described in its prompt.
+pub struct AgentCritic {
+    agent: Box<dyn Agent>,
+}
+
+impl AgentCritic {
+    pub fn new(agent: Box<dyn Agent>) -> Self {
+        Self { agent }
+    }
+}
+
+impl Critic for AgentCritic {
+    fn critique<'a>(
+        &'a self,
+        task: &'a str,
+        rubric: Option<&'a str>,
+        draft: &'a str,
+    ) -> BoxFuture<'a, Result<Critique, CritiqueError>> {
+        Box::pin(async move {
+            let prompt = format!(
+                "{CRITIC_INSTRUCTIONS}\n\n{}",
+                critique_prompt(task, rubric, draft)
+            );
+            let result = self
+                .agent
+                .run_with_result(prompt, CancellationToken::new())
+                .await?;
+            Critique::parse(&result.final_answer)
+        })
+    }
+}
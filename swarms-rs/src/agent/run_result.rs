@@ -0,0 +1,224 @@
+//! # Run Results
+//!
+//! [`RunResult`] is the structured outcome of an agent run, returned by
+//! [`Agent::run_with_result`](crate::structs::agent::Agent::run_with_result). It holds the
+//! final answer alongside the typed transcript, the tool calls made, why the run stopped,
+//! and its usage and timings, so callers don't have to parse the rendered conversation
+//! returned by [`Agent::run`](crate::structs::agent::Agent::run).
+//!
+//! ```rust,no_run
+//! use swarms_rs::structs::agent::{Agent, CancellationToken};
+//!
+//! # async fn example(agent: &dyn Agent) -> Result<(), Box<dyn std::error::Error>> {
+//! let result = agent
+//!     .run_with_result("Summarize the report".to_owned(), CancellationToken::new())
+//!     .await?;
+//! println!("{} (stopped by {:?} after {} loops)", result, result.termination, result.loop_count);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{fmt::Display, time::Duration};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    llm::request::Usage,
+    structs::{
+        artifact::Artifact,
+        budget::BudgetExceeded,
+        conversation::{Message, Role},
+        handoff::HandoffRequest,
+    },
+};
+
+use super::{ToolCallOutput, guardrail::GuardrailOutcome, plan::Plan, reflexion::Critique};
+
+/// The outcome of an agent run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunResult {
+    /// Unique ID of the run.
+    pub run_id: String,
+    pub agent_name: String,
+    pub task: String,
+    /// The last answer of the agent.
+    pub final_answer: String,
+    /// The conversation of the task, without timestamp prefixes.
+    pub transcript: Vec<TranscriptEntry>,
+    /// The tool calls made during the run, in order of completion.
+    pub tool_calls: Vec<ToolCallOutput>,
+    /// Number of loops completed.
+    pub loop_count: u32,
+    pub termination: TerminationReason,
+    /// The budget that stopped the run, if one did.
+    pub budget_exceeded: Option<BudgetExceeded>,
+    /// Tokens used by the run, as reported by the model or estimated.
+    pub usage: Usage,
+    pub timings: RunTimings,
+    /// Artifacts saved during the run.
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// Critiques of the drafts, in reflexion mode.
+    #[serde(default)]
+    pub critiques: Vec<Critique>,
+    /// The plan and the status of its steps, if planning is enabled.
+    #[serde(default)]
+    pub plan: Option<Plan>,
+    /// Results of the agents delegated to with
+    /// [`AgentTool`](crate::structs::agent_tool::AgentTool)s. Their usage is included in
+    /// `usage`.
+    #[serde(default)]
+    pub sub_runs: Vec<RunResult>,
+    /// The agent the conversation was handed off to, if the run ended with a handoff.
+    #[serde(default)]
+    pub handoff: Option<HandoffRequest>,
+    /// The guardrail checks of the task and the final answer, in order.
+    #[serde(default)]
+    pub guardrails: Vec<GuardrailOutcome>,
+    /// The session the run was a turn of, if it was run with
+    /// [`SwarmsAgent::session`](super::SwarmsAgent::session).
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+impl RunResult {
+    /// Builds a result for an agent that only produces an output string.
+    ///
+    /// The output is used as the final answer and the only transcript entry, and the
+    /// termination reason is [`TerminationReason::Unspecified`].
+    pub fn from_output(
+        agent_name: impl Into<String>,
+        task: impl Into<String>,
+        output: String,
+        started_at: DateTime<Local>,
+    ) -> Self {
+        let agent_name = agent_name.into();
+        let finished_at = Local::now();
+        Self {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            transcript: vec![TranscriptEntry {
+                role: Role::Assistant(agent_name.clone()),
+                content: output.clone(),
+                timestamp: Some(finished_at),
+            }],
+            agent_name,
+            task: task.into(),
+            final_answer: output,
+            tool_calls: Vec::new(),
+            loop_count: 0,
+            termination: TerminationReason::Unspecified,
+            budget_exceeded: None,
+            usage: Usage::default(),
+            timings: RunTimings {
+                started_at,
+                finished_at,
+                duration: (finished_at - started_at).to_std().unwrap_or_default(),
+                llm_duration: Duration::ZERO,
+                tool_duration: Duration::ZERO,
+            },
+            artifacts: Vec::new(),
+            critiques: Vec::new(),
+            plan: None,
+            sub_runs: Vec::new(),
+            handoff: None,
+            guardrails: Vec::new(),
+            session_id: None,
+        }
+    }
+
+    /// The final answer.
+    pub fn as_str(&self) -> &str {
+        &self.final_answer
+    }
+}
+
+impl Display for RunResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.final_answer)
+    }
+}
+
+impl From<RunResult> for String {
+    fn from(result: RunResult) -> Self {
+        result.final_answer
+    }
+}
+
+/// Why a run stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// `task_evaluator` reported the task complete.
+    TaskComplete,
+    /// A response contained one of the agent's stop words.
+    StopWord,
+    /// The run reached `max_loops`.
+    MaxLoops,
+    /// A [`RunBudget`](crate::structs::budget::RunBudget) was exhausted.
+    Budget,
+    /// Every retry attempt of a loop failed.
+    RetriesExhausted,
+    /// In reflexion mode, a draft reached the score threshold.
+    CritiquePassed,
+    /// In reflexion mode, the last revision still scored below the threshold.
+    MaxRevisions,
+    /// The agent handed the conversation off to another agent.
+    Handoff,
+    /// The agent doesn't report why it stopped.
+    Unspecified,
+}
+
+/// When a run happened and where its time went.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunTimings {
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    /// Wall-clock time of the run.
+    pub duration: Duration,
+    /// Time spent waiting for the LLM.
+    pub llm_duration: Duration,
+    /// Time spent executing tool calls. Concurrent calls are counted once.
+    pub tool_duration: Duration,
+}
+
+/// A message of the transcript of a run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub role: Role,
+    pub content: String,
+    /// When the message was added to the conversation, if known.
+    pub timestamp: Option<DateTime<Local>>,
+}
+
+impl From<&Message> for TranscriptEntry {
+    fn from(message: &Message) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.text().to_owned(),
+            timestamp: message.timestamp(),
+        }
+    }
+}
+
+/// The final answer of a transcript: the last assistant message that isn't a tool call
+/// summary, or the last assistant message if there is none.
+pub(crate) fn final_answer(transcript: &[TranscriptEntry]) -> String {
+    final_answer_index(transcript)
+        .map(|index| transcript[index].content.clone())
+        .unwrap_or_default()
+}
+
+/// Index of the entry of [`final_answer`] in `transcript`.
+pub(crate) fn final_answer_index(transcript: &[TranscriptEntry]) -> Option<usize> {
+    let mut answers = transcript
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, entry)| matches!(entry.role, Role::Assistant(_)));
+    let last = answers.clone().next();
+    answers
+        .find(|(_, entry)| !entry.content.starts_with("[Tool name]:"))
+        .or(last)
+        .map(|(index, _)| index)
+}
@@ -0,0 +1,230 @@
+//! # Sessions
+//!
+//! [`Agent::run`](crate::structs::agent::Agent::run) keeps one conversation per task text.
+//! A session keeps one conversation per session ID instead, so that each run of the session
+//! is a new turn that sees the previous ones, and that two users asking the same question
+//! don't share a conversation:
+//!
+//! ```rust,no_run
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let agent = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+//!     .session_ttl(Duration::from_secs(30 * 60))
+//!     .max_sessions(1_000)
+//!     .build();
+//!
+//! let session = agent.session("user-42");
+//! session.run("My name is Ada.").await?;
+//! let answer = session.run("What is my name?").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Sessions idle for longer than the [TTL](super::SwarmsAgentBuilder::session_ttl) are
+//! ended, as are the least recently used sessions above the
+//! [maximum](super::SwarmsAgentBuilder::max_sessions). This is checked whenever a session
+//! run starts, or with [`SwarmsAgent::evict_sessions`]. A session with a run in progress is
+//! never ended.
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::{
+    llm,
+    structs::agent::{AgentError, CancellationToken},
+};
+
+use super::{
+    SwarmsAgent,
+    run_result::{RunResult, TranscriptEntry},
+};
+
+/// A conversation with an agent that continues across runs.
+///
+/// Created with [`SwarmsAgent::session`]. Each run adds the task to the conversation of the
+/// session and runs the agent loop on it.
+pub struct AgentSession<'a, M>
+where
+    M: llm::Model + Clone + Send + Sync,
+    M::RawCompletionResponse: Clone + Send + Sync,
+{
+    agent: &'a SwarmsAgent<M>,
+    id: String,
+}
+
+impl<'a, M> AgentSession<'a, M>
+where
+    M: llm::Model + Clone + Send + Sync + 'static,
+    M::RawCompletionResponse: Clone + Send + Sync,
+{
+    pub(crate) fn new(agent: &'a SwarmsAgent<M>, id: String) -> Self {
+        Self { agent, id }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Runs `task` as the next turn of the session and returns the final answer, with the
+    /// `on_complete` hooks applied.
+    pub async fn run(&self, task: impl Into<String>) -> Result<String, AgentError> {
+        self.run_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    /// [`run`](Self::run) a task, stopping when `cancel` is canceled.
+    pub async fn run_with_cancellation(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> Result<String, AgentError> {
+        let result = self.run_with_result(task, cancel).await?;
+        Ok(result.final_answer)
+    }
+
+    /// Runs `task` as the next turn of the session and returns its [`RunResult`]. The
+    /// transcript is the whole conversation of the session.
+    pub async fn run_with_result(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> Result<RunResult, AgentError> {
+        self.agent.run_session(&self.id, task.into(), cancel).await
+    }
+
+    /// The conversation of the session so far, empty if it has no runs yet.
+    pub fn history(&self) -> Vec<TranscriptEntry> {
+        self.agent.session_history(&self.id)
+    }
+
+    /// Ends the session, dropping its conversation. Returns whether it existed.
+    pub fn end(self) -> bool {
+        self.agent.end_session(&self.id)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SessionEntry {
+    last_used: Instant,
+    /// Number of runs in progress
+    runs: usize,
+}
+
+/// The sessions of an agent, with when they were last used.
+#[derive(Clone, Default)]
+pub(crate) struct Sessions {
+    ttl: Option<Duration>,
+    max_sessions: Option<usize>,
+    entries: DashMap<String, SessionEntry>,
+}
+
+impl Sessions {
+    pub(crate) fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = Some(ttl);
+    }
+
+    pub(crate) fn set_max_sessions(&mut self, max_sessions: usize) {
+        self.max_sessions = Some(max_sessions);
+    }
+
+    /// IDs of the sessions, sorted.
+    pub(crate) fn ids(&self) -> Vec<String> {
+        let mut ids = self
+            .entries
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    /// Starts a run of session `id`, returning the sessions to end: the expired ones, `id`
+    /// included if it expired, and the least recently used ones above the maximum.
+    pub(crate) fn begin_run(&self, id: &str) -> (ActiveRun<'_>, Vec<String>) {
+        let mut ended = self.take_expired(Instant::now());
+        self.entries
+            .entry(id.to_owned())
+            .and_modify(|entry| {
+                entry.last_used = Instant::now();
+                entry.runs += 1;
+            })
+            .or_insert(SessionEntry {
+                last_used: Instant::now(),
+                runs: 1,
+            });
+        ended.extend(self.take_over_capacity());
+        let run = ActiveRun {
+            sessions: self,
+            id: id.to_owned(),
+        };
+        (run, ended)
+    }
+
+    /// Removes and returns the sessions to end.
+    pub(crate) fn evict(&self) -> Vec<String> {
+        let mut ended = self.take_expired(Instant::now());
+        ended.extend(self.take_over_capacity());
+        ended
+    }
+
+    /// Removes session `id` unless it has a run in progress. Returns whether it was removed.
+    pub(crate) fn remove(&self, id: &str) -> bool {
+        self.entries
+            .remove_if(id, |_, entry| entry.runs == 0)
+            .is_some()
+    }
+
+    fn take_expired(&self, now: Instant) -> Vec<String> {
+        let Some(ttl) = self.ttl else {
+            return Vec::new();
+        };
+        let expired = self
+            .entries
+            .iter()
+            .filter(|entry| entry.runs == 0 && now.duration_since(entry.last_used) >= ttl)
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        expired.into_iter().filter(|id| self.remove(id)).collect()
+    }
+
+    fn take_over_capacity(&self) -> Vec<String> {
+        let Some(max_sessions) = self.max_sessions else {
+            return Vec::new();
+        };
+        let excess = self.entries.len().saturating_sub(max_sessions);
+        if excess == 0 {
+            return Vec::new();
+        }
+        let mut idle = self
+            .entries
+            .iter()
+            .filter(|entry| entry.runs == 0)
+            .map(|entry| (entry.last_used, entry.key().clone()))
+            .collect::<Vec<_>>();
+        idle.sort();
+        idle.into_iter()
+            .map(|(_, id)| id)
+            .take(excess)
+            .filter(|id| self.remove(id))
+            .collect()
+    }
+}
+
+/// A run of a session in progress. The session is used until the run is dropped.
+pub(crate) struct ActiveRun<'a> {
+    sessions: &'a Sessions,
+    id: String,
+}
+
+impl Drop for ActiveRun<'_> {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.sessions.entries.get_mut(&self.id) {
+            entry.last_used = Instant::now();
+            entry.runs = entry.runs.saturating_sub(1);
+        }
+    }
+}
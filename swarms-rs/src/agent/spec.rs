@@ -0,0 +1,396 @@
+//! # Agent Specs
+//!
+//! An [`AgentSpec`] describes an agent in YAML or TOML: its system prompt, provider and model,
+//! tools, MCP servers, stop words and limits. The [`AgentLoader`] builds agents from specs,
+//! resolving the provider by name and the tools from a [`ToolRegistry`], so an agent can be
+//! changed without recompiling.
+//!
+//! ```yaml
+//! name: Researcher
+//! description: Finds and summarizes sources
+//! system_prompt_file: prompts/researcher.md
+//! provider:
+//!   name: openai
+//!   model: gpt-4o-mini
+//! temperature: 0.2
+//! max_loops: 5
+//! stop_words: [DONE]
+//! tools: [search]
+//! mcp_servers:
+//!   - transport: stdio
+//!     command: uvx
+//!     args: [mcp-server-fetch]
+//!   - transport: streamable_http
+//!     name: search
+//!     url: https://search.example.com/mcp
+//!     headers:
+//!       X-Team: research
+//!     bearer_token_env: SEARCH_TOKEN
+//! ```
+//!
+//! ```rust,no_run
+//! use swarms_rs::agent::spec::AgentLoader;
+//! use swarms_rs::structs::tool_registry::ToolRegistry;
+//!
+//! # async fn example(search: impl swarms_rs::structs::tool::ToolDyn + 'static)
+//! # -> Result<(), Box<dyn std::error::Error>> {
+//! let loader = AgentLoader::new().tools(ToolRegistry::new().with_tool(search));
+//! let agent = loader.load("agents/researcher.yaml").await?;
+//! let answer = agent.run("Summarize the latest Rust release".to_owned()).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Providers are `openai` (and OpenAI-compatible APIs through `base_url`) and `anthropic`.
+//! Agents with other models can apply a spec to their own builder with [`AgentSpec::apply`].
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    llm::{
+        self,
+        provider::{anthropic::Anthropic, openai::OpenAI},
+    },
+    structs::{
+        agent::{Agent, AgentError},
+        budget::{ModelPricing, RunBudget},
+        mcp::{MCPError, StreamableHttpConnector},
+        tool_registry::ToolRegistry,
+    },
+};
+
+use super::SwarmsAgentBuilder;
+
+#[derive(Debug, Error)]
+pub enum SpecError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("YAML error: {0}")]
+    YamlError(#[from] serde_yaml::Error),
+    #[error("TOML error: {0}")]
+    TomlError(#[from] toml::de::Error),
+    #[error("Agent error: {0}")]
+    AgentError(#[from] AgentError),
+    #[error("MCP error: {0}")]
+    Mcp(#[from] MCPError),
+    #[error("Unsupported spec format: {0}, expected .yaml, .yml or .toml")]
+    UnsupportedFormat(String),
+    #[error("Unknown provider: {0}")]
+    UnknownProvider(String),
+    #[error("The spec has no provider")]
+    MissingProvider,
+    #[error("Environment variable {0} is not set")]
+    MissingApiKey(String),
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("Invalid spec: {0}")]
+    InvalidSpec(String),
+}
+
+/// Declarative description of an agent. Unset fields keep the defaults of
+/// [`SwarmsAgentBuilder`], and unknown fields are rejected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentSpec {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub user_name: Option<String>,
+    pub system_prompt: Option<String>,
+    /// File containing the system prompt, relative to the spec file.
+    pub system_prompt_file: Option<PathBuf>,
+    /// Provider and model, needed by [`AgentLoader`].
+    pub provider: Option<ProviderSpec>,
+    pub temperature: Option<f64>,
+    pub max_loops: Option<u32>,
+    pub max_tokens: Option<u64>,
+    pub retry_attempts: Option<u32>,
+    pub stop_words: Vec<String>,
+    /// Enables planning.
+    pub plan: bool,
+    pub planning_prompt: Option<String>,
+    pub autosave: bool,
+    pub save_state_dir: Option<String>,
+    /// Whether the agent gets the `task_evaluator` tool (default: true).
+    pub task_evaluator: Option<bool>,
+    /// Whether tool calls of a response run concurrently (default: true).
+    pub concurrent_tool_calls: Option<bool>,
+    pub budget: Option<RunBudget>,
+    pub pricing: Option<ModelPricing>,
+    /// Names of tools in the [`ToolRegistry`].
+    pub tools: Vec<String>,
+    pub mcp_servers: Vec<McpServerSpec>,
+    pub verbose: bool,
+}
+
+/// The provider and model of an agent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderSpec {
+    /// `openai` or `anthropic`.
+    pub name: String,
+    /// Model name, defaults to the provider's default model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Base URL of the API, e.g. of an OpenAI-compatible provider.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Environment variable holding the API key, defaults to `OPENAI_API_KEY` or
+    /// `ANTHROPIC_API_KEY`.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+}
+
+/// An MCP server whose tools are added to the agent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "snake_case", deny_unknown_fields)]
+pub enum McpServerSpec {
+    /// A server started as a child process.
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// A server reached over SSE.
+    Sse { name: String, url: String },
+    /// A server reached over Streamable HTTP.
+    StreamableHttp {
+        name: String,
+        url: String,
+        /// Headers sent with each request to the server.
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        /// Environment variable holding a bearer token to authenticate to the server with.
+        #[serde(default)]
+        bearer_token_env: Option<String>,
+    },
+}
+
+impl AgentSpec {
+    pub fn from_yaml(yaml: &str) -> Result<Self, SpecError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, SpecError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Loads a spec from a `.yaml`, `.yml` or `.toml` file. A relative `system_prompt_file`
+    /// is resolved against the directory of the spec.
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self, SpecError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let content = tokio::fs::read_to_string(path).await?;
+        let mut spec = match extension.as_str() {
+            "yaml" | "yml" => Self::from_yaml(&content)?,
+            "toml" => Self::from_toml(&content)?,
+            _ => return Err(SpecError::UnsupportedFormat(path.display().to_string())),
+        };
+
+        if let Some(prompt_file) = &mut spec.system_prompt_file
+            && prompt_file.is_relative()
+            && let Some(dir) = path.parent()
+        {
+            *prompt_file = dir.join(&*prompt_file);
+        }
+        Ok(spec)
+    }
+
+    /// Configures `builder` as described by the spec, except for the provider. Tools are
+    /// taken from `tools`, and MCP servers are connected, failing with [`SpecError::Mcp`] if
+    /// one can't be.
+    pub async fn apply<M>(
+        &self,
+        mut builder: SwarmsAgentBuilder<M>,
+        tools: &ToolRegistry,
+    ) -> Result<SwarmsAgentBuilder<M>, SpecError>
+    where
+        M: llm::Model + Clone + Send + Sync,
+        M::RawCompletionResponse: Clone + Send + Sync,
+    {
+        let system_prompt = match (&self.system_prompt, &self.system_prompt_file) {
+            (Some(_), Some(_)) => {
+                return Err(SpecError::InvalidSpec(
+                    "system_prompt and system_prompt_file are both set".to_owned(),
+                ));
+            },
+            (Some(prompt), None) => Some(prompt.clone()),
+            (None, Some(path)) => Some(tokio::fs::read_to_string(path).await?),
+            (None, None) => None,
+        };
+        if let Some(system_prompt) = system_prompt {
+            builder = builder.system_prompt(system_prompt);
+        }
+
+        if let Some(name) = &self.name {
+            builder = builder.agent_name(name);
+        }
+        if let Some(description) = &self.description {
+            builder = builder.description(description);
+        }
+        if let Some(user_name) = &self.user_name {
+            builder = builder.user_name(user_name);
+        }
+        if let Some(temperature) = self.temperature {
+            builder = builder.temperature(temperature);
+        }
+        if let Some(max_loops) = self.max_loops {
+            builder = builder.max_loops(max_loops);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(retry_attempts) = self.retry_attempts {
+            builder = builder.retry_attempts(retry_attempts);
+        }
+        builder = builder.stop_words(self.stop_words.clone());
+        if self.plan {
+            builder = builder.enable_plan(self.planning_prompt.clone());
+        }
+        if self.autosave {
+            builder = builder.enable_autosave();
+        }
+        if let Some(save_state_dir) = &self.save_state_dir {
+            builder = builder.save_state_dir(save_state_dir);
+        }
+        if self.task_evaluator == Some(false) {
+            builder = builder.disable_task_complete_tool();
+        }
+        if self.concurrent_tool_calls == Some(false) {
+            builder = builder.disable_concurrent_tool_call();
+        }
+        if let Some(budget) = &self.budget {
+            builder = builder.budget(budget.clone());
+        }
+        if let Some(pricing) = self.pricing {
+            builder = builder.pricing(pricing);
+        }
+        builder = builder.verbose(self.verbose);
+
+        for name in &self.tools {
+            let tool = tools
+                .get(name)
+                .ok_or_else(|| SpecError::UnknownTool(name.clone()))?;
+            builder = builder.add_shared_tool(tool);
+        }
+
+        for server in &self.mcp_servers {
+            builder = match server {
+                McpServerSpec::Stdio { command, args } => {
+                    builder.try_add_stdio_mcp_server(command, args).await?
+                },
+                McpServerSpec::Sse { name, url } => {
+                    builder.try_add_sse_mcp_server(name, url.as_str()).await?
+                },
+                McpServerSpec::StreamableHttp {
+                    name,
+                    url,
+                    headers,
+                    bearer_token_env,
+                } => {
+                    let mut connector = StreamableHttpConnector::new(url.as_str())?;
+                    for (header, value) in headers {
+                        connector = connector.header(header, value)?;
+                    }
+                    if let Some(env) = bearer_token_env {
+                        let token = std::env::var(env)
+                            .map_err(|_| SpecError::MissingApiKey(env.clone()))?;
+                        connector = connector.bearer_auth(token)?;
+                    }
+                    builder
+                        .try_add_streamable_http_mcp_server(name, connector)
+                        .await?
+                },
+            };
+        }
+
+        Ok(builder)
+    }
+}
+
+impl ProviderSpec {
+    fn api_key(&self, default_env: &str) -> Result<String, SpecError> {
+        let env = self.api_key_env.as_deref().unwrap_or(default_env);
+        std::env::var(env).map_err(|_| SpecError::MissingApiKey(env.to_owned()))
+    }
+}
+
+/// Builds agents from [`AgentSpec`]s.
+#[derive(Clone, Default)]
+pub struct AgentLoader {
+    tools: ToolRegistry,
+}
+
+impl AgentLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The tools that specs can refer to by name.
+    pub fn tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Loads a spec file and builds its agent.
+    pub async fn load(&self, path: impl AsRef<Path>) -> Result<Box<dyn Agent>, SpecError> {
+        let spec = AgentSpec::from_file(path).await?;
+        self.build(&spec).await
+    }
+
+    /// Builds the agent of `spec` with the model of its provider.
+    pub async fn build(&self, spec: &AgentSpec) -> Result<Box<dyn Agent>, SpecError> {
+        let provider = spec.provider.as_ref().ok_or(SpecError::MissingProvider)?;
+        match provider.name.to_lowercase().as_str() {
+            "openai" => {
+                let api_key = provider.api_key("OPENAI_API_KEY")?;
+                let base_url = provider
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| "https://api.openai.com/v1".to_owned());
+                let mut model = OpenAI::from_url(base_url, api_key);
+                if let Some(name) = &provider.model {
+                    model = model.set_model(name);
+                }
+                self.build_with_model(spec, model).await
+            },
+            "anthropic" => {
+                let api_key = provider.api_key("ANTHROPIC_API_KEY")?;
+                let base_url = provider
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| "https://api.anthropic.com".to_owned());
+                let mut model = Anthropic::from_url(base_url, api_key);
+                if let Some(name) = &provider.model {
+                    model = model.set_model(name);
+                }
+                self.build_with_model(spec, model).await
+            },
+            _ => Err(SpecError::UnknownProvider(provider.name.clone())),
+        }
+    }
+
+    async fn build_with_model<M>(
+        &self,
+        spec: &AgentSpec,
+        model: M,
+    ) -> Result<Box<dyn Agent>, SpecError>
+    where
+        M: llm::Model + Clone + Send + Sync + 'static,
+        M::RawCompletionResponse: Clone + Send + Sync,
+    {
+        let builder = spec
+            .apply(SwarmsAgentBuilder::new_with_model(model), &self.tools)
+            .await?;
+        Ok(Box::new(builder.try_build()?))
+    }
+}
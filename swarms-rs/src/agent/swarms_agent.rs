@@ -83,48 +83,70 @@
 //! - **Task Hashing**: Efficient state management using content-based hashing
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
     hash::{Hash, Hasher},
     ops::Deref,
-    path::Path,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Local};
 use colored::*;
 use dashmap::DashMap;
 use futures::{StreamExt, future::BoxFuture, stream};
 use reqwest::IntoUrl;
-use rmcp::{
-    ServiceExt,
-    model::{ClientCapabilities, ClientInfo, Implementation},
-    transport::{SseTransport, TokioChildProcess},
-};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use swarms_macro::tool;
 use thiserror::Error;
-use tokio::{
-    process::Command,
-    sync::{Mutex, mpsc},
-};
+use tokio::sync::{Mutex, mpsc};
+use tokio_util::sync::CancellationToken;
 use twox_hash::XxHash3_64;
 
 use crate::{
     self as swarms_rs,
     llm::{
         self,
-        request::{CompletionRequest, ToolDefinition},
+        capabilities::{estimate_output_tokens, estimate_tokens},
+        completion::ToolFunction,
+        request::{CompletionRequest, ToolDefinition, Usage},
     },
     log_agent, log_error_ctx, log_llm, log_memory, log_perf, log_task,
+    prompts::template::{PromptContext, PromptTemplate, TemplateError},
     structs::{
-        conversation::{AgentShortMemory, Role},
+        agent_tool::{self, Delegation},
+        artifact::{
+            Artifact, ArtifactStore, FsArtifactStore, SAVE_ARTIFACT_TOOL_NAME, SaveArtifactTool,
+        },
+        budget::{BudgetExceeded, BudgetTracker, ModelPricing, RunBudget},
+        conversation::{AgentConversation, AgentShortMemory, Role},
+        handoff::{HandoffRequest, TRANSFER_TOOL_PREFIX, TransferTool},
+        mcp::{
+            MCPClient, MCPError, MCPResourceContext, MCPServerStatus, MCPToolOptions, SseConnector,
+            StdioConnector, StreamableHttpConnector,
+        },
+        memory::{Memory, MetadataFilter},
         persistence,
-        tool::{MCPTool, Tool, ToolDyn},
+        tool::{DuplicateToolPolicy, MCPResourceTool, Tool, ToolDyn, ToolError},
+        tool_registry::{ToolFilter, ToolMetadata, ToolRegistry},
     },
 };
 
 use crate::structs::agent::{Agent, AgentConfig, AgentError};
 
+use super::{
+    approval::{ApprovalDecision, ApprovalHandler, ApprovalPolicy, ApprovalRequest},
+    guardrail::{self, GUARDRAIL_ROLE, Guardrail, GuardrailAction, Guardrails, OutputCheck},
+    hooks::{AgentHook, HookAction, HookContext},
+    output_processor::{OUTPUT_CLEANER_ROLE, OutputPipeline, OutputProcessor},
+    plan::{self, Plan, StepStatus, StepUpdate, UPDATE_PLAN_TOOL_NAME, UpdatePlan},
+    reflexion::{self, CRITIC_ROLE, Critique, CritiqueError, Reflexion},
+    run_result::{self, RunResult, RunTimings, TerminationReason, TranscriptEntry},
+    session::{AgentSession, Sessions},
+};
+
 /// Builder pattern implementation for creating `SwarmsAgent` instances with customizable configuration.
 ///
 /// The `SwarmsAgentBuilder` provides a fluent interface for configuring all aspects of an agent
@@ -166,10 +188,46 @@ where
     config: AgentConfig,
     /// Optional system prompt to guide agent behavior
     system_prompt: Option<String>,
-    /// List of tool definitions available to the agent
-    tools: Vec<ToolDefinition>,
-    /// Implementation instances of tools, keyed by tool name
-    tools_impl: DashMap<String, Arc<dyn ToolDyn>>,
+    /// Template the system prompt is rendered from, if any
+    system_prompt_template: Option<PromptTemplate>,
+    /// Variables of the system prompt template
+    prompt_context: PromptContext,
+    /// Tools in the order they were added, duplicate names included
+    tools: Vec<Arc<dyn ToolDyn>>,
+    /// What to do with tools added with the name of a tool already added
+    duplicate_tool_policy: DuplicateToolPolicy,
+    /// Whether tools are left out instead of failing if the model doesn't support them
+    drop_unsupported_tools: bool,
+    /// Registry of tools that can change while the agent is running
+    tool_registry: Option<ToolRegistry>,
+    /// Tools of the registry the agent may use
+    tool_filter: ToolFilter,
+    /// Optional long-term memory used for retrieval
+    long_term_memory: Option<Arc<dyn Memory>>,
+    /// Lifecycle hooks, called in registration order
+    hooks: Vec<Arc<dyn AgentHook>>,
+    /// Approval policies, keyed by tool name
+    approval_policies: HashMap<String, ApprovalPolicy>,
+    /// Handler asked to approve tool calls
+    approval_handler: Option<Arc<dyn ApprovalHandler>>,
+    /// Timeout and retry policies, keyed by tool name
+    tool_policies: HashMap<String, ToolPolicy>,
+    /// Policy of tools without their own policy
+    default_tool_policy: ToolPolicy,
+    /// Processors applied to the final answer
+    output_pipeline: OutputPipeline,
+    /// Checks of the task and the final answer
+    guardrails: Guardrails,
+    /// Sessions and their eviction settings
+    sessions: Sessions,
+    /// Clients of the MCP servers the tools of which were added
+    mcp_clients: Vec<MCPClient>,
+    /// MCP resources added to the system prompt
+    mcp_resource_contexts: Vec<MCPResourceContext>,
+    /// Store of the artifacts saved with the `save_artifact` tool
+    artifact_store: Option<Arc<dyn ArtifactStore>>,
+    /// Critique and revision of draft answers
+    reflexion: Option<Reflexion>,
 }
 
 impl<M> SwarmsAgentBuilder<M>
@@ -203,8 +261,26 @@ where
             model,
             config: AgentConfig::default(),
             system_prompt: None,
+            system_prompt_template: None,
+            prompt_context: PromptContext::new(),
             tools: vec![],
-            tools_impl: DashMap::new(),
+            duplicate_tool_policy: DuplicateToolPolicy::default(),
+            drop_unsupported_tools: false,
+            tool_registry: None,
+            tool_filter: ToolFilter::default(),
+            long_term_memory: None,
+            hooks: vec![],
+            approval_policies: HashMap::new(),
+            approval_handler: None,
+            tool_policies: HashMap::new(),
+            default_tool_policy: ToolPolicy::default(),
+            output_pipeline: OutputPipeline::new(),
+            guardrails: Guardrails::default(),
+            sessions: Sessions::default(),
+            mcp_clients: Vec::new(),
+            mcp_resource_contexts: Vec::new(),
+            artifact_store: None,
+            reflexion: None,
         }
     }
 
@@ -269,6 +345,48 @@ where
     /// ```
     pub fn system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
         self.system_prompt = Some(system_prompt.into());
+        self.system_prompt_template = None;
+        self
+    }
+
+    /// Sets a system prompt [template](crate::prompts::template) rendered at the start of
+    /// each run. Besides the variables set with [`prompt_variable`](Self::prompt_variable),
+    /// it can use:
+    ///
+    /// - `date` and `datetime`: the current date, and date and time
+    /// - `agent_name`, `agent_description` and `user_name`
+    /// - `tools`: the tools of the agent, one `- name: description` line each
+    ///
+    /// Building the agent fails if the template uses any other variable, unless it is only
+    /// used under an `{{#if}}` on itself, e.g. `{{#if agents}}Your team:\n{{agents}}{{/if}}`.
+    ///
+    /// ```rust
+    /// use swarms_rs::agent::SwarmsAgentBuilder;
+    /// use swarms_rs::llm::provider::openai::OpenAI;
+    /// use swarms_rs::prompts::template::PromptTemplate;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let template = PromptTemplate::parse(
+    ///     "You are {{agent_name}}, an assistant of {{company}}. Today is {{date}}.",
+    /// )?;
+    /// let agent = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+    ///     .agent_name("Concierge")
+    ///     .system_prompt_template(template)
+    ///     .prompt_variable("company", "ACME")
+    ///     .try_build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn system_prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.system_prompt = Some(template.source().to_owned());
+        self.system_prompt_template = Some(template);
+        self
+    }
+
+    /// Sets a variable of the system prompt template. Variables set here take precedence
+    /// over the ones set for each run.
+    pub fn prompt_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.prompt_context.insert(name, value);
         self
     }
 
@@ -313,18 +431,84 @@ where
     /// # Ok(())
     /// # }
     /// ```
-    pub fn add_tool<T: Tool + 'static>(mut self, tool: T) -> Self {
-        self.tools.push(tool.definition());
-        self.tools_impl
-            .insert(tool.name().to_string(), Arc::new(tool) as Arc<dyn ToolDyn>);
+    pub fn add_tool<T: Tool + 'static>(self, tool: T) -> Self {
+        self.add_shared_tool(Arc::new(tool))
+    }
+
+    /// Adds a tool that may be shared with other agents, e.g. from a
+    /// [`ToolRegistry`](crate::structs::tool_registry::ToolRegistry).
+    pub fn add_shared_tool(mut self, tool: Arc<dyn ToolDyn>) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Offers the enabled tools of `registry` to the model on each request, as they are at
+    /// that time, so tools can be added, removed, enabled and disabled while the agent is
+    /// running. Tools added to the agent itself take precedence over registry tools of the
+    /// same name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use swarms_rs::agent::SwarmsAgentBuilder;
+    /// use swarms_rs::llm::provider::openai::OpenAI;
+    /// use swarms_rs::structs::tool_registry::{SideEffects, ToolFilter, ToolRegistry};
+    ///
+    /// let registry = ToolRegistry::new();
+    /// let researcher = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+    ///     .tool_registry_with(
+    ///         registry.clone(),
+    ///         ToolFilter::new().max_side_effects(SideEffects::ReadOnly),
+    ///     )
+    ///     .build();
+    /// let operator = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+    ///     .tool_registry(registry.clone())
+    ///     .build();
+    /// // Tools registered from now on are offered to both agents, as their filters allow
+    /// ```
+    pub fn tool_registry(self, registry: ToolRegistry) -> Self {
+        self.tool_registry_with(registry, ToolFilter::default())
+    }
+
+    /// Like [`tool_registry`](Self::tool_registry), offering only the tools allowed by
+    /// `filter`.
+    pub fn tool_registry_with(mut self, registry: ToolRegistry, filter: ToolFilter) -> Self {
+        self.tool_registry = Some(registry);
+        self.tool_filter = filter;
+        self
+    }
+
+    /// Builds the agent without its tools, with a warning, if the model doesn't support
+    /// tools, instead of failing to build it.
+    pub fn drop_unsupported_tools(mut self) -> Self {
+        self.drop_unsupported_tools = true;
+        self
+    }
+
+    /// What [`try_build`](Self::try_build) and [`SwarmsAgent::tool`] do with tools added with
+    /// the name of a tool already added, built-in tools included (default: fail with
+    /// `AgentError::DuplicateTool`).
+    pub fn duplicate_tool_policy(mut self, policy: DuplicateToolPolicy) -> Self {
+        self.duplicate_tool_policy = policy;
         self
     }
 
+    /// Lets the agent hand the conversation off to the agent named `target`, with a generated
+    /// `transfer_to_<target>` tool. `description` tells the model when to hand off.
+    ///
+    /// Calling the tool ends the run with
+    /// [`TerminationReason::Handoff`](super::run_result::TerminationReason::Handoff); a
+    /// [`HandoffWorkflow`](crate::structs::handoff::HandoffWorkflow) then continues the
+    /// conversation with the target agent.
+    pub fn add_handoff(self, target: impl Into<String>, description: impl Into<String>) -> Self {
+        self.add_shared_tool(Arc::new(TransferTool::new(target, description)))
+    }
+
     /// Adds tools from an MCP (Model Context Protocol) server via SSE (Server-Sent Events).
     ///
     /// This method connects to an external MCP server over HTTP/SSE and automatically
     /// adds all available tools from that server to the agent. The connection is
-    /// established asynchronously and tools are loaded during the build process.
+    /// re-established when it is lost, see [`MCPClient`].
     ///
     /// # Arguments
     ///
@@ -354,34 +538,61 @@ where
     /// - The SSE transport cannot be established
     /// - The MCP server handshake fails
     /// - Tool listing from the server fails
+    ///
+    /// Use [`try_add_sse_mcp_server`](Self::try_add_sse_mcp_server) to handle these as
+    /// errors instead.
     pub async fn add_sse_mcp_server(self, name: impl Into<String>, url: impl IntoUrl) -> Self {
-        let name = name.into();
-
-        let transport = SseTransport::start(url)
+        self.try_add_sse_mcp_server(name, url)
             .await
-            .expect("Failed to start SSE transport");
-
-        let client_info = ClientInfo {
-            protocol_version: Default::default(),
-            capabilities: ClientCapabilities::default(),
-            client_info: Implementation {
-                name: name.clone(),
-                version: "".to_owned(),
-            },
-        };
+            .expect("Failed to add MCP server")
+    }
 
-        let client = Arc::new(
-            client_info
-                .into_dyn()
-                .serve(transport)
-                .await
-                .expect("Failed to start MCP server"),
-        );
+    /// Adds tools from an MCP server via SSE, like
+    /// [`add_sse_mcp_server`](Self::add_sse_mcp_server), returning an error if the server
+    /// can't be connected to within [`DEFAULT_CONNECT_TIMEOUT`](crate::structs::mcp::DEFAULT_CONNECT_TIMEOUT)
+    /// or its tools can't be listed.
+    pub async fn try_add_sse_mcp_server(
+        self,
+        name: impl Into<String>,
+        url: impl IntoUrl,
+    ) -> Result<Self, MCPError> {
+        let client = MCPClient::builder(name, SseConnector::new(url)?)
+            .connect()
+            .await?;
+        self.add_mcp_client(&client).await
+    }
 
-        let mcp_tools = client.list_all_tools().await.expect("Failed to list tools");
-        mcp_tools.into_iter().fold(self, |acc, tool| {
-            acc.add_tool(MCPTool::from_server(tool, Arc::clone(&client)))
-        })
+    /// Adds tools from an MCP server via Streamable HTTP, connected to with `connector`,
+    /// which sets the URL of the server and the headers to send, e.g. a bearer token. The
+    /// connection is re-established when it is lost, see [`MCPClient`].
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the server can't be connected to or its tools can't be
+    /// listed. Use [`try_add_streamable_http_mcp_server`](Self::try_add_streamable_http_mcp_server)
+    /// to handle these as errors instead.
+    pub async fn add_streamable_http_mcp_server(
+        self,
+        name: impl Into<String>,
+        connector: StreamableHttpConnector,
+    ) -> Self {
+        self.try_add_streamable_http_mcp_server(name, connector)
+            .await
+            .expect("Failed to add MCP server")
+    }
+
+    /// Adds tools from an MCP server via Streamable HTTP, like
+    /// [`add_streamable_http_mcp_server`](Self::add_streamable_http_mcp_server), returning an
+    /// error if the server can't be connected to within
+    /// [`DEFAULT_CONNECT_TIMEOUT`](crate::structs::mcp::DEFAULT_CONNECT_TIMEOUT) or its tools
+    /// can't be listed.
+    pub async fn try_add_streamable_http_mcp_server(
+        self,
+        name: impl Into<String>,
+        connector: StreamableHttpConnector,
+    ) -> Result<Self, MCPError> {
+        let client = MCPClient::builder(name, connector).connect().await?;
+        self.add_mcp_client(&client).await
     }
 
     /// Adds tools from an MCP server via stdio (standard input/output).
@@ -389,7 +600,7 @@ where
     /// This method launches an external process that implements the MCP protocol
     /// over stdio and automatically adds all available tools from that process
     /// to the agent. This is useful for integrating with command-line tools or
-    /// scripts that implement MCP.
+    /// scripts that implement MCP. The process is restarted if it dies, see [`MCPClient`].
     ///
     /// # Type Parameters
     ///
@@ -424,28 +635,138 @@ where
     /// - The child process cannot be spawned
     /// - The MCP server handshake fails
     /// - Tool listing from the server fails
+    ///
+    /// Use [`try_add_stdio_mcp_server`](Self::try_add_stdio_mcp_server) to handle these as
+    /// errors instead.
     pub async fn add_stdio_mcp_server<I, S>(self, command: S, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let service = Arc::new(
-            ().into_dyn()
-                .serve(TokioChildProcess::new(Command::new(command).args(args)).unwrap())
-                .await
-                .expect("Failed to start MCP server"),
-        );
+        self.try_add_stdio_mcp_server(command, args)
+            .await
+            .expect("Failed to add MCP server")
+    }
+
+    /// Adds tools from an MCP server via stdio, like
+    /// [`add_stdio_mcp_server`](Self::add_stdio_mcp_server), returning an error if the
+    /// process can't be started, doesn't complete the handshake within
+    /// [`DEFAULT_CONNECT_TIMEOUT`](crate::structs::mcp::DEFAULT_CONNECT_TIMEOUT), or its
+    /// tools can't be listed.
+    pub async fn try_add_stdio_mcp_server<I, S>(self, command: S, args: I) -> Result<Self, MCPError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let name = command.as_ref().to_string_lossy().into_owned();
+        let client = MCPClient::builder(name, StdioConnector::new(command, args))
+            .connect()
+            .await?;
+        self.add_mcp_client(&client).await
+    }
 
-        let mcp_tools = service
-            .list_all_tools()
+    /// Adds the tools of the server of `client`. Its status is then reported by
+    /// [`SwarmsAgent::mcp_server_status`].
+    ///
+    /// The tools keep their names, which may be the names of tools of other servers: use
+    /// [`add_mcp_client_with`](Self::add_mcp_client_with) to prefix or rename them.
+    pub async fn add_mcp_client(self, client: &MCPClient) -> Result<Self, MCPError> {
+        self.add_mcp_client_with(client, &MCPToolOptions::default())
             .await
-            .expect("Failed to list tools");
-        mcp_tools.into_iter().fold(self, |acc, tool| {
-            acc.add_tool(MCPTool::from_server(tool, Arc::clone(&service)))
-        })
     }
 
-    pub fn build(mut self) -> SwarmsAgent<M> {
+    /// Adds the tools of the server of `client` like
+    /// [`add_mcp_client`](Self::add_mcp_client), filtered and named as set by `options`.
+    ///
+    /// ```rust,no_run
+    /// use swarms_rs::agent::SwarmsAgentBuilder;
+    /// use swarms_rs::llm::provider::openai::OpenAI;
+    /// use swarms_rs::structs::mcp::{MCPClient, MCPToolOptions, StdioConnector};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let web = MCPClient::builder("web", StdioConnector::new("mcp-web", []))
+    ///     .connect()
+    ///     .await?;
+    /// let docs = MCPClient::builder("docs", StdioConnector::new("mcp-docs", []))
+    ///     .connect()
+    ///     .await?;
+    /// // Both servers have a `search` tool
+    /// let agent = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+    ///     .add_mcp_client_with(&web, &MCPToolOptions::new().server_prefix())
+    ///     .await?
+    ///     .add_mcp_client_with(&docs, &MCPToolOptions::new().alias("search", "search_docs"))
+    ///     .await?
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_mcp_client_with(
+        mut self,
+        client: &MCPClient,
+        options: &MCPToolOptions,
+    ) -> Result<Self, MCPError> {
+        let tools = client.tools(options).await?;
+        self.track_mcp_client(client);
+        Ok(tools.into_iter().fold(self, |acc, tool| acc.add_tool(tool)))
+    }
+
+    /// Adds tools to list and read the resources of the server of `client`, see
+    /// [`MCPResourceTool`].
+    pub fn add_mcp_resource_tools(mut self, client: &MCPClient) -> Self {
+        self.track_mcp_client(client);
+        self.add_shared_tool(Arc::new(MCPResourceTool::list(client.clone())))
+            .add_shared_tool(Arc::new(MCPResourceTool::read(client.clone())))
+    }
+
+    /// Adds resource `uri` of the server of `client` to the system prompt, kept up to date as
+    /// described in [`MCPClient::resource_context`]. Each run uses the latest text of the
+    /// resource.
+    pub async fn add_mcp_resource_context(
+        mut self,
+        client: &MCPClient,
+        uri: impl Into<String>,
+    ) -> Result<Self, MCPError> {
+        let context = client.resource_context(uri).await?;
+        self.track_mcp_client(client);
+        self.mcp_resource_contexts.push(context);
+        Ok(self)
+    }
+
+    fn track_mcp_client(&mut self, client: &MCPClient) {
+        if !self.mcp_clients.iter().any(|added| added.ptr_eq(client)) {
+            self.mcp_clients.push(client.clone());
+        }
+    }
+
+    /// Builds the agent.
+    ///
+    /// Unlike [`try_build`](Self::try_build), the configuration is fitted to the model's
+    /// [`capabilities`](llm::Model::capabilities) with a warning: `max_tokens` is lowered to
+    /// the model's output limit, and the built-in tools are left out if the model doesn't
+    /// support tools.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration is invalid otherwise, e.g. tools with the same name, or
+    /// tools added to an agent the model of which doesn't support tools, unless
+    /// [`drop_unsupported_tools`](Self::drop_unsupported_tools) is set.
+    /// Use [`SwarmsAgentBuilder::try_build`] to handle this as an error instead.
+    pub fn build(self) -> SwarmsAgent<M> {
+        match self.build_with(false) {
+            Ok(agent) => agent,
+            Err(e) => panic!("Failed to build SwarmsAgent: {e}"),
+        }
+    }
+
+    /// Builds the agent, validating the configured tools and `max_tokens` against the
+    /// model's [`capabilities`](llm::Model::capabilities).
+    pub fn try_build(self) -> Result<SwarmsAgent<M>, AgentError> {
+        self.build_with(true)
+    }
+
+    /// Builds the agent. Unless `strict`, `max_tokens` and built-in tools the model doesn't
+    /// support are adjusted instead of failing.
+    fn build_with(mut self, strict: bool) -> Result<SwarmsAgent<M>, AgentError> {
         if self.config.verbose && log::log_enabled!(log::Level::Info) {
             log::info!(
                 "🏗️  Building SwarmsAgent: {}",
@@ -453,6 +774,8 @@ where
             );
         }
 
+        let added_tools = !self.tools.is_empty() || self.tool_registry.is_some();
+        let mut tools = Vec::with_capacity(self.tools.len() + 2);
         if self.config.task_evaluator_tool_enabled {
             if self.config.verbose {
                 log::debug!(
@@ -460,32 +783,109 @@ where
                     self.config.name.bright_cyan()
                 );
             }
-            self.tools.insert(0, ToolDyn::definition(&TaskEvaluator));
-            self.tools_impl.insert(
-                ToolDyn::name(&TaskEvaluator),
-                Arc::new(TaskEvaluator) as Arc<dyn ToolDyn>,
+            tools.push(Arc::new(TaskEvaluator) as Arc<dyn ToolDyn>);
+        }
+        tools.append(&mut self.tools);
+        if self.config.plan_enabled {
+            tools.push(Arc::new(UpdatePlan));
+        }
+        let (mut tools, tools_impl) = resolve_tools(tools, self.duplicate_tool_policy)?;
+        // Called by the agent itself, which knows the store
+        if self.artifact_store.is_some() {
+            if tools_impl.contains_key(SAVE_ARTIFACT_TOOL_NAME) {
+                return Err(AgentError::DuplicateTool(
+                    SAVE_ARTIFACT_TOOL_NAME.to_owned(),
+                ));
+            }
+            tools.push(SaveArtifactTool::tool_definition());
+        }
+
+        let capabilities = self.model.capabilities();
+        if !strict
+            && let Some(limit) = capabilities.max_output_tokens
+            && self.config.max_tokens > limit
+        {
+            log::warn!(
+                "max_tokens {} of agent {} exceeds the limit of the model, using {limit}",
+                self.config.max_tokens,
+                self.config.name
             );
+            self.config.max_tokens = limit;
+        }
+        if !capabilities.tools
+            && (!tools.is_empty() || added_tools)
+            && (self.drop_unsupported_tools || (!strict && !added_tools))
+        {
+            if added_tools {
+                log::warn!(
+                    "The model of agent {} doesn't support tools, building it without tools",
+                    self.config.name
+                );
+            }
+            tools.clear();
+            tools_impl.clear();
+            self.tool_registry = None;
+        }
+        capabilities.validate_tools(&tools)?;
+        if let Some(registry) = &self.tool_registry {
+            capabilities.validate_tools(&registry.definitions(&self.tool_filter))?;
+        }
+        capabilities.validate_max_tokens(self.config.max_tokens)?;
+        if self.config.budget.max_cost.is_some() && self.config.pricing.is_none() {
+            return Err(AgentError::InvalidConfig(
+                "a cost budget requires model pricing".to_owned(),
+            ));
+        }
+        if let Some(reflexion) = &self.reflexion
+            && !(0.0..=10.0).contains(&reflexion.threshold)
+        {
+            return Err(AgentError::InvalidConfig(format!(
+                "reflexion threshold {} is not between 0 and 10",
+                reflexion.threshold
+            )));
         }
 
         let agent = SwarmsAgent {
             model: self.model,
             config: self.config.clone(),
             system_prompt: self.system_prompt,
+            system_prompt_template: self.system_prompt_template,
+            prompt_context: self.prompt_context,
             short_memory: AgentShortMemory::new(),
-            tools: self.tools.clone(),
-            tools_impl: self.tools_impl,
+            session_memory: AgentShortMemory::new(),
+            tools,
+            tools_impl,
+            duplicate_tool_policy: self.duplicate_tool_policy,
+            tool_registry: self.tool_registry,
+            tool_filter: self.tool_filter,
+            long_term_memory: self.long_term_memory,
+            task_progress: DashMap::new(),
+            retrieved_memories: DashMap::new(),
+            hooks: self.hooks,
+            approval_policies: self.approval_policies,
+            approval_handler: self.approval_handler,
+            tool_policies: self.tool_policies,
+            default_tool_policy: self.default_tool_policy,
+            output_pipeline: self.output_pipeline,
+            guardrails: self.guardrails,
+            sessions: self.sessions,
+            mcp_clients: self.mcp_clients,
+            mcp_resource_contexts: self.mcp_resource_contexts,
+            artifact_store: self.artifact_store,
+            reflexion: self.reflexion,
         };
+        validate_system_prompt(agent.system_prompt_template.as_ref(), &agent.prompt_context)?;
 
         if agent.config.verbose && log::log_enabled!(log::Level::Info) {
             log::info!(
                 "✅ SwarmsAgent built successfully: {} (ID: {}) with {} tools",
                 agent.config.name.bright_cyan().bold(),
                 agent.config.id.bright_yellow(),
-                self.tools.len().to_string().bright_green().bold()
+                agent.tools.len().to_string().bright_green().bold()
             );
         }
 
-        agent
+        Ok(agent)
     }
 
     // Configuration methods
@@ -520,12 +920,23 @@ where
         self
     }
 
+    /// Enables planning: before the first loop the agent asks its model for a
+    /// [`Plan`], then works through its steps, reporting them with the `update_plan` tool.
+    /// The task is appended to `planning_prompt`, which defaults to a generic prompt.
+    /// See [`plan`](super::plan).
     pub fn enable_plan(mut self, planning_prompt: impl Into<Option<String>>) -> Self {
         self.config.plan_enabled = true;
         self.config.planning_prompt = planning_prompt.into();
         self
     }
 
+    /// Maximum number of times the remaining steps of a plan are re-planned after a step
+    /// fails, per run (default: 2).
+    pub fn max_replans(mut self, max_replans: u32) -> Self {
+        self.config.max_replans = max_replans;
+        self
+    }
+
     pub fn enable_autosave(mut self) -> Self {
         self.config.autosave = true;
         self
@@ -541,6 +952,152 @@ where
         self
     }
 
+    /// Sets the long-term memory the agent retrieves context from.
+    ///
+    /// Relevant documents are queried with the task before the first loop and added to
+    /// the conversation. With [`enable_rag_every_loop`](Self::enable_rag_every_loop), the
+    /// memory is queried again at the start of every following loop, and documents not yet
+    /// in the conversation are added.
+    pub fn long_term_memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        self.long_term_memory = Some(memory);
+        self
+    }
+
+    /// Registers a lifecycle hook. Hooks are called in the order they are added.
+    ///
+    /// See [`AgentHook`] for the available events.
+    pub fn add_hook(mut self, hook: impl AgentHook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Adds a processor for the final answer. Processors run in the order they are added.
+    ///
+    /// Once the agent loop finishes, the final answer goes through the processors and the
+    /// result is added to the conversation under the [`OUTPUT_CLEANER_ROLE`] assistant role.
+    /// If a processor fails, the error is logged and the answer is left unprocessed.
+    /// See [`output_processor`](super::output_processor) for the available processors.
+    pub fn add_output_processor(mut self, processor: impl OutputProcessor + 'static) -> Self {
+        self.output_pipeline.push(processor);
+        self
+    }
+
+    /// Adds a guardrail checking the task before each run. A tripped input guardrail fails
+    /// the run with `AgentError::GuardrailTripped`; a redacting one replaces the task in the
+    /// conversation. See [`guardrail`](super::guardrail) for the available guardrails.
+    pub fn add_input_guardrail(mut self, guardrail: impl Guardrail + 'static) -> Self {
+        self.guardrails.add_input(Arc::new(guardrail));
+        self
+    }
+
+    /// Adds a guardrail checking the final answer of each run, after the output processors.
+    /// `action` tells whether a tripped guardrail fails the run or asks the model for a
+    /// revised answer; a redacting one replaces the answer in the conversation.
+    pub fn add_output_guardrail(
+        mut self,
+        guardrail: impl Guardrail + 'static,
+        action: GuardrailAction,
+    ) -> Self {
+        self.guardrails.add_output(Arc::new(guardrail), action);
+        self
+    }
+
+    /// Ends the [sessions](super::session) idle for longer than `ttl`.
+    pub fn session_ttl(mut self, ttl: Duration) -> Self {
+        self.sessions.set_ttl(ttl);
+        self
+    }
+
+    /// Keeps at most `max_sessions` [sessions](super::session), ending the least recently
+    /// used ones.
+    pub fn max_sessions(mut self, max_sessions: usize) -> Self {
+        self.sessions.set_max_sessions(max_sessions);
+        self
+    }
+
+    /// Lets the agent save artifacts to `store` with the built-in `save_artifact` tool.
+    ///
+    /// Artifacts are stored under the ID of the run and listed in its [`RunResult`].
+    /// See [`artifact`](crate::structs::artifact).
+    pub fn artifact_store(mut self, store: impl ArtifactStore + 'static) -> Self {
+        self.artifact_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Lets the agent save artifacts to the default [`FsArtifactStore`] (`./temp/artifacts`).
+    pub fn enable_artifacts(self) -> Self {
+        self.artifact_store(FsArtifactStore::default())
+    }
+
+    /// Enables reflexion mode: a critic scores every draft answer against the task and
+    /// rubric, and the agent revises the answer until it passes the threshold, runs out of
+    /// revisions or reaches `max_loops`. Every critique is listed in the [`RunResult`].
+    ///
+    /// Without a [`critic`](Reflexion::critic), the agent's own model is the critic and its
+    /// calls count in the run budget. See [`reflexion`](super::reflexion).
+    pub fn enable_reflexion(mut self, reflexion: Reflexion) -> Self {
+        self.reflexion = Some(reflexion);
+        self
+    }
+
+    /// Sets the approval policy of a tool. Tools without a policy never need approval.
+    ///
+    /// Calls that need approval are sent to the [`approval_handler`](Self::approval_handler)
+    /// before the tool runs, and denied if there is none.
+    pub fn require_approval(
+        mut self,
+        tool_name: impl Into<String>,
+        policy: ApprovalPolicy,
+    ) -> Self {
+        self.approval_policies.insert(tool_name.into(), policy);
+        self
+    }
+
+    /// Sets the handler asked to approve tool calls.
+    pub fn approval_handler(mut self, handler: impl ApprovalHandler + 'static) -> Self {
+        self.approval_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Sets the timeout and retry policy of a tool, overriding the
+    /// [`default_tool_policy`](Self::default_tool_policy).
+    pub fn tool_policy(mut self, tool_name: impl Into<String>, policy: ToolPolicy) -> Self {
+        self.tool_policies.insert(tool_name.into(), policy);
+        self
+    }
+
+    /// Sets the timeout and retry policy of tools without their own policy.
+    pub fn default_tool_policy(mut self, policy: ToolPolicy) -> Self {
+        self.default_tool_policy = policy;
+        self
+    }
+
+    /// Number of documents retrieved from long-term memory per query (default: 3).
+    pub fn long_term_memory_top_k(mut self, top_k: usize) -> Self {
+        self.config.long_term_memory_top_k = top_k;
+        self
+    }
+
+    /// Only retrieves documents from long-term memory whose metadata matches `filter`.
+    pub fn long_term_memory_filter(mut self, filter: MetadataFilter) -> Self {
+        self.config.long_term_memory_filter = Some(filter);
+        self
+    }
+
+    /// Limits the tokens, tool calls, cost and time of each run.
+    ///
+    /// See [`RunBudget`] for how exhausted budgets end a run.
+    pub fn budget(mut self, budget: RunBudget) -> Self {
+        self.config.budget = budget;
+        self
+    }
+
+    /// Sets the price of the model, used for [`RunBudget::max_cost`].
+    pub fn pricing(mut self, pricing: ModelPricing) -> Self {
+        self.config.pricing = Some(pricing);
+        self
+    }
+
     pub fn save_state_dir(mut self, dir: impl Into<String>) -> Self {
         self.config.save_state_dir = Some(dir.into());
         self
@@ -701,13 +1258,77 @@ where
     config: AgentConfig,
     /// Optional system prompt that guides agent behavior
     system_prompt: Option<String>,
+    /// Template the system prompt is rendered from for each run (not serialized)
+    #[serde(skip)]
+    system_prompt_template: Option<PromptTemplate>,
+    /// Variables of the system prompt template (not serialized)
+    #[serde(skip)]
+    prompt_context: PromptContext,
     /// Short-term memory for maintaining conversation history
     short_memory: AgentShortMemory,
+    /// Conversations of the sessions, by session ID (not serialized)
+    #[serde(skip)]
+    session_memory: AgentShortMemory,
     /// List of available tool definitions
     tools: Vec<ToolDefinition>,
     /// Tool implementation instances (not serialized)
     #[serde(skip)]
     tools_impl: DashMap<String, Arc<dyn ToolDyn>>,
+    /// What [`tool`](Self::tool) does with a tool added with the name of a tool of the agent
+    /// (not serialized)
+    #[serde(skip)]
+    duplicate_tool_policy: DuplicateToolPolicy,
+    /// Registry of tools that can change while the agent is running (not serialized)
+    #[serde(skip)]
+    tool_registry: Option<ToolRegistry>,
+    /// Tools of the registry the agent may use (not serialized)
+    #[serde(skip)]
+    tool_filter: ToolFilter,
+    /// Optional long-term memory used for retrieval (not serialized)
+    #[serde(skip)]
+    long_term_memory: Option<Arc<dyn Memory>>,
+    /// Loop progress of each task, saved alongside the conversation
+    #[serde(skip)]
+    task_progress: DashMap<ConversationKey, TaskProgress>,
+    /// IDs of the long-term memory documents added to each conversation (not serialized)
+    #[serde(skip)]
+    retrieved_memories: DashMap<ConversationKey, HashSet<String>>,
+    /// Lifecycle hooks (not serialized)
+    #[serde(skip)]
+    hooks: Vec<Arc<dyn AgentHook>>,
+    /// Approval policies, keyed by tool name (not serialized)
+    #[serde(skip)]
+    approval_policies: HashMap<String, ApprovalPolicy>,
+    /// Handler asked to approve tool calls (not serialized)
+    #[serde(skip)]
+    approval_handler: Option<Arc<dyn ApprovalHandler>>,
+    /// Timeout and retry policies, keyed by tool name (not serialized)
+    #[serde(skip)]
+    tool_policies: HashMap<String, ToolPolicy>,
+    /// Policy of tools without their own policy (not serialized)
+    #[serde(skip)]
+    default_tool_policy: ToolPolicy,
+    /// Processors applied to the final answer (not serialized)
+    #[serde(skip)]
+    output_pipeline: OutputPipeline,
+    /// Checks of the task and the final answer (not serialized)
+    #[serde(skip)]
+    guardrails: Guardrails,
+    /// Sessions and when they were last used (not serialized)
+    #[serde(skip)]
+    sessions: Sessions,
+    /// Clients of the MCP servers of the tools (not serialized)
+    #[serde(skip)]
+    mcp_clients: Vec<MCPClient>,
+    /// MCP resources added to the system prompt (not serialized)
+    #[serde(skip)]
+    mcp_resource_contexts: Vec<MCPResourceContext>,
+    /// Store of the artifacts saved with the `save_artifact` tool (not serialized)
+    #[serde(skip)]
+    artifact_store: Option<Arc<dyn ArtifactStore>>,
+    /// Critique and revision of draft answers (not serialized)
+    #[serde(skip)]
+    reflexion: Option<Reflexion>,
 }
 
 impl<M> SwarmsAgent<M>
@@ -741,10 +1362,31 @@ where
         Self {
             model,
             system_prompt: system_prompt.into(),
+            system_prompt_template: None,
+            prompt_context: PromptContext::new(),
             config: AgentConfig::default(),
             short_memory: AgentShortMemory::new(),
+            session_memory: AgentShortMemory::new(),
             tools: vec![],
             tools_impl: DashMap::new(),
+            duplicate_tool_policy: DuplicateToolPolicy::default(),
+            tool_registry: None,
+            tool_filter: ToolFilter::default(),
+            long_term_memory: None,
+            task_progress: DashMap::new(),
+            retrieved_memories: DashMap::new(),
+            hooks: vec![],
+            approval_policies: HashMap::new(),
+            approval_handler: None,
+            tool_policies: HashMap::new(),
+            default_tool_policy: ToolPolicy::default(),
+            output_pipeline: OutputPipeline::new(),
+            guardrails: Guardrails::default(),
+            sessions: Sessions::default(),
+            mcp_clients: Vec::new(),
+            mcp_resource_contexts: Vec::new(),
+            artifact_store: None,
+            reflexion: None,
         }
     }
 
@@ -798,33 +1440,49 @@ where
     /// # Errors
     ///
     /// Returns an `AgentError` if:
+    /// - The request uses tools or content the model doesn't support
     /// - The LLM request fails
-    /// - Tool execution fails
     /// - No response choice is available
+    ///
+    /// Failed tool calls don't fail the chat: they are returned as a [`ToolCallOutput`]
+    /// with an [`error`](ToolCallOutput::error) kind, so the model can correct itself.
     pub async fn chat(
         &self,
         prompt: impl Into<String>,
         chat_history: impl Into<Vec<llm::completion::Message>>,
     ) -> Result<ChatResponse, AgentError> {
-        let chat_history = chat_history.into();
+        self.chat_for_task(None, None, prompt.into(), chat_history.into())
+            .await
+    }
+
+    /// [`chat`](Self::chat) on behalf of `task`, so hooks know which task they are called for,
+    /// as part of `run` if there is one.
+    async fn chat_for_task(
+        &self,
+        task: Option<&str>,
+        run: Option<&RunContext>,
+        prompt: String,
+        chat_history: Vec<llm::completion::Message>,
+    ) -> Result<ChatResponse, AgentError> {
+        let ctx = self.hook_context(task);
 
         let request = CompletionRequest {
             prompt: llm::completion::Message::user(prompt),
-            system_prompt: self.system_prompt.clone(),
+            system_prompt: self.request_system_prompt(run)?,
             chat_history,
-            tools: self.tools.clone(),
+            tools: self.request_tools(),
             temperature: Some(self.config.temperature),
             max_tokens: Some(self.config.max_tokens),
         };
 
-        let response = self.model.completion(request).await?;
+        let choices = self.complete(&ctx, request, run).await?;
 
-        let choice = response.choice.first().ok_or(AgentError::NoChoiceFound)?;
+        let choice = choices.first().ok_or(AgentError::NoChoiceFound)?;
         match ToOwned::to_owned(choice) {
             llm::completion::AssistantContent::Text(text) => Ok(ChatResponse::Text(text.text)), // <--- return Text
             llm::completion::AssistantContent::ToolCall(tool_call) => {
                 let mut all_tool_calls = vec![tool_call.function];
-                all_tool_calls.extend(response.choice.iter().skip(1).filter_map(|choice| {
+                all_tool_calls.extend(choices.iter().skip(1).filter_map(|choice| {
                     match ToOwned::to_owned(choice) {
                         llm::completion::AssistantContent::Text(_) => None,
                         llm::completion::AssistantContent::ToolCall(tool_call) => {
@@ -834,73 +1492,27 @@ where
                 }));
 
                 // Call tools concurrently
+                let started = Instant::now();
                 let results = Arc::new(Mutex::new(Vec::new()));
                 if self.config.concurrent_tool_call_enabled {
                     stream::iter(all_tool_calls)
                         .for_each_concurrent(None, |tool_call| {
                             let results = Arc::clone(&results);
                             async move {
-                                let tool = Arc::clone(
-                                    match self.tools_impl.get(&tool_call.name) {
-                                        Some(tool) => tool,
-                                        None => {
-                                            tracing::error!("Tool not found: {}", tool_call.name);
-                                            results.lock().await.push(ToolCallOutput {
-                                                name: tool_call.name,
-                                                args: tool_call.arguments.to_string(),
-                                                result: "Tool not found".to_owned(),
-                                            });
-                                            return;
-                                        },
-                                    }
-                                    .deref(),
-                                );
-                                let args = tool_call.arguments.to_string();
-                                // execute tool
-                                let result = match tool.call(args.clone()).await {
-                                    Ok(result) => result,
-                                    Err(e) => {
-                                        tracing::error!(
-                                            "Failed to call tool<{}>, args: {}, error: {}",
-                                            tool.name(),
-                                            args,
-                                            e
-                                        );
-                                        results.lock().await.push(ToolCallOutput {
-                                            name: tool_call.name,
-                                            args,
-                                            result: e.to_string(),
-                                        });
-                                        return;
-                                    },
-                                };
-                                results.lock().await.push(ToolCallOutput {
-                                    name: tool_call.name,
-                                    args,
-                                    result,
-                                });
+                                let output = self.execute_tool_call(&ctx, tool_call, run).await;
+                                results.lock().await.push(output);
                             }
                         })
                         .await;
                 } else {
                     for tool_call in all_tool_calls {
-                        let tool = Arc::clone(
-                            self.tools_impl
-                                .get(&tool_call.name)
-                                .ok_or(AgentError::ToolNotFound(tool_call.name.clone()))?
-                                .deref(),
-                        );
-                        let args = tool_call.arguments.to_string();
-                        // execute tool
-                        let result_str = tool.call(args.clone()).await?;
-                        // collect results
-                        results.lock().await.push(ToolCallOutput {
-                            name: tool_call.name.clone(),
-                            args,
-                            result: result_str,
-                        });
+                        let output = self.execute_tool_call(&ctx, tool_call, run).await;
+                        results.lock().await.push(output);
                     }
                 }
+                if let Some(run) = run {
+                    *run.tool_duration.lock().unwrap() += started.elapsed();
+                }
 
                 Ok(ChatResponse::ToolCalls(
                     Arc::clone(&results).lock().await.clone(),
@@ -909,42 +1521,478 @@ where
         }
     }
 
-    pub async fn prompt(&self, prompt: impl Into<String>) -> Result<String, AgentError> {
-        let prompt = prompt.into();
-        let start_time = std::time::Instant::now();
+    /// Runs the `before_llm_call` hooks, validates the request against the model's
+    /// capabilities, sends it and returns the choices after the `after_llm_call` hooks.
+    ///
+    /// Within a run, the request is abandoned if the run is canceled, and the usage of the
+    /// completion is recorded in its budget. Usage is estimated from the request and response
+    /// text if the model doesn't report it.
+    async fn complete(
+        &self,
+        ctx: &HookContext<'_>,
+        request: CompletionRequest,
+        run: Option<&RunContext>,
+    ) -> Result<Vec<llm::completion::AssistantContent>, AgentError> {
+        self.complete_with(ctx, request, run, true).await
+    }
 
-        if self.config.verbose {
-            log_llm!(
-                info,
-                &self.config.name,
-                &self.config.id,
-                "Prompt Request",
-                "Sending prompt to LLM: '{}'",
-                prompt.chars().take(100).collect::<String>()
-            );
+    /// [`complete`](Self::complete), abandoning the completion with
+    /// `AgentError::BudgetExceeded` once the time budget of `run` is exhausted if
+    /// `enforce_time_budget`.
+    async fn complete_with(
+        &self,
+        ctx: &HookContext<'_>,
+        mut request: CompletionRequest,
+        run: Option<&RunContext>,
+        enforce_time_budget: bool,
+    ) -> Result<Vec<llm::completion::AssistantContent>, AgentError> {
+        for hook in &self.hooks {
+            if let HookAction::Veto { reason } = hook.before_llm_call(ctx, &mut request) {
+                return Err(AgentError::Vetoed(reason));
+            }
         }
 
-        let request = CompletionRequest {
-            prompt: llm::completion::Message::user(prompt.clone()),
-            system_prompt: self.system_prompt.clone(),
-            chat_history: vec![],
-            tools: vec![],
-            temperature: Some(self.config.temperature),
-            max_tokens: Some(self.config.max_tokens),
+        self.model.capabilities().validate_request(&request)?;
+
+        let estimated_input_tokens = estimate_tokens(&request);
+        let started = Instant::now();
+        let completion = self.model.completion(request);
+        let response = match run {
+            Some(run) => tokio::select! {
+                biased;
+                _ = run.cancel.cancelled() => return Err(AgentError::Canceled),
+                response = completion => response?,
+                exceeded = run.budget.time_exhausted(), if enforce_time_budget => {
+                    return Err(AgentError::BudgetExceeded(exceeded));
+                },
+            },
+            None => completion.await?,
         };
+        if let Some(run) = run {
+            *run.llm_duration.lock().unwrap() += started.elapsed();
+            let usage = self.model.usage(&response.raw_response).unwrap_or_else(|| {
+                Usage::new(
+                    estimated_input_tokens,
+                    estimate_output_tokens(&response.choice),
+                )
+            });
+            run.budget.record_usage(usage);
+        }
 
-        let response = self.model.completion(request).await.map_err(|e| {
-            if self.config.verbose {
-                log_error_ctx!(&self.config.name, &self.config.id, &e, "LLM completion");
+        let mut choices = response.choice;
+        for hook in &self.hooks {
+            hook.after_llm_call(ctx, &mut choices);
+        }
+        Ok(choices)
+    }
+
+    /// Executes a single tool call, running the `before_tool_call` and `after_tool_call` hooks.
+    ///
+    /// Failures are returned as an output with an error kind rather than an error. Within a
+    /// run, calls are skipped once its budget is exhausted and abandoned if it is canceled.
+    async fn execute_tool_call(
+        &self,
+        ctx: &HookContext<'_>,
+        mut tool_call: ToolFunction,
+        run: Option<&RunContext>,
+    ) -> ToolCallOutput {
+        for hook in &self.hooks {
+            if let HookAction::Veto { reason } = hook.before_tool_call(ctx, &mut tool_call) {
+                return ToolCallOutput::error(
+                    tool_call.name,
+                    tool_call.arguments.to_string(),
+                    ToolCallErrorKind::Vetoed,
+                    format!("Tool call vetoed: {reason}"),
+                );
             }
-            e
-        })?;
+        }
 
-        let choice = response.choice.first().ok_or(AgentError::NoChoiceFound)?;
-        let result = match ToOwned::to_owned(choice) {
-            llm::completion::AssistantContent::Text(text) => {
-                let duration = start_time.elapsed().as_millis() as u64;
-                if self.config.verbose {
+        let mut output = match run.map(|run| run.budget.start_tool_call()) {
+            Some(Err(exceeded)) => ToolCallOutput::error(
+                tool_call.name,
+                tool_call.arguments.to_string(),
+                ToolCallErrorKind::BudgetExceeded,
+                format!("Tool call skipped: {exceeded}"),
+            ),
+            _ => self.call_tool(ctx, tool_call, run).await,
+        };
+        if let Some(kind) = output.error {
+            tracing::error!(
+                "Failed to call tool<{}>, args: {}, error ({:?}): {}",
+                output.name,
+                output.args,
+                kind,
+                output.result
+            );
+        }
+        for hook in &self.hooks {
+            hook.after_tool_call(ctx, &mut output);
+        }
+        output
+    }
+
+    /// Looks up, approves and calls a tool according to its [`ToolPolicy`].
+    async fn call_tool(
+        &self,
+        ctx: &HookContext<'_>,
+        mut tool_call: ToolFunction,
+        run: Option<&RunContext>,
+    ) -> ToolCallOutput {
+        let Some(tool) = self.find_tool(&tool_call.name, run) else {
+            let mut available = self
+                .tools_impl
+                .iter()
+                .map(|tool| tool.key().clone())
+                .collect::<Vec<_>>();
+            if self.artifact_store.is_some() && run.is_some() {
+                available.push(SAVE_ARTIFACT_TOOL_NAME.to_owned());
+            }
+            available.extend(
+                self.registry_tools()
+                    .into_iter()
+                    .map(|definition| definition.name),
+            );
+            available.sort();
+            let result = format!(
+                "Tool not found: {}. Available tools: {}",
+                tool_call.name,
+                available.join(", ")
+            );
+            return ToolCallOutput::error(
+                tool_call.name,
+                tool_call.arguments.to_string(),
+                ToolCallErrorKind::NotFound,
+                result,
+            );
+        };
+
+        // Waiting for approval is abandoned if the run is canceled
+        let approval = async {
+            self.check_approval(ctx, &mut tool_call)
+                .await
+                .map(|reason| {
+                    (
+                        ToolCallErrorKind::Denied,
+                        format!("Tool call denied: {reason}"),
+                    )
+                })
+        };
+        let rejection = match run {
+            Some(run) => tokio::select! {
+                biased;
+                _ = run.cancel.cancelled() => Some((
+                    ToolCallErrorKind::Canceled,
+                    "the run was canceled".to_owned(),
+                )),
+                rejection = approval => rejection,
+            },
+            None => approval.await,
+        };
+        if let Some((kind, result)) = rejection {
+            return ToolCallOutput::error(
+                tool_call.name,
+                tool_call.arguments.to_string(),
+                kind,
+                result,
+            );
+        }
+
+        let policy = self
+            .tool_policies
+            .get(&tool_call.name)
+            .unwrap_or(&self.default_tool_policy);
+        let args = tool_call.arguments.to_string();
+        let call = Self::call_with_policy(&tool_call.name, tool, args.clone(), policy);
+        let result = match run {
+            Some(run) => {
+                // Agents called by the tool run on behalf of this run
+                let delegation = Delegation::new(run.depth, run.cancel.clone());
+                let sub_runs = delegation.sub_runs();
+                let result = tokio::select! {
+                    biased;
+                    _ = run.cancel.cancelled() => Err((
+                        ToolCallErrorKind::Canceled,
+                        "the run was canceled".to_owned(),
+                    )),
+                    result = delegation.scope(call) => result,
+                    exceeded = run.budget.time_exhausted() => Err((
+                        ToolCallErrorKind::BudgetExceeded,
+                        exceeded.to_string(),
+                    )),
+                };
+                for sub_run in sub_runs.lock().unwrap().drain(..) {
+                    run.budget.record_usage(sub_run.usage);
+                    run.sub_runs.lock().unwrap().push(sub_run);
+                }
+                result
+            },
+            None => call.await,
+        };
+
+        match result {
+            Ok(result) => ToolCallOutput {
+                name: tool_call.name,
+                args,
+                result,
+                error: None,
+            },
+            Err((kind, error)) => ToolCallOutput::error(
+                tool_call.name,
+                args,
+                kind,
+                format!("Tool call failed ({kind}): {error}"),
+            ),
+        }
+    }
+
+    /// The tools offered to the model: those of the agent, followed by the tools of the
+    /// registry currently available to it.
+    fn request_tools(&self) -> Vec<ToolDefinition> {
+        let mut tools = self.tools.clone();
+        tools.append(&mut self.registry_tools());
+        tools
+    }
+
+    /// The enabled tools of the registry allowed by the filter, except those shadowed by a
+    /// tool of the agent.
+    fn registry_tools(&self) -> Vec<ToolDefinition> {
+        let Some(registry) = &self.tool_registry else {
+            return Vec::new();
+        };
+        let mut tools = registry.definitions(&self.tool_filter);
+        tools.retain(|tool| !self.is_agent_tool(&tool.name));
+        tools
+    }
+
+    fn is_agent_tool(&self, name: &str) -> bool {
+        self.tools_impl.contains_key(name)
+            || (name == SAVE_ARTIFACT_TOOL_NAME && self.artifact_store.is_some())
+    }
+
+    /// Looks up a tool by name, in the tools of the agent, then in the registry. The
+    /// `save_artifact` tool is only available within a run, since artifacts are stored under
+    /// its ID.
+    fn find_tool(&self, name: &str, run: Option<&RunContext>) -> Option<Arc<dyn ToolDyn>> {
+        if name == SAVE_ARTIFACT_TOOL_NAME
+            && let Some(store) = &self.artifact_store
+        {
+            let run = run?;
+            return Some(Arc::new(SaveArtifactTool::new(
+                Arc::clone(store),
+                run.id.clone(),
+                Arc::clone(&run.artifacts),
+            )));
+        }
+        if let Some(tool) = self.tools_impl.get(name) {
+            return Some(Arc::clone(tool.deref()));
+        }
+        self.tool_registry
+            .as_ref()?
+            .get_allowed(name, &self.tool_filter)
+    }
+
+    /// Metadata of tool `name` in the registry, unless a tool of the agent shadows it.
+    fn registry_metadata(&self, name: &str) -> Option<ToolMetadata> {
+        if self.tools_impl.contains_key(name) {
+            return None;
+        }
+        self.tool_registry.as_ref()?.metadata(name)
+    }
+
+    /// Calls a tool, retrying failed and timed out attempts as allowed by `policy`.
+    async fn call_with_policy(
+        name: &str,
+        tool: Arc<dyn ToolDyn>,
+        args: String,
+        policy: &ToolPolicy,
+    ) -> Result<String, (ToolCallErrorKind, String)> {
+        let mut attempt = 0;
+        loop {
+            let call = tool.call(args.clone());
+            let result = match policy.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, call).await {
+                    Ok(result) => result.map_err(|e| (ToolCallErrorKind::from(&e), e.to_string())),
+                    Err(_) => Err((
+                        ToolCallErrorKind::Timeout,
+                        format!("no result after {timeout:?}"),
+                    )),
+                },
+                None => call
+                    .await
+                    .map_err(|e| (ToolCallErrorKind::from(&e), e.to_string())),
+            };
+
+            match result {
+                Err((kind, error)) if kind.is_retryable() && attempt < policy.max_retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Tool<{}> failed ({}), retrying ({}/{})",
+                        name,
+                        error,
+                        attempt,
+                        policy.max_retries
+                    );
+                    tokio::time::sleep(policy.retry_backoff * attempt).await;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Asks the approval handler about `tool_call` if its policy requires it. The arguments
+    /// are replaced if the handler edits them. Returns the reason if the call is denied.
+    async fn check_approval(
+        &self,
+        ctx: &HookContext<'_>,
+        tool_call: &mut ToolFunction,
+    ) -> Option<String> {
+        let policy = self.approval_policies.get(&tool_call.name)?;
+        if !policy.requires_approval(&tool_call.arguments) {
+            return None;
+        }
+
+        let Some(handler) = &self.approval_handler else {
+            tracing::warn!(
+                "Tool<{}> requires approval but agent<{}> has no approval handler",
+                tool_call.name,
+                self.config.name
+            );
+            return Some("approval required but no approval handler is configured".to_owned());
+        };
+
+        let request = ApprovalRequest {
+            agent_name: self.config.name.clone(),
+            task: ctx.task.map(ToOwned::to_owned),
+            tool_name: tool_call.name.clone(),
+            args: tool_call.arguments.clone(),
+            metadata: self.registry_metadata(&tool_call.name),
+        };
+        match handler.request_approval(request).await {
+            Ok(ApprovalDecision::Approve) => None,
+            Ok(ApprovalDecision::Deny { reason }) => Some(reason),
+            Ok(ApprovalDecision::Edit { args }) => {
+                tool_call.arguments = args;
+                None
+            },
+            Err(e) => {
+                tracing::error!("Failed to get approval for tool<{}>: {}", tool_call.name, e);
+                Some(format!("approval failed: {e}"))
+            },
+        }
+    }
+
+    /// Ends a run whose budget is exhausted: records why in the conversation and, unless
+    /// disabled, asks the LLM for a final answer without tools.
+    async fn finish_exhausted_run(
+        &self,
+        ctx: &HookContext<'_>,
+        key: &ConversationKey,
+        exceeded: &BudgetExceeded,
+        run: &RunContext,
+    ) {
+        tracing::warn!("Agent<{}> stopped task: {}", self.config.name, exceeded);
+        for hook in &self.hooks {
+            hook.on_budget_exhausted(ctx, exceeded);
+        }
+
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::User("Budget".to_owned()),
+            format!("Run stopped. {exceeded}."),
+        );
+        if !run.budget.budget().final_summary {
+            return;
+        }
+
+        let history = self
+            .memory(key)
+            .0
+            .get(key.id())
+            .map(|conversation| conversation.deref().into())
+            .unwrap_or_default();
+        let request = CompletionRequest {
+            prompt: llm::completion::Message::user(
+                "The budget of this run is exhausted and no more tools can be called. \
+                Summarize what has been done so far and give the best final answer to the task you can.",
+            ),
+            system_prompt: run.system_prompt.clone(),
+            chat_history: history,
+            tools: vec![],
+            temperature: Some(self.config.temperature),
+            max_tokens: Some(self.config.max_tokens),
+        };
+        // Not limited by the time budget, which may be what ended the run
+        match self.complete_with(ctx, request, Some(run), false).await {
+            Ok(choices) => {
+                let summary = choices
+                    .into_iter()
+                    .filter_map(|choice| match choice {
+                        llm::completion::AssistantContent::Text(text) => Some(text.text),
+                        llm::completion::AssistantContent::ToolCall(_) => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.memory(key).add(
+                    key.id(),
+                    &self.config.name,
+                    Role::Assistant(self.config.name.to_owned()),
+                    summary,
+                );
+            },
+            Err(e) => tracing::error!(
+                "Failed to get final summary for agent<{}>: {}",
+                self.config.name,
+                e
+            ),
+        }
+    }
+
+    fn hook_context<'a>(&'a self, task: Option<&'a str>) -> HookContext<'a> {
+        HookContext {
+            agent_id: &self.config.id,
+            agent_name: &self.config.name,
+            task,
+        }
+    }
+
+    pub async fn prompt(&self, prompt: impl Into<String>) -> Result<String, AgentError> {
+        let prompt = prompt.into();
+        let start_time = std::time::Instant::now();
+
+        if self.config.verbose {
+            log_llm!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                "Prompt Request",
+                "Sending prompt to LLM: '{}'",
+                prompt.chars().take(100).collect::<String>()
+            );
+        }
+
+        let request = CompletionRequest {
+            prompt: llm::completion::Message::user(prompt.clone()),
+            system_prompt: self.render_system_prompt()?,
+            chat_history: vec![],
+            tools: vec![],
+            temperature: Some(self.config.temperature),
+            max_tokens: Some(self.config.max_tokens),
+        };
+
+        let ctx = self.hook_context(None);
+        let choices = self.complete(&ctx, request, None).await.map_err(|e| {
+            if self.config.verbose {
+                log_error_ctx!(&self.config.name, &self.config.id, &e, "LLM completion");
+            }
+            e
+        })?;
+
+        let choice = choices.first().ok_or(AgentError::NoChoiceFound)?;
+        let result = match ToOwned::to_owned(choice) {
+            llm::completion::AssistantContent::Text(text) => {
+                let duration = start_time.elapsed().as_millis() as u64;
+                if self.config.verbose {
                     log_perf!(info, "LLM", "completion_time", duration, "ms");
                     log_llm!(
                         debug,
@@ -966,376 +2014,1518 @@ where
         result
     }
 
-    pub fn tool(mut self, tool: impl ToolDyn + 'static) -> Self {
-        let toolname = tool.name();
-        let definition = tool.definition();
-        self.tools.push(definition);
-        self.tools_impl.insert(toolname, Arc::new(tool));
-        self
+    /// Adds a tool to the built agent. A tool with the name of a tool of the agent, built-in
+    /// tools included, is handled as set by
+    /// [`SwarmsAgentBuilder::duplicate_tool_policy`].
+    pub fn tool(mut self, tool: impl ToolDyn + 'static) -> Result<Self, AgentError> {
+        let name = tool.name();
+        // Called by the agent itself, which knows the store
+        if self.artifact_store.is_some() && name == SAVE_ARTIFACT_TOOL_NAME {
+            return Err(AgentError::DuplicateTool(name));
+        }
+        resolve_tool(
+            &mut self.tools,
+            &self.tools_impl,
+            Arc::new(tool),
+            self.duplicate_tool_policy,
+        )?;
+        Ok(self)
     }
 
     pub fn system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
         self.system_prompt = Some(system_prompt.into());
+        self.system_prompt_template = None;
         self
     }
 
+    /// Returns the system prompt, or the source of its template.
     pub fn get_system_prompt(&self) -> Option<&str> {
         self.system_prompt.as_deref()
     }
 
-    /// Handle error in attempts
-    async fn handle_error_in_attempts(&self, task: &str, error: AgentError, attempt: u32) {
-        let err_msg = format!("Attempt {}, task: {}, failed: {}", attempt + 1, task, error);
-        tracing::error!(err_msg);
+    /// See [`SwarmsAgentBuilder::system_prompt_template`].
+    pub fn system_prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.system_prompt = Some(template.source().to_owned());
+        self.system_prompt_template = Some(template);
+        self
+    }
 
-        if self.config.autosave {
-            let _ = self.save_task_state(task.to_owned()).await.map_err(|e| {
-                tracing::error!(
-                    "Failed to save agent<{}> task<{}>,  state: {}",
-                    self.config.name,
-                    task,
-                    e
+    pub fn get_system_prompt_template(&self) -> Option<&PromptTemplate> {
+        self.system_prompt_template.as_ref()
+    }
+
+    /// See [`SwarmsAgentBuilder::prompt_variable`].
+    pub fn prompt_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.prompt_context.insert(name, value);
+        self
+    }
+
+    /// Checks that the system prompt template only uses variables that are set.
+    pub fn validate_system_prompt(&self) -> Result<(), TemplateError> {
+        validate_system_prompt(self.system_prompt_template.as_ref(), &self.prompt_context)
+    }
+
+    /// The system prompt, with its template rendered for the current time, followed by the
+    /// MCP resources added to it.
+    fn render_system_prompt(&self) -> Result<Option<String>, TemplateError> {
+        let prompt = match &self.system_prompt_template {
+            Some(template) => Some(self.render_template(template)?),
+            None => self.system_prompt.clone(),
+        };
+        if self.mcp_resource_contexts.is_empty() {
+            return Ok(prompt);
+        }
+        let resources = self
+            .mcp_resource_contexts
+            .iter()
+            .map(|context| {
+                format!(
+                    "<resource server=\"{}\" uri=\"{}\">\n{}\n</resource>",
+                    context.server(),
+                    context.uri(),
+                    context.text()
                 )
-            });
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(Some(match prompt {
+            Some(prompt) => format!("{prompt}\n\n{resources}"),
+            None => resources,
+        }))
+    }
+
+    fn render_template(&self, template: &PromptTemplate) -> Result<String, TemplateError> {
+        let now = Local::now();
+        let tools = self
+            .tools
+            .iter()
+            .map(|tool| format!("- {}: {}", tool.name, tool.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut context = PromptContext::new()
+            .with("date", now.format("%Y-%m-%d").to_string())
+            .with("datetime", now.format("%Y-%m-%d %H:%M:%S %:z").to_string())
+            .with("agent_name", &self.config.name)
+            .with(
+                "agent_description",
+                self.config.description.clone().unwrap_or_default(),
+            )
+            .with("user_name", &self.config.user_name)
+            .with("tools", tools);
+        context.extend(&self.prompt_context);
+        template.render(&context)
+    }
+
+    /// The system prompt of a request: the one rendered for the run, if there is one.
+    fn request_system_prompt(
+        &self,
+        run: Option<&RunContext>,
+    ) -> Result<Option<String>, AgentError> {
+        match run {
+            Some(run) => Ok(run.system_prompt.clone()),
+            None => Ok(self.render_system_prompt()?),
         }
     }
-}
 
-impl<M> Agent for SwarmsAgent<M>
-where
-    M: llm::Model + Clone + Send + Sync + 'static,
-    M::RawCompletionResponse: Clone + Send + Sync,
-{
-    fn run(&self, task: String) -> BoxFuture<Result<String, AgentError>> {
-        Box::pin(async move {
-            let start_time = std::time::Instant::now();
+    /// Runs the agent loop for a task whose conversation is already in short memory,
+    /// starting from `progress`.
+    ///
+    /// If the run is canceled, in-flight LLM and tool calls are abandoned, the state of the
+    /// task is saved (if a `save_state_dir` is configured) so it can be resumed, and
+    /// `AgentError::Canceled` is returned.
+    async fn execute_task_loops(
+        &self,
+        task: String,
+        key: ConversationKey,
+        progress: TaskProgress,
+        run: RunContext,
+    ) -> Result<RunResult, AgentError> {
+        let ctx = self.hook_context(Some(&task));
+
+        // Run agent loop
+        let mut was_prev_call_task_evaluator = progress.pending_context.is_some();
+        let mut last_response_text = progress.pending_context.clone().unwrap_or_default();
+        let mut task_complete = progress.finished;
+        let progress_finished = progress.finished;
+        let start_loop = progress.loop_count;
+        let mut budget_exceeded = progress.budget_exceeded.clone();
+        let mut termination = progress.termination.unwrap_or(if progress_finished {
+            TerminationReason::Unspecified
+        } else {
+            TerminationReason::MaxLoops
+        });
+        let mut plan = progress.plan.clone();
+        self.task_progress.insert(key.clone(), progress);
+        let mut canceled = false;
+        let mut run_tool_calls = Vec::new();
+        let mut critiques = Vec::new();
+        let replans_at_start = plan.as_ref().map_or(0, |plan| plan.replans);
+        let mut revisions = 0;
+        let mut reflexion_termination = None;
+        let mut handoff = None;
+
+        if self.config.verbose {
+            log_agent!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                "Starting autonomous execution loop - Max loops: {}",
+                self.config.max_loops
+            );
+        }
+
+        for loop_count in start_loop..self.config.max_loops {
+            if task_complete {
+                if self.config.verbose {
+                    log_agent!(
+                        info,
+                        &self.config.name,
+                        &self.config.id,
+                        "Task completed early at loop {} of {}",
+                        loop_count,
+                        self.config.max_loops
+                    );
+                }
+                break;
+            }
+
+            if run.cancel.is_cancelled() {
+                canceled = true;
+                break;
+            }
+
+            if let Some(exceeded) = run.budget.check() {
+                budget_exceeded = Some(exceeded);
+                break;
+            }
+
+            if self.config.verbose {
+                log_agent!(
+                    debug,
+                    &self.config.name,
+                    &self.config.id,
+                    "Starting loop iteration {} of {}",
+                    loop_count + 1,
+                    self.config.max_loops
+                );
+            }
+
+            for hook in &self.hooks {
+                hook.on_loop_start(&ctx, loop_count);
+            }
+
+            let current_prompt: String;
+
+            if was_prev_call_task_evaluator {
+                current_prompt = format!(
+                    "You previously called task_evaluator and indicated the task was not complete. The required next step or context provided was: '{}'. \
+                    Focus ONLY on addressing this context. DO NOT call task_evaluator again in this turn. Proceed with the task based on the context.",
+                    last_response_text // last_response is the context provided by task_evaluator
+                );
+
+                was_prev_call_task_evaluator = false;
+            } else if loop_count > 0 {
+                current_prompt = format!(
+                    "Now, you are in loop {} of {}, The dialogue will terminate upon reaching maximum iteration count. You must:
+                     - Complete the user's task before termination
+                     - Optimize loop efficiency
+                     - Minimize resource consumption through minimal iterations
+
+                    You should consider to use tools if they can help, but only if they are relevant to the task and are necessary for the task.
+                    origin task:\n{}",
+                    loop_count + 1,
+                    self.config.max_loops,
+                    task
+                )
+            } else {
+                // first loop
+                // task is already in short_memory, short_memory will be passed to llm
+                // empty prompt should be ignored by LLM provider
+                current_prompt = "".to_owned();
+            }
+
+            // Retrieve once per loop, outside of the retry attempts. Documents retrieved by
+            // an earlier loop are skipped.
+            if loop_count > 0 && self.config.rag_every_loop {
+                let query = format!("{task}\n{last_response_text}");
+                if let Err(e) = self.retrieve_long_term_memory(&key, query).await {
+                    tracing::error!(
+                        "Failed to query long term memory for agent<{}>: {}",
+                        self.config.name,
+                        e
+                    );
+                }
+            }
+
+            let current_prompt = match &plan {
+                Some(plan) if current_prompt.is_empty() => plan::step_prompt(plan),
+                Some(plan) => format!("{current_prompt}\n\n{}", plan::step_prompt(plan)),
+                None => current_prompt,
+            };
+
+            let mut success = false;
+            let mut is_draft = false;
+            let mut failed_step = None;
+            for attempt in 0..self.config.retry_attempts {
+                if success {
+                    break;
+                }
+
+                // Generate response using LLM. The history is copied out of the conversation,
+                // which would otherwise stay locked during the call (key is in its memory).
+                let history: Vec<_> = self.memory(&key).0.get(key.id()).unwrap().deref().into();
+                let current_chat_response = match self
+                    .chat_for_task(Some(&task), Some(&run), current_prompt.clone(), history)
+                    .await
+                {
+                    Ok(response) => response,
+                    Err(AgentError::Canceled) => {
+                        canceled = true;
+                        break;
+                    },
+                    Err(AgentError::BudgetExceeded(exceeded)) => {
+                        budget_exceeded = Some(exceeded);
+                        break;
+                    },
+                    Err(e) => {
+                        self.handle_error_in_attempts(&task, &key, e, attempt).await;
+                        continue;
+                    },
+                };
+
+                // handle ChatResponse
+                let mut assistant_memory_content = String::new();
+                let mut is_task_evaluator_called = false;
+                match current_chat_response {
+                    ChatResponse::Text(text) => {
+                        is_draft = true;
+                        last_response_text = text.clone();
+                        assistant_memory_content = text;
+                    },
+                    ChatResponse::ToolCalls(tool_calls) => {
+                        is_draft = false;
+                        run_tool_calls.extend(tool_calls.iter().cloned());
+                        let mut formatted_tool_results = String::new();
+                        for tool_call in tool_calls {
+                            if tool_call.name == UPDATE_PLAN_TOOL_NAME
+                                && !tool_call.is_error()
+                                && let Some(plan) = &mut plan
+                            {
+                                match serde_json::from_str::<StepUpdate>(&tool_call.result)
+                                    .map_err(|e| e.to_string())
+                                    .and_then(|update| {
+                                        plan.update(&update).map_err(|e| e.to_string())?;
+                                        Ok(update)
+                                    }) {
+                                    Ok(update) if update.status == StepStatus::Failed => {
+                                        failed_step = Some(update);
+                                    },
+                                    Ok(_) => {},
+                                    Err(e) => tracing::warn!(
+                                        "Agent<{}> ignored plan update: {}",
+                                        self.config.name,
+                                        e
+                                    ),
+                                }
+                            }
+                            if tool_call.name.starts_with(TRANSFER_TOOL_PREFIX)
+                                && !tool_call.is_error()
+                                && let Ok(request) =
+                                    serde_json::from_str::<HandoffRequest>(&tool_call.result)
+                            {
+                                handoff.get_or_insert(request);
+                            }
+                            let formatted = format!(
+                                "[Tool name]: {}\n[Tool args]: {}\n[Tool result]: {}\n\n",
+                                tool_call.name, tool_call.args, tool_call.result
+                            );
+                            formatted_tool_results.push_str(&formatted);
+                            if tool_call.name == ToolDyn::name(&TaskEvaluator) {
+                                is_task_evaluator_called = true;
+                                match serde_json::from_str::<TaskStatus>(&tool_call.result) {
+                                    Ok(task_status) => {
+                                        tracing::info!(
+                                            "Task evaluator tool called, task status: {:#?}",
+                                            task_status,
+                                        );
+                                        for hook in &self.hooks {
+                                            hook.on_task_evaluation(&ctx, &task_status);
+                                        }
+
+                                        match task_status {
+                                            TaskStatus::Complete => {
+                                                task_complete = true;
+                                                // Task is complete
+                                                // This may be a bit redundant, but it's here for clarity
+                                                // last_response_text = format!(
+                                                //     "Task marked as complete by task_evaluator. Result: {}",
+                                                //     tool_call.result
+                                                // );
+                                                assistant_memory_content = formatted;
+                                                // Store the final tool call in memory
+                                            },
+                                            TaskStatus::Incomplete { context } => {
+                                                task_complete = false;
+                                                // If not complete, store the context for the next loop's prompt
+                                                last_response_text = context;
+                                                // Keep the raw tool result for memory
+                                                assistant_memory_content = formatted;
+                                            },
+                                        }
+                                    },
+                                    Err(e) => {
+                                        tracing::error!(
+                                            "Failed to parse task status from task_evaluator: {}. Raw result: {}",
+                                            e,
+                                            tool_call.result
+                                        );
+
+                                        task_complete = false;
+
+                                        last_response_text = format!(
+                                            "Error parsing task_evaluator result. Raw output: {}",
+                                            tool_call.result
+                                        );
+                                        assistant_memory_content = formatted;
+                                        // Store the problematic call
+                                    },
+                                }
+                            } else {
+                                // Handle other tool calls if necessary, for now just format them
+                                // If this is the *only* response part, update last_response_text
+                                if formatted_tool_results.len() == formatted.len() {
+                                    // Check if it's the first/only tool result string being built
+                                    last_response_text = formatted_tool_results.clone();
+                                } else {
+                                    // Append to existing text/tool results for the final response string
+                                    last_response_text.push_str(&formatted);
+                                }
+                            }
+                        }
+                        // If multiple tools were called, or if task_evaluator wasn't the only one,
+                        // ensure assistant_memory_content reflects all calls.
+                        if assistant_memory_content.is_empty() || !is_task_evaluator_called {
+                            assistant_memory_content = formatted_tool_results.clone();
+                            // Update last_response_text if it wasn't set by task_evaluator
+                            if !is_task_evaluator_called {
+                                last_response_text = formatted_tool_results;
+                            }
+                        }
+                    },
+                }
+
+                // Update the flag for the *next* iteration based on *this* iteration's call
+                was_prev_call_task_evaluator = is_task_evaluator_called && !task_complete;
+
+                self.memory(&key).add(
+                    key.id(),
+                    &self.config.name,
+                    Role::Assistant(self.config.name.to_owned()),
+                    assistant_memory_content.clone(), // Add the text or formatted tool calls
+                );
+
+                success = true;
+            }
+
+            if budget_exceeded.is_some() {
+                break;
+            }
+            if canceled || !success {
+                // Exit the loop if the run was canceled or all retry failed
+                termination = TerminationReason::RetriesExhausted;
+                break;
+            }
+
+            if let Some(failed) = failed_step
+                && let Some(current) = &mut plan
+                && current.replans - replans_at_start < self.config.max_replans
+            {
+                match self.replan(&ctx, &task, &key, current, &failed, &run).await {
+                    Ok(()) => {},
+                    Err(AgentError::Canceled) => {
+                        canceled = true;
+                        break;
+                    },
+                    Err(e) => tracing::error!(
+                        "Failed to re-plan task of agent<{}>: {}",
+                        self.config.name,
+                        e
+                    ),
+                }
+            }
+
+            for hook in &self.hooks {
+                hook.on_loop_end(&ctx, loop_count);
+            }
+
+            self.task_progress.insert(
+                key.clone(),
+                TaskProgress {
+                    loop_count: loop_count + 1,
+                    pending_context: was_prev_call_task_evaluator
+                        .then(|| last_response_text.clone()),
+                    finished: false,
+                    budget_exceeded: None,
+                    termination: None,
+                    plan: plan.clone(),
+                },
+            );
+
+            // Save state in each loop
+            if self.config.autosave {
+                self.save_state(&key).await?;
+            }
+
+            if let Some(request) = &handoff {
+                if self.config.verbose {
+                    log_agent!(
+                        info,
+                        &self.config.name,
+                        &self.config.id,
+                        "Handing off to {}",
+                        request.target
+                    );
+                }
+                break;
+            }
+
+            if let Some(reflexion) = &self.reflexion
+                && (is_draft || task_complete)
+            {
+                match self
+                    .critique_draft(&ctx, &task, &key, reflexion, &run)
+                    .await
+                {
+                    Ok(critique) => {
+                        let passed = critique.score >= reflexion.threshold;
+                        critiques.push(critique);
+                        if passed {
+                            reflexion_termination = Some(TerminationReason::CritiquePassed);
+                            break;
+                        }
+                        if revisions >= reflexion.max_revisions {
+                            reflexion_termination = Some(TerminationReason::MaxRevisions);
+                            break;
+                        }
+                        revisions += 1;
+                        task_complete = false;
+                        was_prev_call_task_evaluator = false;
+                        continue;
+                    },
+                    Err(CritiqueError::AgentError(AgentError::Canceled)) => {
+                        canceled = true;
+                        break;
+                    },
+                    Err(e) => {
+                        // The draft is kept as if reflexion was off
+                        tracing::error!(
+                            "Failed to critique draft of agent<{}>: {}",
+                            self.config.name,
+                            e
+                        );
+                    },
+                }
+            }
+
+            if self.is_response_complete(last_response_text.clone()) {
+                if self.config.verbose {
+                    log_agent!(
+                        info,
+                        &self.config.name,
+                        &self.config.id,
+                        "Response marked as complete by completion checker"
+                    );
+                }
+                termination = TerminationReason::StopWord;
+                break;
+            }
+
+            // TODO: Loop interval, maybe add a sleep here
+        }
+
+        // A tool call of the last loop may have been abandoned
+        canceled |= run.cancel.is_cancelled();
+        if canceled {
+            // Keep the task unfinished, so it can be resumed from the last completed loop
+            tracing::warn!("Agent<{}> task canceled: {}", self.config.name, task);
+            self.save_state(&key).await?;
+            return Err(AgentError::Canceled);
+        }
+
+        if !progress_finished {
+            if handoff.is_some() {
+                termination = TerminationReason::Handoff;
+            } else if let Some(reason) = reflexion_termination {
+                termination = reason;
+            } else if task_complete {
+                termination = TerminationReason::TaskComplete;
+            } else if let Some(exceeded) = &budget_exceeded {
+                termination = TerminationReason::Budget;
+                self.finish_exhausted_run(&ctx, &key, exceeded, &run).await;
+            }
+        }
+
+        // The agent handed off gives the answer
+        if !progress_finished && handoff.is_none() && !self.output_pipeline.is_empty() {
+            self.process_output(&key).await;
+        }
+
+        // A blocked answer still finishes the task
+        let mut guardrail_error = None;
+        if !progress_finished && handoff.is_none() && self.guardrails.has_output() {
+            match self.guard_output(&ctx, &key, &run).await {
+                Ok(()) => {},
+                Err(AgentError::Canceled) => {
+                    self.save_state(&key).await?;
+                    return Err(AgentError::Canceled);
+                },
+                Err(e) => guardrail_error = Some(e),
+            }
+        }
+
+        {
+            let mut progress = self.task_progress.entry(key.clone()).or_default();
+            progress.finished = true;
+            progress.budget_exceeded = budget_exceeded.clone();
+            progress.termination = Some(termination);
+            progress.plan = plan.clone();
+        }
+
+        // Save state
+        if self.config.autosave {
+            if self.config.verbose {
+                log_memory!(
+                    debug,
+                    &self.config.name,
+                    &self.config.id,
+                    "Final Autosave",
+                    "Saving final agent state after task completion"
+                );
+            }
+            self.save_state(&key).await?;
+        }
+
+        if let Some(e) = guardrail_error {
+            return Err(e);
+        }
+
+        let total_duration = run.budget.elapsed().as_millis() as u64;
+        if self.config.verbose {
+            log_perf!(info, "Agent", "total_execution_time", total_duration, "ms");
+
+            log_task!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                &task,
+                "Task execution completed successfully in {}ms",
+                total_duration
+            );
+        }
+
+        let transcript = self
+            .memory(&key)
+            .0
+            .get(key.id())
+            .expect("Task should exist in short memory")
+            .history
+            .iter()
+            .map(TranscriptEntry::from)
+            .collect::<Vec<_>>();
+        let loop_count = self
+            .task_progress
+            .get(&key)
+            .map_or(0, |progress| progress.loop_count);
+        let timings = RunTimings {
+            started_at: run.started_at,
+            finished_at: Local::now(),
+            duration: run.budget.elapsed(),
+            llm_duration: *run.llm_duration.lock().unwrap(),
+            tool_duration: *run.tool_duration.lock().unwrap(),
+        };
+        Ok(RunResult {
+            run_id: run.id,
+            agent_name: self.config.name.clone(),
+            task,
+            final_answer: run_result::final_answer(&transcript),
+            transcript,
+            tool_calls: run_tool_calls,
+            loop_count,
+            termination,
+            budget_exceeded,
+            usage: run.budget.usage(),
+            timings,
+            artifacts: run.artifacts.lock().unwrap().clone(),
+            critiques,
+            plan,
+            sub_runs: run.sub_runs.into_inner().unwrap(),
+            handoff,
+            guardrails: run.guardrails.into_inner().unwrap(),
+            session_id: None,
+        })
+    }
+
+    /// Asks the model for new remaining steps of `plan` after the step of `failed` failed,
+    /// and adds the new plan to the conversation `key`. The plan is kept if the
+    /// response has no steps.
+    async fn replan(
+        &self,
+        ctx: &HookContext<'_>,
+        task: &str,
+        key: &ConversationKey,
+        plan: &mut Plan,
+        failed: &StepUpdate,
+        run: &RunContext,
+    ) -> Result<(), AgentError> {
+        let prompt =
+            plan::replanning_prompt(self.config.planning_prompt.as_deref(), task, plan, failed);
+        let request = CompletionRequest {
+            prompt: llm::completion::Message::user(prompt),
+            system_prompt: run.system_prompt.clone(),
+            chat_history: vec![],
+            tools: vec![],
+            temperature: Some(self.config.temperature),
+            max_tokens: Some(self.config.max_tokens),
+        };
+        let text = self
+            .complete(ctx, request, Some(run))
+            .await?
+            .into_iter()
+            .filter_map(|choice| match choice {
+                llm::completion::AssistantContent::Text(text) => Some(text.text),
+                llm::completion::AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let Some(new_plan) = Plan::parse(&text) else {
+            // Keep the plan, the agent moves on to the next pending step
+            tracing::warn!(
+                "Agent<{}> got no plan steps when re-planning: {}",
+                self.config.name,
+                text
+            );
+            return Ok(());
+        };
+
+        plan.replan(new_plan.steps.into_iter().map(|step| step.description));
+        if self.config.verbose {
+            log_agent!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                "Re-planned after step {} failed",
+                failed.step
+            );
+        }
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::Assistant(self.config.name.to_owned()),
+            format!("[Plan]\n{plan}"),
+        );
+        Ok(())
+    }
+
+    /// Replaces the final answer of `task` so far in its conversation.
+    fn replace_answer(&self, key: &ConversationKey, answer: String) {
+        let Some(mut conversation) = self.memory(key).0.get_mut(key.id()) else {
+            return;
+        };
+        let transcript = conversation
+            .history
+            .iter()
+            .map(TranscriptEntry::from)
+            .collect::<Vec<_>>();
+        if let Some(index) = run_result::final_answer_index(&transcript) {
+            conversation.history[index].set_text(answer);
+        }
+    }
+
+    /// The final answer of `task` so far.
+    fn current_answer(&self, key: &ConversationKey) -> Option<String> {
+        self.memory(key).0.get(key.id()).map(|conversation| {
+            run_result::final_answer(
+                &conversation
+                    .history
+                    .iter()
+                    .map(TranscriptEntry::from)
+                    .collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    /// Checks the current answer of `task` with the output guardrails. While a repairing
+    /// guardrail trips, the model is asked for a revised answer, which is checked again.
+    async fn guard_output(
+        &self,
+        ctx: &HookContext<'_>,
+        key: &ConversationKey,
+        run: &RunContext,
+    ) -> Result<(), AgentError> {
+        let mut repairs = 0;
+        loop {
+            let Some(answer) = self.current_answer(key) else {
+                return Ok(());
+            };
+            let mut outcomes = Vec::new();
+            let check = self
+                .guardrails
+                .check_output(answer.clone(), repairs, &mut outcomes)
+                .await;
+            run.guardrails.lock().unwrap().extend(outcomes);
+
+            match check {
+                OutputCheck::Passed(output) => {
+                    // The redacted answer replaces the original everywhere it's kept
+                    if output != answer {
+                        self.replace_answer(key, output);
+                    }
+                    return Ok(());
+                },
+                OutputCheck::Repair { guardrail, reason } => {
+                    repairs += 1;
+                    self.repair_answer(ctx, key, &guardrail, &reason, run)
+                        .await?;
+                    if !self.output_pipeline.is_empty() {
+                        self.process_output(key).await;
+                    }
+                },
+                OutputCheck::Blocked(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Asks the model for a revised answer of `task` after `guardrail` tripped, without tools.
+    async fn repair_answer(
+        &self,
+        ctx: &HookContext<'_>,
+        key: &ConversationKey,
+        guardrail: &str,
+        reason: &str,
+        run: &RunContext,
+    ) -> Result<(), AgentError> {
+        let history = self
+            .memory(key)
+            .0
+            .get(key.id())
+            .map(|conversation| conversation.deref().into())
+            .unwrap_or_default();
+        let prompt = guardrail::repair_prompt(guardrail, reason);
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::User(GUARDRAIL_ROLE.to_owned()),
+            &prompt,
+        );
+        let request = CompletionRequest {
+            prompt: llm::completion::Message::user(prompt),
+            system_prompt: run.system_prompt.clone(),
+            chat_history: history,
+            tools: vec![],
+            temperature: Some(self.config.temperature),
+            max_tokens: Some(self.config.max_tokens),
+        };
+        let answer = self
+            .complete(ctx, request, Some(run))
+            .await?
+            .into_iter()
+            .filter_map(|choice| match choice {
+                llm::completion::AssistantContent::Text(text) => Some(text.text),
+                llm::completion::AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::Assistant(self.config.name.to_owned()),
+            answer,
+        );
+        Ok(())
+    }
+
+    /// Asks the critic of `reflexion` to score the current answer of `task`, and adds the
+    /// critique to the conversation `key` under the [`CRITIC_ROLE`] role.
+    async fn critique_draft(
+        &self,
+        ctx: &HookContext<'_>,
+        task: &str,
+        key: &ConversationKey,
+        reflexion: &Reflexion,
+        run: &RunContext,
+    ) -> Result<Critique, CritiqueError> {
+        let draft = self.current_answer(key).unwrap_or_default();
+        let rubric = reflexion.rubric.as_deref();
+        let critique = match &reflexion.critic {
+            Some(critic) => tokio::select! {
+                critique = critic.critique(task, rubric, &draft) => critique?,
+                _ = run.cancel.cancelled() => return Err(AgentError::Canceled.into()),
+            },
+            None => {
+                let request = reflexion::critique_request(task, rubric, &draft);
+                reflexion::parse_choices(self.complete(ctx, request, Some(run)).await?)?
+            },
+        };
+
+        let passed = critique.score >= reflexion.threshold;
+        let mut message = format!("Score: {}/10.", critique.score);
+        if !critique.feedback.is_empty() {
+            message.push_str(&format!(" Feedback: {}", critique.feedback));
+        }
+        if !passed {
+            message.push_str("\nRevise your answer to address this feedback.");
+        }
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::User(CRITIC_ROLE.to_owned()),
+            message,
+        );
+
+        if self.config.verbose {
+            log_agent!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                "Draft scored {}/10 (threshold {})",
+                critique.score,
+                reflexion.threshold
+            );
+        }
+        Ok(critique)
+    }
+
+    /// Runs the output processors on the final answer of `task` and adds the result to its
+    /// conversation under the [`OUTPUT_CLEANER_ROLE`] role.
+    async fn process_output(&self, key: &ConversationKey) {
+        let Some(answer) = self.current_answer(key) else {
+            return;
+        };
+        if answer.is_empty() {
+            return;
+        }
+
+        match self.output_pipeline.process(answer).await {
+            Ok(output) => self.memory(key).add(
+                key.id(),
+                &self.config.name,
+                Role::Assistant(OUTPUT_CLEANER_ROLE.to_owned()),
+                output,
+            ),
+            Err(e) => tracing::error!(
+                "Failed to process the output of agent<{}>: {}",
+                self.config.name,
+                e
+            ),
+        }
+    }
+
+    /// Renders the conversation of a finished task, with the `on_complete` hooks applied.
+    ///
+    /// This is the output of [`Agent::run`].
+    fn conversation_output(&self, task: &str) -> String {
+        let ctx = self.hook_context(Some(task));
+        // TODO: More flexible output types, e.g. JSON, CSV, etc.
+        let mut output = self
+            .short_memory
+            .0
+            .get(task)
+            .expect("Task should exist in short memory")
+            .to_string();
+        for hook in &self.hooks {
+            hook.on_complete(&ctx, &mut output);
+        }
+        output
+    }
+
+    /// Query long-term memory and add the results to the conversation of `task`.
+    /// Does nothing if no long-term memory is configured.
+    async fn retrieve_long_term_memory(
+        &self,
+        key: &ConversationKey,
+        query: String,
+    ) -> Result<(), AgentError> {
+        let Some(memory) = &self.long_term_memory else {
+            return Ok(());
+        };
+
+        let mut records = memory
+            .query(
+                query,
+                self.config.long_term_memory_top_k,
+                self.config.long_term_memory_filter.clone(),
+            )
+            .await?;
+        // Documents already in the conversation aren't added again
+        let mut retrieved = self.retrieved_memories.entry(key.clone()).or_default();
+        records.retain(|record| retrieved.insert(record.id.clone()));
+        drop(retrieved);
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        if self.config.verbose {
+            log_memory!(
+                debug,
+                &self.config.name,
+                &self.config.id,
+                "Long Term Memory",
+                "Retrieved {} documents from long-term memory",
+                records.len()
+            );
+        }
+
+        let context = records
+            .iter()
+            .map(|record| format!("- {}", record.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.memory(key).add(
+            key.id(),
+            &self.config.name,
+            Role::User("LongTermMemory".to_owned()),
+            format!("Relevant information retrieved from long-term memory:\n{context}"),
+        );
+        Ok(())
+    }
+
+    /// Path of the state file of conversation `key`: `{save_state_dir}/{name}_{hash}.json`
+    /// for a task, `{save_state_dir}/{name}_session_{hash}.json` for a session.
+    fn task_state_path(&self, key: &ConversationKey) -> Option<PathBuf> {
+        let save_state_dir = self.config.save_state_dir.as_ref()?;
+
+        let mut hasher = XxHash3_64::default();
+        key.id().hash(&mut hasher);
+        let hash = hasher.finish();
+        let hash = format!("{:x}", hash & 0xFFFFFFFF); // lower 32 bits of the hash
+
+        let file_name = match key {
+            ConversationKey::Task(_) => format!("{}_{}", self.config.name, hash),
+            ConversationKey::Session(_) => format!("{}_session_{}", self.config.name, hash),
+        };
+        Some(
+            Path::new(save_state_dir)
+                .join(file_name)
+                .with_extension("json"),
+        )
+    }
+
+    /// Loads the saved state of `task` from `save_state_dir` and restores its
+    /// conversation and loop progress, replacing any conversation of the task in
+    /// short memory.
+    ///
+    /// State files written before loop progress was saved only contain the
+    /// conversation; they are restored with the loop counter at zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::InvalidSaveStatePath` if no `save_state_dir` is configured,
+    /// or an IO error if there is no saved state for the task.
+    pub async fn load_task_state(&self, task: &str) -> Result<AgentTaskState, AgentError> {
+        let key = ConversationKey::Task(task.to_owned());
+        let path = self.task_state_path(&key).ok_or_else(|| {
+            AgentError::InvalidSaveStatePath("save_state_dir is not set".to_owned())
+        })?;
+        let data = persistence::load_from_file(&path).await?;
+
+        let state = match serde_json::from_slice::<AgentTaskState>(&data) {
+            Ok(state) => state,
+            Err(_) => AgentTaskState {
+                task: task.to_owned(),
+                loop_count: 0,
+                pending_context: None,
+                finished: false,
+                budget_exceeded: None,
+                termination: None,
+                plan: None,
+                conversation: serde_json::from_slice::<AgentConversation>(&data)?,
+            },
+        };
+
+        self.short_memory
+            .0
+            .insert(task.to_owned(), state.conversation.clone());
+        self.task_progress.insert(
+            key,
+            TaskProgress {
+                loop_count: state.loop_count,
+                pending_context: state.pending_context.clone(),
+                finished: state.finished,
+                budget_exceeded: state.budget_exceeded.clone(),
+                termination: state.termination,
+                plan: state.plan.clone(),
+            },
+        );
+
+        if self.config.verbose {
+            log_memory!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                "Load State",
+                "Restored task state from {} at loop {}",
+                path.display(),
+                state.loop_count
+            );
+        }
+
+        Ok(state)
+    }
+
+    /// Resumes a task from its saved state.
+    ///
+    /// The conversation, loop counter and pending `task_evaluator` context are restored
+    /// with [`load_task_state`](Self::load_task_state) and the agent loop continues where
+    /// it stopped. If the saved run had already finished, the saved conversation is
+    /// returned without calling the LLM. If there is no saved state for the task, it is
+    /// run from the start like [`Agent::run`].
+    pub async fn resume(&self, task: String) -> Result<String, AgentError> {
+        self.resume_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    /// [`resume`](Self::resume) a task, stopping when `cancel` is canceled.
+    ///
+    /// See [`Agent::run_with_cancellation`].
+    pub async fn resume_with_cancellation(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> Result<String, AgentError> {
+        let run = RunContext::new(&self.config, self.render_system_prompt()?, cancel.clone());
+
+        let path = self
+            .task_state_path(&ConversationKey::Task(task.clone()))
+            .ok_or_else(|| {
+                AgentError::InvalidSaveStatePath("save_state_dir is not set".to_owned())
+            })?;
+        if !path.exists() {
+            return self.run_with_cancellation(task, cancel).await;
+        }
+
+        let state = self.load_task_state(&task).await?;
+        if self.config.verbose {
+            log_task!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                &task,
+                "Resuming task at loop {} of {}",
+                state.loop_count + 1,
+                self.config.max_loops
+            );
+        }
+
+        let progress = TaskProgress {
+            loop_count: state.loop_count,
+            pending_context: state.pending_context,
+            finished: state.finished,
+            budget_exceeded: state.budget_exceeded,
+            termination: state.termination,
+            plan: state.plan,
+        };
+        self.execute_task_loops(
+            task.clone(),
+            ConversationKey::Task(task.clone()),
+            progress,
+            run,
+        )
+        .await?;
+        Ok(self.conversation_output(&task))
+    }
+
+    /// Runs the task from the start: adds it to its conversation (or seeds the conversation with
+    /// `history` if it isn't empty), plans it, queries long-term memory and runs the agent loop.
+    /// The conversation is kept under `key`, the task itself if `key` is `None`.
+    async fn start_run(
+        &self,
+        task: String,
+        key: Option<ConversationKey>,
+        history: Vec<TranscriptEntry>,
+        cancel: CancellationToken,
+    ) -> Result<RunResult, AgentError> {
+        if cancel.is_cancelled() {
+            return Err(AgentError::Canceled);
+        }
+        let run = RunContext::new(&self.config, self.render_system_prompt()?, cancel);
+
+        // A redacted task replaces the task for the whole run
+        let mut outcomes = Vec::new();
+        let task = self.guardrails.check_input(task, &mut outcomes).await?;
+        *run.guardrails.lock().unwrap() = outcomes;
+        let key = key.unwrap_or_else(|| ConversationKey::Task(task.clone()));
+
+        if self.config.verbose {
+            log_task!(
+                info,
+                &self.config.name,
+                &self.config.id,
+                &task,
+                "Task initializing - Agent starting autonomous execution loop"
+            );
+        }
+
+        if history.is_empty() {
+            self.memory(&key).add(
+                key.id(),
+                &self.config.name,
+                Role::User(self.config.user_name.clone()),
+                &task,
+            );
+        } else {
+            // The conversation continues from the history, not from an earlier run of the task
+            self.memory(&key).0.remove(key.id());
+            self.retrieved_memories.remove(&key);
+            for entry in history {
+                self.memory(&key)
+                    .add(key.id(), &self.config.name, entry.role, entry.content);
+            }
+        }
+
+        if self.config.verbose {
+            log_memory!(
+                debug,
+                &self.config.name,
+                &self.config.id,
+                "Save Task",
+                "Added task to short-term memory"
+            );
+        }
 
+        // Plan
+        if self.config.plan_enabled {
             if self.config.verbose {
-                log_task!(
+                log_agent!(
                     info,
                     &self.config.name,
                     &self.config.id,
-                    &task,
-                    "Task initializing - Agent starting autonomous execution loop"
+                    "Planning phase initiated"
                 );
             }
+            match self.plan_task(&task, key.clone(), Some(&run)).await {
+                // The agent loop ends the run on its first budget check
+                Ok(()) | Err(AgentError::BudgetExceeded(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
 
-            self.short_memory.add(
-                &task,
-                &self.config.name,
-                Role::User(self.config.user_name.clone()),
-                &task,
-            );
+        // Query long term memory
+        if self.long_term_memory.is_some() {
+            self.retrieve_long_term_memory(&key, task.clone()).await?;
+        }
 
+        // Save state
+        if self.config.autosave {
             if self.config.verbose {
                 log_memory!(
                     debug,
                     &self.config.name,
                     &self.config.id,
-                    "Save Task",
-                    "Added task to short-term memory"
+                    "Autosave",
+                    "Saving agent state to disk"
                 );
             }
+            self.save_state(&key).await?;
+        }
 
-            // Plan
-            if self.config.plan_enabled {
-                if self.config.verbose {
-                    log_agent!(
-                        info,
-                        &self.config.name,
-                        &self.config.id,
-                        "Planning phase initiated"
-                    );
-                }
-                self.plan(task.clone()).await?;
-            }
+        let plan = if self.config.plan_enabled {
+            self.task_progress
+                .get(&key)
+                .and_then(|progress| progress.plan.clone())
+        } else {
+            None
+        };
+        self.execute_task_loops(
+            task,
+            key,
+            TaskProgress {
+                plan,
+                ..Default::default()
+            },
+            run,
+        )
+        .await
+    }
 
-            // Query long term memory
-            // if self.long_term_memory.is_some() {
-            //     self.query_long_term_memory(task.clone()).await?;
-            // }
+    /// Plans `task` and adds the plan to the conversation `key`. As part of `run`, planning
+    /// is canceled with it and counted in its budget.
+    async fn plan_task(
+        &self,
+        task: &str,
+        key: ConversationKey,
+        run: Option<&RunContext>,
+    ) -> Result<(), AgentError> {
+        let ctx = self.hook_context(Some(task));
+        let planning_prompt = plan::planning_prompt(self.config.planning_prompt.as_deref(), task);
+        let request = CompletionRequest {
+            prompt: llm::completion::Message::user(planning_prompt),
+            system_prompt: self.request_system_prompt(run)?,
+            chat_history: vec![],
+            tools: vec![],
+            temperature: Some(self.config.temperature),
+            max_tokens: Some(self.config.max_tokens),
+        };
+        let response = self
+            .complete(&ctx, request, run)
+            .await?
+            .into_iter()
+            .filter_map(|choice| match choice {
+                llm::completion::AssistantContent::Text(text) => Some(text.text),
+                llm::completion::AssistantContent::ToolCall(_) => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        tracing::debug!("Plan: {}", response);
+        // Keep the plan text as is if it has no steps
+        let plan = Plan::parse(&response);
+        let content = match &plan {
+            Some(plan) => format!("[Plan]\n{plan}"),
+            None => response,
+        };
+        self.task_progress.insert(
+            key.clone(),
+            TaskProgress {
+                plan,
+                ..Default::default()
+            },
+        );
+        // Add plan to memory
+        self.memory(&key).add(
+            key.id(),
+            self.config.name.clone(),
+            Role::Assistant(self.config.name.clone()),
+            content,
+        );
+        Ok(())
+    }
 
-            // Save state
-            if self.config.autosave {
-                if self.config.verbose {
-                    log_memory!(
-                        debug,
-                        &self.config.name,
-                        &self.config.id,
-                        "Autosave",
-                        "Saving agent state to disk"
-                    );
-                }
-                self.save_task_state(task.clone()).await?;
-            }
+    /// Status of the connections to the MCP servers of the agent's tools.
+    pub fn mcp_server_status(&self) -> Vec<MCPServerStatus> {
+        self.mcp_clients.iter().map(MCPClient::status).collect()
+    }
 
-            // Run agent loop
-            let mut last_response_text = String::new();
-            let mut task_complete = false;
-            let mut was_prev_call_task_evaluator = false;
+    /// A conversation with the agent that continues across runs, see
+    /// [`session`](super::session).
+    pub fn session(&self, id: impl Into<String>) -> AgentSession<'_, M> {
+        AgentSession::new(self, id.into())
+    }
 
-            if self.config.verbose {
-                log_agent!(
-                    info,
-                    &self.config.name,
-                    &self.config.id,
-                    "Starting autonomous execution loop - Max loops: {}",
-                    self.config.max_loops
-                );
-            }
+    /// IDs of the sessions of the agent, sorted.
+    pub fn session_ids(&self) -> Vec<String> {
+        self.sessions.ids()
+    }
 
-            for loop_count in 0..self.config.max_loops {
-                if task_complete {
-                    if self.config.verbose {
-                        log_agent!(
-                            info,
-                            &self.config.name,
-                            &self.config.id,
-                            "Task completed early at loop {} of {}",
-                            loop_count,
-                            self.config.max_loops
-                        );
-                    }
-                    break;
-                }
+    /// Ends session `id`, dropping its conversation. Returns whether it was ended: a session
+    /// with a run in progress isn't.
+    pub fn end_session(&self, id: &str) -> bool {
+        let ended = self.sessions.remove(id);
+        if ended {
+            self.drop_session_conversations([id.to_owned()]);
+        }
+        ended
+    }
 
-                if self.config.verbose {
-                    log_agent!(
-                        debug,
-                        &self.config.name,
-                        &self.config.id,
-                        "Starting loop iteration {} of {}",
-                        loop_count + 1,
-                        self.config.max_loops
-                    );
-                }
+    /// Ends the sessions idle for longer than the session TTL, and the least recently used
+    /// sessions above the maximum number of sessions. Returns the IDs of the ended sessions.
+    pub fn evict_sessions(&self) -> Vec<String> {
+        let ended = self.sessions.evict();
+        self.drop_session_conversations(ended.iter().cloned());
+        ended
+    }
 
-                let current_prompt: String;
+    fn drop_session_conversations(&self, ids: impl IntoIterator<Item = String>) {
+        for id in ids {
+            self.session_memory.0.remove(&id);
+            let key = ConversationKey::Session(id);
+            self.task_progress.remove(&key);
+            self.retrieved_memories.remove(&key);
+        }
+    }
 
-                if was_prev_call_task_evaluator {
-                    current_prompt = format!(
-                        "You previously called task_evaluator and indicated the task was not complete. The required next step or context provided was: '{}'. \
-                        Focus ONLY on addressing this context. DO NOT call task_evaluator again in this turn. Proceed with the task based on the context.",
-                        last_response_text // last_response is the context provided by task_evaluator
-                    );
+    /// Runs `task` as the next turn of session `id`.
+    pub(crate) async fn run_session(
+        &self,
+        id: &str,
+        task: String,
+        cancel: CancellationToken,
+    ) -> Result<RunResult, AgentError> {
+        let (_active, ended) = self.sessions.begin_run(id);
+        self.drop_session_conversations(ended);
 
-                    was_prev_call_task_evaluator = false;
-                } else if loop_count > 0 {
-                    current_prompt = format!(
-                        "Now, you are in loop {} of {}, The dialogue will terminate upon reaching maximum iteration count. You must:
-                         - Complete the user's task before termination
-                         - Optimize loop efficiency
-                         - Minimize resource consumption through minimal iterations
-
-                        You should consider to use tools if they can help, but only if they are relevant to the task and are necessary for the task.
-                        origin task:\n{}",
-                        loop_count + 1,
-                        self.config.max_loops,
-                        task
-                    )
-                } else {
-                    // first loop
-                    // task is already in short_memory, short_memory will be passed to llm
-                    // empty prompt should be ignored by LLM provider
-                    current_prompt = "".to_owned();
-                }
+        let mut result = self
+            .start_run(
+                task,
+                Some(ConversationKey::Session(id.to_owned())),
+                Vec::new(),
+                cancel,
+            )
+            .await?;
+        result.session_id = Some(id.to_owned());
+        let ctx = self.hook_context(Some(&result.task));
+        for hook in &self.hooks {
+            hook.on_complete(&ctx, &mut result.final_answer);
+        }
+        Ok(result)
+    }
 
-                let mut success = false;
-                // let task_prompt = self.short_memory.0.get(&task).unwrap().to_string(); // Safety: task is in short_memory
-                for attempt in 0..self.config.retry_attempts {
-                    if success {
-                        break;
-                    }
+    /// The conversation of session `id`.
+    pub(crate) fn session_history(&self, id: &str) -> Vec<TranscriptEntry> {
+        self.session_memory
+            .0
+            .get(id)
+            .map(|conversation| {
+                conversation
+                    .history
+                    .iter()
+                    .map(TranscriptEntry::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 
-                    // if self.long_term_memory.is_some() && self.config.rag_every_loop {
-                    //     // FIXME: if RAG success, but then LLM fails, then RAG is not removed and maybe causes issues
-                    //     if let Err(e) = self.query_long_term_memory(task_prompt.clone()).await {
-                    //         self.handle_error_in_attempts(&task, e, attempt).await;
-                    //         continue;
-                    //     };
-                    // }
-
-                    // Generate response using LLM
-                    let history = self.short_memory.0.get(&task).unwrap(); // Safety: task is in short_memory
-                    let current_chat_response =
-                        match self.chat(&current_prompt, history.deref()).await {
-                            Ok(response) => response,
-                            Err(e) => {
-                                self.handle_error_in_attempts(&task, e, attempt).await;
-                                continue;
-                            },
-                        };
-                    // needed to drop the lock
-                    // if use:
-                    // let history = (&(*self.short_memory.0.get(&task).unwrap())).into();
-                    // we don't need to drop the lock, because the lock is owned by temporary variable
-                    drop(history);
-
-                    // handle ChatResponse
-                    let mut assistant_memory_content = String::new();
-                    let mut is_task_evaluator_called = false;
-                    match current_chat_response {
-                        ChatResponse::Text(text) => {
-                            last_response_text = text.clone();
-                            assistant_memory_content = text;
-                        },
-                        ChatResponse::ToolCalls(tool_calls) => {
-                            let mut formatted_tool_results = String::new();
-                            for tool_call in tool_calls {
-                                let formatted = format!(
-                                    "[Tool name]: {}\n[Tool args]: {}\n[Tool result]: {}\n\n",
-                                    tool_call.name, tool_call.args, tool_call.result
-                                );
-                                formatted_tool_results.push_str(&formatted);
-                                if tool_call.name == ToolDyn::name(&TaskEvaluator) {
-                                    is_task_evaluator_called = true;
-                                    match serde_json::from_str::<TaskStatus>(&tool_call.result) {
-                                        Ok(task_status) => {
-                                            tracing::info!(
-                                                "Task evaluator tool called, task status: {:#?}",
-                                                task_status,
-                                            );
-
-                                            match task_status {
-                                                TaskStatus::Complete => {
-                                                    task_complete = true;
-                                                    // Task is complete
-                                                    // This may be a bit redundant, but it's here for clarity
-                                                    // last_response_text = format!(
-                                                    //     "Task marked as complete by task_evaluator. Result: {}",
-                                                    //     tool_call.result
-                                                    // );
-                                                    assistant_memory_content = formatted;
-                                                    // Store the final tool call in memory
-                                                },
-                                                TaskStatus::Incomplete { context } => {
-                                                    task_complete = false;
-                                                    // If not complete, store the context for the next loop's prompt
-                                                    last_response_text = context;
-                                                    // Keep the raw tool result for memory
-                                                    assistant_memory_content = formatted;
-                                                },
-                                            }
-                                        },
-                                        Err(e) => {
-                                            tracing::error!(
-                                                "Failed to parse task status from task_evaluator: {}. Raw result: {}",
-                                                e,
-                                                tool_call.result
-                                            );
-
-                                            task_complete = false;
-
-                                            last_response_text = format!(
-                                                "Error parsing task_evaluator result. Raw output: {}",
-                                                tool_call.result
-                                            );
-                                            assistant_memory_content = formatted;
-                                            // Store the problematic call
-                                        },
-                                    }
-                                } else {
-                                    // Handle other tool calls if necessary, for now just format them
-                                    // If this is the *only* response part, update last_response_text
-                                    if formatted_tool_results.len() == formatted.len() {
-                                        // Check if it's the first/only tool result string being built
-                                        last_response_text = formatted_tool_results.clone();
-                                    } else {
-                                        // Append to existing text/tool results for the final response string
-                                        last_response_text.push_str(&formatted);
-                                    }
-                                }
-                            }
-                            // If multiple tools were called, or if task_evaluator wasn't the only one,
-                            // ensure assistant_memory_content reflects all calls.
-                            if assistant_memory_content.is_empty() || !is_task_evaluator_called {
-                                assistant_memory_content = formatted_tool_results.clone();
-                                // Update last_response_text if it wasn't set by task_evaluator
-                                if !is_task_evaluator_called {
-                                    last_response_text = formatted_tool_results;
-                                }
-                            }
-                        },
-                    }
+    /// The map holding the conversation `key`.
+    fn memory(&self, key: &ConversationKey) -> &AgentShortMemory {
+        match key {
+            ConversationKey::Task(_) => &self.short_memory,
+            ConversationKey::Session(_) => &self.session_memory,
+        }
+    }
 
-                    // Update the flag for the *next* iteration based on *this* iteration's call
-                    was_prev_call_task_evaluator = is_task_evaluator_called && !task_complete;
+    /// Saves the conversation `key` and its loop progress to `save_state_dir`, if set.
+    async fn save_state(&self, key: &ConversationKey) -> Result<(), AgentError> {
+        if let Some(path) = self.task_state_path(key) {
+            if let Some(save_state_dir) = path.parent()
+                && !save_state_dir.exists()
+            {
+                tokio::fs::create_dir_all(save_state_dir).await?;
+            }
 
-                    self.short_memory.add(
-                        &task,
-                        &self.config.name,
-                        Role::Assistant(self.config.name.to_owned()),
-                        assistant_memory_content.clone(), // Add the text or formatted tool calls
-                    );
+            let Some(conversation) = self.memory(key).0.get(key.id()).map(|c| c.clone()) else {
+                return Ok(());
+            };
+            let progress = self
+                .task_progress
+                .get(key)
+                .map(|p| p.clone())
+                .unwrap_or_default();
+            let state = AgentTaskState {
+                task: key.id().to_owned(),
+                loop_count: progress.loop_count,
+                pending_context: progress.pending_context,
+                finished: progress.finished,
+                budget_exceeded: progress.budget_exceeded,
+                termination: progress.termination,
+                plan: progress.plan,
+                conversation,
+            };
 
-                    success = true;
-                }
+            let json = serde_json::to_string_pretty(&state)?;
+            persistence::save_to_file(&json, path).await?;
+        }
+        Ok(())
+    }
 
-                if !success {
-                    // Exit the loop if all retry failed
-                    break;
-                }
+    /// Handle error in attempts
+    async fn handle_error_in_attempts(
+        &self,
+        task: &str,
+        key: &ConversationKey,
+        error: AgentError,
+        attempt: u32,
+    ) {
+        let err_msg = format!("Attempt {}, task: {}, failed: {}", attempt + 1, task, error);
+        tracing::error!(err_msg);
 
-                // Save state in each loop
-                if self.config.autosave {
-                    self.save_task_state(task.clone()).await?;
-                }
+        let ctx = self.hook_context(Some(task));
+        for hook in &self.hooks {
+            hook.on_retry(&ctx, attempt, &error);
+        }
 
-                if self.is_response_complete(last_response_text.clone()) {
-                    if self.config.verbose {
-                        log_agent!(
-                            info,
-                            &self.config.name,
-                            &self.config.id,
-                            "Response marked as complete by completion checker"
-                        );
-                    }
-                    break;
-                }
+        if self.config.autosave {
+            let _ = self.save_state(key).await.map_err(|e| {
+                tracing::error!(
+                    "Failed to save agent<{}> task<{}>,  state: {}",
+                    self.config.name,
+                    task,
+                    e
+                )
+            });
+        }
+    }
+}
 
-                // TODO: Loop interval, maybe add a sleep here
-            }
+impl<M> Agent for SwarmsAgent<M>
+where
+    M: llm::Model + Clone + Send + Sync + 'static,
+    M::RawCompletionResponse: Clone + Send + Sync,
+{
+    fn run(&self, task: String) -> BoxFuture<Result<String, AgentError>> {
+        self.run_with_cancellation(task, CancellationToken::new())
+    }
 
-            // TODO: Apply the cleaning function to the responses
-            // clean and add to short memory. role: Assistant(Output Cleaner)
+    /// Runs the task until it finishes or `cancel` is canceled.
+    ///
+    /// Cancellation is checked between loops, and in-flight LLM and tool calls are
+    /// abandoned. The task state is saved before `AgentError::Canceled` is returned, so it
+    /// can be continued with [`SwarmsAgent::resume`].
+    fn run_with_cancellation(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move {
+            let result = self.start_run(task, None, Vec::new(), cancel).await?;
+            Ok(self.conversation_output(&result.task))
+        })
+    }
 
-            // Save state
-            if self.config.autosave {
-                if self.config.verbose {
-                    log_memory!(
-                        debug,
-                        &self.config.name,
-                        &self.config.id,
-                        "Final Autosave",
-                        "Saving final agent state after task completion"
-                    );
-                }
-                self.save_task_state(task.clone()).await?;
+    /// Runs the task like [`run_with_cancellation`](Agent::run_with_cancellation) and
+    /// returns its [`RunResult`]. The `on_complete` hooks receive the final answer.
+    fn run_with_result(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<RunResult, AgentError>> {
+        Box::pin(async move {
+            let mut result = self.start_run(task, None, Vec::new(), cancel).await?;
+            let ctx = self.hook_context(Some(&result.task));
+            for hook in &self.hooks {
+                hook.on_complete(&ctx, &mut result.final_answer);
             }
+            Ok(result)
+        })
+    }
 
-            let total_duration = start_time.elapsed().as_millis() as u64;
-            if self.config.verbose {
-                log_perf!(info, "Agent", "total_execution_time", total_duration, "ms");
+    /// Runs the task like [`run_with_result`](Agent::run_with_result), also returning its
+    /// conversation like [`run_with_cancellation`](Agent::run_with_cancellation). The
+    /// `on_complete` hooks receive the conversation.
+    fn run_with_output(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<(String, RunResult), AgentError>> {
+        Box::pin(async move {
+            let result = self.start_run(task, None, Vec::new(), cancel).await?;
+            Ok((self.conversation_output(&result.task), result))
+        })
+    }
 
-                log_task!(
-                    info,
-                    &self.config.name,
-                    &self.config.id,
-                    &task,
-                    "Task execution completed successfully in {}ms",
-                    total_duration
-                );
+    /// Runs the task like [`run_with_result`](Agent::run_with_result), with a conversation
+    /// made of `history` instead of the task alone. The agent answers with its own system
+    /// prompt and tools.
+    fn run_with_history(
+        &self,
+        task: String,
+        history: Vec<TranscriptEntry>,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<RunResult, AgentError>> {
+        Box::pin(async move {
+            let mut result = self.start_run(task, None, history, cancel).await?;
+            let ctx = self.hook_context(Some(&result.task));
+            for hook in &self.hooks {
+                hook.on_complete(&ctx, &mut result.final_answer);
             }
-
-            // TODO: Handle artifacts
-
-            // TODO: More flexible output types, e.g. JSON, CSV, etc.
-            Ok(self
-                .short_memory
-                .0
-                .get(&task)
-                .expect("Task should exist in short memory")
-                .to_string())
+            Ok(result)
         })
     }
 
@@ -1378,49 +3568,21 @@ where
 
     fn plan(&self, task: String) -> BoxFuture<Result<(), AgentError>> {
         Box::pin(async move {
-            if let Some(planning_prompt) = &self.config.planning_prompt {
-                let planning_prompt = format!("{} {}", planning_prompt, task);
-                let plan = self.prompt(planning_prompt).await?;
-                tracing::debug!("Plan: {}", plan);
-                // Add plan to memory
-                self.short_memory.add(
-                    task,
-                    self.config.name.clone(),
-                    Role::Assistant(self.config.name.clone()),
-                    plan,
-                );
-            };
-            Ok(())
+            self.plan_task(&task, ConversationKey::Task(task.clone()), None)
+                .await
         })
     }
 
-    fn query_long_term_memory(&self, _task: String) -> BoxFuture<Result<(), AgentError>> {
-        unimplemented!("query_long_term_memory not implemented")
+    fn query_long_term_memory(&self, task: String) -> BoxFuture<Result<(), AgentError>> {
+        Box::pin(async move {
+            let query = task.clone();
+            self.retrieve_long_term_memory(&ConversationKey::Task(task), query)
+                .await
+        })
     }
 
     fn save_task_state(&self, task: String) -> BoxFuture<Result<(), AgentError>> {
-        let mut hasher = XxHash3_64::default();
-        task.hash(&mut hasher);
-        let task_hash = hasher.finish();
-        let task_hash = format!("{:x}", task_hash & 0xFFFFFFFF); // lower 32 bits of the hash
-
-        Box::pin(async move {
-            let save_state_dir = self.config.save_state_dir.clone();
-            if let Some(save_state_dir) = save_state_dir {
-                let save_state_dir = Path::new(&save_state_dir);
-                if !save_state_dir.exists() {
-                    tokio::fs::create_dir_all(save_state_dir).await?;
-                }
-
-                let path = save_state_dir
-                    .join(format!("{}_{}", self.name(), task_hash))
-                    .with_extension("json");
-
-                let json = serde_json::to_string_pretty(&self.short_memory.0.get(&task).unwrap())?; // TODO: Safety?
-                persistence::save_to_file(&json, path).await?;
-            }
-            Ok(())
-        })
+        Box::pin(async move { self.save_state(&ConversationKey::Task(task)).await })
     }
 
     fn is_response_complete(&self, response: String) -> bool {
@@ -1511,6 +3673,7 @@ pub enum ChatResponse {
 ///     name: "calculator".to_string(),
 ///     args: r#"{"operation": "add", "a": 5, "b": 3}"#.to_string(),
 ///     result: "8".to_string(),
+///     error: None,
 /// };
 ///
 /// println!("Tool {} with args {} returned: {}",
@@ -1532,8 +3695,313 @@ pub struct ToolCallOutput {
     /// The result returned by the tool's execution as a string.
     ///
     /// All tool results are converted to strings for consistent handling,
-    /// even if the tool internally works with other data types.
+    /// even if the tool internally works with other data types. For failed calls
+    /// this is the error message returned to the LLM.
     pub result: String,
+
+    /// Why the call failed, or `None` if it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ToolCallErrorKind>,
+}
+
+impl ToolCallOutput {
+    fn error(name: String, args: String, kind: ToolCallErrorKind, result: String) -> Self {
+        Self {
+            name,
+            args,
+            result,
+            error: Some(kind),
+        }
+    }
+
+    /// Whether the tool call failed.
+    pub fn is_error(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Why a tool call failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallErrorKind {
+    /// No tool with the requested name is registered.
+    NotFound,
+    /// The arguments couldn't be parsed by the tool.
+    InvalidArguments,
+    /// The tool returned an error.
+    ExecutionFailed,
+    /// The tool didn't return within its [`ToolPolicy::timeout`].
+    Timeout,
+    /// The approval handler denied the call.
+    Denied,
+    /// A hook vetoed the call.
+    Vetoed,
+    /// The run's [`RunBudget`] is exhausted.
+    BudgetExceeded,
+    /// The run was canceled while the tool was running.
+    Canceled,
+}
+
+impl ToolCallErrorKind {
+    /// Whether a call that failed this way may succeed when retried with the same arguments.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::ExecutionFailed | Self::Timeout)
+    }
+}
+
+impl From<&ToolError> for ToolCallErrorKind {
+    fn from(error: &ToolError) -> Self {
+        match error {
+            ToolError::ToolCallError(_) => Self::ExecutionFailed,
+            ToolError::JsonError(_) => Self::InvalidArguments,
+        }
+    }
+}
+
+impl std::fmt::Display for ToolCallErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Self::NotFound => "not found",
+            Self::InvalidArguments => "invalid arguments",
+            Self::ExecutionFailed => "execution failed",
+            Self::Timeout => "timeout",
+            Self::Denied => "denied",
+            Self::Vetoed => "vetoed",
+            Self::BudgetExceeded => "budget exceeded",
+            Self::Canceled => "canceled",
+        };
+        f.write_str(kind)
+    }
+}
+
+/// Timeout and retry policy of a tool.
+///
+/// Set per tool with [`SwarmsAgentBuilder::tool_policy`], or for all tools with
+/// [`SwarmsAgentBuilder::default_tool_policy`]. Only calls that failed or timed out are
+/// retried; calls with invalid arguments are returned to the LLM right away.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use swarms_rs::agent::ToolPolicy;
+///
+/// let policy = ToolPolicy::default()
+///     .timeout(Duration::from_secs(10))
+///     .max_retries(2)
+///     .retry_backoff(Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolPolicy {
+    /// How long a single attempt may take, or `None` to wait indefinitely (default: `None`).
+    pub timeout: Option<Duration>,
+    /// How many times a failed call is retried (default: 0).
+    pub max_retries: u32,
+    /// Delay before the first retry, growing linearly with each retry (default: 1 second).
+    pub retry_backoff: Duration,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            max_retries: 0,
+            retry_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ToolPolicy {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Wait for the tool indefinitely.
+    pub fn no_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+}
+
+/// Names of the system prompt template variables set for each run.
+const RUN_PROMPT_VARIABLES: [&str; 6] = [
+    "date",
+    "datetime",
+    "agent_name",
+    "agent_description",
+    "user_name",
+    "tools",
+];
+
+fn validate_system_prompt(
+    template: Option<&PromptTemplate>,
+    context: &PromptContext,
+) -> Result<(), TemplateError> {
+    match template {
+        Some(template) => {
+            template.validate(RUN_PROMPT_VARIABLES.into_iter().chain(context.names()))
+        },
+        None => Ok(()),
+    }
+}
+
+/// The definitions and implementations of `tools`, in order, tools with the name of a tool
+/// before them handled as set by `policy`.
+fn resolve_tools(
+    tools: Vec<Arc<dyn ToolDyn>>,
+    policy: DuplicateToolPolicy,
+) -> Result<(Vec<ToolDefinition>, DashMap<String, Arc<dyn ToolDyn>>), AgentError> {
+    let mut definitions = Vec::with_capacity(tools.len());
+    let tools_impl = DashMap::with_capacity(tools.len());
+    for tool in tools {
+        resolve_tool(&mut definitions, &tools_impl, tool, policy)?;
+    }
+    Ok((definitions, tools_impl))
+}
+
+/// Adds `tool` to `definitions` and `tools_impl`, a tool with the name of a tool already
+/// added handled as set by `policy`.
+fn resolve_tool(
+    definitions: &mut Vec<ToolDefinition>,
+    tools_impl: &DashMap<String, Arc<dyn ToolDyn>>,
+    tool: Arc<dyn ToolDyn>,
+    policy: DuplicateToolPolicy,
+) -> Result<(), AgentError> {
+    let definition = tool.definition();
+    let Some(index) = definitions
+        .iter()
+        .position(|added| added.name == definition.name)
+    else {
+        tools_impl.insert(definition.name.clone(), tool);
+        definitions.push(definition);
+        return Ok(());
+    };
+    match policy {
+        DuplicateToolPolicy::Error => {
+            return Err(AgentError::DuplicateTool(definition.name));
+        },
+        DuplicateToolPolicy::KeepFirst => {
+            log::warn!("Ignoring tool {}, added more than once", definition.name);
+        },
+        DuplicateToolPolicy::KeepLast => {
+            log::warn!("Replacing tool {}, added more than once", definition.name);
+            tools_impl.insert(definition.name.clone(), tool);
+            definitions[index] = definition;
+        },
+    }
+    Ok(())
+}
+
+/// State of a single run, shared by its LLM and tool calls.
+struct RunContext {
+    id: String,
+    budget: BudgetTracker,
+    cancel: CancellationToken,
+    started_at: DateTime<Local>,
+    /// Time spent waiting for the LLM
+    llm_duration: std::sync::Mutex<Duration>,
+    /// Time spent executing tool calls
+    tool_duration: std::sync::Mutex<Duration>,
+    /// Artifacts saved during the run
+    artifacts: Arc<std::sync::Mutex<Vec<Artifact>>>,
+    /// Nesting depth of the run, 0 unless it was delegated to by another agent
+    depth: u32,
+    /// Results of the agents delegated to during the run
+    sub_runs: std::sync::Mutex<Vec<RunResult>>,
+    /// System prompt of the run, rendered from the template when the run started
+    system_prompt: Option<String>,
+    /// Guardrail checks made during the run
+    guardrails: std::sync::Mutex<Vec<guardrail::GuardrailOutcome>>,
+}
+
+impl RunContext {
+    fn new(config: &AgentConfig, system_prompt: Option<String>, cancel: CancellationToken) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            budget: BudgetTracker::new(config.budget.clone(), config.pricing),
+            cancel,
+            started_at: Local::now(),
+            llm_duration: std::sync::Mutex::default(),
+            tool_duration: std::sync::Mutex::default(),
+            artifacts: Arc::default(),
+            depth: agent_tool::current_depth(),
+            sub_runs: std::sync::Mutex::default(),
+            system_prompt,
+            guardrails: std::sync::Mutex::default(),
+        }
+    }
+}
+
+/// Key of a conversation of the agent: tasks and sessions are kept in separate maps, so a
+/// task can't read or continue the conversation of a session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConversationKey {
+    /// The conversation of a run of the task
+    Task(String),
+    /// The conversation of the session with this ID
+    Session(String),
+}
+
+impl ConversationKey {
+    /// The task or session ID, the key of the conversation in its map.
+    fn id(&self) -> &str {
+        match self {
+            Self::Task(id) | Self::Session(id) => id,
+        }
+    }
+}
+
+/// Loop progress of a task, used to resume it from saved state.
+#[derive(Debug, Clone, Default)]
+struct TaskProgress {
+    /// Number of loops already completed
+    loop_count: u32,
+    /// Context given by `task_evaluator` for the next loop, if it reported the task incomplete
+    pending_context: Option<String>,
+    /// Whether the run finished (task complete, stop word, max loops, retries or budget exhausted)
+    finished: bool,
+    /// The budget that stopped the run, if one did
+    budget_exceeded: Option<BudgetExceeded>,
+    /// Why the run stopped, once it finished
+    termination: Option<TerminationReason>,
+    /// The plan of the task, if planning is enabled
+    plan: Option<Plan>,
+}
+
+/// The state of a task as saved by [`Agent::save_task_state`] and restored by
+/// [`SwarmsAgent::load_task_state`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AgentTaskState {
+    /// The task, or the session ID for the state of a session.
+    pub task: String,
+    /// Number of loops already completed.
+    pub loop_count: u32,
+    /// Context given by `task_evaluator` for the next loop, if it reported the task incomplete.
+    pub pending_context: Option<String>,
+    /// Whether the run already finished.
+    pub finished: bool,
+    /// The budget that stopped the run, if one did.
+    #[serde(default)]
+    pub budget_exceeded: Option<BudgetExceeded>,
+    /// Why the run stopped, once it finished.
+    #[serde(default)]
+    pub termination: Option<TerminationReason>,
+    /// The plan of the task, if planning is enabled.
+    #[serde(default)]
+    pub plan: Option<Plan>,
+    /// The conversation of the task.
+    pub conversation: AgentConversation,
 }
 
 #[tool(
@@ -0,0 +1,226 @@
+//! Capability descriptors for LLM providers.
+//!
+//! Every [`Model`](super::Model) can describe what it accepts (tools, images, documents,
+//! audio) and what limits apply to it (context window, output tokens). Agents use these
+//! descriptors to reject unsupported requests before they are sent to the provider.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    completion::{AssistantContent, Message, ToolResultContent, UserContent},
+    request::{CompletionRequest, ToolDefinition},
+};
+
+/// Describes the features and limits of a model.
+///
+/// The [`Default`] value is permissive: everything is assumed to be supported and no
+/// limits are known. Providers override [`Model::capabilities`](super::Model::capabilities)
+/// to report what they actually support.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Whether the model supports tool (function) calling.
+    pub tools: bool,
+    /// Whether the model accepts image content.
+    pub vision: bool,
+    /// Whether the model accepts document content (PDF, text files, ...).
+    pub documents: bool,
+    /// Whether the model accepts audio content.
+    pub audio: bool,
+    /// Whether the provider implementation can stream responses.
+    pub streaming: bool,
+    /// Whether the model supports JSON schema constrained output.
+    pub json_schema: bool,
+    /// Maximum number of tokens in the context window, if known.
+    pub max_context_tokens: Option<u64>,
+    /// Maximum number of tokens the model can generate in one response, if known.
+    pub max_output_tokens: Option<u64>,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            vision: true,
+            documents: true,
+            audio: true,
+            streaming: true,
+            json_schema: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// Capabilities of a model that only handles plain text, without tool calling.
+    pub fn text_only() -> Self {
+        Self {
+            tools: false,
+            vision: false,
+            documents: false,
+            audio: false,
+            streaming: false,
+            json_schema: false,
+            max_context_tokens: None,
+            max_output_tokens: None,
+        }
+    }
+
+    /// Check that the given tools can be sent to the model.
+    pub fn validate_tools(&self, tools: &[ToolDefinition]) -> Result<(), CapabilityError> {
+        if !tools.is_empty() && !self.tools {
+            return Err(CapabilityError::ToolsNotSupported(tools.len()));
+        }
+        Ok(())
+    }
+
+    /// Check that `max_tokens` does not exceed the model's output limit.
+    pub fn validate_max_tokens(&self, max_tokens: u64) -> Result<(), CapabilityError> {
+        match self.max_output_tokens {
+            Some(limit) if max_tokens > limit => Err(CapabilityError::MaxTokensExceeded {
+                requested: max_tokens,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that every piece of content in `messages` is supported by the model.
+    pub fn validate_messages<'a>(
+        &self,
+        messages: impl IntoIterator<Item = &'a Message>,
+    ) -> Result<(), CapabilityError> {
+        for message in messages {
+            let Message::User { content } = message else {
+                continue;
+            };
+            for item in content {
+                match item {
+                    UserContent::Text(_) => {},
+                    UserContent::Image(_) => self.require(self.vision, ContentKind::Image)?,
+                    UserContent::Audio(_) => self.require(self.audio, ContentKind::Audio)?,
+                    UserContent::Document(_) => {
+                        self.require(self.documents, ContentKind::Document)?
+                    },
+                    UserContent::ToolResult(tool_result) => {
+                        for content in &tool_result.content {
+                            if let ToolResultContent::Image(_) = content {
+                                self.require(self.vision, ContentKind::Image)?;
+                            }
+                        }
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate a complete request: tools, content, output tokens and a rough estimate
+    /// of the prompt size against the context window.
+    pub fn validate_request(&self, request: &CompletionRequest) -> Result<(), CapabilityError> {
+        self.validate_tools(&request.tools)?;
+        self.validate_messages(request.chat_history.iter().chain([&request.prompt]))?;
+        if let Some(max_tokens) = request.max_tokens {
+            self.validate_max_tokens(max_tokens)?;
+        }
+        if let Some(limit) = self.max_context_tokens {
+            let estimated = estimate_tokens(request);
+            if estimated > limit {
+                return Err(CapabilityError::ContextWindowExceeded { estimated, limit });
+            }
+        }
+        Ok(())
+    }
+
+    fn require(&self, supported: bool, kind: ContentKind) -> Result<(), CapabilityError> {
+        if supported {
+            Ok(())
+        } else {
+            Err(CapabilityError::ContentNotSupported(kind))
+        }
+    }
+}
+
+/// A rough, provider independent estimate of the number of prompt tokens in a request
+/// (about four characters per token).
+pub(crate) fn estimate_tokens(request: &CompletionRequest) -> u64 {
+    let text_len = |message: &Message| -> usize {
+        match message {
+            Message::User { content } => content
+                .iter()
+                .map(|c| match c {
+                    UserContent::Text(text) => text.text.len(),
+                    _ => 0,
+                })
+                .sum(),
+            Message::Assistant { content } => content
+                .iter()
+                .map(|c| match c {
+                    AssistantContent::Text(text) => text.text.len(),
+                    AssistantContent::ToolCall(call) => call.function.arguments.to_string().len(),
+                })
+                .sum(),
+        }
+    };
+
+    let chars = request.system_prompt.as_deref().map_or(0, str::len)
+        + request.chat_history.iter().map(text_len).sum::<usize>()
+        + text_len(&request.prompt)
+        + request
+            .tools
+            .iter()
+            .map(|tool| tool.description.len() + tool.parameters.to_string().len())
+            .sum::<usize>();
+
+    (chars / 4) as u64
+}
+
+/// The same estimate for the choices of a response.
+pub(crate) fn estimate_output_tokens(choices: &[AssistantContent]) -> u64 {
+    let chars = choices
+        .iter()
+        .map(|choice| match choice {
+            AssistantContent::Text(text) => text.text.len(),
+            AssistantContent::ToolCall(call) => {
+                call.function.name.len() + call.function.arguments.to_string().len()
+            },
+        })
+        .sum::<usize>();
+
+    (chars / 4) as u64
+}
+
+/// Kinds of non-text content a model may not support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentKind {
+    Image,
+    Audio,
+    Document,
+}
+
+impl Display for ContentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentKind::Image => write!(f, "image"),
+            ContentKind::Audio => write!(f, "audio"),
+            ContentKind::Document => write!(f, "document"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum CapabilityError {
+    #[error("Model does not support tool calling, but {0} tool(s) were provided")]
+    ToolsNotSupported(usize),
+    #[error("Model does not support {0} content")]
+    ContentNotSupported(ContentKind),
+    #[error("max_tokens {requested} exceeds the model's output limit of {limit} tokens")]
+    MaxTokensExceeded { requested: u64, limit: u64 },
+    #[error(
+        "Request is about {estimated} tokens, which exceeds the model's context window of {limit} tokens"
+    )]
+    ContextWindowExceeded { estimated: u64, limit: u64 },
+}
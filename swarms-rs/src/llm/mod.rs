@@ -1,7 +1,9 @@
+use capabilities::ModelCapabilities;
 use futures::future::BoxFuture;
-use request::{CompletionRequest, CompletionResponse};
+use request::{CompletionRequest, CompletionResponse, Usage};
 use thiserror::Error;
 
+pub mod capabilities;
 pub mod completion;
 pub mod provider;
 pub mod request;
@@ -13,6 +15,19 @@ pub trait Model {
         &self,
         request: CompletionRequest,
     ) -> BoxFuture<Result<CompletionResponse<Self::RawCompletionResponse>, CompletionError>>;
+
+    /// Describes what this model supports. Models that don't override this are assumed
+    /// to support everything, and unsupported requests are reported by the provider.
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::default()
+    }
+
+    /// Token usage reported in a raw response, if the provider reports it. Agents estimate
+    /// usage from the request and response text otherwise.
+    fn usage(&self, response: &Self::RawCompletionResponse) -> Option<Usage> {
+        let _ = response;
+        None
+    }
 }
 
 // Errors
@@ -175,7 +175,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::llm::{
     self, CompletionError, Model,
-    request::{CompletionRequest, CompletionResponse},
+    capabilities::ModelCapabilities,
+    request::{CompletionRequest, CompletionResponse, Usage},
 };
 
 /// Anthropic API client for Claude models
@@ -304,6 +305,29 @@ impl Anthropic {
         &self.model
     }
 
+    /// Capabilities of a Claude model by name.
+    ///
+    /// Multimedia content is not supported yet by the request conversion, so only text and
+    /// tools are reported regardless of the model.
+    fn capabilities_for_model(model: &str) -> ModelCapabilities {
+        let max_output_tokens = match model {
+            m if m.starts_with("claude-opus-4") => Some(32_000),
+            m if m.starts_with("claude-sonnet-4") || m.starts_with("claude-3-7-sonnet") => {
+                Some(64_000)
+            },
+            m if m.starts_with("claude-3-5") => Some(8_192),
+            m if m.starts_with("claude-3") => Some(4_096),
+            _ => None,
+        };
+
+        ModelCapabilities {
+            tools: true,
+            max_context_tokens: model.starts_with("claude").then_some(200_000),
+            max_output_tokens,
+            ..ModelCapabilities::text_only()
+        }
+    }
+
     /// Helper function to create Anthropic client with cached fields for performance
     ///
     /// This function pre-computes and caches:
@@ -709,6 +733,17 @@ impl Model for Anthropic {
             })
         })
     }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        Self::capabilities_for_model(&self.model)
+    }
+
+    fn usage(&self, response: &Self::RawCompletionResponse) -> Option<Usage> {
+        Some(Usage::new(
+            response.usage.input_tokens.into(),
+            response.usage.output_tokens.into(),
+        ))
+    }
 }
 
 /// Convert internal user content to Anthropic format
@@ -843,4 +878,18 @@ mod tests {
         let anthropic = Anthropic::new("test-key").set_model("claude-3-haiku-20240307");
         assert_eq!(anthropic.model, "claude-3-haiku-20240307");
     }
+
+    #[test]
+    fn test_anthropic_capabilities() {
+        let capabilities = Anthropic::new("test-key").capabilities();
+        assert!(capabilities.tools);
+        assert!(!capabilities.vision);
+        assert!(!capabilities.audio);
+        assert!(!capabilities.documents);
+        assert_eq!(capabilities.max_context_tokens, Some(200_000));
+        assert_eq!(capabilities.max_output_tokens, Some(8_192));
+
+        let haiku = Anthropic::new("test-key").set_model("claude-3-haiku-20240307");
+        assert_eq!(haiku.capabilities().max_output_tokens, Some(4_096));
+    }
 }
@@ -22,7 +22,8 @@ use crate::{
     agent::SwarmsAgentBuilder, // Updated import path - now from crate::agent instead of crate::structs::agent
     llm::{
         self, CompletionError, Model,
-        request::{CompletionRequest, CompletionResponse},
+        capabilities::ModelCapabilities,
+        request::{CompletionRequest, CompletionResponse, Usage},
     },
 };
 
@@ -88,6 +89,41 @@ impl OpenAI {
     pub fn agent_builder(&self) -> SwarmsAgentBuilder<Self> {
         SwarmsAgentBuilder::new_with_model(self.clone())
     }
+
+    /// Capabilities of a model by name.
+    ///
+    /// Document content is never supported because the request conversion doesn't handle it,
+    /// and streaming is not implemented. Unknown models (e.g. OpenAI compatible endpoints)
+    /// report no token limits.
+    fn capabilities_for_model(model: &str) -> ModelCapabilities {
+        let base = ModelCapabilities {
+            documents: false,
+            streaming: false,
+            ..Default::default()
+        };
+
+        let (vision, json_schema, max_context_tokens, max_output_tokens) = match model {
+            m if m.starts_with("gpt-4.1") => (true, true, 1_047_576, 32_768),
+            m if m.starts_with("gpt-4o") || m.starts_with("chatgpt-4o") => {
+                (true, true, 128_000, 16_384)
+            },
+            m if m.starts_with("gpt-4-turbo") => (true, false, 128_000, 4_096),
+            m if m.starts_with("gpt-3.5-turbo") => (false, false, 16_385, 4_096),
+            m if m.starts_with("o1") || m.starts_with("o3") || m.starts_with("o4") => {
+                (true, true, 200_000, 100_000)
+            },
+            _ => return base,
+        };
+
+        ModelCapabilities {
+            vision,
+            audio: model.contains("audio"),
+            json_schema,
+            max_context_tokens: Some(max_context_tokens),
+            max_output_tokens: Some(max_output_tokens),
+            ..base
+        }
+    }
 }
 
 impl Model for OpenAI {
@@ -178,6 +214,17 @@ impl Model for OpenAI {
             Ok(response)
         })
     }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        Self::capabilities_for_model(&self.model)
+    }
+
+    fn usage(&self, response: &Self::RawCompletionResponse) -> Option<Usage> {
+        response
+            .usage
+            .as_ref()
+            .map(|usage| Usage::new(usage.prompt_tokens.into(), usage.completion_tokens.into()))
+    }
 }
 
 impl From<async_openai::error::OpenAIError> for CompletionError {
@@ -1,2 +1,22 @@
 pub mod multi_agent_collab_prompt;
 pub mod multi_agent_collab_prompt_new;
+pub mod swarm_rules_prompt;
+pub mod template;
+
+use multi_agent_collab_prompt::MULTI_AGENT_COLLAB_PROMPT;
+use multi_agent_collab_prompt_new::MULTI_AGENT_COLLAB_PROMPT_NEW;
+use swarm_rules_prompt::SWARM_RULES_PROMPT;
+
+/// The built-in prompts that templates can include, by partial name:
+///
+/// - `multi_agent_collab`: [`MULTI_AGENT_COLLAB_PROMPT`]
+/// - `multi_agent_collab_new`: [`MULTI_AGENT_COLLAB_PROMPT_NEW`]
+/// - `swarm_rules`: [`SWARM_RULES_PROMPT`]
+pub fn partial(name: &str) -> Option<&'static str> {
+    match name {
+        "multi_agent_collab" => Some(MULTI_AGENT_COLLAB_PROMPT),
+        "multi_agent_collab_new" => Some(MULTI_AGENT_COLLAB_PROMPT_NEW),
+        "swarm_rules" => Some(SWARM_RULES_PROMPT),
+        _ => None,
+    }
+}
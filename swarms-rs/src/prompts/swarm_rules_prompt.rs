@@ -0,0 +1,3 @@
+/// Rules appended to the system prompt of every agent of a swarm, as a template of the `rules`
+/// variable.
+pub const SWARM_RULES_PROMPT: &str = "\n### SWARM RULES ###\n{{rules}}";
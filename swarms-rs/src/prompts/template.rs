@@ -0,0 +1,378 @@
+//! # Prompt Templates
+//!
+//! A [`PromptTemplate`] is a prompt with placeholders, rendered with a [`PromptContext`]:
+//!
+//! - `{{name}}` is replaced with the value of the variable `name`,
+//! - `{{#if name}}...{{else}}...{{/if}}` keeps the first part if `name` is set and not empty,
+//!   and the `{{else}}` part otherwise,
+//! - `{{> name}}` includes the partial `name`: one of the [built-in prompts](super::partial),
+//!   or a partial given to [`PromptTemplate::parse_with_partials`].
+//!
+//! Partials are included when the template is parsed, so an unknown partial is an error right
+//! away. [`PromptTemplate::validate`] reports the variables a context would lack before anything
+//! is rendered. A variable used only under an `{{#if}}` on itself is optional.
+//!
+//! ```rust
+//! use swarms_rs::prompts::template::{PromptContext, PromptTemplate};
+//!
+//! let template = PromptTemplate::parse(
+//!     "You are {{agent_name}}.{{#if tools}} Your tools:\n{{tools}}{{/if}}",
+//! )
+//! .unwrap();
+//! assert!(template.validate(["tools"]).is_err());
+//!
+//! let context = PromptContext::new().with("agent_name", "Researcher");
+//! assert_eq!(template.render(&context).unwrap(), "You are Researcher.");
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum TemplateError {
+    #[error("Template syntax error at byte {position}: {message}")]
+    Syntax { position: usize, message: String },
+    #[error("Unknown partial: {0}")]
+    UnknownPartial(String),
+    #[error("Partial {0} includes itself")]
+    RecursivePartial(String),
+    #[error("Missing template variables: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+}
+
+/// Values of the variables of a template.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptContext {
+    variables: BTreeMap<String, String>,
+}
+
+impl PromptContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Sets a variable, returning its previous value.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.variables.insert(name.into(), value.into())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.variables.contains_key(name)
+    }
+
+    /// Names of the variables, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.variables.keys().map(String::as_str)
+    }
+
+    /// Sets the variables of `other`, replacing the ones with the same name.
+    pub fn extend(&mut self, other: &PromptContext) {
+        self.variables.extend(
+            other
+                .variables
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+    }
+
+    /// Whether `name` is set and not empty, as checked by `{{#if name}}`.
+    fn is_truthy(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|value| !value.trim().is_empty())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Variable(String),
+    If {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// A parsed prompt template.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PromptTemplate {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+impl PromptTemplate {
+    /// Parses a template that can include the built-in partials.
+    pub fn parse(source: impl Into<String>) -> Result<Self, TemplateError> {
+        Self::parse_with_partials(source, &HashMap::new())
+    }
+
+    /// Parses a template that can include `partials` as well as the built-in ones. Partials
+    /// are templates too, and take precedence over the built-in partials of the same name.
+    pub fn parse_with_partials(
+        source: impl Into<String>,
+        partials: &HashMap<String, String>,
+    ) -> Result<Self, TemplateError> {
+        let source = source.into();
+        let nodes = parse_nodes(&source, partials, &mut Vec::new())?;
+        Ok(Self { source, nodes })
+    }
+
+    /// A template rendering `text` as is, without placeholders.
+    pub fn literal(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Self {
+            nodes: vec![Node::Text(text.clone())],
+            source: text,
+        }
+    }
+
+    /// The template followed by `other`.
+    pub fn append(mut self, other: PromptTemplate) -> Self {
+        self.source.push_str(&other.source);
+        self.nodes.extend(other.nodes);
+        self
+    }
+
+    /// The text the template was made from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The variables that must be set to render the template, sorted.
+    pub fn variables(&self) -> BTreeSet<String> {
+        let mut variables = BTreeSet::new();
+        collect_variables(&self.nodes, &mut Vec::new(), &mut variables);
+        variables
+    }
+
+    /// Checks that `variables` are all the required variables of the template.
+    pub fn validate<'a>(
+        &self,
+        variables: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), TemplateError> {
+        let mut missing = self.variables();
+        for variable in variables {
+            missing.remove(variable);
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(TemplateError::MissingVariables(
+                missing.into_iter().collect(),
+            ))
+        }
+    }
+
+    pub fn render(&self, context: &PromptContext) -> Result<String, TemplateError> {
+        self.validate(context.names())?;
+        let mut output = String::with_capacity(self.source.len());
+        render_nodes(&self.nodes, context, &mut output);
+        Ok(output)
+    }
+}
+
+enum Tag<'a> {
+    Variable(&'a str),
+    If(&'a str),
+    Else,
+    EndIf,
+    Partial(&'a str),
+}
+
+fn parse_tag(tag: &str, position: usize) -> Result<Tag<'_>, TemplateError> {
+    let tag = tag.trim();
+    if let Some(rest) = tag.strip_prefix("#if ") {
+        Ok(Tag::If(parse_name(rest, position)?))
+    } else if tag == "else" {
+        Ok(Tag::Else)
+    } else if tag == "/if" {
+        Ok(Tag::EndIf)
+    } else if let Some(rest) = tag.strip_prefix('>') {
+        Ok(Tag::Partial(parse_name(rest, position)?))
+    } else {
+        Ok(Tag::Variable(parse_name(tag, position)?))
+    }
+}
+
+fn parse_name(name: &str, position: usize) -> Result<&str, TemplateError> {
+    let name = name.trim();
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(name)
+    } else {
+        Err(TemplateError::Syntax {
+            position,
+            message: format!("invalid name `{name}`"),
+        })
+    }
+}
+
+/// An `{{#if}}` block being parsed.
+struct Block {
+    name: String,
+    position: usize,
+    then: Vec<Node>,
+    otherwise: Option<Vec<Node>>,
+}
+
+fn parse_nodes(
+    source: &str,
+    partials: &HashMap<String, String>,
+    including: &mut Vec<String>,
+) -> Result<Vec<Node>, TemplateError> {
+    let mut root = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut rest = source;
+    let mut offset = 0;
+
+    loop {
+        let target = match blocks.last_mut() {
+            Some(Block {
+                otherwise: Some(otherwise),
+                ..
+            }) => otherwise,
+            Some(block) => &mut block.then,
+            None => &mut root,
+        };
+        let Some(start) = rest.find("{{") else {
+            if !rest.is_empty() {
+                target.push(Node::Text(rest.to_owned()));
+            }
+            break;
+        };
+        if start > 0 {
+            target.push(Node::Text(rest[..start].to_owned()));
+        }
+        let position = offset + start;
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| TemplateError::Syntax {
+                position,
+                message: "unclosed `{{`".to_owned(),
+            })?;
+        let tag = &rest[start + 2..start + end];
+        offset += start + end + 2;
+        rest = &rest[start + end + 2..];
+
+        match parse_tag(tag, position)? {
+            Tag::Variable(name) => target.push(Node::Variable(name.to_owned())),
+            Tag::Partial(name) => {
+                if including.iter().any(|included| included == name) {
+                    return Err(TemplateError::RecursivePartial(name.to_owned()));
+                }
+                let partial = partials
+                    .get(name)
+                    .map(String::as_str)
+                    .or_else(|| super::partial(name))
+                    .ok_or_else(|| TemplateError::UnknownPartial(name.to_owned()))?;
+                including.push(name.to_owned());
+                let nodes = parse_nodes(partial, partials, including)?;
+                including.pop();
+                target.extend(nodes);
+            },
+            Tag::If(name) => blocks.push(Block {
+                name: name.to_owned(),
+                position,
+                then: Vec::new(),
+                otherwise: None,
+            }),
+            Tag::Else => match blocks.last_mut() {
+                Some(block) if block.otherwise.is_none() => block.otherwise = Some(Vec::new()),
+                _ => {
+                    return Err(TemplateError::Syntax {
+                        position,
+                        message: "`{{else}}` outside of an `{{#if}}` block".to_owned(),
+                    });
+                },
+            },
+            Tag::EndIf => {
+                let block = blocks.pop().ok_or_else(|| TemplateError::Syntax {
+                    position,
+                    message: "`{{/if}}` without `{{#if}}`".to_owned(),
+                })?;
+                let node = Node::If {
+                    name: block.name,
+                    then: block.then,
+                    otherwise: block.otherwise.unwrap_or_default(),
+                };
+                match blocks.last_mut() {
+                    Some(Block {
+                        otherwise: Some(otherwise),
+                        ..
+                    }) => otherwise.push(node),
+                    Some(parent) => parent.then.push(node),
+                    None => root.push(node),
+                }
+            },
+        }
+    }
+
+    match blocks.pop() {
+        Some(block) => Err(TemplateError::Syntax {
+            position: block.position,
+            message: format!("unclosed `{{{{#if {}}}}}`", block.name),
+        }),
+        None => Ok(root),
+    }
+}
+
+/// Adds the variables of `nodes` that aren't guarded by an `{{#if}}` on themselves.
+fn collect_variables<'a>(
+    nodes: &'a [Node],
+    guards: &mut Vec<&'a str>,
+    variables: &mut BTreeSet<String>,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {},
+            Node::Variable(name) => {
+                if !guards.contains(&name.as_str()) {
+                    variables.insert(name.clone());
+                }
+            },
+            Node::If {
+                name,
+                then,
+                otherwise,
+            } => {
+                guards.push(name);
+                collect_variables(then, guards, variables);
+                guards.pop();
+                collect_variables(otherwise, guards, variables);
+            },
+        }
+    }
+}
+
+fn render_nodes(nodes: &[Node], context: &PromptContext, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Variable(name) => output.push_str(context.get(name).unwrap_or_default()),
+            Node::If {
+                name,
+                then,
+                otherwise,
+            } => {
+                if context.is_truthy(name) {
+                    render_nodes(then, context, output);
+                } else {
+                    render_nodes(otherwise, context, output);
+                }
+            },
+        }
+    }
+}
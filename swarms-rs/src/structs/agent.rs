@@ -1,3 +1,6 @@
+use crate::agent::run_result::{RunResult, TranscriptEntry};
+use crate::structs::budget::{ModelPricing, RunBudget};
+use crate::structs::memory::MetadataFilter;
 use crate::structs::persistence;
 use crate::structs::tool::ToolError;
 use colored::*;
@@ -11,6 +14,8 @@ use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::broadcast;
 
+pub use tokio_util::sync::CancellationToken;
+
 #[derive(Debug, Error)]
 pub enum AgentError {
     #[error("IO error: {0}")]
@@ -29,8 +34,30 @@ pub enum AgentError {
     NoChoiceFound,
     #[error("Tool {0} not found")]
     ToolNotFound(String),
+    #[error("Tool {0} was added more than once")]
+    DuplicateTool(String),
     #[error("Tool error: {0}")]
     ToolError(#[from] ToolError),
+    #[error("Capability error: {0}")]
+    CapabilityError(#[from] crate::llm::capabilities::CapabilityError),
+    #[error("Memory error: {0}")]
+    MemoryError(#[from] crate::structs::memory::MemoryError),
+    #[error("Vetoed by hook: {0}")]
+    Vetoed(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Run canceled")]
+    Canceled,
+    #[error("{0}")]
+    BudgetExceeded(#[from] crate::structs::budget::BudgetExceeded),
+    #[error("{stage} guardrail {guardrail} tripped: {reason}")]
+    GuardrailTripped {
+        guardrail: String,
+        stage: crate::agent::guardrail::GuardrailStage,
+        reason: String,
+    },
+    #[error("System prompt template error: {0}")]
+    TemplateError(#[from] crate::prompts::template::TemplateError),
 
     #[cfg(test)]
     #[error("Test error")]
@@ -94,11 +121,36 @@ impl AgentConfigBuilder {
         self
     }
 
+    pub fn max_replans(mut self, max_replans: u32) -> Self {
+        Arc::make_mut(&mut self.config).max_replans = max_replans;
+        self
+    }
+
     pub fn enable_rag_every_loop(mut self) -> Self {
         Arc::make_mut(&mut self.config).rag_every_loop = true;
         self
     }
 
+    pub fn long_term_memory_top_k(mut self, top_k: usize) -> Self {
+        Arc::make_mut(&mut self.config).long_term_memory_top_k = top_k;
+        self
+    }
+
+    pub fn long_term_memory_filter(mut self, filter: MetadataFilter) -> Self {
+        Arc::make_mut(&mut self.config).long_term_memory_filter = Some(filter);
+        self
+    }
+
+    pub fn budget(mut self, budget: RunBudget) -> Self {
+        Arc::make_mut(&mut self.config).budget = budget;
+        self
+    }
+
+    pub fn pricing(mut self, pricing: ModelPricing) -> Self {
+        Arc::make_mut(&mut self.config).pricing = Some(pricing);
+        self
+    }
+
     pub fn save_sate_path(mut self, path: impl Into<String>) -> Self {
         Arc::make_mut(&mut self.config).save_state_dir = Some(path.into());
         self
@@ -150,10 +202,25 @@ pub struct AgentConfig {
     pub max_tokens: u64,
     pub plan_enabled: bool,
     pub planning_prompt: Option<String>,
+    /// Times the remaining steps of a plan can be re-planned after a failure, per run
+    #[serde(default = "default_max_replans")]
+    pub max_replans: u32,
     pub autosave: bool,
     pub retry_attempts: u32,
     pub rag_every_loop: bool,
+    /// Number of documents retrieved from long-term memory per query
+    #[serde(default = "default_long_term_memory_top_k")]
+    pub long_term_memory_top_k: usize,
+    /// Restricts long-term memory queries to matching documents
+    #[serde(default)]
+    pub long_term_memory_filter: Option<MetadataFilter>,
     pub save_state_dir: Option<String>,
+    /// Limits of each run, checked in addition to `max_loops`
+    #[serde(default)]
+    pub budget: RunBudget,
+    /// Price of the model, needed for cost budgets
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
     #[serde(with = "hashset_serde")]
     pub stop_words: HashSet<String>,
     pub task_evaluator_tool_enabled: bool,
@@ -163,6 +230,14 @@ pub struct AgentConfig {
     pub response_cache: HashMap<String, String>,
 }
 
+fn default_long_term_memory_top_k() -> usize {
+    3
+}
+
+fn default_max_replans() -> u32 {
+    2
+}
+
 // Helper module for HashSet serialization
 mod hashset_serde {
     use super::*;
@@ -225,10 +300,15 @@ impl Default for AgentConfig {
             max_tokens: 8192,
             plan_enabled: false,
             planning_prompt: None,
+            max_replans: default_max_replans(),
             autosave: false,
             retry_attempts: 3,
             rag_every_loop: false,
+            long_term_memory_top_k: default_long_term_memory_top_k(),
+            long_term_memory_filter: None,
             save_state_dir: None,
+            budget: RunBudget::default(),
+            pricing: None,
             stop_words: HashSet::with_capacity(16), // Pre-allocate capacity
             task_evaluator_tool_enabled: true,
             concurrent_tool_call_enabled: true,
@@ -251,6 +331,88 @@ pub trait Agent: Send + Sync {
     /// Runs the autonomous agent loop to complete the given task.
     fn run(&self, task: String) -> BoxFuture<Result<String, AgentError>>;
 
+    /// Runs the task until it finishes or `cancel` is canceled, in which case
+    /// `AgentError::Canceled` is returned.
+    ///
+    /// The default implementation drops the [`run`](Agent::run) future on cancellation.
+    /// Agents that can stop cleanly (e.g. saving their state first) should override it.
+    fn run_with_cancellation(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move {
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => Err(AgentError::Canceled),
+                result = self.run(task) => result,
+            }
+        })
+    }
+
+    /// Runs the task like [`run_with_cancellation`](Agent::run_with_cancellation) and
+    /// returns a structured [`RunResult`].
+    ///
+    /// The default implementation uses the output of `run_with_cancellation` as the final
+    /// answer, without a transcript, tool calls, usage or termination reason.
+    fn run_with_result(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<RunResult, AgentError>> {
+        Box::pin(async move {
+            let started_at = chrono::Local::now();
+            let output = self.run_with_cancellation(task.clone(), cancel).await?;
+            Ok(RunResult::from_output(
+                self.name(),
+                task,
+                output,
+                started_at,
+            ))
+        })
+    }
+
+    /// Runs the task like [`run_with_result`](Agent::run_with_result), also returning the
+    /// output [`run_with_cancellation`](Agent::run_with_cancellation) would give.
+    ///
+    /// The default implementation uses that output as the final answer.
+    fn run_with_output(
+        &self,
+        task: String,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<(String, RunResult), AgentError>> {
+        Box::pin(async move {
+            let started_at = chrono::Local::now();
+            let output = self.run_with_cancellation(task.clone(), cancel).await?;
+            let result = RunResult::from_output(self.name(), task, output.clone(), started_at);
+            Ok((output, result))
+        })
+    }
+
+    /// Runs the task like [`run_with_result`](Agent::run_with_result), continuing the
+    /// conversation in `history`, e.g. after a [handoff](crate::structs::handoff).
+    ///
+    /// The default implementation renders the history into the task.
+    fn run_with_history(
+        &self,
+        task: String,
+        history: Vec<TranscriptEntry>,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'_, Result<RunResult, AgentError>> {
+        if history.is_empty() {
+            return self.run_with_result(task, cancel);
+        }
+        let conversation = history
+            .iter()
+            .map(|entry| format!("{}: {}", entry.role, entry.content))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        self.run_with_result(
+            format!("Continue this conversation:\n\n{conversation}\n\nTask: {task}"),
+            cancel,
+        )
+    }
+
     /// Run multiple tasks concurrently
     fn run_multiple_tasks(
         &mut self,
@@ -0,0 +1,221 @@
+//! Agents as tools.
+//!
+//! [`AgentTool`] exposes any [`Agent`] as a tool with a single `task` argument, so a manager
+//! agent can delegate to specialist agents through function calling. When a
+//! [`SwarmsAgent`](crate::agent::SwarmsAgent) calls an agent tool during a run:
+//!
+//! - the run result of the delegated agent is listed in the
+//!   [`sub_runs`](crate::agent::run_result::RunResult::sub_runs) of the manager's run result,
+//! - its token usage counts in the usage and budget of the manager's run,
+//! - canceling the manager's run cancels it,
+//! - its nesting depth is checked against [`AgentTool::max_depth`], so agents delegating to
+//!   each other can't recurse forever.
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//!
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//! use swarms_rs::structs::agent_tool::AgentTool;
+//!
+//! let model = OpenAI::from_env_with_model("gpt-4o-mini");
+//! let researcher = SwarmsAgentBuilder::new_with_model(model.clone())
+//!     .agent_name("Researcher")
+//!     .description("Finds and summarizes sources on a topic")
+//!     .build();
+//! let manager = SwarmsAgentBuilder::new_with_model(model)
+//!     .agent_name("Manager")
+//!     .add_shared_tool(Arc::new(AgentTool::new(Box::new(researcher)).max_depth(2)))
+//!     .build();
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    agent::run_result::RunResult,
+    llm::request::ToolDefinition,
+    structs::{
+        agent::{Agent, AgentError},
+        tool::{ToolDyn, ToolError},
+    },
+};
+
+/// Default maximum nesting depth of delegated runs.
+pub const DEFAULT_MAX_DEPTH: u32 = 3;
+
+#[derive(Debug, Error)]
+pub enum AgentToolError {
+    #[error("Delegation depth limit reached: depth {depth} exceeds {max_depth}")]
+    DepthLimit { depth: u32, max_depth: u32 },
+    #[error("Agent error: {0}")]
+    AgentError(#[from] AgentError),
+}
+
+tokio::task_local! {
+    static DELEGATION: Delegation;
+}
+
+/// The run on whose behalf a tool is called.
+#[derive(Clone)]
+pub(crate) struct Delegation {
+    /// Nesting depth of the run: 0 for a top-level run.
+    depth: u32,
+    cancel: CancellationToken,
+    /// Results of the agents delegated to by the tool call
+    sub_runs: Arc<Mutex<Vec<RunResult>>>,
+}
+
+impl Delegation {
+    pub(crate) fn new(depth: u32, cancel: CancellationToken) -> Self {
+        Self {
+            depth,
+            cancel,
+            sub_runs: Arc::default(),
+        }
+    }
+
+    pub(crate) fn sub_runs(&self) -> Arc<Mutex<Vec<RunResult>>> {
+        Arc::clone(&self.sub_runs)
+    }
+
+    /// Runs `future` on behalf of this run.
+    pub(crate) async fn scope<F: Future>(self, future: F) -> F::Output {
+        DELEGATION.scope(self, future).await
+    }
+}
+
+/// Nesting depth of the current run: 0 outside of delegated runs.
+pub(crate) fn current_depth() -> u32 {
+    DELEGATION
+        .try_with(|delegation| delegation.depth)
+        .unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+struct AgentToolArgs {
+    task: String,
+}
+
+/// A tool that runs an agent on a task and returns its final answer.
+pub struct AgentTool {
+    agent: Box<dyn Agent>,
+    name: String,
+    description: String,
+    max_depth: u32,
+}
+
+impl AgentTool {
+    /// Creates a tool named after the agent, with the agent's description.
+    pub fn new(agent: Box<dyn Agent>) -> Self {
+        let name = tool_name(&agent.name());
+        let description = match agent.description() {
+            description if description.trim().is_empty() => {
+                format!("Delegate a task to the {} agent.", agent.name())
+            },
+            description => description,
+        };
+        Self {
+            agent,
+            name,
+            description,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Maximum nesting depth at which the agent can run (default: 3). An agent called by a
+    /// top-level run is at depth 1.
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+impl ToolDyn for AgentTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "string",
+                        "description": "The task for the agent, with all the context it needs"
+                    }
+                },
+                "required": ["task"]
+            }),
+        }
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move {
+            let args = serde_json::from_str::<AgentToolArgs>(&args)?;
+            let parent = DELEGATION.try_with(Clone::clone).ok();
+            let depth = parent.as_ref().map_or(0, |parent| parent.depth) + 1;
+            if depth > self.max_depth {
+                return Err(ToolError::ToolCallError(Box::new(
+                    AgentToolError::DepthLimit {
+                        depth,
+                        max_depth: self.max_depth,
+                    },
+                )));
+            }
+
+            let cancel = parent
+                .as_ref()
+                .map(|parent| parent.cancel.child_token())
+                .unwrap_or_default();
+            let result = Delegation::new(depth, cancel.clone())
+                .scope(self.agent.run_with_result(args.task, cancel))
+                .await
+                .map_err(|e| ToolError::ToolCallError(Box::new(AgentToolError::from(e))))?;
+
+            let answer = result.final_answer.clone();
+            if let Some(parent) = parent {
+                parent.sub_runs.lock().unwrap().push(result);
+            }
+            Ok(answer)
+        })
+    }
+}
+
+/// A function name for an agent name, e.g. `research_agent` for "Research Agent".
+pub(crate) fn tool_name(agent_name: &str) -> String {
+    let name = agent_name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect::<String>();
+    if name.is_empty() {
+        "agent".to_owned()
+    } else {
+        name
+    }
+}
@@ -0,0 +1,321 @@
+//! Artifacts produced by agents.
+//!
+//! Agents with an [`ArtifactStore`] (see
+//! [`SwarmsAgentBuilder::artifact_store`](crate::agent::SwarmsAgentBuilder::artifact_store))
+//! get a built-in `save_artifact` tool to emit named files, such as reports, CSV data or
+//! code, or binary files as base64. Artifacts are stored under the ID of the run that created them and listed in its
+//! [`RunResult`](crate::agent::run_result::RunResult). Workflows record them in their
+//! metadata, and [`SequentialWorkflow`](crate::structs::sequential_workflow::SequentialWorkflow)
+//! tells each agent about the artifacts of the previous one.
+//!
+//! [`FsArtifactStore`] keeps artifacts in a directory per run.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use base64::Engine;
+use chrono::{DateTime, Local};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    llm::request::ToolDefinition,
+    structs::{
+        persistence::{self, PersistenceError},
+        tool::{ToolDyn, ToolError},
+    },
+};
+
+/// Name of the built-in tool that saves artifacts.
+pub const SAVE_ARTIFACT_TOOL_NAME: &str = "save_artifact";
+
+#[derive(Debug, Error)]
+pub enum ArtifactError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Persistence error: {0}")]
+    PersistenceError(#[from] PersistenceError),
+    #[error("Invalid artifact name: {0}")]
+    InvalidName(String),
+    #[error("Artifact not found: {0}")]
+    NotFound(String),
+    #[error("Invalid base64 content: {0}")]
+    InvalidBase64(#[from] base64::DecodeError),
+}
+
+/// Reference to a stored artifact.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// ID of the run that created the artifact.
+    pub run_id: String,
+    /// File name of the artifact, unique within its run.
+    pub name: String,
+    pub mime_type: String,
+    /// Size of the content in bytes.
+    pub size: u64,
+    /// Where the store keeps the artifact, e.g. a file path.
+    pub location: String,
+    pub created_at: DateTime<Local>,
+}
+
+/// Storage for artifacts, keyed by run ID and name.
+pub trait ArtifactStore: Send + Sync {
+    /// Stores an artifact, replacing an artifact of the same run with the same name.
+    fn save(
+        &self,
+        run_id: &str,
+        name: &str,
+        mime_type: &str,
+        content: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Artifact, ArtifactError>>;
+
+    /// Loads the content of an artifact.
+    fn load(&self, run_id: &str, name: &str) -> BoxFuture<'_, Result<Vec<u8>, ArtifactError>>;
+
+    /// Lists the artifacts of a run, sorted by name.
+    fn list(&self, run_id: &str) -> BoxFuture<'_, Result<Vec<Artifact>, ArtifactError>>;
+}
+
+/// Stores artifacts as files in `<root>/<run_id>/`, with their metadata in
+/// `<root>/<run_id>/.metadata/`.
+#[derive(Clone, Debug)]
+pub struct FsArtifactStore {
+    root: PathBuf,
+}
+
+impl FsArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn run_dir(&self, run_id: &str) -> Result<PathBuf, ArtifactError> {
+        validate_name(run_id)?;
+        Ok(self.root.join(run_id))
+    }
+
+    fn metadata_path(run_dir: &Path, name: &str) -> PathBuf {
+        run_dir.join(".metadata").join(format!("{name}.json"))
+    }
+}
+
+impl Default for FsArtifactStore {
+    fn default() -> Self {
+        Self::new("./temp/artifacts")
+    }
+}
+
+impl ArtifactStore for FsArtifactStore {
+    fn save(
+        &self,
+        run_id: &str,
+        name: &str,
+        mime_type: &str,
+        content: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Artifact, ArtifactError>> {
+        let (run_id, name, mime_type) = (run_id.to_owned(), name.to_owned(), mime_type.to_owned());
+        Box::pin(async move {
+            validate_name(&name)?;
+            let run_dir = self.run_dir(&run_id)?;
+            let path = run_dir.join(&name);
+            persistence::save_to_file(&content, &path).await?;
+
+            let artifact = Artifact {
+                run_id,
+                size: content.len() as u64,
+                location: path.to_string_lossy().into_owned(),
+                name,
+                mime_type,
+                created_at: Local::now(),
+            };
+            let metadata = serde_json::to_vec_pretty(&artifact)?;
+            persistence::save_to_file(metadata, Self::metadata_path(&run_dir, &artifact.name))
+                .await?;
+            Ok(artifact)
+        })
+    }
+
+    fn load(&self, run_id: &str, name: &str) -> BoxFuture<'_, Result<Vec<u8>, ArtifactError>> {
+        let (run_id, name) = (run_id.to_owned(), name.to_owned());
+        Box::pin(async move {
+            validate_name(&name)?;
+            let path = self.run_dir(&run_id)?.join(&name);
+            match tokio::fs::read(&path).await {
+                Ok(content) => Ok(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Err(ArtifactError::NotFound(format!("{run_id}/{name}")))
+                },
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn list(&self, run_id: &str) -> BoxFuture<'_, Result<Vec<Artifact>, ArtifactError>> {
+        let run_id = run_id.to_owned();
+        Box::pin(async move {
+            let metadata_dir = self.run_dir(&run_id)?.join(".metadata");
+            let mut entries = match tokio::fs::read_dir(&metadata_dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+                Err(e) => return Err(e.into()),
+            };
+
+            let mut artifacts = Vec::new();
+            while let Some(entry) = entries.next_entry().await? {
+                let data = persistence::load_from_file(entry.path()).await?;
+                artifacts.push(serde_json::from_slice::<Artifact>(&data)?);
+            }
+            artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(artifacts)
+        })
+    }
+}
+
+/// Rejects names that are empty, hidden or not a single path component.
+fn validate_name(name: &str) -> Result<(), ArtifactError> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control)
+    {
+        return Err(ArtifactError::InvalidName(name.to_owned()));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct SaveArtifactArgs {
+    name: String,
+    content: String,
+    #[serde(default = "default_mime_type")]
+    mime_type: String,
+    #[serde(default)]
+    encoding: ContentEncoding,
+}
+
+/// How the `content` argument of `save_artifact` is encoded.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ContentEncoding {
+    #[default]
+    Text,
+    Base64,
+}
+
+impl ContentEncoding {
+    fn decode(self, content: String) -> Result<Vec<u8>, ArtifactError> {
+        match self {
+            Self::Text => Ok(content.into_bytes()),
+            Self::Base64 => Ok(base64::engine::general_purpose::STANDARD.decode(content)?),
+        }
+    }
+}
+
+fn default_mime_type() -> String {
+    "text/plain".to_owned()
+}
+
+/// The `save_artifact` tool of a run. It saves artifacts under the ID of the run and
+/// records them in `artifacts`.
+pub(crate) struct SaveArtifactTool {
+    store: Arc<dyn ArtifactStore>,
+    run_id: String,
+    artifacts: Arc<Mutex<Vec<Artifact>>>,
+}
+
+impl SaveArtifactTool {
+    pub(crate) fn new(
+        store: Arc<dyn ArtifactStore>,
+        run_id: String,
+        artifacts: Arc<Mutex<Vec<Artifact>>>,
+    ) -> Self {
+        Self {
+            store,
+            run_id,
+            artifacts,
+        }
+    }
+
+    pub(crate) fn tool_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: SAVE_ARTIFACT_TOOL_NAME.to_owned(),
+            description: "Save a file produced for the task, such as a report, CSV data or \
+                code, so it is returned with the result. Saving a file with the same name again \
+                replaces it."
+                .to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "File name, e.g. report.md"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "Content of the file, as text or base64"
+                    },
+                    "encoding": {
+                        "type": "string",
+                        "enum": ["text", "base64"],
+                        "description": "Encoding of the content, base64 for binary files such as images (default: text)"
+                    },
+                    "mime_type": {
+                        "type": "string",
+                        "description": "MIME type of the content, e.g. text/csv (default: text/plain)"
+                    }
+                },
+                "required": ["name", "content"]
+            }),
+        }
+    }
+}
+
+impl ToolDyn for SaveArtifactTool {
+    fn name(&self) -> String {
+        SAVE_ARTIFACT_TOOL_NAME.to_owned()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        Self::tool_definition()
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move {
+            let args = serde_json::from_str::<SaveArtifactArgs>(&args)?;
+            let content = args
+                .encoding
+                .decode(args.content)
+                .map_err(|e| ToolError::ToolCallError(Box::new(e)))?;
+            let artifact = self
+                .store
+                .save(&self.run_id, &args.name, &args.mime_type, content)
+                .await
+                .map_err(|e| ToolError::ToolCallError(Box::new(e)))?;
+
+            let mut artifacts = self.artifacts.lock().unwrap();
+            artifacts.retain(|saved| saved.name != artifact.name);
+            artifacts.push(artifact.clone());
+            Ok(serde_json::to_string(&artifact)?)
+        })
+    }
+}
+
+/// Describes artifacts for the prompt of another agent.
+pub(crate) fn describe_artifacts(artifacts: &[Artifact]) -> String {
+    let mut description = String::from("[Artifacts]");
+    for artifact in artifacts {
+        description.push_str(&format!(
+            "\n- {} ({}, {} bytes): {}",
+            artifact.name, artifact.mime_type, artifact.size, artifact.location
+        ));
+    }
+    description
+}
@@ -0,0 +1,252 @@
+//! Run budgets.
+//!
+//! A [`RunBudget`] limits the tokens, tool calls, cost and wall-clock time of a single agent
+//! run, in addition to `max_loops`. Budgets are checked between loops and before every tool
+//! call. When one is exhausted the agent stops calling tools, asks the LLM for a final
+//! summary of its progress (unless [`RunBudget::final_summary`] is disabled) and reports
+//! which budget ran out as a [`BudgetExceeded`].
+//!
+//! Token, cost and tool call budgets are checked between steps, so a run can overshoot them
+//! by the cost of the step in progress and of the final summary. The time budget also
+//! interrupts the LLM and tool calls in progress; only the final summary can overshoot it.
+
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::llm::request::Usage;
+
+/// Limits of a single agent run. Every limit is unset by default.
+///
+/// Only the LLM and tool calls of the agent are counted: its completions, including planning
+/// and the default reflexion critic, and the usage of agents it delegates to. Components that
+/// call a model of their own aren't counted:
+/// [`LlmOutputCleaner`](crate::agent::output_processor::LlmOutputCleaner),
+/// [`ModelCritic`](crate::agent::reflexion::ModelCritic) and
+/// [`LlmClassifier`](crate::agent::guardrail::LlmClassifier).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunBudget {
+    /// Total input and output tokens.
+    pub max_total_tokens: Option<u64>,
+    /// Total tool calls, including `task_evaluator`.
+    pub max_tool_calls: Option<u32>,
+    /// Total cost, in the currency of the agent's [`ModelPricing`].
+    pub max_cost: Option<f64>,
+    /// Wall-clock time since the run started.
+    pub max_duration: Option<Duration>,
+    /// Whether to ask the LLM for a final answer when a budget is exhausted (default: true).
+    pub final_summary: bool,
+}
+
+impl Default for RunBudget {
+    fn default() -> Self {
+        Self {
+            max_total_tokens: None,
+            max_tool_calls: None,
+            max_cost: None,
+            max_duration: None,
+            final_summary: true,
+        }
+    }
+}
+
+impl RunBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_total_tokens(mut self, max_total_tokens: u64) -> Self {
+        self.max_total_tokens = Some(max_total_tokens);
+        self
+    }
+
+    pub fn max_tool_calls(mut self, max_tool_calls: u32) -> Self {
+        self.max_tool_calls = Some(max_tool_calls);
+        self
+    }
+
+    /// Requires the agent to have a [`ModelPricing`].
+    pub fn max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn final_summary(mut self, final_summary: bool) -> Self {
+        self.final_summary = final_summary;
+        self
+    }
+
+    /// Whether no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_total_tokens.is_none()
+            && self.max_tool_calls.is_none()
+            && self.max_cost.is_none()
+            && self.max_duration.is_none()
+    }
+}
+
+/// Price of a model, used to compute the cost of a run.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// Price of one million input tokens.
+    pub input_per_million_tokens: f64,
+    /// Price of one million output tokens.
+    pub output_per_million_tokens: f64,
+}
+
+impl ModelPricing {
+    pub fn new(input_per_million_tokens: f64, output_per_million_tokens: f64) -> Self {
+        Self {
+            input_per_million_tokens,
+            output_per_million_tokens,
+        }
+    }
+
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_million_tokens
+            + usage.output_tokens as f64 * self.output_per_million_tokens)
+            / 1_000_000.0
+    }
+}
+
+/// The budget that stopped a run.
+#[derive(Clone, Debug, Error, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "budget", rename_all = "snake_case")]
+pub enum BudgetExceeded {
+    #[error("Token budget exhausted: used {used} of {limit} tokens")]
+    Tokens { used: u64, limit: u64 },
+    #[error("Tool call budget exhausted: made {used} of {limit} tool calls")]
+    ToolCalls { used: u32, limit: u32 },
+    #[error("Cost budget exhausted: spent {spent:.4} of {limit:.4}")]
+    Cost { spent: f64, limit: f64 },
+    #[error("Time budget exhausted: ran for {elapsed:?} of {limit:?}")]
+    Duration { elapsed: Duration, limit: Duration },
+}
+
+/// Tracks the consumption of a [`RunBudget`] during a run.
+///
+/// The tracker can be shared between concurrent tool calls.
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: RunBudget,
+    pricing: Option<ModelPricing>,
+    started: Instant,
+    usage: Mutex<Usage>,
+    tool_calls: AtomicU32,
+}
+
+impl BudgetTracker {
+    /// Starts tracking a run now.
+    pub fn new(budget: RunBudget, pricing: Option<ModelPricing>) -> Self {
+        Self {
+            budget,
+            pricing,
+            started: Instant::now(),
+            usage: Mutex::new(Usage::default()),
+            tool_calls: AtomicU32::new(0),
+        }
+    }
+
+    pub fn budget(&self) -> &RunBudget {
+        &self.budget
+    }
+
+    pub fn record_usage(&self, usage: Usage) {
+        *self.usage.lock().unwrap() += usage;
+    }
+
+    pub fn usage(&self) -> Usage {
+        *self.usage.lock().unwrap()
+    }
+
+    pub fn tool_calls(&self) -> u32 {
+        self.tool_calls.load(Ordering::SeqCst)
+    }
+
+    /// Cost so far, or `None` without pricing.
+    pub fn cost(&self) -> Option<f64> {
+        self.pricing.map(|pricing| pricing.cost(&self.usage()))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Completes once the time budget is exhausted, never if there is none.
+    pub async fn time_exhausted(&self) -> BudgetExceeded {
+        let Some(limit) = self.budget.max_duration else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep_until((self.started + limit).into()).await;
+        BudgetExceeded::Duration {
+            elapsed: self.elapsed(),
+            limit,
+        }
+    }
+
+    /// Returns the first exhausted budget, if any.
+    pub fn check(&self) -> Option<BudgetExceeded> {
+        self.check_consumption().or_else(|| {
+            let limit = self.budget.max_tool_calls?;
+            let used = self.tool_calls();
+            (used >= limit).then_some(BudgetExceeded::ToolCalls { used, limit })
+        })
+    }
+
+    /// Counts a tool call, unless a budget is exhausted.
+    pub fn start_tool_call(&self) -> Result<(), BudgetExceeded> {
+        if let Some(exceeded) = self.check_consumption() {
+            return Err(exceeded);
+        }
+        match self.budget.max_tool_calls {
+            Some(limit) => self
+                .tool_calls
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    (used < limit).then_some(used + 1)
+                })
+                .map(|_| ())
+                .map_err(|used| BudgetExceeded::ToolCalls { used, limit }),
+            None => {
+                self.tool_calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            },
+        }
+    }
+
+    /// Checks the token, cost and time budgets.
+    fn check_consumption(&self) -> Option<BudgetExceeded> {
+        let usage = self.usage();
+        if let Some(limit) = self.budget.max_total_tokens {
+            let used = usage.total_tokens();
+            if used >= limit {
+                return Some(BudgetExceeded::Tokens { used, limit });
+            }
+        }
+        if let (Some(limit), Some(pricing)) = (self.budget.max_cost, self.pricing) {
+            let spent = pricing.cost(&usage);
+            if spent >= limit {
+                return Some(BudgetExceeded::Cost { spent, limit });
+            }
+        }
+        if let Some(limit) = self.budget.max_duration {
+            let elapsed = self.elapsed();
+            if elapsed >= limit {
+                return Some(BudgetExceeded::Duration { elapsed, limit });
+            }
+        }
+        None
+    }
+}
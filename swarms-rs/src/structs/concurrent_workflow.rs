@@ -12,7 +12,7 @@ use twox_hash::XxHash3_64;
 use uuid::Uuid;
 
 use crate::structs::{
-    agent::{Agent, AgentError},
+    agent::{Agent, AgentError, CancellationToken},
     conversation::{AgentConversation, AgentShortMemory, Role},
     persistence::{self, PersistenceError},
     swarm::{MetadataSchema, Swarm, SwarmError},
@@ -33,6 +33,8 @@ pub enum ConcurrentWorkflowError {
     TaskAlreadyExists,
     #[error("Json error: {0}")]
     JsonError(#[from] serde_json::Error),
+    #[error("Workflow execution canceled")]
+    Canceled,
 }
 
 #[derive(Default)]
@@ -100,6 +102,20 @@ impl ConcurrentWorkflow {
     pub async fn run(
         &self,
         task: impl Into<String>,
+    ) -> Result<AgentConversation, ConcurrentWorkflowError> {
+        self.run_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    /// Runs the workflow until all agents finish or `cancel` is canceled.
+    ///
+    /// On cancellation the agents are stopped with [`Agent::run_with_cancellation`], the
+    /// outputs of the agents that finished are still saved to the conversation and metadata,
+    /// and `ConcurrentWorkflowError::Canceled` is returned.
+    pub async fn run_with_cancellation(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
     ) -> Result<AgentConversation, ConcurrentWorkflowError> {
         let task = task.into();
 
@@ -119,9 +135,12 @@ impl ConcurrentWorkflow {
             .for_each_concurrent(None, |agent| {
                 let tx = tx.clone();
                 let task = task.clone();
+                let cancel = cancel.clone();
                 async move {
                     let output =
-                        match run_agent_with_output_schema(agent.as_ref(), task.clone()).await {
+                        match run_agent_with_output_schema(agent.as_ref(), task.clone(), cancel)
+                            .await
+                        {
                             Ok(output) => output,
                             Err(e) => {
                                 tracing::error!(
@@ -170,6 +189,10 @@ impl ConcurrentWorkflow {
         let metadata_data = serde_json::to_string_pretty(&metadata)?;
         persistence::save_to_file(metadata_data, &metadata_output_dir).await?;
 
+        if cancel.is_cancelled() {
+            return Err(ConcurrentWorkflowError::Canceled);
+        }
+
         // Safety: we know that the task exists
         Ok(self.conversation.0.get(&task).unwrap().clone())
     }
@@ -4,7 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -49,7 +49,7 @@ impl Default for AgentShortMemory {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AgentConversation {
     agent_name: String,
     save_filepath: Option<PathBuf>,
@@ -90,7 +90,7 @@ impl AgentConversation {
         let timestamp = Local::now().timestamp_millis();
         let message = Message {
             role,
-            content: Content::Text(format!("Timestamp(millis): {timestamp} \n{message}")),
+            content: Content::Text(format!("{TIMESTAMP_PREFIX}{timestamp} \n{message}")),
         };
         self.history.push(message);
 
@@ -207,6 +207,38 @@ pub struct Message {
     pub content: Content,
 }
 
+const TIMESTAMP_PREFIX: &str = "Timestamp(millis): ";
+
+impl Message {
+    /// The text of the message, without the timestamp prefix added by
+    /// [`AgentConversation::add`].
+    pub fn text(&self) -> &str {
+        let Content::Text(text) = &self.content;
+        text.strip_prefix(TIMESTAMP_PREFIX)
+            .and_then(|rest| rest.split_once(" \n"))
+            .filter(|(millis, _)| millis.parse::<i64>().is_ok())
+            .map_or(text, |(_, text)| text)
+    }
+
+    /// Replaces the text of the message, keeping its timestamp prefix.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        let prefix_len = {
+            let Content::Text(content) = &self.content;
+            content.len() - self.text().len()
+        };
+        let Content::Text(content) = &mut self.content;
+        content.replace_range(prefix_len.., &text.into());
+    }
+
+    /// When the message was added, parsed from its timestamp prefix.
+    pub fn timestamp(&self) -> Option<DateTime<Local>> {
+        let Content::Text(text) = &self.content;
+        let (millis, _) = text.strip_prefix(TIMESTAMP_PREFIX)?.split_once(" \n")?;
+        let utc = DateTime::from_timestamp_millis(millis.parse().ok()?)?;
+        Some(utc.with_timezone(&Local))
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Role {
     User(String),
@@ -14,7 +14,7 @@ use petgraph::{
 use thiserror::Error;
 use tokio::sync::Mutex;
 
-use crate::structs::agent::Agent;
+use crate::structs::agent::{Agent, AgentError, CancellationToken};
 
 /// The main graph-based workflow structure
 pub struct DAGWorkflow {
@@ -26,8 +26,15 @@ pub struct DAGWorkflow {
     workflow: StableGraph<AgentNode, Flow>,
     /// Map from agent name to node index for quick lookup
     name_to_node: HashMap<String, NodeIndex>,
+    /// Cancellation token of the current execution
+    cancel: CancellationToken,
+    /// Called as agents of the current execution finish
+    progress: Option<Arc<WorkflowProgress>>,
 }
 
+/// Called with the number of agents that finished and the number of agents.
+type WorkflowProgress = dyn Fn(usize, usize) + Send + Sync;
+
 impl DAGWorkflow {
     pub fn new<S: Into<String>>(name: S, description: S) -> Self {
         Self {
@@ -36,6 +43,8 @@ impl DAGWorkflow {
             agents: DashMap::new(),
             workflow: StableGraph::new(),
             name_to_node: HashMap::new(),
+            cancel: CancellationToken::new(),
+            progress: None,
         }
     }
 
@@ -210,9 +219,12 @@ impl DAGWorkflow {
     ) -> Result<String, GraphWorkflowError> {
         if let Some(agent) = self.agents.get(name) {
             agent
-                .run(input)
+                .run_with_cancellation(input, self.cancel.clone())
                 .await
-                .map_err(|e| GraphWorkflowError::AgentError(e.to_string()))
+                .map_err(|e| match e {
+                    AgentError::Canceled => GraphWorkflowError::Canceled,
+                    e => GraphWorkflowError::AgentError(e.to_string()),
+                })
         } else {
             Err(GraphWorkflowError::AgentNotFound(format!(
                 "Agent '{}' not found",
@@ -227,7 +239,50 @@ impl DAGWorkflow {
         start_agent: &str,
         input: impl Into<String>,
     ) -> Result<DashMap<String, Result<String, GraphWorkflowError>>, GraphWorkflowError> {
-        let input = input.into();
+        self.execute_workflow_with_cancellation(start_agent, input, CancellationToken::new())
+            .await
+    }
+
+    /// Execute the workflow until it finishes or `cancel` is canceled.
+    ///
+    /// Running agents are stopped with [`Agent::run_with_cancellation`] and agents that
+    /// haven't started are skipped. Their results are `GraphWorkflowError::Canceled`; the
+    /// results of the agents that finished are kept. Fails with
+    /// `GraphWorkflowError::Canceled` if the start agent didn't finish.
+    pub async fn execute_workflow_with_cancellation(
+        &mut self,
+        start_agent: &str,
+        input: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> Result<DashMap<String, Result<String, GraphWorkflowError>>, GraphWorkflowError> {
+        self.execute(start_agent, input.into(), cancel, None).await
+    }
+
+    /// Execute the workflow like
+    /// [`execute_workflow_with_cancellation`](Self::execute_workflow_with_cancellation),
+    /// calling `progress` with the number of agents that finished and the number of agents
+    /// each time an agent finishes. Agents skipped by edge conditions don't finish, so the
+    /// count doesn't always reach the number of agents.
+    pub async fn execute_workflow_with_progress(
+        &mut self,
+        start_agent: &str,
+        input: impl Into<String>,
+        cancel: CancellationToken,
+        progress: impl Fn(usize, usize) + Send + Sync + 'static,
+    ) -> Result<DashMap<String, Result<String, GraphWorkflowError>>, GraphWorkflowError> {
+        self.execute(start_agent, input.into(), cancel, Some(Arc::new(progress)))
+            .await
+    }
+
+    async fn execute(
+        &mut self,
+        start_agent: &str,
+        input: String,
+        cancel: CancellationToken,
+        progress: Option<Arc<WorkflowProgress>>,
+    ) -> Result<DashMap<String, Result<String, GraphWorkflowError>>, GraphWorkflowError> {
+        self.cancel = cancel;
+        self.progress = progress;
 
         let start_idx = self.name_to_node.get(start_agent).ok_or_else(|| {
             GraphWorkflowError::AgentNotFound(format!("Start agent '{}' not found", start_agent))
@@ -281,6 +336,12 @@ impl DAGWorkflow {
             return entry.value().clone();
         }
 
+        if self.cancel.is_cancelled() {
+            let result = Err(GraphWorkflowError::Canceled);
+            results.entry(agent_name.clone()).or_insert(result.clone());
+            return result;
+        }
+
         // Execute the agent with timeout protection
         let result = tokio::time::timeout(
             Duration::from_secs(300), // 5-minute timeout
@@ -291,6 +352,9 @@ impl DAGWorkflow {
 
         // Store the result
         results.entry(agent_name.clone()).or_insert(result.clone());
+        if let Some(progress) = &self.progress {
+            progress(results.len(), self.workflow.node_count());
+        }
 
         // Update the node's last result
         if let Some(node_weight) = self.workflow.node_weight(node_idx) {
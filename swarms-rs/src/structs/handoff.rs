@@ -0,0 +1,406 @@
+//! Agent handoffs.
+//!
+//! A [`SwarmsAgent`](crate::agent::SwarmsAgent) declared with
+//! [`add_handoff`](crate::agent::SwarmsAgentBuilder::add_handoff) gets a `transfer_to_<agent>`
+//! tool. When the model calls it, the agent ends its run with
+//! [`TerminationReason::Handoff`](crate::agent::run_result::TerminationReason::Handoff) and the
+//! [`HandoffRequest`] in [`RunResult::handoff`].
+//!
+//! A [`HandoffWorkflow`] follows the handoffs: it continues the conversation with the target
+//! agent, which answers with its own system prompt and tools, until an agent answers without
+//! handing off. The history passed to the next agent can be filtered with a [`HistoryFilter`].
+//!
+//! ```rust,no_run
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//! use swarms_rs::structs::handoff::{HandoffWorkflow, HistoryFilter};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let model = OpenAI::from_env_with_model("gpt-4o-mini");
+//! let triage = SwarmsAgentBuilder::new_with_model(model.clone())
+//!     .agent_name("Triage")
+//!     .system_prompt("Route the customer to the right team.")
+//!     .add_handoff("Billing", "Questions about invoices and payments")
+//!     .add_handoff("Support", "Technical problems")
+//!     .build();
+//! let billing = SwarmsAgentBuilder::new_with_model(model.clone())
+//!     .agent_name("Billing")
+//!     .build();
+//! let support = SwarmsAgentBuilder::new_with_model(model)
+//!     .agent_name("Support")
+//!     .build();
+//!
+//! let workflow = HandoffWorkflow::builder()
+//!     .add_agent(Box::new(triage))
+//!     .add_agent(Box::new(billing))
+//!     .add_agent(Box::new(support))
+//!     .history_filter(HistoryFilter::WithoutToolCalls)
+//!     .build();
+//! let outcome = workflow.run("I was charged twice this month").await?;
+//! println!("{}: {}", outcome.agent_name, outcome.final_answer);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    agent::run_result::{RunResult, TranscriptEntry},
+    llm::request::ToolDefinition,
+    structs::{
+        agent::{Agent, AgentError, CancellationToken},
+        agent_tool,
+        tool::{ToolDyn, ToolError},
+    },
+};
+
+/// Prefix of the names of the generated transfer tools.
+pub const TRANSFER_TOOL_PREFIX: &str = "transfer_to_";
+
+/// Default maximum number of handoffs in a [`HandoffWorkflow`] run.
+pub const DEFAULT_MAX_HANDOFFS: u32 = 10;
+
+#[derive(Debug, Error)]
+pub enum HandoffError {
+    #[error("Handoff workflow has no agents")]
+    NoAgents,
+    #[error("Unknown agent: {0}")]
+    UnknownAgent(String),
+    #[error("Too many handoffs: the limit is {0}")]
+    TooManyHandoffs(u32),
+    #[error("Agent error: {0}")]
+    AgentError(#[from] AgentError),
+}
+
+/// A request of an agent to continue the conversation with another agent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HandoffRequest {
+    /// Name of the target agent.
+    pub target: String,
+    /// Why the agent handed off, as given by the model.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Name of the tool transferring to `agent_name`, e.g. `transfer_to_billing_team` for
+/// "Billing Team".
+pub fn transfer_tool_name(agent_name: &str) -> String {
+    format!(
+        "{TRANSFER_TOOL_PREFIX}{}",
+        agent_tool::tool_name(agent_name)
+    )
+}
+
+#[derive(Deserialize)]
+struct TransferArgs {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// The `transfer_to_<agent>` tool. It only validates its arguments; the agent loop ends the
+/// run with the returned [`HandoffRequest`].
+pub(crate) struct TransferTool {
+    name: String,
+    target: String,
+    description: String,
+}
+
+impl TransferTool {
+    pub(crate) fn new(target: impl Into<String>, description: impl Into<String>) -> Self {
+        let target = target.into();
+        let description = match description.into() {
+            description if description.trim().is_empty() => {
+                format!("Transfer the conversation to the {target} agent.")
+            },
+            description => {
+                format!("Transfer the conversation to the {target} agent: {description}")
+            },
+        };
+        Self {
+            name: transfer_tool_name(&target),
+            target,
+            description,
+        }
+    }
+}
+
+impl ToolDyn for TransferTool {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "reason": {
+                        "type": "string",
+                        "description": "Why the conversation is transferred"
+                    }
+                }
+            }),
+        }
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move {
+            let args = if args.trim().is_empty() {
+                TransferArgs { reason: None }
+            } else {
+                serde_json::from_str::<TransferArgs>(&args)?
+            };
+            Ok(serde_json::to_string(&HandoffRequest {
+                target: self.target.clone(),
+                reason: args.reason,
+            })?)
+        })
+    }
+}
+
+/// Which part of the conversation is passed to the agent handed off to.
+#[derive(Clone, Default)]
+pub enum HistoryFilter {
+    /// The whole conversation.
+    #[default]
+    Full,
+    /// The conversation without tool call summaries.
+    WithoutToolCalls,
+    /// The first message (the task) and the last `n` messages.
+    LastMessages(usize),
+    /// A custom filter.
+    Custom(Arc<dyn Fn(Vec<TranscriptEntry>) -> Vec<TranscriptEntry> + Send + Sync>),
+}
+
+impl HistoryFilter {
+    pub fn custom(
+        filter: impl Fn(Vec<TranscriptEntry>) -> Vec<TranscriptEntry> + Send + Sync + 'static,
+    ) -> Self {
+        Self::Custom(Arc::new(filter))
+    }
+
+    pub fn apply(&self, history: Vec<TranscriptEntry>) -> Vec<TranscriptEntry> {
+        match self {
+            Self::Full => history,
+            Self::WithoutToolCalls => history
+                .into_iter()
+                .filter(|entry| !entry.content.starts_with("[Tool name]:"))
+                .collect(),
+            Self::LastMessages(n) => {
+                let skip = history.len().saturating_sub(*n).max(1);
+                let mut history = history.into_iter();
+                history
+                    .next()
+                    .into_iter()
+                    .chain(history.skip(skip - 1))
+                    .collect()
+            },
+            Self::Custom(filter) => filter(history),
+        }
+    }
+}
+
+/// The outcome of a [`HandoffWorkflow`] run.
+#[derive(Clone, Debug)]
+pub struct HandoffOutcome {
+    /// The answer of the last agent.
+    pub final_answer: String,
+    /// Name of the agent that answered.
+    pub agent_name: String,
+    /// The runs of the agents in order. Every run but the last ended with a handoff.
+    pub runs: Vec<RunResult>,
+}
+
+impl HandoffOutcome {
+    /// Names of the agents in the order they had the conversation.
+    pub fn path(&self) -> Vec<&str> {
+        self.runs
+            .iter()
+            .map(|run| run.agent_name.as_str())
+            .collect()
+    }
+}
+
+pub struct HandoffWorkflowBuilder {
+    name: String,
+    description: String,
+    agents: Vec<Box<dyn Agent>>,
+    entry_agent: Option<String>,
+    max_handoffs: u32,
+    history_filter: HistoryFilter,
+    target_filters: HashMap<String, HistoryFilter>,
+}
+
+impl HandoffWorkflowBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    pub fn add_agent(mut self, agent: Box<dyn Agent>) -> Self {
+        self.agents.push(agent);
+        self
+    }
+
+    pub fn agents(mut self, agents: Vec<Box<dyn Agent>>) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Name of the agent that receives the task (default: the first agent).
+    pub fn entry_agent(mut self, name: impl Into<String>) -> Self {
+        self.entry_agent = Some(name.into());
+        self
+    }
+
+    /// Maximum number of handoffs in a run (default: 10).
+    pub fn max_handoffs(mut self, max_handoffs: u32) -> Self {
+        self.max_handoffs = max_handoffs;
+        self
+    }
+
+    /// Filter of the history passed to the agents handed off to (default: the whole
+    /// conversation).
+    pub fn history_filter(mut self, filter: HistoryFilter) -> Self {
+        self.history_filter = filter;
+        self
+    }
+
+    /// Filter of the history passed to the agent named `target`, instead of the default one.
+    pub fn target_history_filter(
+        mut self,
+        target: impl Into<String>,
+        filter: HistoryFilter,
+    ) -> Self {
+        self.target_filters.insert(target.into(), filter);
+        self
+    }
+
+    pub fn build(self) -> HandoffWorkflow {
+        HandoffWorkflow {
+            name: self.name,
+            description: self.description,
+            agents: self.agents,
+            entry_agent: self.entry_agent,
+            max_handoffs: self.max_handoffs,
+            history_filter: self.history_filter,
+            target_filters: self.target_filters,
+        }
+    }
+}
+
+/// A conversation passed between agents by the agents themselves.
+pub struct HandoffWorkflow {
+    name: String,
+    description: String,
+    agents: Vec<Box<dyn Agent>>,
+    entry_agent: Option<String>,
+    max_handoffs: u32,
+    history_filter: HistoryFilter,
+    target_filters: HashMap<String, HistoryFilter>,
+}
+
+impl HandoffWorkflow {
+    pub fn builder() -> HandoffWorkflowBuilder {
+        HandoffWorkflowBuilder {
+            name: "HandoffWorkflow".to_owned(),
+            description: "A workflow in which agents transfer the conversation to each other."
+                .to_owned(),
+            agents: Vec::new(),
+            entry_agent: None,
+            max_handoffs: DEFAULT_MAX_HANDOFFS,
+            history_filter: HistoryFilter::default(),
+            target_filters: HashMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub async fn run(&self, task: impl Into<String>) -> Result<HandoffOutcome, HandoffError> {
+        self.run_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    /// Runs the task with the entry agent, then with each agent handed off to, until an agent
+    /// answers without handing off.
+    pub async fn run_with_cancellation(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> Result<HandoffOutcome, HandoffError> {
+        let task = task.into();
+        let mut current = match &self.entry_agent {
+            Some(name) => self.agent_index(name)?,
+            None if self.agents.is_empty() => return Err(HandoffError::NoAgents),
+            None => 0,
+        };
+
+        let mut history = Vec::new();
+        let mut runs = Vec::new();
+        loop {
+            let result = self.agents[current]
+                .run_with_history(task.clone(), history, cancel.clone())
+                .await?;
+            let handoff = result.handoff.clone();
+            let transcript = result.transcript.clone();
+            runs.push(result);
+
+            let Some(handoff) = handoff else {
+                break;
+            };
+            if runs.len() > self.max_handoffs as usize {
+                return Err(HandoffError::TooManyHandoffs(self.max_handoffs));
+            }
+            tracing::info!(
+                "HandoffWorkflow<{}>: {} handed off to {}",
+                self.name,
+                self.agents[current].name(),
+                handoff.target
+            );
+            current = self.agent_index(&handoff.target)?;
+            history = self
+                .target_filters
+                .get(&handoff.target)
+                .unwrap_or(&self.history_filter)
+                .apply(transcript);
+        }
+
+        let last = runs.last().expect("a run was made"); // Safety: the loop runs at least once
+        Ok(HandoffOutcome {
+            final_answer: last.final_answer.clone(),
+            agent_name: last.agent_name.clone(),
+            runs,
+        })
+    }
+
+    fn agent_index(&self, name: &str) -> Result<usize, HandoffError> {
+        self.agents
+            .iter()
+            .position(|agent| agent.name() == name)
+            .or_else(|| {
+                // The model may only know the tool name of the target
+                let tool_name = agent_tool::tool_name(name);
+                self.agents
+                    .iter()
+                    .position(|agent| agent_tool::tool_name(&agent.name()) == tool_name)
+            })
+            .ok_or_else(|| HandoffError::UnknownAgent(name.to_owned()))
+    }
+}
@@ -0,0 +1,1074 @@
+//! Managed connections to MCP (Model Context Protocol) servers.
+//!
+//! An [`MCPClient`] connects to a server through an [`MCPConnector`]: a [`StdioConnector`]
+//! for a server run as a child process, a [`StreamableHttpConnector`] or an [`SseConnector`]
+//! for a remote server, the HTTP connectors sending custom headers and bearer tokens if
+//! needed. The client reconnects when the connection is lost: a stdio server whose process
+//! died is restarted, an HTTP server is connected to again. Reconnection is tried with
+//! exponential backoff as configured by a [`ReconnectPolicy`], and the [`MCPHealth`] of each
+//! client is available from its [`status`](MCPClient::status).
+//!
+//! Connecting fails with an [`MCPError`] instead of panicking, after the connection
+//! timeout at most, so an agent can start without a server that is down:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//! use swarms_rs::structs::mcp::{MCPClient, StdioConnector};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut builder = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"));
+//!
+//! let client = MCPClient::builder("files", StdioConnector::new("mcp-files", ["--root", "."]))
+//!     .connect_timeout(Duration::from_secs(10))
+//!     .request_timeout(Duration::from_secs(30))
+//!     .connect()
+//!     .await;
+//! match client {
+//!     Ok(client) => builder = builder.add_mcp_client(&client).await?,
+//!     Err(e) => eprintln!("Starting without the files server: {e}"),
+//! }
+//! let agent = builder.build();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Besides tools, a client gives access to the resources and prompts of its server:
+//!
+//! - [`add_mcp_resource_tools`](crate::agent::SwarmsAgentBuilder::add_mcp_resource_tools)
+//!   lets an agent list and read the resources itself,
+//! - [`add_mcp_resource_context`](crate::agent::SwarmsAgentBuilder::add_mcp_resource_context)
+//!   adds a resource to the system prompt, kept up to date with the server's notifications
+//!   (see [`MCPClient::resource_context`]),
+//! - [`MCPClient::prompt_template`] imports a prompt as a system prompt
+//!   [template](crate::prompts::template), its arguments becoming template variables.
+//!
+//! The other way around, an [`MCPServer`] serves agents and workflows as tools to MCP
+//! clients.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::{OsStr, OsString},
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use reqwest::{
+    Url,
+    header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue},
+};
+use rmcp::{
+    ClientHandler, Peer, RoleClient, ServiceExt,
+    model::{
+        CallToolRequestParam, CallToolResult, ClientCapabilities, ClientInfo, ClientRequest,
+        GetPromptRequestParam, GetPromptResult, Implementation, PingRequest, Prompt,
+        PromptMessageContent, ReadResourceRequestParam, Resource, ResourceContents,
+        ResourceUpdatedNotificationParam, SubscribeRequestParam, Tool, UnsubscribeRequestParam,
+    },
+    service::{DynService, RunningService, ServiceError},
+    transport::{SseTransport, TokioChildProcess},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{process::Command, sync::broadcast};
+
+use crate::{
+    prompts::template::{PromptTemplate, TemplateError},
+    structs::tool::MCPTool,
+};
+
+mod server;
+mod streamable_http;
+
+pub use server::{MCPHttpServer, MCPServer};
+pub use streamable_http::StreamableHttpConnector;
+
+/// A running connection to an MCP server.
+pub type MCPService = RunningService<RoleClient, Box<dyn DynService<RoleClient>>>;
+
+/// Default timeout of connecting to a server, handshake included.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum MCPError {
+    #[error("MCP server {server} timed out after {timeout:?}")]
+    Timeout { server: String, timeout: Duration },
+    #[error("Failed to connect to MCP server {server}: {message}")]
+    Connection { server: String, message: String },
+    #[error(
+        "MCP server {server} is disconnected after {attempts} reconnection attempts: {message}"
+    )]
+    Disconnected {
+        server: String,
+        attempts: u32,
+        message: String,
+    },
+    #[error("MCP service error: {0}")]
+    ServiceError(#[from] ServiceError),
+    #[error("Invalid MCP server URL: {0}")]
+    InvalidUrl(String),
+    #[error("Invalid HTTP header {0}")]
+    InvalidHeader(String),
+    #[error("MCP server {server} has no prompt {name}")]
+    UnknownPrompt { server: String, name: String },
+    #[error("Invalid prompt template: {0}")]
+    Template(#[from] TemplateError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Opens connections to an MCP server.
+pub trait MCPConnector: Send + Sync {
+    /// Connects to the server and completes the handshake, with `handler` as the client
+    /// side of the connection.
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>>;
+}
+
+/// The client side of a connection: introduces the client to the server and receives the
+/// notifications of the server.
+#[derive(Clone)]
+pub struct MCPClientHandler {
+    info: ClientInfo,
+    updates: broadcast::Sender<String>,
+    peer: Option<Peer<RoleClient>>,
+}
+
+impl ClientHandler for MCPClientHandler {
+    async fn on_resource_updated(&self, params: ResourceUpdatedNotificationParam) {
+        // Nobody listening is fine
+        let _ = self.updates.send(params.uri);
+    }
+
+    fn get_peer(&self) -> Option<Peer<RoleClient>> {
+        self.peer.clone()
+    }
+
+    fn set_peer(&mut self, peer: Peer<RoleClient>) {
+        self.peer = Some(peer);
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        self.info.clone()
+    }
+}
+
+/// Starts an MCP server as a child process and talks to it over stdio. Each connection
+/// starts a new process, and the process is killed when its connection is dropped.
+#[derive(Clone, Debug)]
+pub struct StdioConnector {
+    command: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+}
+
+impl StdioConnector {
+    pub fn new<I, S>(command: S, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        Self {
+            command: command.as_ref().to_owned(),
+            args: args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_owned())
+                .collect(),
+            envs: Vec::new(),
+        }
+    }
+
+    /// Sets an environment variable of the server process.
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+}
+
+impl MCPConnector for StdioConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            let mut command = Command::new(&self.command);
+            command.args(&self.args).envs(self.envs.iter().cloned());
+            let process = TokioChildProcess::new(&mut command).map_err(|e| e.to_string())?;
+            handler
+                .into_dyn()
+                .serve(process)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Connects to an MCP server over HTTP with Server-Sent Events.
+///
+/// SSE is deprecated by newer versions of MCP in favor of
+/// [Streamable HTTP](StreamableHttpConnector).
+#[derive(Clone, Debug)]
+pub struct SseConnector {
+    url: Url,
+    headers: HeaderMap,
+}
+
+impl SseConnector {
+    pub fn new(url: impl reqwest::IntoUrl) -> Result<Self, MCPError> {
+        let url = url
+            .into_url()
+            .map_err(|e| MCPError::InvalidUrl(e.to_string()))?;
+        Ok(Self {
+            url,
+            headers: HeaderMap::new(),
+        })
+    }
+
+    /// Sends a header with each HTTP request to the server.
+    pub fn header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Self, MCPError> {
+        insert_header(&mut self.headers, name.as_ref(), value.as_ref(), false)?;
+        Ok(self)
+    }
+
+    /// Authenticates to the server with a bearer token.
+    pub fn bearer_auth(mut self, token: impl AsRef<str>) -> Result<Self, MCPError> {
+        let value = format!("Bearer {}", token.as_ref());
+        insert_header(&mut self.headers, AUTHORIZATION.as_str(), &value, true)?;
+        Ok(self)
+    }
+}
+
+impl MCPConnector for SseConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            let http = http_client(&self.headers)?;
+            let transport = SseTransport::start_with_client(self.url.clone(), http)
+                .await
+                .map_err(|e| e.to_string())?;
+            handler
+                .into_dyn()
+                .serve(transport)
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Sets header `name`, marking it as sensitive so it isn't logged if `sensitive` is set.
+fn insert_header(
+    headers: &mut HeaderMap,
+    name: &str,
+    value: &str,
+    sensitive: bool,
+) -> Result<(), MCPError> {
+    let name =
+        HeaderName::try_from(name).map_err(|e| MCPError::InvalidHeader(format!("{name}: {e}")))?;
+    let mut value = HeaderValue::try_from(value)
+        .map_err(|e| MCPError::InvalidHeader(format!("{name}: {e}")))?;
+    value.set_sensitive(sensitive);
+    headers.insert(name, value);
+    Ok(())
+}
+
+/// An HTTP client sending `headers` with each request.
+fn http_client(headers: &HeaderMap) -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .default_headers(headers.clone())
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// The information the client sends to servers in the handshake.
+fn client_info(name: &str) -> ClientInfo {
+    ClientInfo {
+        protocol_version: Default::default(),
+        capabilities: ClientCapabilities::default(),
+        client_info: Implementation {
+            name: name.to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+        },
+    }
+}
+
+/// How a lost connection is re-established.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Attempts before the server is considered disconnected, `None` for no limit.
+    pub max_attempts: Option<u32>,
+    /// Wait after the first failed attempt, doubled after each failed attempt.
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Never reconnect: a lost connection fails every request.
+    pub fn never() -> Self {
+        Self {
+            max_attempts: Some(0),
+            ..Self::default()
+        }
+    }
+
+    /// The wait after failed attempt `attempt`, starting at 1.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: Some(5),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Which tools of an MCP server an agent gets, and the names the model sees them by.
+///
+/// Filters apply to the names of the tools on the server. A tool with an alias is named
+/// after it; the other tools get the prefix, if any, e.g. `github_search` for the `search`
+/// tool with the prefix `github`.
+///
+/// ```rust
+/// use swarms_rs::structs::mcp::MCPToolOptions;
+///
+/// let options = MCPToolOptions::new()
+///     .prefix("github")
+///     .exclude(["delete_repository"])
+///     .alias("search_code", "find_code");
+/// assert_eq!(options.tool_name("github-server", "search").as_deref(), Some("github_search"));
+/// assert_eq!(options.tool_name("github-server", "search_code").as_deref(), Some("find_code"));
+/// assert_eq!(options.tool_name("github-server", "delete_repository"), None);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MCPToolOptions {
+    prefix: Option<ToolPrefix>,
+    aliases: BTreeMap<String, String>,
+    include: Option<BTreeSet<String>>,
+    exclude: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ToolPrefix {
+    ServerName,
+    Custom(String),
+}
+
+impl MCPToolOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the tools `{prefix}_{name}`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(ToolPrefix::Custom(prefix.into()));
+        self
+    }
+
+    /// Names the tools `{server}_{name}`, after the name of the server, with characters
+    /// other than ASCII letters, digits, `_` and `-` replaced by `_`.
+    pub fn server_prefix(mut self) -> Self {
+        self.prefix = Some(ToolPrefix::ServerName);
+        self
+    }
+
+    /// Names the tool `tool` of the server `alias`, without prefix.
+    pub fn alias(mut self, tool: impl Into<String>, alias: impl Into<String>) -> Self {
+        self.aliases.insert(tool.into(), alias.into());
+        self
+    }
+
+    /// Only adds these tools. Calling it again adds to the list.
+    pub fn include<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include
+            .get_or_insert_default()
+            .extend(tools.into_iter().map(Into::into));
+        self
+    }
+
+    /// Doesn't add these tools, even if included.
+    pub fn exclude<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude.extend(tools.into_iter().map(Into::into));
+        self
+    }
+
+    /// The name of the tool `tool` of the server `server` for the model, `None` if the tool
+    /// is filtered out.
+    pub fn tool_name(&self, server: &str, tool: &str) -> Option<String> {
+        if self.exclude.contains(tool)
+            || self
+                .include
+                .as_ref()
+                .is_some_and(|include| !include.contains(tool))
+        {
+            return None;
+        }
+        if let Some(alias) = self.aliases.get(tool) {
+            return Some(alias.clone());
+        }
+        Some(match &self.prefix {
+            None => tool.to_owned(),
+            Some(ToolPrefix::Custom(prefix)) => format!("{prefix}_{tool}"),
+            Some(ToolPrefix::ServerName) => {
+                let server = server
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect::<String>();
+                format!("{server}_{tool}")
+            },
+        })
+    }
+}
+
+/// Health of the connection to an MCP server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum MCPHealth {
+    Connected,
+    /// The connection was lost and is being re-established.
+    Reconnecting {
+        attempt: u32,
+    },
+    /// The connection was lost and could not be re-established. The next request tries
+    /// again.
+    Disconnected,
+}
+
+/// Status of an [`MCPClient`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MCPServerStatus {
+    /// Name of the server
+    pub name: String,
+    pub health: MCPHealth,
+    /// Number of times the connection was re-established
+    pub reconnects: u32,
+    /// The last connection error
+    pub last_error: Option<String>,
+}
+
+/// Builder of an [`MCPClient`].
+pub struct MCPClientBuilder {
+    name: String,
+    connector: Box<dyn MCPConnector>,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+    reconnect: ReconnectPolicy,
+}
+
+impl MCPClientBuilder {
+    /// Timeout of each connection attempt, [`DEFAULT_CONNECT_TIMEOUT`] by default.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Timeout of each request to the server. Requests have no timeout by default.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// Connects to the server. The first connection is attempted once.
+    pub async fn connect(self) -> Result<MCPClient, MCPError> {
+        let client = MCPClient {
+            inner: Arc::new(Inner {
+                name: self.name,
+                connector: self.connector,
+                connect_timeout: self.connect_timeout,
+                request_timeout: self.request_timeout,
+                reconnect: self.reconnect,
+                connection: Mutex::new(Connection {
+                    service: None,
+                    generation: 0,
+                }),
+                status: Mutex::new(Status {
+                    health: MCPHealth::Disconnected,
+                    reconnects: 0,
+                    last_error: None,
+                }),
+                reconnecting: tokio::sync::Mutex::new(()),
+                updates: broadcast::channel(RESOURCE_UPDATES_CAPACITY).0,
+                subscriptions: Mutex::new(BTreeSet::new()),
+            }),
+        };
+        let service = client.connect_once().await.inspect_err(|e| {
+            client.inner.status.lock().unwrap().last_error = Some(e.to_string())
+        })?;
+        client.replace_service(service, false);
+        Ok(client)
+    }
+}
+
+struct Connection {
+    service: Option<Arc<MCPService>>,
+    /// Incremented each time the connection is replaced
+    generation: u64,
+}
+
+struct Status {
+    health: MCPHealth,
+    reconnects: u32,
+    last_error: Option<String>,
+}
+
+struct Inner {
+    name: String,
+    connector: Box<dyn MCPConnector>,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+    reconnect: ReconnectPolicy,
+    connection: Mutex<Connection>,
+    status: Mutex<Status>,
+    /// Held while reconnecting, so that concurrent requests reconnect once
+    reconnecting: tokio::sync::Mutex<()>,
+    /// URIs of the updated resources
+    updates: broadcast::Sender<String>,
+    /// URIs of the resources subscribed to, subscribed to again after reconnecting
+    subscriptions: Mutex<BTreeSet<String>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // rmcp keeps serving a connection whose handle was dropped, so it's stopped here
+        let service = self.connection.get_mut().unwrap().service.take();
+        if let Some(service) = service.and_then(Arc::into_inner)
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            runtime.spawn(service.cancel());
+        }
+    }
+}
+
+/// Number of resource updates kept for receivers that are behind.
+const RESOURCE_UPDATES_CAPACITY: usize = 64;
+
+/// A connection to an MCP server that is re-established when it is lost.
+///
+/// Requests that fail because the connection was lost are retried once on the new
+/// connection, except tool calls, which may have side effects. Clones share the connection.
+#[derive(Clone)]
+pub struct MCPClient {
+    inner: Arc<Inner>,
+}
+
+impl MCPClient {
+    pub fn builder(
+        name: impl Into<String>,
+        connector: impl MCPConnector + 'static,
+    ) -> MCPClientBuilder {
+        MCPClientBuilder {
+            name: name.into(),
+            connector: Box::new(connector),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: None,
+            reconnect: ReconnectPolicy::default(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Whether both clients share the same connection.
+    pub(crate) fn ptr_eq(&self, other: &MCPClient) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub fn health(&self) -> MCPHealth {
+        self.inner.status.lock().unwrap().health.clone()
+    }
+
+    pub fn status(&self) -> MCPServerStatus {
+        let status = self.inner.status.lock().unwrap();
+        MCPServerStatus {
+            name: self.inner.name.clone(),
+            health: status.health.clone(),
+            reconnects: status.reconnects,
+            last_error: status.last_error.clone(),
+        }
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<Tool>, MCPError> {
+        self.request(|service| Box::pin(async move { service.list_all_tools().await }))
+            .await
+    }
+
+    /// The tools of the server as [`MCPTool`]s, filtered and named as set by `options`.
+    pub async fn tools(&self, options: &MCPToolOptions) -> Result<Vec<MCPTool>, MCPError> {
+        let tools = self.list_tools().await?;
+        for name in options
+            .include
+            .iter()
+            .flatten()
+            .chain(options.aliases.keys())
+        {
+            if !tools.iter().any(|tool| tool.name == name.as_str()) {
+                tracing::warn!("MCP server {} has no tool {}", self.name(), name);
+            }
+        }
+        Ok(tools
+            .into_iter()
+            .filter_map(|tool| {
+                let name = options.tool_name(self.name(), &tool.name)?;
+                Some(MCPTool::from_client(tool, self.clone()).with_name(name))
+            })
+            .collect())
+    }
+
+    /// Calls a tool of the server. Unlike the other requests, a tool call isn't sent again if
+    /// the connection was lost, since the server may have run it: the error is returned once
+    /// the connection is re-established.
+    pub async fn call_tool(&self, param: CallToolRequestParam) -> Result<CallToolResult, MCPError> {
+        self.request_once(move |service| {
+            let param = param.clone();
+            Box::pin(async move { service.call_tool(param).await })
+        })
+        .await
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<Resource>, MCPError> {
+        self.request(|service| Box::pin(async move { service.list_all_resources().await }))
+            .await
+    }
+
+    pub async fn read_resource(
+        &self,
+        uri: impl Into<String>,
+    ) -> Result<Vec<ResourceContents>, MCPError> {
+        let uri = uri.into();
+        self.request(move |service| {
+            let param = ReadResourceRequestParam { uri: uri.clone() };
+            Box::pin(async move {
+                let result = service.read_resource(param).await?;
+                Ok(result.contents)
+            })
+        })
+        .await
+    }
+
+    /// Reads resource `uri` as text, see [`resource_text`].
+    pub async fn read_resource_text(&self, uri: impl Into<String>) -> Result<String, MCPError> {
+        Ok(resource_text(&self.read_resource(uri).await?))
+    }
+
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>, MCPError> {
+        self.request(|service| Box::pin(async move { service.list_all_prompts().await }))
+            .await
+    }
+
+    /// Gets prompt `name` with its `arguments` filled in.
+    pub async fn get_prompt(
+        &self,
+        name: impl Into<String>,
+        arguments: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<GetPromptResult, MCPError> {
+        let name = name.into();
+        self.request(move |service| {
+            let param = GetPromptRequestParam {
+                name: name.clone(),
+                arguments: Some(arguments.clone()),
+            };
+            Box::pin(async move { service.get_prompt(param).await })
+        })
+        .await
+    }
+
+    /// Imports prompt `name` as a template, each argument of the prompt being a variable of
+    /// the same name. The text of the messages of the prompt is joined, whatever their role,
+    /// and images are left out.
+    ///
+    /// The prompt is got from the server with each argument set to its `{{name}}`
+    /// placeholder, so it must include its arguments as is.
+    pub async fn prompt_template(&self, name: &str) -> Result<PromptTemplate, MCPError> {
+        let prompt = self
+            .list_prompts()
+            .await?
+            .into_iter()
+            .find(|prompt| prompt.name == name)
+            .ok_or_else(|| MCPError::UnknownPrompt {
+                server: self.inner.name.clone(),
+                name: name.to_owned(),
+            })?;
+        let arguments = prompt
+            .arguments
+            .unwrap_or_default()
+            .into_iter()
+            .map(|argument| {
+                let placeholder = format!("{{{{{}}}}}", argument.name);
+                (argument.name, serde_json::Value::String(placeholder))
+            })
+            .collect();
+        let result = self.get_prompt(name, arguments).await?;
+        let text = result
+            .messages
+            .into_iter()
+            .filter_map(|message| match message.content {
+                PromptMessageContent::Text { text } => Some(text),
+                PromptMessageContent::Resource { resource } => {
+                    Some(resource_text(std::slice::from_ref(&resource.resource)))
+                },
+                PromptMessageContent::Image { .. } => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(PromptTemplate::parse(text)?)
+    }
+
+    /// Subscribes to the updates of resource `uri`, received from
+    /// [`resource_updates`](Self::resource_updates). The subscription is renewed when the
+    /// connection is re-established.
+    pub async fn subscribe(&self, uri: impl Into<String>) -> Result<(), MCPError> {
+        let uri = uri.into();
+        let param = SubscribeRequestParam { uri: uri.clone() };
+        self.request(move |service| {
+            let param = param.clone();
+            Box::pin(async move { service.subscribe(param).await })
+        })
+        .await?;
+        self.inner.subscriptions.lock().unwrap().insert(uri);
+        Ok(())
+    }
+
+    pub async fn unsubscribe(&self, uri: impl Into<String>) -> Result<(), MCPError> {
+        let uri = uri.into();
+        self.inner.subscriptions.lock().unwrap().remove(&uri);
+        self.request(move |service| {
+            let param = UnsubscribeRequestParam { uri: uri.clone() };
+            Box::pin(async move { service.unsubscribe(param).await })
+        })
+        .await
+    }
+
+    /// Receives the URI of each updated resource subscribed to. After the connection is
+    /// re-established, every resource subscribed to is reported as updated, since updates
+    /// may have been missed.
+    pub fn resource_updates(&self) -> broadcast::Receiver<String> {
+        self.inner.updates.subscribe()
+    }
+
+    /// Reads resource `uri` and keeps its text up to date, subscribing to its updates.
+    ///
+    /// If the server doesn't support subscriptions, the text is the one read now. Otherwise
+    /// it is read again on each update, until the context and its clones are dropped.
+    pub async fn resource_context(
+        &self,
+        uri: impl Into<String>,
+    ) -> Result<MCPResourceContext, MCPError> {
+        let uri = uri.into();
+        // Listen before reading, so that no update is missed
+        let mut updates = self.resource_updates();
+        let context = MCPResourceContext {
+            server: self.inner.name.clone(),
+            uri: uri.clone(),
+            text: Arc::new(RwLock::new(self.read_resource_text(&uri).await?)),
+        };
+        if let Err(e) = self.subscribe(&uri).await {
+            tracing::warn!(
+                "Resource {} of MCP server {} won't be kept up to date: {}",
+                uri,
+                self.inner.name,
+                e
+            );
+            return Ok(context);
+        }
+
+        let client = self.clone();
+        let text = Arc::downgrade(&context.text);
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(updated) if updated == uri => {},
+                    Ok(_) => continue,
+                    // The update may have been among the ones skipped
+                    Err(broadcast::error::RecvError::Lagged(_)) => {},
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+                if text.strong_count() == 0 {
+                    break;
+                }
+                match client.read_resource_text(&uri).await {
+                    Ok(updated) => match Weak::upgrade(&text) {
+                        Some(text) => *text.write().unwrap() = updated,
+                        None => break,
+                    },
+                    Err(e) => tracing::warn!(
+                        "Failed to read updated resource {} of MCP server {}: {}",
+                        uri,
+                        client.inner.name,
+                        e
+                    ),
+                }
+            }
+        });
+        Ok(context)
+    }
+
+    /// Pings the server, reconnecting if the connection was lost. Returns the health of the
+    /// client afterwards.
+    pub async fn check_health(&self) -> MCPHealth {
+        let ping = self
+            .request(|service| {
+                Box::pin(async move {
+                    service
+                        .send_request(ClientRequest::PingRequest(PingRequest {
+                            method: Default::default(),
+                        }))
+                        .await
+                        .map(|_| ())
+                })
+            })
+            .await;
+        if let Err(e) = ping {
+            tracing::warn!(
+                "MCP server {} failed the health check: {}",
+                self.inner.name,
+                e
+            );
+        }
+        self.health()
+    }
+
+    /// Sends a request that is safe to repeat (a list, read or ping), reconnecting and retrying
+    /// it once if the connection was lost.
+    async fn request<T, F>(&self, send: F) -> Result<T, MCPError>
+    where
+        F: Fn(Arc<MCPService>) -> BoxFuture<'static, Result<T, ServiceError>>,
+    {
+        self.send(send, true).await
+    }
+
+    /// Sends a request that may have side effects, such as a tool call. If the connection was
+    /// lost, it is re-established for the next requests but the request isn't sent again.
+    async fn request_once<T, F>(&self, send: F) -> Result<T, MCPError>
+    where
+        F: Fn(Arc<MCPService>) -> BoxFuture<'static, Result<T, ServiceError>>,
+    {
+        self.send(send, false).await
+    }
+
+    async fn send<T, F>(&self, send: F, retry: bool) -> Result<T, MCPError>
+    where
+        F: Fn(Arc<MCPService>) -> BoxFuture<'static, Result<T, ServiceError>>,
+    {
+        let (service, generation) = self.current();
+        let service = match service {
+            Some(service) => service,
+            None => self.reconnect(generation).await?,
+        };
+        match self.send_with_timeout(&send, service).await {
+            Err(MCPError::ServiceError(ServiceError::Transport(e))) => {
+                tracing::warn!("Lost connection to MCP server {}: {}", self.inner.name, e);
+                self.inner.status.lock().unwrap().last_error = Some(e.to_string());
+                let service = self.reconnect(generation).await?;
+                if !retry {
+                    return Err(MCPError::ServiceError(ServiceError::Transport(e)));
+                }
+                self.send_with_timeout(&send, service).await
+            },
+            result => result,
+        }
+    }
+
+    async fn send_with_timeout<T, F>(
+        &self,
+        send: &F,
+        service: Arc<MCPService>,
+    ) -> Result<T, MCPError>
+    where
+        F: Fn(Arc<MCPService>) -> BoxFuture<'static, Result<T, ServiceError>>,
+    {
+        let response = match self.inner.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, send(service))
+                .await
+                .map_err(|_| MCPError::Timeout {
+                    server: self.inner.name.clone(),
+                    timeout,
+                })?,
+            None => send(service).await,
+        };
+        Ok(response?)
+    }
+
+    fn current(&self) -> (Option<Arc<MCPService>>, u64) {
+        let connection = self.inner.connection.lock().unwrap();
+        (connection.service.clone(), connection.generation)
+    }
+
+    /// Re-establishes the connection of `generation`, unless another request already did.
+    async fn reconnect(&self, generation: u64) -> Result<Arc<MCPService>, MCPError> {
+        let _reconnecting = self.inner.reconnecting.lock().await;
+        if let (Some(service), current) = self.current()
+            && current != generation
+        {
+            return Ok(service);
+        }
+
+        let policy = self.inner.reconnect;
+        let mut attempt = 0;
+        loop {
+            if policy.max_attempts.is_some_and(|max| attempt >= max) {
+                let mut status = self.inner.status.lock().unwrap();
+                status.health = MCPHealth::Disconnected;
+                return Err(MCPError::Disconnected {
+                    server: self.inner.name.clone(),
+                    attempts: attempt,
+                    message: status.last_error.clone().unwrap_or_default(),
+                });
+            }
+            if attempt > 0 {
+                tokio::time::sleep(policy.backoff(attempt)).await;
+            }
+            attempt += 1;
+            self.inner.status.lock().unwrap().health = MCPHealth::Reconnecting { attempt };
+
+            match self.connect_once().await {
+                Ok(service) => {
+                    tracing::info!(
+                        "Reconnected to MCP server {} after {} attempts",
+                        self.inner.name,
+                        attempt
+                    );
+                    let service = self.replace_service(service, true);
+                    self.resubscribe(&service).await;
+                    return Ok(service);
+                },
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to reconnect to MCP server {} (attempt {}): {}",
+                        self.inner.name,
+                        attempt,
+                        e
+                    );
+                    self.inner.status.lock().unwrap().last_error = Some(e.to_string());
+                },
+            }
+        }
+    }
+
+    /// Renews the subscriptions on a new connection, and reports the resources as updated.
+    async fn resubscribe(&self, service: &MCPService) {
+        let subscriptions = self.inner.subscriptions.lock().unwrap().clone();
+        for uri in subscriptions {
+            let param = SubscribeRequestParam { uri: uri.clone() };
+            if let Err(e) = service.subscribe(param).await {
+                tracing::warn!(
+                    "Failed to subscribe again to resource {} of MCP server {}: {}",
+                    uri,
+                    self.inner.name,
+                    e
+                );
+            }
+            let _ = self.inner.updates.send(uri);
+        }
+    }
+
+    async fn connect_once(&self) -> Result<MCPService, MCPError> {
+        let timeout = self.inner.connect_timeout;
+        let handler = MCPClientHandler {
+            info: client_info(&self.inner.name),
+            updates: self.inner.updates.clone(),
+            peer: None,
+        };
+        match tokio::time::timeout(timeout, self.inner.connector.connect(handler)).await {
+            Ok(Ok(service)) => Ok(service),
+            Ok(Err(message)) => Err(MCPError::Connection {
+                server: self.inner.name.clone(),
+                message,
+            }),
+            Err(_) => Err(MCPError::Timeout {
+                server: self.inner.name.clone(),
+                timeout,
+            }),
+        }
+    }
+
+    fn replace_service(&self, service: MCPService, reconnected: bool) -> Arc<MCPService> {
+        let service = Arc::new(service);
+        let previous = {
+            let mut connection = self.inner.connection.lock().unwrap();
+            connection.generation += 1;
+            connection.service.replace(Arc::clone(&service))
+        };
+        // Stop the old connection, e.g. kill the process of a stdio server
+        if let Some(previous) = previous.and_then(Arc::into_inner) {
+            tokio::spawn(previous.cancel());
+        }
+
+        let mut status = self.inner.status.lock().unwrap();
+        status.health = MCPHealth::Connected;
+        if reconnected {
+            status.reconnects += 1;
+        }
+        service
+    }
+}
+
+/// The text of a resource, kept up to date by [`MCPClient::resource_context`].
+#[derive(Clone, Debug)]
+pub struct MCPResourceContext {
+    server: String,
+    uri: String,
+    text: Arc<RwLock<String>>,
+}
+
+impl MCPResourceContext {
+    /// Name of the server of the resource
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// The latest text of the resource.
+    pub fn text(&self) -> String {
+        self.text.read().unwrap().clone()
+    }
+}
+
+/// The text of resource contents: the text contents as is, and a line in place of each
+/// binary content.
+pub fn resource_text(contents: &[ResourceContents]) -> String {
+    contents
+        .iter()
+        .map(|content| match content {
+            ResourceContents::TextResourceContents { text, .. } => text.clone(),
+            ResourceContents::BlobResourceContents { uri, mime_type, .. } => format!(
+                "[Binary resource {uri}{}]",
+                mime_type
+                    .as_deref()
+                    .map(|mime| format!(" ({mime})"))
+                    .unwrap_or_default()
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
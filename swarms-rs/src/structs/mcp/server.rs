@@ -0,0 +1,1047 @@
+//! Serving agents and workflows to MCP clients.
+//!
+//! An [`MCPServer`] is the inverse of [`MCPTool`](crate::structs::tool::MCPTool): it
+//! exposes agents, [`SequentialWorkflow`]s and [`DAGWorkflow`]s as tools of an MCP server, so
+//! IDEs and other MCP clients can call them. Each tool takes a `task` argument and returns
+//! the final output of its agent or workflow, failures being tool errors.
+//!
+//! A client that sends a progress token with a call receives progress notifications: the
+//! number of agents of the workflow that finished out of the number of agents (0 then 1 of 1
+//! for an agent). A call canceled by the client cancels its run.
+//!
+//! The server is served over stdio with [`MCPServer::serve_stdio`], e.g. as the main
+//! function of a process started by an IDE, over any reader and writer with
+//! [`MCPServer::serve`], or over Streamable HTTP with [`MCPServer::serve_http`].
+//!
+//! Over HTTP, sessions idle for [`http_session_ttl`](MCPServer::http_session_ttl) are
+//! closed, and the session idle the longest is closed to start a new one once there are
+//! [`max_http_sessions`](MCPServer::max_http_sessions). Requests from a browser page are
+//! only accepted from local pages and [`allowed_origins`](MCPServer::allowed_origins), and
+//! with [`bearer_token`](MCPServer::bearer_token) every request must carry the token.
+//!
+//! ```rust,no_run
+//! use swarms_rs::agent::SwarmsAgentBuilder;
+//! use swarms_rs::llm::provider::openai::OpenAI;
+//! use swarms_rs::structs::mcp::MCPServer;
+//! use swarms_rs::structs::sequential_workflow::SequentialWorkflow;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let model = OpenAI::from_env_with_model("gpt-4o-mini");
+//! let researcher = SwarmsAgentBuilder::new_with_model(model.clone())
+//!     .agent_name("Researcher")
+//!     .description("Finds and summarizes sources on a topic")
+//!     .build();
+//! let writer = SwarmsAgentBuilder::new_with_model(model)
+//!     .agent_name("Writer")
+//!     .build();
+//! let pipeline = SequentialWorkflow::builder()
+//!     .name("Report")
+//!     .description("Researches a topic and writes a report on it")
+//!     .agents(vec![Box::new(researcher.clone()), Box::new(writer)])
+//!     .build();
+//!
+//! MCPServer::new("research")
+//!     .add_agent(Box::new(researcher))
+//!     .add_sequential_workflow(pipeline)
+//!     .serve_stdio()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    io,
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{
+    Sink, SinkExt, Stream, StreamExt,
+    channel::mpsc as channel,
+    future::{self, BoxFuture},
+};
+use http_body_util::{
+    BodyExt, Full, LengthLimitError, Limited, StreamBody, combinators::UnsyncBoxBody,
+};
+use hyper::{
+    Method, Request, Response, StatusCode,
+    body::{Frame, Incoming},
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, ORIGIN, WWW_AUTHENTICATE},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use rmcp::{
+    Error as McpError, RoleServer, ServerHandler, ServiceExt,
+    model::{
+        CallToolRequestParam, CallToolResult, Content, Implementation, ListToolsResult,
+        PaginatedRequestParam, ProgressNotificationParam, ProgressToken, RequestId,
+        ServerCapabilities, ServerInfo, Tool,
+    },
+    service::{RequestContext, RxJsonRpcMessage, TxJsonRpcMessage},
+};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, ToSocketAddrs},
+    sync::mpsc,
+};
+use tokio_util::{
+    codec::{FramedRead, FramedWrite, LinesCodec},
+    sync::CancellationToken,
+};
+
+use super::MCPError;
+use crate::structs::{
+    agent::Agent,
+    agent_tool::tool_name,
+    graph_workflow::{DAGWorkflow, GraphWorkflowError},
+    sequential_workflow::SequentialWorkflow,
+};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Messages of the server unrelated to a request, kept until an HTTP client listens for
+/// them.
+const NOTIFICATIONS_CAPACITY: usize = 64;
+
+/// Largest body of an HTTP request, larger requests being rejected with
+/// `413 Payload Too Large`.
+const MAX_REQUEST_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default [`MCPServer::http_session_ttl`].
+const DEFAULT_HTTP_SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Default [`MCPServer::max_http_sessions`].
+const DEFAULT_MAX_HTTP_SESSIONS: usize = 1024;
+
+/// Longest wait between two checks for idle HTTP sessions.
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An MCP server exposing agents and workflows as tools.
+///
+/// Adding a tool with the name of a tool already added replaces it.
+#[derive(Clone)]
+pub struct MCPServer {
+    name: String,
+    version: String,
+    instructions: Option<String>,
+    tools: BTreeMap<String, Arc<ServedTool>>,
+    http: HttpOptions,
+}
+
+/// Settings of [`MCPServer::serve_http`].
+#[derive(Clone)]
+struct HttpOptions {
+    session_ttl: Duration,
+    max_sessions: usize,
+    allowed_origins: Vec<String>,
+    bearer_token: Option<String>,
+}
+
+struct ServedTool {
+    description: String,
+    target: Box<dyn Target>,
+}
+
+impl MCPServer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            instructions: None,
+            tools: BTreeMap::new(),
+            http: HttpOptions {
+                session_ttl: DEFAULT_HTTP_SESSION_TTL,
+                max_sessions: DEFAULT_MAX_HTTP_SESSIONS,
+                allowed_origins: Vec::new(),
+                bearer_token: None,
+            },
+        }
+    }
+
+    /// Version of the server reported to clients (default: the version of swarms-rs).
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Instructions on using the server, sent to clients when they connect.
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Time after which an HTTP session without requests or open streams is closed
+    /// (default: 30 minutes). Its client gets `404 Not Found` and must start a new session.
+    pub fn http_session_ttl(mut self, ttl: Duration) -> Self {
+        self.http.session_ttl = ttl;
+        self
+    }
+
+    /// Most HTTP sessions open at once (default: 1024). Starting a session when there are as
+    /// many closes the session idle the longest.
+    pub fn max_http_sessions(mut self, max_sessions: usize) -> Self {
+        self.http.max_sessions = max_sessions.max(1);
+        self
+    }
+
+    /// Origins of the browser pages allowed to send HTTP requests, e.g.
+    /// `https://app.example.com`, besides pages on `localhost`. Requests with another
+    /// `Origin` header are rejected with `403 Forbidden`, to prevent DNS rebinding attacks.
+    /// Requests without `Origin` are accepted.
+    pub fn allowed_origins<I, S>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.http
+            .allowed_origins
+            .extend(origins.into_iter().map(Into::into));
+        self
+    }
+
+    /// Requires HTTP requests to carry `Authorization: Bearer <token>`, rejecting the others
+    /// with `401 Unauthorized`.
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.http.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Adds a tool running the agent, named after the agent, with the agent's description.
+    pub fn add_agent(self, agent: Box<dyn Agent>) -> Self {
+        let description = match agent.description() {
+            description if description.trim().is_empty() => {
+                format!("Run the {} agent on a task.", agent.name())
+            },
+            description => description,
+        };
+        self.add_tool(tool_name(&agent.name()), description, agent)
+    }
+
+    /// Adds a tool running the workflow, named after the workflow, with the workflow's
+    /// description. Its output is the output of the last agent.
+    pub fn add_sequential_workflow(self, workflow: SequentialWorkflow) -> Self {
+        self.add_tool(
+            tool_name(workflow.name()),
+            workflow.description().to_owned(),
+            workflow,
+        )
+    }
+
+    /// Adds a tool executing the workflow from `start_agent`, named after the workflow, with
+    /// the workflow's description. Its output is a JSON object of the output of each agent
+    /// that ran. Calls of the tool run one at a time.
+    pub fn add_dag_workflow(self, workflow: DAGWorkflow, start_agent: impl Into<String>) -> Self {
+        let name = tool_name(&workflow.name);
+        let description = workflow.description.clone();
+        let target = DagTarget {
+            workflow: tokio::sync::Mutex::new(workflow),
+            start_agent: start_agent.into(),
+        };
+        self.add_tool(name, description, target)
+    }
+
+    fn add_tool(
+        mut self,
+        name: String,
+        description: String,
+        target: impl Target + 'static,
+    ) -> Self {
+        let tool = ServedTool {
+            description,
+            target: Box::new(target),
+        };
+        if self.tools.insert(name.clone(), Arc::new(tool)).is_some() {
+            tracing::warn!("MCP server {}: replaced the tool {}", self.name, name);
+        }
+        self
+    }
+
+    /// Names of the tools of the server.
+    pub fn tool_names(&self) -> Vec<String> {
+        self.tools.keys().cloned().collect()
+    }
+
+    /// Serves a client over stdin and stdout until the client disconnects.
+    pub async fn serve_stdio(self) -> Result<(), MCPError> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serves a client sending newline-delimited JSON-RPC messages to `reader` and receiving
+    /// them from `writer`, until the client disconnects.
+    pub async fn serve<R, W>(self, reader: R, writer: W) -> Result<(), MCPError>
+    where
+        R: AsyncRead + Send + 'static,
+        W: AsyncWrite + Send + 'static,
+    {
+        let session = ServerSession::new(Arc::new(self));
+        let transport = io_transport(reader, writer, session.progress_tokens.clone());
+        let service = session.serve(transport).await?;
+        service.waiting().await.map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    /// Serves clients over Streamable HTTP on `addr`, until
+    /// [`shutdown`](MCPHttpServer::shutdown).
+    ///
+    /// Every path is served. The response to a request is an event stream with the
+    /// progress notifications of the request; the other messages of the server are sent on
+    /// the stream opened by `GET`. Request bodies larger than 4 MiB are rejected.
+    pub async fn serve_http(self, addr: impl ToSocketAddrs) -> Result<MCPHttpServer, MCPError> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let shutdown = CancellationToken::new();
+        let state = Arc::new(HttpState {
+            server: Arc::new(self),
+            sessions: Mutex::default(),
+            shutdown: shutdown.clone(),
+        });
+        tokio::spawn(sweep_sessions(Arc::clone(&state)));
+        tokio::spawn(accept(listener, state));
+        Ok(MCPHttpServer {
+            local_addr,
+            shutdown,
+        })
+    }
+}
+
+/// An [`MCPServer`] served over Streamable HTTP.
+pub struct MCPHttpServer {
+    local_addr: SocketAddr,
+    shutdown: CancellationToken,
+}
+
+impl MCPHttpServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// URL for clients, e.g. `http://127.0.0.1:8000/mcp`.
+    pub fn url(&self) -> String {
+        format!("http://{}/mcp", self.local_addr)
+    }
+
+    /// Stops accepting connections and closes all sessions.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// What a tool runs.
+trait Target: Send + Sync {
+    fn run<'a>(
+        &'a self,
+        task: String,
+        progress: &'a Progress,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<String, String>>;
+}
+
+impl Target for Box<dyn Agent> {
+    fn run<'a>(
+        &'a self,
+        task: String,
+        progress: &'a Progress,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            progress.report(0, 1);
+            let output = self
+                .run_with_cancellation(task, cancel)
+                .await
+                .map_err(|e| e.to_string())?;
+            progress.report(1, 1);
+            Ok(output)
+        })
+    }
+}
+
+impl Target for SequentialWorkflow {
+    fn run<'a>(
+        &'a self,
+        task: String,
+        progress: &'a Progress,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            progress.report(0, self.agents_len());
+            let conversation = self
+                .run_with_progress(task, cancel, |done, total| progress.report(done, total))
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(conversation
+                .history
+                .last()
+                .map(|message| message.text().to_owned())
+                .unwrap_or_default())
+        })
+    }
+}
+
+struct DagTarget {
+    workflow: tokio::sync::Mutex<DAGWorkflow>,
+    start_agent: String,
+}
+
+impl Target for DagTarget {
+    fn run<'a>(
+        &'a self,
+        task: String,
+        progress: &'a Progress,
+        cancel: CancellationToken,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let mut workflow = self.workflow.lock().await;
+            progress.report(0, workflow.node_count());
+            let results = workflow
+                .execute_workflow_with_progress(
+                    &self.start_agent,
+                    task,
+                    cancel,
+                    progress.detached(),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            let outputs = results
+                .into_iter()
+                .map(|(agent, result)| {
+                    let output =
+                        result.unwrap_or_else(|e: GraphWorkflowError| format!("Error: {e}"));
+                    (agent, Value::String(output))
+                })
+                .collect::<serde_json::Map<_, _>>();
+            serde_json::to_string_pretty(&outputs).map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Sends progress notifications for a call, if the client asked for them.
+#[derive(Default)]
+struct Progress {
+    notifications: Option<(
+        ProgressToken,
+        mpsc::UnboundedSender<ProgressNotificationParam>,
+    )>,
+}
+
+impl Progress {
+    fn report(&self, done: usize, total: usize) {
+        if let Some((token, notifications)) = &self.notifications {
+            let _ = notifications.send(notification(token, done, total));
+        }
+    }
+
+    /// Reports like [`report`](Self::report), for callbacks that can outlive the call: the
+    /// notifications of the call end with the call.
+    fn detached(&self) -> impl Fn(usize, usize) + Send + Sync + 'static {
+        let notifications = self
+            .notifications
+            .as_ref()
+            .map(|(token, notifications)| (token.clone(), notifications.downgrade()));
+        move |done, total| {
+            if let Some((token, notifications)) = &notifications
+                && let Some(notifications) = notifications.upgrade()
+            {
+                let _ = notifications.send(notification(token, done, total));
+            }
+        }
+    }
+}
+
+fn notification(token: &ProgressToken, done: usize, total: usize) -> ProgressNotificationParam {
+    ProgressNotificationParam {
+        progress_token: token.clone(),
+        progress: done as u32,
+        total: Some(total as u32),
+    }
+}
+
+/// Progress tokens of the tool calls of a client, by request id.
+type ProgressTokens = Arc<Mutex<HashMap<RequestId, ProgressToken>>>;
+
+/// The server side of a connection to a client.
+#[derive(Clone)]
+struct ServerSession {
+    server: Arc<MCPServer>,
+    // rmcp 0.1.5 drops the `_meta` of requests, so progress tokens are read by the transport
+    progress_tokens: ProgressTokens,
+}
+
+impl ServerSession {
+    fn new(server: Arc<MCPServer>) -> Self {
+        Self {
+            server,
+            progress_tokens: ProgressTokens::default(),
+        }
+    }
+}
+
+impl ServerHandler for ServerSession {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: self.server.name.clone(),
+                version: self.server.version.clone(),
+            },
+            instructions: self.server.instructions.clone(),
+            ..Default::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "task": {
+                    "type": "string",
+                    "description": "The task, with all the context it needs"
+                }
+            },
+            "required": ["task"]
+        });
+        let schema = Arc::new(schema.as_object().cloned().unwrap_or_default());
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: self
+                .server
+                .tools
+                .iter()
+                .map(|(name, tool)| {
+                    Tool::new(name.clone(), tool.description.clone(), Arc::clone(&schema))
+                })
+                .collect(),
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let token = self.progress_tokens.lock().unwrap().remove(&context.id);
+        let tool = self
+            .server
+            .tools
+            .get(request.name.as_ref())
+            .ok_or_else(|| {
+                McpError::invalid_params(format!("Unknown tool {}", request.name), None)
+            })?;
+        let task = request
+            .arguments
+            .as_ref()
+            .and_then(|args| args.get("task"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| McpError::invalid_params("Missing the task argument", None))?
+            .to_owned();
+
+        // Notifications are sent in order by a single task
+        let (progress, sending) = match token {
+            Some(token) => {
+                let (sender, mut notifications) = mpsc::unbounded_channel();
+                let peer = context.peer.clone();
+                let sending = tokio::spawn(async move {
+                    while let Some(notification) = notifications.recv().await {
+                        // rmcp 0.1.5 reports notifications that were sent as failed
+                        let _ = peer.notify_progress(notification).await;
+                    }
+                });
+                let progress = Progress {
+                    notifications: Some((token, sender)),
+                };
+                (progress, Some(sending))
+            },
+            None => (Progress::default(), None),
+        };
+
+        // Runs that stop on cancellation do so before being dropped
+        let result = tokio::select! {
+            biased;
+            result = tool.target.run(task, &progress, context.ct.clone()) => result,
+            _ = context.ct.cancelled() => Err("Canceled by the client".to_owned()),
+        };
+        // The notifications are sent before the result
+        drop(progress);
+        if let Some(sending) = sending {
+            let _ = sending.await;
+        }
+
+        Ok(match result {
+            Ok(output) => CallToolResult::success(vec![Content::text(output)]),
+            Err(e) => CallToolResult::error(vec![Content::text(e)]),
+        })
+    }
+}
+
+/// The progress token of a tool call, if the client asked for progress notifications.
+fn progress_token(message: &Value) -> Option<(RequestId, ProgressToken)> {
+    if message.get("method")?.as_str()? != "tools/call" {
+        return None;
+    }
+    let id = serde_json::from_value(message.get("id")?.clone()).ok()?;
+    let token =
+        serde_json::from_value(message.pointer("/params/_meta/progressToken")?.clone()).ok()?;
+    Some((id, token))
+}
+
+/// Reads a message of a client, remembering the progress token of a tool call.
+fn client_message(
+    message: Value,
+    progress_tokens: &ProgressTokens,
+) -> Option<RxJsonRpcMessage<RoleServer>> {
+    if let Some((id, token)) = progress_token(&message) {
+        progress_tokens.lock().unwrap().insert(id, token);
+    }
+    serde_json::from_value(message)
+        .inspect_err(|e| tracing::warn!("Invalid MCP message: {e}"))
+        .ok()
+}
+
+/// Newline-delimited JSON-RPC messages over a reader and a writer.
+fn io_transport<R, W>(
+    reader: R,
+    writer: W,
+    progress_tokens: ProgressTokens,
+) -> (
+    impl Sink<TxJsonRpcMessage<RoleServer>, Error = io::Error> + Send + 'static,
+    impl Stream<Item = RxJsonRpcMessage<RoleServer>> + Send + 'static,
+)
+where
+    R: AsyncRead + Send + 'static,
+    W: AsyncWrite + Send + 'static,
+{
+    let lines = FramedWrite::new(writer, LinesCodec::new());
+    let sink = SinkExt::<String>::sink_map_err(lines, io::Error::other).with(
+        |message: TxJsonRpcMessage<RoleServer>| {
+            future::ready(serde_json::to_string(&message).map_err(io::Error::from))
+        },
+    );
+    let stream = FramedRead::new(reader, LinesCodec::new())
+        .take_while(|line| future::ready(line.is_ok()))
+        .filter_map(move |line| {
+            let message = line
+                .ok()
+                .and_then(|line| serde_json::from_str(&line).ok())
+                .and_then(|message| client_message(message, &progress_tokens));
+            future::ready(message)
+        });
+    (sink, stream)
+}
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+struct HttpState {
+    server: Arc<MCPServer>,
+    sessions: Mutex<HashMap<String, Arc<HttpSession>>>,
+    shutdown: CancellationToken,
+}
+
+impl HttpState {
+    /// Closes the sessions idle for the session TTL.
+    fn close_idle_sessions(&self) {
+        let ttl = self.server.http.session_ttl;
+        self.sessions.lock().unwrap().retain(|_, session| {
+            let idle = session.is_idle_for(ttl);
+            if idle {
+                session.closed.cancel();
+            }
+            !idle
+        });
+    }
+
+    /// Adds a session, closing the session idle the longest if there are already as many
+    /// sessions as allowed.
+    fn add_session(&self, id: String, session: Arc<HttpSession>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        while sessions.len() >= self.server.http.max_sessions {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, session)| (session.open_streams(), session.last_active()))
+                .map(|(id, _)| id.clone());
+            match oldest.and_then(|id| sessions.remove(&id)) {
+                Some(session) => session.closed.cancel(),
+                None => break,
+            }
+        }
+        sessions.insert(id, session);
+    }
+
+    /// The response rejecting the request, unless it comes from an allowed origin and carries
+    /// the bearer token, if one is required.
+    fn rejection(&self, headers: &HeaderMap) -> Option<Response<Body>> {
+        if let Some(origin) = headers.get(ORIGIN)
+            && !origin
+                .to_str()
+                .is_ok_and(|origin| self.is_allowed_origin(origin))
+        {
+            return Some(status(StatusCode::FORBIDDEN));
+        }
+
+        if let Some(token) = &self.server.http.bearer_token {
+            let authorized = headers
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes()));
+            if !authorized {
+                let mut response = status(StatusCode::UNAUTHORIZED);
+                response
+                    .headers_mut()
+                    .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                return Some(response);
+            }
+        }
+        None
+    }
+
+    fn is_allowed_origin(&self, origin: &str) -> bool {
+        if self
+            .server
+            .http
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed == origin)
+        {
+            return true;
+        }
+        url::Url::parse(origin)
+            .is_ok_and(|url| matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")))
+    }
+}
+
+/// Compares the token sent with the expected token in a time independent of where they
+/// differ.
+fn constant_time_eq(sent: &[u8], expected: &[u8]) -> bool {
+    sent.len() == expected.len()
+        && sent
+            .iter()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Closes idle sessions until the server shuts down.
+async fn sweep_sessions(state: Arc<HttpState>) {
+    let mut interval = tokio::time::interval(
+        state
+            .server
+            .http
+            .session_ttl
+            .min(SESSION_SWEEP_INTERVAL)
+            .max(Duration::from_millis(10)),
+    );
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => state.close_idle_sessions(),
+        }
+    }
+}
+
+/// A client connected over HTTP, identified by the `Mcp-Session-Id` header.
+struct HttpSession {
+    to_server: channel::UnboundedSender<RxJsonRpcMessage<RoleServer>>,
+    progress_tokens: ProgressTokens,
+    routes: Arc<Mutex<Routes>>,
+    /// Messages unrelated to a request, until the client opens its `GET` stream
+    notifications: Mutex<Option<mpsc::Receiver<Value>>>,
+    closed: CancellationToken,
+    /// When the client last sent a request or a stream of the session ended
+    last_active: Mutex<Instant>,
+    /// Response and `GET` streams of the session still open
+    open_streams: AtomicUsize,
+}
+
+/// Keeps its session from being idle while a stream of the session is open.
+struct OpenStream(Arc<HttpSession>);
+
+impl OpenStream {
+    fn new(session: Arc<HttpSession>) -> Self {
+        session.open_streams.fetch_add(1, Ordering::SeqCst);
+        Self(session)
+    }
+}
+
+impl Drop for OpenStream {
+    fn drop(&mut self) {
+        self.0.open_streams.fetch_sub(1, Ordering::SeqCst);
+        self.0.touch();
+    }
+}
+
+/// Where messages of the server related to a request are sent.
+#[derive(Default)]
+struct Routes {
+    /// Streams of the responses to the requests, by request id
+    requests: HashMap<RequestId, mpsc::UnboundedSender<Value>>,
+    progress: HashMap<ProgressToken, RequestId>,
+}
+
+impl Routes {
+    fn route(&mut self, message: &Value) -> Option<mpsc::UnboundedSender<Value>> {
+        match message.get("method") {
+            // A response ends the stream of its request
+            None => {
+                let id = serde_json::from_value(message.get("id")?.clone()).ok()?;
+                self.progress.retain(|_, request| *request != id);
+                self.requests.remove(&id)
+            },
+            Some(method) if method == "notifications/progress" => {
+                let token = message.pointer("/params/progressToken")?.clone();
+                let id = self.progress.get(&serde_json::from_value(token).ok()?)?;
+                self.requests.get(id).cloned()
+            },
+            Some(_) => None,
+        }
+    }
+}
+
+impl HttpSession {
+    fn start(server: Arc<MCPServer>, closed: CancellationToken) -> Arc<Self> {
+        let handler = ServerSession::new(server);
+        let progress_tokens = handler.progress_tokens.clone();
+        let (to_server, from_client) = channel::unbounded();
+        let (to_client, mut from_server) = channel::unbounded::<TxJsonRpcMessage<RoleServer>>();
+        let (notify, notifications) = mpsc::channel(NOTIFICATIONS_CAPACITY);
+        let routes = Arc::new(Mutex::new(Routes::default()));
+
+        let from_client = from_client.take_until(closed.clone().cancelled_owned());
+        tokio::spawn(async move {
+            match handler
+                .serve((to_client.sink_map_err(io::Error::other), from_client))
+                .await
+            {
+                Ok(service) => {
+                    let _ = service.waiting().await;
+                },
+                Err(e) => tracing::warn!("Failed to start MCP session: {e}"),
+            }
+        });
+        let routing = Arc::clone(&routes);
+        tokio::spawn(async move {
+            while let Some(message) = from_server.next().await {
+                let Ok(message) = serde_json::to_value(&message) else {
+                    continue;
+                };
+                let route = routing.lock().unwrap().route(&message);
+                match route {
+                    Some(request) => {
+                        let _ = request.send(message);
+                    },
+                    // Dropped if the client isn't listening
+                    None => {
+                        let _ = notify.try_send(message);
+                    },
+                }
+            }
+        });
+
+        Arc::new(Self {
+            to_server,
+            progress_tokens,
+            routes,
+            notifications: Mutex::new(Some(notifications)),
+            closed,
+            last_active: Mutex::new(Instant::now()),
+            open_streams: AtomicUsize::new(0),
+        })
+    }
+
+    fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    fn last_active(&self) -> Instant {
+        *self.last_active.lock().unwrap()
+    }
+
+    fn open_streams(&self) -> usize {
+        self.open_streams.load(Ordering::SeqCst)
+    }
+
+    fn is_idle_for(&self, ttl: Duration) -> bool {
+        self.open_streams() == 0 && self.last_active().elapsed() >= ttl
+    }
+
+    fn send(&self, message: Value) {
+        if let Some(message) = client_message(message, &self.progress_tokens) {
+            let _ = self.to_server.unbounded_send(message);
+        }
+    }
+}
+
+async fn accept(listener: TcpListener, state: Arc<HttpState>) {
+    loop {
+        let stream = tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Failed to accept an MCP connection: {e}");
+                    continue;
+                },
+            },
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let shutdown = state.shutdown.clone();
+            let service = service_fn(move |request| handle(Arc::clone(&state), request));
+            let connection = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+            tokio::select! {
+                _ = shutdown.cancelled() => {},
+                _ = connection => {},
+            }
+        });
+    }
+    state.sessions.lock().unwrap().clear();
+}
+
+async fn handle(
+    state: Arc<HttpState>,
+    request: Request<Incoming>,
+) -> Result<Response<Body>, Infallible> {
+    if let Some(response) = state.rejection(request.headers()) {
+        return Ok(response);
+    }
+    let session_id = request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let session = session_id
+        .as_ref()
+        .and_then(|id| state.sessions.lock().unwrap().get(id).cloned());
+    if let Some(session) = &session {
+        session.touch();
+    }
+
+    let response = match *request.method() {
+        Method::POST => {
+            let body = match Limited::new(request.into_body(), MAX_REQUEST_BODY_SIZE)
+                .collect()
+                .await
+            {
+                Ok(body) => body.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    return Ok(status(StatusCode::PAYLOAD_TOO_LARGE));
+                },
+                Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+            };
+            post(&state, session_id, session, &body)
+        },
+        Method::GET => match session {
+            Some(session) => listen(session),
+            None => status(StatusCode::NOT_FOUND),
+        },
+        Method::DELETE => {
+            match session_id.and_then(|id| state.sessions.lock().unwrap().remove(&id)) {
+                Some(session) => {
+                    session.closed.cancel();
+                    status(StatusCode::OK)
+                },
+                None => status(StatusCode::NOT_FOUND),
+            }
+        },
+        _ => status(StatusCode::METHOD_NOT_ALLOWED),
+    };
+    Ok(response)
+}
+
+fn post(
+    state: &HttpState,
+    session_id: Option<String>,
+    session: Option<Arc<HttpSession>>,
+    body: &[u8],
+) -> Response<Body> {
+    let Ok(message) = serde_json::from_slice::<Value>(body) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let (session_id, session) = match (session_id, session) {
+        _ if message
+            .get("method")
+            .is_some_and(|method| method == "initialize") =>
+        {
+            let id = uuid::Uuid::new_v4().to_string();
+            let session =
+                HttpSession::start(Arc::clone(&state.server), state.shutdown.child_token());
+            state.add_session(id.clone(), Arc::clone(&session));
+            (id, session)
+        },
+        (Some(id), Some(session)) => (id, session),
+        (Some(_), None) => return status(StatusCode::NOT_FOUND),
+        (None, _) => return status(StatusCode::BAD_REQUEST),
+    };
+
+    // Notifications and client responses are forwarded to the session and acknowledged with 202
+    let request_id = message
+        .get("method")
+        .and(message.get("id"))
+        .and_then(|id| serde_json::from_value::<RequestId>(id.clone()).ok());
+    let Some(request_id) = request_id else {
+        session.send(message);
+        return status(StatusCode::ACCEPTED);
+    };
+
+    let (sender, mut messages) = mpsc::unbounded_channel::<Value>();
+    {
+        let mut routes = session.routes.lock().unwrap();
+        if let Some((_, token)) = progress_token(&message) {
+            routes.progress.insert(token, request_id.clone());
+        }
+        routes.requests.insert(request_id, sender);
+    }
+    session.send(message);
+
+    // The progress notifications of the request, then its response
+    let closed = session.closed.clone().cancelled_owned();
+    let open = OpenStream::new(session);
+    let events = futures::stream::poll_fn(move |cx| messages.poll_recv(cx))
+        .map(move |message| {
+            let _open = &open;
+            let event = format!("event: message\ndata: {message}\n\n");
+            Ok::<_, Infallible>(Frame::data(Bytes::from(event)))
+        })
+        .take_until(closed);
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(SESSION_HEADER, session_id)
+        .body(StreamBody::new(events).boxed_unsync())
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn listen(session: Arc<HttpSession>) -> Response<Body> {
+    let Some(mut notifications) = session.notifications.lock().unwrap().take() else {
+        // Only one stream per session
+        return status(StatusCode::CONFLICT);
+    };
+    let closed = session.closed.clone().cancelled_owned();
+    let open = OpenStream::new(session);
+    let events = futures::stream::poll_fn(move |cx| notifications.poll_recv(cx))
+        .map(move |message| {
+            let _open = &open;
+            Ok::<_, Infallible>(Frame::data(Bytes::from(format!("data: {message}\n\n"))))
+        })
+        .take_until(closed);
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .body(StreamBody::new(events).boxed_unsync())
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Full::new(Bytes::new()).boxed_unsync());
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,443 @@
+//! The Streamable HTTP transport of MCP.
+//!
+//! Each message to the server is POSTed to the endpoint of the server. The server answers a
+//! request with a JSON response, or with a stream of Server-Sent Events ending with the
+//! response. Once the session is initialized, a GET request opens a stream of the messages
+//! the server sends on its own, such as resource updates, if the server offers one. The
+//! session is identified by the `Mcp-Session-Id` header the server sets when initializing,
+//! and is terminated with a DELETE request when the connection is closed.
+
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+};
+
+use futures::{Sink, Stream, StreamExt, future::BoxFuture};
+use reqwest::{
+    Response, StatusCode, Url,
+    header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap},
+};
+use rmcp::{
+    RoleClient, ServiceExt,
+    model::JsonRpcMessage,
+    service::{RxJsonRpcMessage, TxJsonRpcMessage},
+};
+use serde_json::Value;
+use sse_stream::SseStream;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::{MCPClientHandler, MCPConnector, MCPError, MCPService, http_client, insert_header};
+
+/// Header of the session ID
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// JSON-RPC error code of errors of the transport
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Connects to an MCP server over Streamable HTTP.
+///
+/// ```rust,no_run
+/// use swarms_rs::agent::SwarmsAgentBuilder;
+/// use swarms_rs::llm::provider::openai::OpenAI;
+/// use swarms_rs::structs::mcp::StreamableHttpConnector;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let connector = StreamableHttpConnector::new("https://tools.example.com/mcp")?
+///     .bearer_auth(std::env::var("TOOLS_TOKEN")?)?
+///     .header("X-Team", "research")?;
+/// let agent = SwarmsAgentBuilder::new_with_model(OpenAI::from_env_with_model("gpt-4o-mini"))
+///     .try_add_streamable_http_mcp_server("tools", connector)
+///     .await?
+///     .build();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StreamableHttpConnector {
+    url: Url,
+    headers: HeaderMap,
+}
+
+impl StreamableHttpConnector {
+    pub fn new(url: impl reqwest::IntoUrl) -> Result<Self, MCPError> {
+        let url = url
+            .into_url()
+            .map_err(|e| MCPError::InvalidUrl(e.to_string()))?;
+        Ok(Self {
+            url,
+            headers: HeaderMap::new(),
+        })
+    }
+
+    /// Sends a header with each HTTP request to the server.
+    pub fn header(
+        mut self,
+        name: impl AsRef<str>,
+        value: impl AsRef<str>,
+    ) -> Result<Self, MCPError> {
+        insert_header(&mut self.headers, name.as_ref(), value.as_ref(), false)?;
+        Ok(self)
+    }
+
+    /// Authenticates to the server with a bearer token.
+    pub fn bearer_auth(mut self, token: impl AsRef<str>) -> Result<Self, MCPError> {
+        let value = format!("Bearer {}", token.as_ref());
+        insert_header(&mut self.headers, AUTHORIZATION.as_str(), &value, true)?;
+        Ok(self)
+    }
+}
+
+impl MCPConnector for StreamableHttpConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            let http = http_client(&self.headers)?;
+            handler
+                .into_dyn()
+                .serve(transport(http, self.url.clone()))
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// The sink of the messages to the server and the stream of the messages from the server of a
+/// connection.
+fn transport(
+    http: reqwest::Client,
+    url: Url,
+) -> (
+    HttpSink,
+    impl Stream<Item = RxJsonRpcMessage<RoleClient>> + Send + 'static,
+) {
+    let (incoming, received) = mpsc::unbounded_channel();
+    let session = Arc::new(Session {
+        http,
+        url,
+        id: Mutex::new(None),
+        incoming,
+        closed: CancellationToken::new(),
+    });
+    let received = futures::stream::unfold(received, |mut received| async move {
+        received.recv().await.map(|message| (message, received))
+    })
+    .take_until(session.closed.clone().cancelled_owned());
+    let sink = HttpSink {
+        session,
+        in_flight: None,
+    };
+    (sink, received)
+}
+
+struct Session {
+    http: reqwest::Client,
+    url: Url,
+    /// ID the server gave to the session
+    id: Mutex<Option<String>>,
+    incoming: mpsc::UnboundedSender<RxJsonRpcMessage<RoleClient>>,
+    /// Canceled when the connection is closed, which ends the stream of incoming messages
+    closed: CancellationToken,
+}
+
+/// What went wrong sending a message.
+enum SendError {
+    /// The connection is lost
+    Disconnected(String),
+    /// The message failed
+    Failed(String),
+}
+
+impl Session {
+    fn id(&self) -> Option<String> {
+        self.id.lock().unwrap().clone()
+    }
+
+    fn request(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.id() {
+            Some(id) => request.header(SESSION_ID_HEADER, id),
+            None => request,
+        }
+    }
+
+    /// POSTs `message`, then receives the response to it, if it is a request.
+    async fn post(self: Arc<Self>, message: TxJsonRpcMessage<RoleClient>) {
+        let message = match serde_json::to_value(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!("Failed to serialize MCP message: {}", e);
+                return;
+            },
+        };
+        let method = message.get("method").and_then(Value::as_str);
+        let request_id = method.and(message.get("id")).cloned();
+        let initialized = method == Some("notifications/initialized");
+
+        let result = match self.send(&message).await {
+            Ok(response) => self.receive(response, request_id.as_ref()).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) if initialized => {
+                tokio::spawn(Arc::clone(&self).listen());
+            },
+            Ok(()) => {},
+            Err(SendError::Disconnected(e)) => {
+                tracing::warn!("Lost connection to MCP server {}: {}", self.url, e);
+                self.closed.cancel();
+            },
+            Err(SendError::Failed(e)) => {
+                tracing::warn!("MCP request to {} failed: {}", self.url, e);
+                if let Some(id) = request_id {
+                    self.deliver_error(id, e);
+                }
+            },
+        }
+    }
+
+    async fn send(&self, message: &Value) -> Result<Response, SendError> {
+        let request = self
+            .http
+            .post(self.url.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        let response = self
+            .request(request)
+            .send()
+            .await
+            .map_err(|e| SendError::Disconnected(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            if let Some(id) = response.headers().get(SESSION_ID_HEADER)
+                && let Ok(id) = id.to_str()
+            {
+                *self.id.lock().unwrap() = Some(id.to_owned());
+            }
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let error = format!("HTTP {status}: {body}");
+        // The server ended the session
+        if status == StatusCode::NOT_FOUND && self.id().is_some() {
+            Err(SendError::Disconnected(error))
+        } else {
+            Err(SendError::Failed(error))
+        }
+    }
+
+    /// Delivers the messages of `response`, which must include the response to request
+    /// `request_id`, if any.
+    async fn receive(
+        &self,
+        response: Response,
+        request_id: Option<&Value>,
+    ) -> Result<(), SendError> {
+        if response.status() == StatusCode::ACCEPTED {
+            return Ok(());
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        let mut responded = false;
+        if content_type.starts_with("text/event-stream") {
+            let mut events = SseStream::from_byte_stream(response.bytes_stream());
+            while let Some(event) = events.next().await {
+                let event = event.map_err(|e| SendError::Disconnected(e.to_string()))?;
+                if let Some(data) = event.data {
+                    let message = serde_json::from_str(&data)
+                        .map_err(|e| SendError::Failed(format!("Invalid message: {e}")))?;
+                    responded |= self.deliver(message, request_id);
+                }
+                if responded {
+                    break;
+                }
+            }
+        } else if content_type.starts_with("application/json") {
+            let message = response
+                .json()
+                .await
+                .map_err(|e| SendError::Failed(format!("Invalid message: {e}")))?;
+            responded = self.deliver(message, request_id);
+        }
+
+        match request_id {
+            Some(_) if !responded => Err(SendError::Failed(
+                "the server didn't send a response".to_owned(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Receives the messages the server sends on its own, if it offers a stream of them.
+    async fn listen(self: Arc<Self>) {
+        let request = self
+            .http
+            .get(self.url.clone())
+            .header(ACCEPT, "text/event-stream");
+        let response = match self.request(request).send().await {
+            Ok(response) if response.status().is_success() => response,
+            // The server doesn't offer a stream
+            Ok(response) if response.status() == StatusCode::METHOD_NOT_ALLOWED => return,
+            Ok(response) => {
+                tracing::warn!(
+                    "MCP server {} refused the message stream: HTTP {}",
+                    self.url,
+                    response.status()
+                );
+                return;
+            },
+            Err(e) => {
+                tracing::warn!("Failed to open the message stream of {}: {}", self.url, e);
+                return;
+            },
+        };
+
+        let mut events = SseStream::from_byte_stream(response.bytes_stream());
+        loop {
+            let event = tokio::select! {
+                _ = self.closed.cancelled() => break,
+                event = events.next() => event,
+            };
+            match event {
+                Some(Ok(event)) => {
+                    if let Some(data) = event.data {
+                        match serde_json::from_str(&data) {
+                            Ok(message) => {
+                                self.deliver(message, None);
+                            },
+                            Err(e) => tracing::warn!("Invalid MCP message: {}", e),
+                        }
+                    }
+                },
+                Some(Err(e)) => {
+                    tracing::warn!("Message stream of {} failed: {}", self.url, e);
+                    break;
+                },
+                None => break,
+            }
+        }
+    }
+
+    /// Delivers `message`, a message or a batch of messages. Returns whether it includes the
+    /// response to request `request_id`.
+    fn deliver(&self, message: Value, request_id: Option<&Value>) -> bool {
+        let messages = match message {
+            Value::Array(messages) => messages,
+            message => vec![message],
+        };
+        let mut responded = false;
+        for message in messages {
+            let is_response = message.get("method").is_none();
+            responded |= is_response && request_id.is_some() && message.get("id") == request_id;
+            match serde_json::from_value(message) {
+                Ok(message) => {
+                    let _ = self.incoming.send(message);
+                },
+                Err(e) => tracing::warn!("Invalid MCP message: {}", e),
+            }
+        }
+        responded
+    }
+
+    /// Delivers an error response to request `id`, so that the request fails.
+    fn deliver_error(&self, id: Value, message: String) {
+        let error = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": INTERNAL_ERROR, "message": message },
+        });
+        self.deliver(error, None);
+    }
+
+    /// Closes the connection, terminating the session.
+    fn close(self: &Arc<Self>) {
+        if self.closed.is_cancelled() {
+            return;
+        }
+        self.closed.cancel();
+        if let Some(id) = self.id()
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            let request = self
+                .http
+                .delete(self.url.clone())
+                .header(SESSION_ID_HEADER, id);
+            runtime.spawn(async move {
+                // The server may not allow terminating sessions
+                let _ = request.send().await;
+            });
+        }
+    }
+}
+
+/// Sends the messages to the server, each with its own HTTP request.
+///
+/// Requests are sent concurrently. Notifications and responses are sent in order: the next
+/// message waits until the server received them, as the server may rely on their order, e.g.
+/// expect the `initialized` notification before any other request.
+struct HttpSink {
+    session: Arc<Session>,
+    in_flight: Option<BoxFuture<'static, ()>>,
+}
+
+impl HttpSink {
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(in_flight) = &mut self.in_flight {
+            ready!(in_flight.as_mut().poll(cx));
+            self.in_flight = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Sink<TxJsonRpcMessage<RoleClient>> for HttpSink {
+    type Error = std::io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_in_flight(cx));
+        if this.session.closed.is_cancelled() {
+            Poll::Ready(Err(std::io::Error::other("disconnected")))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        message: TxJsonRpcMessage<RoleClient>,
+    ) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let is_request = matches!(message, JsonRpcMessage::Request(_));
+        let post = Arc::clone(&this.session).post(message);
+        if is_request {
+            tokio::spawn(post);
+        } else {
+            this.in_flight = Some(Box::pin(post));
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.get_mut().poll_in_flight(cx));
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_in_flight(cx));
+        this.session.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for HttpSink {
+    fn drop(&mut self) {
+        self.session.close();
+    }
+}
@@ -0,0 +1,283 @@
+//! Long-term memory for agents.
+//!
+//! A [`Memory`] stores documents with metadata and retrieves the ones most relevant to a
+//! query. Agents built with [`SwarmsAgentBuilder::long_term_memory`](crate::agent::SwarmsAgentBuilder::long_term_memory)
+//! query it before the first loop (and, with `rag_every_loop`, before every loop) and add
+//! the results to the conversation.
+//!
+//! [`InMemoryVectorStore`] is a simple implementation that keeps embeddings in memory and
+//! ranks documents by cosine similarity. The embedding function is pluggable through the
+//! [`Embedder`] trait; [`HashingEmbedder`] is a dependency free default.
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use twox_hash::XxHash3_64;
+
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("Embedding error: {0}")]
+    EmbeddingError(String),
+    #[error("Embedding dimension mismatch: expected {expected}, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+    #[error("Storage error: {0}")]
+    StorageError(String),
+}
+
+/// Metadata attached to a memory document.
+pub type Metadata = HashMap<String, serde_json::Value>;
+
+/// A document to store in long-term memory.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MemoryDocument {
+    pub content: String,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl MemoryDocument {
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn with_metadata(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// A document returned by [`Memory::query`], with its relevance score (higher is better).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    pub id: String,
+    pub content: String,
+    pub metadata: Metadata,
+    pub score: f32,
+}
+
+/// Restricts a query to documents whose metadata matches all given key/value pairs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MetadataFilter {
+    equals: Metadata,
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match documents whose metadata `key` equals `value`.
+    pub fn eq(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.equals.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.equals
+            .iter()
+            .all(|(key, value)| metadata.get(key) == Some(value))
+    }
+}
+
+/// Pluggable long-term memory.
+pub trait Memory: Send + Sync {
+    /// Store a document and return its id.
+    fn add(&self, document: MemoryDocument) -> BoxFuture<'_, Result<String, MemoryError>>;
+
+    /// Return at most `top_k` documents relevant to `query`, best match first.
+    fn query(
+        &self,
+        query: String,
+        top_k: usize,
+        filter: Option<MetadataFilter>,
+    ) -> BoxFuture<'_, Result<Vec<MemoryRecord>, MemoryError>>;
+
+    /// Delete a document by id. Returns `false` if no such document exists.
+    fn delete(&self, id: String) -> BoxFuture<'_, Result<bool, MemoryError>>;
+}
+
+/// Turns text into an embedding vector.
+pub trait Embedder: Send + Sync {
+    fn embed(&self, text: String) -> BoxFuture<'_, Result<Vec<f32>, MemoryError>>;
+}
+
+/// An [`Embedder`] based on feature hashing of lowercase words.
+///
+/// It only captures lexical overlap, which is enough for small knowledge bases and
+/// tests. Use a model based [`Embedder`] for semantic retrieval.
+#[derive(Clone, Debug)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn embed_sync(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        for word in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            let mut hasher = XxHash3_64::default();
+            word.to_lowercase().hash(&mut hasher);
+            let hash = hasher.finish();
+            let index = (hash % self.dimensions as u64) as usize;
+            // use an independent bit as the sign to reduce collision bias
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(512)
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn embed(&self, text: String) -> BoxFuture<'_, Result<Vec<f32>, MemoryError>> {
+        Box::pin(async move { Ok(self.embed_sync(&text)) })
+    }
+}
+
+struct StoredDocument {
+    content: String,
+    metadata: Metadata,
+    embedding: Vec<f32>,
+}
+
+/// A [`Memory`] that keeps documents and their embeddings in memory and ranks them by
+/// cosine similarity.
+#[derive(Clone)]
+pub struct InMemoryVectorStore {
+    embedder: Arc<dyn Embedder>,
+    documents: Arc<DashMap<String, StoredDocument>>,
+}
+
+impl InMemoryVectorStore {
+    /// Create a store using the [`HashingEmbedder`].
+    pub fn new() -> Self {
+        Self::with_embedder(HashingEmbedder::default())
+    }
+
+    pub fn with_embedder(embedder: impl Embedder + 'static) -> Self {
+        Self {
+            embedder: Arc::new(embedder),
+            documents: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
+impl Default for InMemoryVectorStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Memory for InMemoryVectorStore {
+    fn add(&self, document: MemoryDocument) -> BoxFuture<'_, Result<String, MemoryError>> {
+        Box::pin(async move {
+            let embedding = self.embedder.embed(document.content.clone()).await?;
+            let id = uuid::Uuid::new_v4().to_string();
+            self.documents.insert(
+                id.clone(),
+                StoredDocument {
+                    content: document.content,
+                    metadata: document.metadata,
+                    embedding,
+                },
+            );
+            Ok(id)
+        })
+    }
+
+    fn query(
+        &self,
+        query: String,
+        top_k: usize,
+        filter: Option<MetadataFilter>,
+    ) -> BoxFuture<'_, Result<Vec<MemoryRecord>, MemoryError>> {
+        Box::pin(async move {
+            let query_embedding = self.embedder.embed(query).await?;
+
+            let mut records = Vec::new();
+            for entry in self.documents.iter() {
+                let document = entry.value();
+                if filter
+                    .as_ref()
+                    .is_some_and(|filter| !filter.matches(&document.metadata))
+                {
+                    continue;
+                }
+                if document.embedding.len() != query_embedding.len() {
+                    return Err(MemoryError::DimensionMismatch {
+                        expected: document.embedding.len(),
+                        actual: query_embedding.len(),
+                    });
+                }
+                records.push(MemoryRecord {
+                    id: entry.key().clone(),
+                    content: document.content.clone(),
+                    metadata: document.metadata.clone(),
+                    score: cosine_similarity(&query_embedding, &document.embedding),
+                });
+            }
+
+            records.sort_by(|a, b| b.score.total_cmp(&a.score));
+            records.truncate(top_k);
+            Ok(records)
+        })
+    }
+
+    fn delete(&self, id: String) -> BoxFuture<'_, Result<bool, MemoryError>> {
+        Box::pin(async move { Ok(self.documents.remove(&id).is_some()) })
+    }
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
@@ -1,12 +1,19 @@
 pub mod agent;
+pub mod agent_tool;
+pub mod artifact;
+pub mod budget;
 pub mod concurrent_workflow;
 pub mod conversation;
 pub mod execute_agent_batch;
 pub mod graph_workflow;
+pub mod handoff;
+pub mod mcp;
+pub mod memory;
 pub mod persistence;
 pub mod rearrange;
 pub mod sequential_workflow;
 pub mod swarm;
 pub mod swarms_router;
 pub mod tool;
+pub mod tool_registry;
 mod utils;
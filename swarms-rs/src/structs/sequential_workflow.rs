@@ -10,7 +10,8 @@ use twox_hash::XxHash3_64;
 use uuid::Uuid;
 
 use crate::structs::{
-    agent::{Agent, AgentError},
+    agent::{Agent, AgentError, CancellationToken},
+    artifact::describe_artifacts,
     conversation::{AgentConversation, Role},
     persistence,
     swarm::MetadataSchema,
@@ -77,9 +78,45 @@ impl SequentialWorkflow {
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn agents_len(&self) -> usize {
+        self.agents.len()
+    }
+
     pub async fn run(
         &self,
         task: impl Into<String>,
+    ) -> Result<AgentConversation, SequentialWorkflowError> {
+        self.run_with_cancellation(task, CancellationToken::new())
+            .await
+    }
+
+    /// Runs the workflow until it finishes or `cancel` is canceled. The running agent is
+    /// stopped with [`Agent::run_with_cancellation`] and the next agents don't run; the
+    /// workflow fails with `AgentError::Canceled`.
+    pub async fn run_with_cancellation(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
+    ) -> Result<AgentConversation, SequentialWorkflowError> {
+        self.run_with_progress(task, cancel, |_, _| {}).await
+    }
+
+    /// Runs the workflow like [`run_with_cancellation`](Self::run_with_cancellation), calling
+    /// `progress` with the number of agents that finished and the number of agents after
+    /// each agent.
+    pub async fn run_with_progress(
+        &self,
+        task: impl Into<String>,
+        cancel: CancellationToken,
+        progress: impl Fn(usize, usize) + Send + Sync,
     ) -> Result<AgentConversation, SequentialWorkflowError> {
         let task = task.into();
 
@@ -97,10 +134,17 @@ impl SequentialWorkflow {
         let mut next_input = task.clone();
         let mut agents_output_schema = Vec::with_capacity(self.agents.len());
         for agent in &self.agents {
-            let output = run_agent_with_output_schema(agent.deref(), next_input.clone()).await?;
+            let output =
+                run_agent_with_output_schema(agent.deref(), next_input.clone(), cancel.clone())
+                    .await?;
             conversation.add(Role::Assistant(agent.name()), output.output.clone());
             next_input = format!("[From Agent] {}:\n{}", agent.name(), output.output);
+            if !output.artifacts.is_empty() {
+                next_input.push_str("\n\n");
+                next_input.push_str(&describe_artifacts(&output.artifacts));
+            }
             agents_output_schema.push(output);
+            progress(agents_output_schema.len(), self.agents.len());
         }
 
         let metadata = MetadataSchema {
@@ -6,7 +6,7 @@ use serde::Serialize;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::structs::concurrent_workflow::ConcurrentWorkflowError;
+use crate::structs::{artifact::Artifact, concurrent_workflow::ConcurrentWorkflowError};
 
 pub trait Swarm {
     fn name(&self) -> &str;
@@ -49,4 +49,6 @@ pub struct AgentOutputSchema {
     pub start: DateTime<Local>,
     pub end: DateTime<Local>,
     pub duration: i64,
+    /// Artifacts saved by the agent during the run.
+    pub artifacts: Vec<Artifact>,
 }
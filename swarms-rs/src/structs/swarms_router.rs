@@ -3,7 +3,7 @@ use serde::Deserialize;
 
 use crate::agent::SwarmsAgent;
 use crate::llm::provider::openai::OpenAI;
-use crate::prompts::multi_agent_collab_prompt::MULTI_AGENT_COLLAB_PROMPT;
+use crate::prompts::template::{PromptTemplate, TemplateError};
 use crate::structs::agent::Agent;
 use crate::structs::concurrent_workflow::ConcurrentWorkflow;
 use crate::structs::concurrent_workflow::ConcurrentWorkflowError;
@@ -71,39 +71,63 @@ impl SwarmRouterConfig {
         Ok(())
     }
 
+    /// Sets the `agents` prompt variable of every agent to the roster of the swarm, one
+    /// `- name: description` line per agent.
+    fn set_agent_roster(&mut self) {
+        let roster = self
+            .agents
+            .iter()
+            .map(|agent| format!("- {}: {}", agent.name(), agent.description()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let agents = std::mem::take(&mut self.agents);
+        self.agents = agents
+            .into_iter()
+            .map(|agent| agent.prompt_variable("agents", roster.clone()))
+            .collect();
+    }
+
     /// Append the different rules provided by the user at the end of the prompt.
-    fn handle_rules(&mut self) {
-        let rules = match self.rules.as_deref() {
+    fn handle_rules(&mut self) -> Result<(), TemplateError> {
+        let rules = match self.rules.clone() {
             Some(rules) => rules,
-            None => return,
+            None => return Ok(()),
         };
 
         tracing::info!("Injecting rules to every agent!");
+        self.append_to_system_prompts("{{> swarm_rules}}")?;
         let agents = std::mem::take(&mut self.agents);
         self.agents = agents
             .into_iter()
-            .map(|agent| {
-                let system_prompt = agent.get_system_prompt().unwrap_or("");
-                let new_system_prompt = format!("{system_prompt}\n### SWARM RULES ###\n{rules}");
-                agent.system_prompt(new_system_prompt)
-            })
+            .map(|agent| agent.prompt_variable("rules", rules.clone()))
             .collect();
         tracing::info!("Finished injecting rules");
+        Ok(())
     }
 
     /// Activate automatic prompt engineering for agents that support it
-    fn update_system_prompt_for_agent_in_swarm(&mut self) {
+    fn update_system_prompt_for_agent_in_swarm(&mut self) -> Result<(), TemplateError> {
         tracing::info!("Injecting multi-agent prompt to every agent!");
+        self.append_to_system_prompts("\n{{> multi_agent_collab}}")?;
+        tracing::info!("Finished injecting multi-agent prompt");
+        Ok(())
+    }
+
+    /// Appends a template to the system prompt of every agent.
+    fn append_to_system_prompts(&mut self, template: &str) -> Result<(), TemplateError> {
+        let template = PromptTemplate::parse(template)?;
         let agents = std::mem::take(&mut self.agents);
         self.agents = agents
             .into_iter()
             .map(|agent| {
-                let system_prompt = agent.get_system_prompt().unwrap_or("");
-                let new_system_prompt = format!("{system_prompt}\n{MULTI_AGENT_COLLAB_PROMPT}");
-                agent.system_prompt(new_system_prompt)
+                let system_prompt = match agent.get_system_prompt_template() {
+                    Some(system_prompt) => system_prompt.clone(),
+                    None => PromptTemplate::literal(agent.get_system_prompt().unwrap_or("")),
+                };
+                agent.system_prompt_template(system_prompt.append(template.clone()))
             })
             .collect();
-        tracing::info!("Finished injecting multi-agent prompt");
+        Ok(())
     }
 
     /// Validate that agents are valid
@@ -155,10 +179,15 @@ impl SwarmRouter {
         );
 
         let mut config = config;
-        config.handle_rules();
+        config.set_agent_roster();
+        config.handle_rules()?;
 
         if config.multi_agent_collab_prompt {
-            config.update_system_prompt_for_agent_in_swarm();
+            config.update_system_prompt_for_agent_in_swarm()?;
+        }
+
+        for agent in &config.agents {
+            agent.validate_system_prompt()?;
         }
 
         let swarm_router = SwarmRouter::create_swarm_router(config);
@@ -373,4 +402,7 @@ pub enum SwarmRouterError {
 
     #[error(transparent)]
     AgentRearrangeError(#[from] crate::structs::rearrange::AgentRearrangeError),
+
+    #[error("System prompt template error: {0}")]
+    TemplateError(#[from] TemplateError),
 }
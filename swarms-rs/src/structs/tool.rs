@@ -8,7 +8,10 @@ use serde::{Deserialize, Serialize};
 use std::{future::Future, ops::Deref, sync::Arc};
 use thiserror::Error;
 
-use crate::llm::request::ToolDefinition;
+use crate::{
+    llm::request::ToolDefinition,
+    structs::mcp::{MCPClient, MCPError},
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ToolError {
@@ -72,9 +75,28 @@ impl<T: Tool> ToolDyn for T {
     }
 }
 
+/// What an agent builder does with a tool added with the name of a tool already added.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateToolPolicy {
+    /// Building the agent fails.
+    #[default]
+    Error,
+    /// The tool added first is kept.
+    KeepFirst,
+    /// The tool added last replaces it, in the place of the first.
+    KeepLast,
+}
+
 pub struct MCPTool {
     tool: rmcp::model::Tool,
-    client: Arc<RunningService<RoleClient, Box<dyn DynService<RoleClient>>>>,
+    /// Name of the tool for the model, the name on the server by default
+    name: String,
+    client: MCPToolClient,
+}
+
+enum MCPToolClient {
+    Service(Arc<RunningService<RoleClient, Box<dyn DynService<RoleClient>>>>),
+    Managed(MCPClient),
 }
 
 impl MCPTool {
@@ -82,7 +104,28 @@ impl MCPTool {
         tool: rmcp::model::Tool,
         client: Arc<RunningService<RoleClient, Box<dyn DynService<RoleClient>>>>,
     ) -> Self {
-        Self { tool, client }
+        Self {
+            name: tool.name.to_string(),
+            tool,
+            client: MCPToolClient::Service(client),
+        }
+    }
+
+    /// A tool of the server of `client`, called through it so that a lost connection is
+    /// re-established.
+    pub fn from_client(tool: rmcp::model::Tool, client: MCPClient) -> Self {
+        Self {
+            name: tool.name.to_string(),
+            tool,
+            client: MCPToolClient::Managed(client),
+        }
+    }
+
+    /// Exposes the tool to the model as `name`, e.g. to tell apart tools of different
+    /// servers with the same name. The server is still called with its own name.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
     }
 }
 
@@ -97,25 +140,38 @@ impl Tool for MCPTool {
     const NAME: &'static str = "";
 
     fn name(&self) -> String {
-        self.tool.name.to_string()
+        self.name.clone()
     }
 
     fn definition(&self) -> ToolDefinition {
-        ToolDefinition::from(&self.tool)
+        ToolDefinition {
+            name: self.name.clone(),
+            ..ToolDefinition::from(&self.tool)
+        }
     }
 
     async fn call(
         &self,
         args: serde_json::Map<String, serde_json::Value>,
     ) -> Result<Self::Output, Self::Error> {
-        let result = self
-            .client
-            .call_tool(CallToolRequestParam {
-                name: Tool::name(self).into(),
-                arguments: Some(args),
-            })
-            .await
-            .map_err(|e| MCPToolError(format!("MCP tool call failed: {e}")))?;
+        let param = CallToolRequestParam {
+            name: self.tool.name.clone(),
+            arguments: Some(args),
+        };
+        let result = match &self.client {
+            MCPToolClient::Service(service) => {
+                service.call_tool(param).await.map_err(MCPError::from)
+            },
+            MCPToolClient::Managed(client) => {
+                // Tool futures must be `Sync`, which reconnecting isn't, so run the call on
+                // its own task
+                let client = client.clone();
+                tokio::spawn(async move { client.call_tool(param).await })
+                    .await
+                    .map_err(|e| MCPToolError(format!("MCP tool call failed: {e}")))?
+            },
+        }
+        .map_err(|e| MCPToolError(format!("MCP tool call failed: {e}")))?;
 
         if result.is_error.unwrap_or(false) {
             return Err(ToolError::from(MCPToolError(format!(
@@ -168,6 +224,109 @@ impl Tool for MCPTool {
     }
 }
 
+enum MCPResourceToolKind {
+    List,
+    Read,
+}
+
+/// A built-in tool to list or read the resources of the server of an [`MCPClient`], named
+/// `<server>_list_resources` and `<server>_read_resource`.
+pub struct MCPResourceTool {
+    client: MCPClient,
+    kind: MCPResourceToolKind,
+}
+
+#[derive(Deserialize)]
+struct ReadResourceArgs {
+    uri: String,
+}
+
+impl MCPResourceTool {
+    /// Lists the resources of the server, with their URI, name, description and MIME type.
+    pub fn list(client: MCPClient) -> Self {
+        Self {
+            client,
+            kind: MCPResourceToolKind::List,
+        }
+    }
+
+    /// Reads a resource of the server by URI.
+    pub fn read(client: MCPClient) -> Self {
+        Self {
+            client,
+            kind: MCPResourceToolKind::Read,
+        }
+    }
+}
+
+impl ToolDyn for MCPResourceTool {
+    fn name(&self) -> String {
+        match self.kind {
+            MCPResourceToolKind::List => format!("{}_list_resources", self.client.name()),
+            MCPResourceToolKind::Read => format!("{}_read_resource", self.client.name()),
+        }
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        let server = self.client.name();
+        let (description, parameters) = match self.kind {
+            MCPResourceToolKind::List => (
+                format!("List the resources of the {server} server."),
+                serde_json::json!({ "type": "object", "properties": {} }),
+            ),
+            MCPResourceToolKind::Read => (
+                format!("Read a resource of the {server} server by its URI."),
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "uri": {
+                            "type": "string",
+                            "description": "URI of the resource"
+                        }
+                    },
+                    "required": ["uri"]
+                }),
+            ),
+        };
+        ToolDefinition {
+            name: ToolDyn::name(self),
+            description,
+            parameters,
+        }
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move {
+            let failed = |e: MCPError| MCPToolError(format!("MCP resource request failed: {e}"));
+            match self.kind {
+                MCPResourceToolKind::List => {
+                    let resources = self.client.list_resources().await.map_err(failed)?;
+                    let resources = resources
+                        .into_iter()
+                        .map(|resource| {
+                            serde_json::json!({
+                                "uri": resource.raw.uri,
+                                "name": resource.raw.name,
+                                "description": resource.raw.description,
+                                "mime_type": resource.raw.mime_type,
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    Ok(serde_json::to_string(&resources)?)
+                },
+                MCPResourceToolKind::Read => {
+                    let args = serde_json::from_str::<ReadResourceArgs>(&args)?;
+                    Ok(self
+                        .client
+                        .read_resource_text(args.uri)
+                        .await
+                        .map_err(failed)?)
+                },
+            }
+        })
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("MCPToolError: {0}")]
 pub struct MCPToolError(String);
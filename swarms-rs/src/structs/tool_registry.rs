@@ -0,0 +1,303 @@
+//! A registry of tools by name.
+//!
+//! Tools are registered once and looked up by name, e.g. by the
+//! [`AgentLoader`](crate::agent::spec::AgentLoader) when an agent spec lists them.
+//! Clones of a registry share its tools.
+//!
+//! A registry can also be attached to agents with
+//! [`SwarmsAgentBuilder::tool_registry`](crate::agent::SwarmsAgentBuilder::tool_registry).
+//! Tools can then be added, removed, enabled and disabled while the agents are running: each
+//! request to the model offers the tools enabled at that time, narrowed down for each agent by
+//! a [`ToolFilter`]. [`ToolMetadata`] describes the tools to such filters and to other policy
+//! decisions, like approval handlers.
+//!
+//! ```rust
+//! use swarms_rs::structs::tool_registry::{SideEffects, ToolFilter, ToolMetadata};
+//!
+//! let metadata = ToolMetadata::new()
+//!     .tag("files")
+//!     .cost(0.01)
+//!     .side_effects(SideEffects::Destructive);
+//! let read_only = ToolFilter::new().tag("files").max_side_effects(SideEffects::ReadOnly);
+//! assert!(!read_only.allows("delete_file", &metadata));
+//! assert!(ToolFilter::new().allows("delete_file", &metadata));
+//! ```
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use super::tool::ToolDyn;
+use crate::llm::request::ToolDefinition;
+
+/// How much a tool changes the world outside the agent.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum SideEffects {
+    /// Only reads, e.g. a search.
+    ReadOnly,
+    /// Changes something that can be changed back, e.g. writes a file. Tools are assumed to
+    /// do so unless stated otherwise.
+    #[default]
+    Write,
+    /// Changes something that can't be changed back, e.g. deletes a file or sends an email.
+    Destructive,
+}
+
+/// Description of a tool for policy decisions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolMetadata {
+    pub tags: BTreeSet<String>,
+    /// Estimated cost of a call, in a unit of the caller's choice
+    pub cost: Option<f64>,
+    pub side_effects: SideEffects,
+}
+
+impl ToolMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    pub fn cost(mut self, cost: f64) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    pub fn side_effects(mut self, side_effects: SideEffects) -> Self {
+        self.side_effects = side_effects;
+        self
+    }
+}
+
+/// The tools of a registry an agent may use.
+///
+/// A tool is allowed if it's listed by name or has one of the listed tags (any tool if
+/// neither names nor tags are listed), isn't denied by name, and its side effects and cost
+/// don't exceed the limits.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ToolFilter {
+    names: BTreeSet<String>,
+    tags: BTreeSet<String>,
+    denied: BTreeSet<String>,
+    max_side_effects: Option<SideEffects>,
+    max_cost: Option<f64>,
+}
+
+impl ToolFilter {
+    /// Allows every tool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the tool named `name`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.names.insert(name.into());
+        self
+    }
+
+    /// Allows the tools named in `names`.
+    pub fn names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.names.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Allows the tools tagged `tag`.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    /// Denies the tool named `name`, even if allowed otherwise.
+    pub fn deny(mut self, name: impl Into<String>) -> Self {
+        self.denied.insert(name.into());
+        self
+    }
+
+    /// Denies tools with more side effects than `side_effects`.
+    pub fn max_side_effects(mut self, side_effects: SideEffects) -> Self {
+        self.max_side_effects = Some(side_effects);
+        self
+    }
+
+    /// Denies tools that cost more than `cost` per call. Tools without a cost are allowed.
+    pub fn max_cost(mut self, cost: f64) -> Self {
+        self.max_cost = Some(cost);
+        self
+    }
+
+    /// Whether the tool named `name` described by `metadata` is allowed.
+    pub fn allows(&self, name: &str, metadata: &ToolMetadata) -> bool {
+        let listed = (self.names.is_empty() && self.tags.is_empty())
+            || self.names.contains(name)
+            || !self.tags.is_disjoint(&metadata.tags);
+        listed
+            && !self.denied.contains(name)
+            && self
+                .max_side_effects
+                .is_none_or(|max| metadata.side_effects <= max)
+            && self
+                .max_cost
+                .zip(metadata.cost)
+                .is_none_or(|(max, cost)| cost <= max)
+    }
+}
+
+struct Entry {
+    tool: Arc<dyn ToolDyn>,
+    definition: ToolDefinition,
+    metadata: ToolMetadata,
+    enabled: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Arc<DashMap<String, Entry>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `tool` under its name.
+    pub fn with_tool(self, tool: impl ToolDyn + 'static) -> Self {
+        self.register(tool);
+        self
+    }
+
+    /// Registers `tool` under its name, with `metadata`.
+    pub fn with_tool_metadata(self, tool: impl ToolDyn + 'static, metadata: ToolMetadata) -> Self {
+        self.register_with_metadata(Arc::new(tool), metadata);
+        self
+    }
+
+    /// Registers `tool` under its name, returning the tool it replaces.
+    pub fn register(&self, tool: impl ToolDyn + 'static) -> Option<Arc<dyn ToolDyn>> {
+        self.register_shared(Arc::new(tool))
+    }
+
+    /// Registers a shared tool under its name, returning the tool it replaces.
+    pub fn register_shared(&self, tool: Arc<dyn ToolDyn>) -> Option<Arc<dyn ToolDyn>> {
+        self.register_with_metadata(tool, ToolMetadata::default())
+    }
+
+    /// Registers a shared tool under its name, with `metadata`, returning the tool it
+    /// replaces. The tool is enabled.
+    pub fn register_with_metadata(
+        &self,
+        tool: Arc<dyn ToolDyn>,
+        metadata: ToolMetadata,
+    ) -> Option<Arc<dyn ToolDyn>> {
+        let definition = tool.definition();
+        let entry = Entry {
+            tool,
+            definition,
+            metadata,
+            enabled: true,
+        };
+        self.tools
+            .insert(entry.definition.name.clone(), entry)
+            .map(|replaced| replaced.tool)
+    }
+
+    /// Removes the tool named `name`, returning it.
+    pub fn remove(&self, name: &str) -> Option<Arc<dyn ToolDyn>> {
+        self.tools.remove(name).map(|(_, entry)| entry.tool)
+    }
+
+    /// Offers the tool named `name` to agents again. Returns `false` if there is no such tool.
+    pub fn enable(&self, name: &str) -> bool {
+        self.set_enabled(name, true)
+    }
+
+    /// Stops offering the tool named `name` to agents, without removing it. Returns `false`
+    /// if there is no such tool.
+    pub fn disable(&self, name: &str) -> bool {
+        self.set_enabled(name, false)
+    }
+
+    fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        self.tools
+            .get_mut(name)
+            .map(|mut entry| entry.enabled = enabled)
+            .is_some()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|entry| entry.enabled)
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<ToolMetadata> {
+        self.tools.get(name).map(|entry| entry.metadata.clone())
+    }
+
+    /// Replaces the metadata of the tool named `name`. Returns `false` if there is no such
+    /// tool.
+    pub fn set_metadata(&self, name: &str, metadata: ToolMetadata) -> bool {
+        self.tools
+            .get_mut(name)
+            .map(|mut entry| entry.metadata = metadata)
+            .is_some()
+    }
+
+    /// The tool named `name`, whether enabled or not.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ToolDyn>> {
+        self.tools.get(name).map(|entry| Arc::clone(&entry.tool))
+    }
+
+    /// The tool named `name`, if it's enabled and allowed by `filter`.
+    pub fn get_allowed(&self, name: &str, filter: &ToolFilter) -> Option<Arc<dyn ToolDyn>> {
+        self.tools
+            .get(name)
+            .filter(|entry| entry.enabled && filter.allows(name, &entry.metadata))
+            .map(|entry| Arc::clone(&entry.tool))
+    }
+
+    /// Definitions of the tools that are enabled and allowed by `filter`, sorted by name.
+    pub fn definitions(&self, filter: &ToolFilter) -> Vec<ToolDefinition> {
+        let mut definitions = self
+            .tools
+            .iter()
+            .filter(|entry| entry.enabled && filter.allows(entry.key(), &entry.metadata))
+            .map(|entry| entry.definition.clone())
+            .collect::<Vec<_>>();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        definitions
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
+    /// Names of the registered tools, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self
+            .tools
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
@@ -2,28 +2,32 @@ use chrono::Local;
 use uuid::Uuid;
 
 use crate::structs::{
-    agent::{Agent, AgentError},
+    agent::{Agent, AgentError, CancellationToken},
     swarm::AgentOutputSchema,
 };
 
+/// Runs `agent` with [`Agent::run_with_output`] and records its output and artifacts.
 pub async fn run_agent_with_output_schema(
     agent: &dyn Agent,
     task: String,
+    cancel: CancellationToken,
 ) -> Result<AgentOutputSchema, AgentError> {
     let start = Local::now();
-    let output = agent.run(task.clone()).await?;
+    let (output, result) = agent.run_with_output(task.clone(), cancel).await?;
 
     let end = Local::now();
     let duration = end.signed_duration_since(start).num_seconds();
 
     let agent_output = AgentOutputSchema {
-        run_id: Uuid::new_v4(),
+        // Keep the ID of the run, which is the key of its artifacts
+        run_id: Uuid::parse_str(&result.run_id).unwrap_or_else(|_| Uuid::new_v4()),
         agent_name: agent.name(),
         task,
         output,
         start,
         end,
         duration,
+        artifacts: result.artifacts,
     };
 
     Ok(agent_output)
@@ -0,0 +1,85 @@
+//! Mocks shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+
+// Mock model that returns scripted responses, then a plain text answer, and records the
+// requests
+#[derive(Clone, Default)]
+pub struct ScriptedModel {
+    pub responses: Arc<Mutex<VecDeque<Vec<AssistantContent>>>>,
+    pub requests: Arc<Mutex<Vec<CompletionRequest>>>,
+}
+
+impl ScriptedModel {
+    /// Responds with each list of choices in turn.
+    pub fn new(responses: Vec<Vec<AssistantContent>>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            requests: Arc::default(),
+        }
+    }
+
+    /// Responds with each text in turn.
+    pub fn texts(texts: &[&str]) -> Self {
+        Self::new(
+            texts
+                .iter()
+                .map(|text| vec![AssistantContent::text(*text)])
+                .collect(),
+        )
+    }
+
+    /// Names of the tools offered in each request.
+    pub fn tools(&self) -> Vec<Vec<String>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.tools.iter().map(|tool| tool.name.clone()).collect())
+            .collect()
+    }
+
+    /// Names of the tools offered in the first request.
+    pub fn first_tools(&self) -> Vec<String> {
+        self.tools().remove(0)
+    }
+
+    /// The chat history of the last request, debug formatted.
+    pub fn last_history(&self) -> String {
+        let requests = self.requests.lock().unwrap();
+        format!("{:?}", requests.last().unwrap().chat_history)
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.requests.lock().unwrap().push(request);
+        let choice = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| vec![AssistantContent::text("Done")]);
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice,
+                raw_response: (),
+            })
+        })
+    }
+}
@@ -0,0 +1,270 @@
+//! Tests for agent lifecycle hooks
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::hooks::{AgentHook, HookAction, HookContext};
+use swarms_rs::agent::{ChatResponse, SwarmsAgentBuilder, TaskStatus, ToolCallOutput};
+use swarms_rs::llm::{
+    completion::{AssistantContent, ToolFunction},
+    request::{CompletionRequest, ToolDefinition},
+};
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::tool::Tool;
+
+use common::ScriptedModel;
+
+#[derive(Deserialize)]
+struct EchoArgs {
+    input: String,
+}
+
+#[derive(Serialize)]
+struct EchoOutput {
+    echo: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("echo error")]
+struct EchoError;
+
+struct EchoTool;
+
+impl Tool for EchoTool {
+    type Error = EchoError;
+    type Args = EchoArgs;
+    type Output = EchoOutput;
+
+    const NAME: &'static str = "echo";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Echo the input".to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "input": { "type": "string" } },
+                "required": ["input"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok(EchoOutput { echo: args.input })
+    }
+}
+
+fn echo_call(input: &str) -> AssistantContent {
+    AssistantContent::tool_call("call_1", "echo", serde_json::json!({ "input": input }))
+}
+
+// Hook that records the name of every event it receives
+#[derive(Clone, Default)]
+struct RecordingHook {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingHook {
+    fn events(&self) -> Vec<String> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: impl Into<String>) {
+        self.events.lock().unwrap().push(event.into());
+    }
+}
+
+impl AgentHook for RecordingHook {
+    fn on_loop_start(&self, ctx: &HookContext, loop_count: u32) {
+        assert_eq!(ctx.task, Some("Echo hello"));
+        self.record(format!("loop_start:{loop_count}"));
+    }
+
+    fn on_loop_end(&self, _ctx: &HookContext, loop_count: u32) {
+        self.record(format!("loop_end:{loop_count}"));
+    }
+
+    fn before_llm_call(&self, _ctx: &HookContext, _request: &mut CompletionRequest) -> HookAction {
+        self.record("before_llm");
+        HookAction::Continue
+    }
+
+    fn after_llm_call(&self, _ctx: &HookContext, _choices: &mut Vec<AssistantContent>) {
+        self.record("after_llm");
+    }
+
+    fn before_tool_call(&self, _ctx: &HookContext, call: &mut ToolFunction) -> HookAction {
+        self.record(format!("before_tool:{}", call.name));
+        HookAction::Continue
+    }
+
+    fn after_tool_call(&self, _ctx: &HookContext, output: &mut ToolCallOutput) {
+        self.record(format!("after_tool:{}", output.name));
+    }
+
+    fn on_task_evaluation(&self, _ctx: &HookContext, status: &TaskStatus) {
+        if let TaskStatus::Complete = status {
+            self.record("task_complete");
+        }
+    }
+
+    fn on_complete(&self, _ctx: &HookContext, _output: &mut String) {
+        self.record("complete");
+    }
+}
+
+#[tokio::test]
+async fn test_hooks_receive_lifecycle_events() {
+    let model = ScriptedModel::new(vec![
+        vec![echo_call("hello")],
+        vec![AssistantContent::tool_call(
+            "call_2",
+            "task_evaluator",
+            serde_json::json!({ "status": "Complete" }),
+        )],
+    ]);
+    let hook = RecordingHook::default();
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .add_tool(EchoTool)
+        .add_hook(hook.clone())
+        .max_loops(3)
+        .build();
+
+    agent.run("Echo hello".to_owned()).await.unwrap();
+
+    assert_eq!(
+        hook.events(),
+        vec![
+            "loop_start:0",
+            "before_llm",
+            "after_llm",
+            "before_tool:echo",
+            "after_tool:echo",
+            "loop_end:0",
+            "loop_start:1",
+            "before_llm",
+            "after_llm",
+            "before_tool:task_evaluator",
+            "after_tool:task_evaluator",
+            "task_complete",
+            "loop_end:1",
+            "complete",
+        ]
+    );
+}
+
+struct RedactHook;
+
+impl AgentHook for RedactHook {
+    fn before_tool_call(&self, _ctx: &HookContext, call: &mut ToolFunction) -> HookAction {
+        call.arguments = serde_json::json!({ "input": "[REDACTED]" });
+        HookAction::Continue
+    }
+
+    fn on_complete(&self, _ctx: &HookContext, output: &mut String) {
+        *output = output.replace("secret", "[REDACTED]");
+    }
+}
+
+#[tokio::test]
+async fn test_hooks_can_mutate_tool_calls_and_output() {
+    let model = ScriptedModel::new(vec![vec![echo_call("secret")]]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .add_tool(EchoTool)
+        .disable_task_complete_tool()
+        .add_hook(RedactHook)
+        .build();
+
+    let response = agent.chat("Echo my secret", vec![]).await.unwrap();
+    let ChatResponse::ToolCalls(outputs) = response else {
+        panic!("expected tool calls");
+    };
+    assert_eq!(outputs[0].result, r#"{"echo":"[REDACTED]"}"#);
+
+    let model = ScriptedModel::new(vec![vec![AssistantContent::text("The secret is 42")]]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .disable_task_complete_tool()
+        .add_hook(RedactHook)
+        .build();
+
+    let output = agent.run("Tell me".to_owned()).await.unwrap();
+    assert!(output.contains("The [REDACTED] is 42"));
+    assert!(!output.contains("secret"));
+}
+
+struct VetoToolHook;
+
+impl AgentHook for VetoToolHook {
+    fn before_tool_call(&self, _ctx: &HookContext, call: &mut ToolFunction) -> HookAction {
+        HookAction::Veto {
+            reason: format!("{} is disabled", call.name),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_hook_can_veto_tool_call() {
+    let model = ScriptedModel::new(vec![vec![echo_call("hello")]]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .add_tool(EchoTool)
+        .disable_task_complete_tool()
+        .add_hook(VetoToolHook)
+        .build();
+
+    let response = agent.chat("Echo hello", vec![]).await.unwrap();
+    let ChatResponse::ToolCalls(outputs) = response else {
+        panic!("expected tool calls");
+    };
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].result, "Tool call vetoed: echo is disabled");
+}
+
+struct VetoLlmHook;
+
+impl AgentHook for VetoLlmHook {
+    fn before_llm_call(&self, _ctx: &HookContext, _request: &mut CompletionRequest) -> HookAction {
+        HookAction::Veto {
+            reason: "offline".to_owned(),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_hook_can_veto_llm_call() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .disable_task_complete_tool()
+        .add_hook(VetoLlmHook)
+        .build();
+
+    let result = agent.chat("Hello", vec![]).await;
+    assert!(matches!(result, Err(AgentError::Vetoed(reason)) if reason == "offline"));
+}
+
+#[derive(Clone, Default)]
+struct RetryHook {
+    attempts: Arc<Mutex<Vec<u32>>>,
+}
+
+impl AgentHook for RetryHook {
+    fn on_retry(&self, _ctx: &HookContext, attempt: u32, error: &AgentError) {
+        assert!(matches!(error, AgentError::Vetoed(_)));
+        self.attempts.lock().unwrap().push(attempt);
+    }
+}
+
+#[tokio::test]
+async fn test_hook_on_retry() {
+    let hook = RetryHook::default();
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .disable_task_complete_tool()
+        .retry_attempts(2)
+        .add_hook(VetoLlmHook)
+        .add_hook(hook.clone())
+        .build();
+
+    agent.run("Hello".to_owned()).await.unwrap();
+
+    assert_eq!(*hook.attempts.lock().unwrap(), vec![0, 1]);
+}
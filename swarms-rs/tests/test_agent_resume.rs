@@ -0,0 +1,197 @@
+//! Tests for resuming agent runs from saved state
+
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::{AgentTaskState, SwarmsAgentBuilder};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::{AssistantContent, Message, UserContent},
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::Agent;
+use swarms_rs::structs::conversation::{AgentConversation, Role};
+use tempfile::TempDir;
+
+// Mock model that records the prompt of every request
+#[derive(Clone, Default)]
+struct RecordingModel {
+    prompts: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingModel {
+    fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl Model for RecordingModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        let prompt = match request.prompt {
+            Message::User { content } => content
+                .into_iter()
+                .filter_map(|c| match c {
+                    UserContent::Text(text) => Some(text.text),
+                    _ => None,
+                })
+                .collect::<String>(),
+            Message::Assistant { .. } => String::new(),
+        };
+        self.prompts.lock().unwrap().push(prompt);
+
+        Box::pin(async {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("Still working")],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+fn create_agent(
+    model: RecordingModel,
+    dir: &TempDir,
+) -> swarms_rs::agent::SwarmsAgent<RecordingModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .agent_name("ResumeAgent")
+        .disable_task_complete_tool()
+        .max_loops(3)
+        .enable_autosave()
+        .save_state_dir(dir.path().to_string_lossy())
+        .build()
+}
+
+fn state_file(dir: &TempDir) -> std::path::PathBuf {
+    std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .expect("state file should exist")
+        .unwrap()
+        .path()
+}
+
+#[tokio::test]
+async fn test_autosave_records_loop_progress() {
+    let dir = TempDir::new().unwrap();
+    let model = RecordingModel::default();
+    let agent = create_agent(model.clone(), &dir);
+
+    agent.run("Write a poem".to_owned()).await.unwrap();
+
+    let state: AgentTaskState =
+        serde_json::from_slice(&std::fs::read(state_file(&dir)).unwrap()).unwrap();
+    assert_eq!(state.task, "Write a poem");
+    assert_eq!(state.loop_count, 3);
+    assert!(state.finished);
+    assert!(state.pending_context.is_none());
+    assert_eq!(model.prompts().len(), 3);
+}
+
+#[tokio::test]
+async fn test_resume_continues_from_saved_loop() {
+    let dir = TempDir::new().unwrap();
+    let task = "Write a poem".to_owned();
+
+    // Simulate an agent that was killed after its first loop, with pending
+    // task_evaluator context for the next one.
+    let writer = create_agent(RecordingModel::default(), &dir);
+    writer.run(task.clone()).await.unwrap();
+    let path = state_file(&dir);
+    let mut conversation = AgentConversation::new("ResumeAgent".to_owned());
+    conversation.add(Role::User("User".to_owned()), task.clone());
+    conversation.add(
+        Role::Assistant("ResumeAgent".to_owned()),
+        "First draft".to_owned(),
+    );
+    let state = AgentTaskState {
+        task: task.clone(),
+        loop_count: 1,
+        pending_context: Some("Add a second stanza".to_owned()),
+        finished: false,
+        budget_exceeded: None,
+        termination: None,
+        plan: None,
+        conversation,
+    };
+    std::fs::write(&path, serde_json::to_string(&state).unwrap()).unwrap();
+
+    let model = RecordingModel::default();
+    let agent = create_agent(model.clone(), &dir);
+    let result = agent.resume(task).await.unwrap();
+
+    let prompts = model.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].contains("Add a second stanza"));
+    assert!(prompts[1].contains("loop 3 of 3"));
+    assert!(result.contains("First draft"));
+
+    let state: AgentTaskState = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(state.loop_count, 3);
+    assert!(state.finished);
+}
+
+#[tokio::test]
+async fn test_resume_finished_task_does_not_call_model() {
+    let dir = TempDir::new().unwrap();
+    create_agent(RecordingModel::default(), &dir)
+        .run("Write a poem".to_owned())
+        .await
+        .unwrap();
+
+    let model = RecordingModel::default();
+    let agent = create_agent(model.clone(), &dir);
+    let result = agent.resume("Write a poem".to_owned()).await.unwrap();
+
+    assert!(model.prompts().is_empty());
+    assert!(result.contains("Still working"));
+}
+
+#[tokio::test]
+async fn test_resume_without_saved_state_runs_task() {
+    let dir = TempDir::new().unwrap();
+    let model = RecordingModel::default();
+    let agent = create_agent(model.clone(), &dir);
+
+    agent.resume("Write a poem".to_owned()).await.unwrap();
+
+    assert_eq!(model.prompts().len(), 3);
+}
+
+#[tokio::test]
+async fn test_load_legacy_conversation_state() {
+    let dir = TempDir::new().unwrap();
+    create_agent(RecordingModel::default(), &dir)
+        .run("Write a poem".to_owned())
+        .await
+        .unwrap();
+
+    // Older versions saved only the conversation
+    let path = state_file(&dir);
+    let state: AgentTaskState = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    std::fs::write(&path, serde_json::to_string(&state.conversation).unwrap()).unwrap();
+
+    let agent = create_agent(RecordingModel::default(), &dir);
+    let loaded = agent.load_task_state("Write a poem").await.unwrap();
+
+    assert_eq!(loaded.loop_count, 0);
+    assert!(!loaded.finished);
+    assert_eq!(
+        loaded.conversation.history.len(),
+        state.conversation.history.len()
+    );
+}
+
+#[tokio::test]
+async fn test_load_task_state_requires_save_state_dir() {
+    let agent = SwarmsAgentBuilder::new_with_model(RecordingModel::default())
+        .disable_task_complete_tool()
+        .build();
+
+    assert!(agent.load_task_state("Write a poem").await.is_err());
+    assert!(agent.resume("Write a poem".to_owned()).await.is_err());
+}
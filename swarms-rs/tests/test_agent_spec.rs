@@ -0,0 +1,285 @@
+//! Tests for declarative agent specs
+
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::spec::{AgentLoader, AgentSpec, McpServerSpec, SpecError};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse, ToolDefinition},
+};
+use swarms_rs::structs::agent::Agent;
+use swarms_rs::structs::tool::{ToolDyn, ToolError};
+use swarms_rs::structs::tool_registry::ToolRegistry;
+
+// Mock model that records its requests
+#[derive(Clone, Default)]
+struct RecordingModel {
+    requests: Arc<Mutex<Vec<CompletionRequest>>>,
+}
+
+impl Model for RecordingModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.requests.lock().unwrap().push(request);
+        Box::pin(async {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("Done")],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+// Mock tool that echoes its arguments
+struct EchoTool;
+
+impl ToolDyn for EchoTool {
+    fn name(&self) -> String {
+        "echo".to_owned()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "echo".to_owned(),
+            description: "Echoes its arguments".to_owned(),
+            parameters: serde_json::json!({ "type": "object" }),
+        }
+    }
+
+    fn call(&self, args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async move { Ok(args) })
+    }
+}
+
+const YAML_SPEC: &str = r#"
+name: Researcher
+description: Finds sources
+system_prompt_file: prompts/researcher.md
+provider:
+  name: openai
+  model: gpt-4o-mini
+temperature: 0.2
+max_loops: 3
+stop_words: [DONE]
+task_evaluator: false
+budget:
+  max_tool_calls: 10
+tools: [echo]
+mcp_servers:
+  - transport: stdio
+    command: uvx
+    args: [mcp-server-fetch]
+  - transport: sse
+    name: search
+    url: http://localhost:8000/sse
+  - transport: streamable_http
+    name: docs
+    url: http://localhost:8000/mcp
+    headers:
+      X-Team: research
+    bearer_token_env: DOCS_TOKEN
+"#;
+
+#[test]
+fn test_parse_yaml_spec() {
+    let spec = AgentSpec::from_yaml(YAML_SPEC).unwrap();
+
+    assert_eq!(spec.name.as_deref(), Some("Researcher"));
+    assert_eq!(spec.provider.as_ref().unwrap().name, "openai");
+    assert_eq!(spec.max_loops, Some(3));
+    assert_eq!(spec.task_evaluator, Some(false));
+    assert_eq!(spec.budget.unwrap().max_tool_calls, Some(10));
+    assert_eq!(
+        spec.mcp_servers,
+        [
+            McpServerSpec::Stdio {
+                command: "uvx".to_owned(),
+                args: vec!["mcp-server-fetch".to_owned()],
+            },
+            McpServerSpec::Sse {
+                name: "search".to_owned(),
+                url: "http://localhost:8000/sse".to_owned(),
+            },
+            McpServerSpec::StreamableHttp {
+                name: "docs".to_owned(),
+                url: "http://localhost:8000/mcp".to_owned(),
+                headers: [("X-Team".to_owned(), "research".to_owned())].into(),
+                bearer_token_env: Some("DOCS_TOKEN".to_owned()),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_toml_spec() {
+    let spec = AgentSpec::from_toml(
+        r#"
+        name = "Researcher"
+        system_prompt = "You research."
+        stop_words = ["DONE"]
+        tools = ["echo"]
+
+        [provider]
+        name = "anthropic"
+        api_key_env = "MY_KEY"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(spec.system_prompt.as_deref(), Some("You research."));
+    assert_eq!(
+        spec.provider.unwrap().api_key_env.as_deref(),
+        Some("MY_KEY")
+    );
+    assert_eq!(spec.tools, ["echo"]);
+    assert!(spec.max_loops.is_none());
+}
+
+#[test]
+fn test_unknown_fields_are_rejected() {
+    let result = AgentSpec::from_yaml("name: Researcher\nmax_loop: 3\n");
+    assert!(matches!(result, Err(SpecError::YamlError(_))));
+}
+
+#[tokio::test]
+async fn test_apply_spec_from_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("prompts")).unwrap();
+    std::fs::write(
+        dir.path().join("prompts/researcher.md"),
+        "You are a researcher.",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("researcher.yaml"),
+        "name: Researcher\nsystem_prompt_file: prompts/researcher.md\nmax_loops: 2\n\
+        task_evaluator: false\ntools: [echo]\n",
+    )
+    .unwrap();
+
+    let spec = AgentSpec::from_file(dir.path().join("researcher.yaml"))
+        .await
+        .unwrap();
+    let model = RecordingModel::default();
+    let agent = spec
+        .apply(
+            SwarmsAgentBuilder::new_with_model(model.clone()),
+            &ToolRegistry::new().with_tool(EchoTool),
+        )
+        .await
+        .unwrap()
+        .build();
+
+    agent.run("Find sources".to_owned()).await.unwrap();
+
+    assert_eq!(agent.name(), "Researcher");
+    let requests = model.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].system_prompt.as_deref(),
+        Some("You are a researcher.")
+    );
+    assert_eq!(
+        requests[0]
+            .tools
+            .iter()
+            .map(|tool| tool.name.as_str())
+            .collect::<Vec<_>>(),
+        ["echo"]
+    );
+}
+
+#[tokio::test]
+async fn test_mcp_server_errors() {
+    let apply = |yaml: &str| {
+        let spec = AgentSpec::from_yaml(yaml).unwrap();
+        async move {
+            spec.apply(
+                SwarmsAgentBuilder::new_with_model(RecordingModel::default()),
+                &ToolRegistry::new(),
+            )
+            .await
+        }
+    };
+
+    let result =
+        apply("mcp_servers:\n  - transport: stdio\n    command: swarms-rs-missing-mcp-server\n")
+            .await;
+    assert!(matches!(result, Err(SpecError::Mcp(_))));
+
+    let result = apply(
+        "mcp_servers:\n  - transport: streamable_http\n    name: docs\n    \
+         url: http://localhost:1/mcp\n    bearer_token_env: SWARMS_RS_MISSING_TOKEN\n",
+    )
+    .await;
+    assert!(
+        matches!(result, Err(SpecError::MissingApiKey(env)) if env == "SWARMS_RS_MISSING_TOKEN")
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_tool() {
+    let spec = AgentSpec::from_yaml("tools: [search]").unwrap();
+    let result = spec
+        .apply(
+            SwarmsAgentBuilder::new_with_model(RecordingModel::default()),
+            &ToolRegistry::new().with_tool(EchoTool),
+        )
+        .await;
+
+    assert!(matches!(result, Err(SpecError::UnknownTool(name)) if name == "search"));
+}
+
+#[tokio::test]
+async fn test_loader_resolves_provider() {
+    let loader = AgentLoader::new();
+
+    let spec = AgentSpec::from_yaml("provider:\n  name: mistral\n").unwrap();
+    assert!(matches!(
+        loader.build(&spec).await,
+        Err(SpecError::UnknownProvider(name)) if name == "mistral"
+    ));
+
+    let spec = AgentSpec::from_yaml(
+        "provider:\n  name: openai\n  api_key_env: SWARMS_SPEC_TEST_MISSING_KEY\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        loader.build(&spec).await,
+        Err(SpecError::MissingApiKey(env)) if env == "SWARMS_SPEC_TEST_MISSING_KEY"
+    ));
+
+    assert!(matches!(
+        loader.build(&AgentSpec::default()).await,
+        Err(SpecError::MissingProvider)
+    ));
+
+    // Safety: no other test reads this variable
+    unsafe { std::env::set_var("SWARMS_SPEC_TEST_KEY", "test-key") };
+    let spec = AgentSpec::from_yaml(
+        "name: Writer\nprovider:\n  name: anthropic\n  model: claude-3-5-haiku-latest\n  \
+        api_key_env: SWARMS_SPEC_TEST_KEY\n",
+    )
+    .unwrap();
+    let agent = loader.build(&spec).await.unwrap();
+    assert_eq!(agent.name(), "Writer");
+}
+
+#[tokio::test]
+async fn test_unsupported_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("agent.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    assert!(matches!(
+        AgentSpec::from_file(&path).await,
+        Err(SpecError::UnsupportedFormat(_))
+    ));
+}
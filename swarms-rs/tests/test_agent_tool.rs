@@ -0,0 +1,167 @@
+//! Tests for agents as tools
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::{SwarmsAgent, SwarmsAgentBuilder, ToolCallErrorKind};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::{Agent, CancellationToken};
+use swarms_rs::structs::agent_tool::AgentTool;
+use swarms_rs::structs::tool::ToolDyn;
+
+// Mock model that returns scripted responses, then a plain text answer
+#[derive(Clone)]
+struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<AssistantContent>>>,
+    answer: &'static str,
+}
+
+impl ScriptedModel {
+    fn new(responses: Vec<AssistantContent>, answer: &'static str) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            answer,
+        }
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        let choice = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| AssistantContent::text(self.answer));
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice: vec![choice],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+fn delegate(tool: &str, task: &str) -> AssistantContent {
+    AssistantContent::tool_call("call_1", tool, serde_json::json!({ "task": task }))
+}
+
+fn agent(
+    name: &str,
+    responses: Vec<AssistantContent>,
+    answer: &'static str,
+) -> SwarmsAgentBuilder<ScriptedModel> {
+    SwarmsAgentBuilder::new_with_model(ScriptedModel::new(responses, answer))
+        .agent_name(name)
+        .disable_task_complete_tool()
+        .max_loops(2)
+}
+
+fn researcher() -> SwarmsAgent<ScriptedModel> {
+    agent(
+        "Research Agent",
+        vec![],
+        "Rust 1.85 stabilized async closures",
+    )
+    .description("Finds facts about a topic")
+    .build()
+}
+
+#[test]
+fn test_tool_definition_from_agent() {
+    let tool = AgentTool::new(Box::new(researcher()));
+    let definition = tool.definition();
+
+    assert_eq!(definition.name, "research_agent");
+    assert_eq!(definition.description, "Finds facts about a topic");
+    assert_eq!(
+        definition.parameters["required"],
+        serde_json::json!(["task"])
+    );
+
+    let tool = AgentTool::new(Box::new(researcher())).name("research");
+    assert_eq!(ToolDyn::name(&tool), "research");
+}
+
+#[tokio::test]
+async fn test_manager_delegates_to_agent() {
+    let manager = agent(
+        "Manager",
+        vec![delegate("research_agent", "What is new in Rust?")],
+        "Report written",
+    )
+    .add_shared_tool(Arc::new(AgentTool::new(Box::new(researcher()))))
+    .build();
+
+    let result = manager
+        .run_with_result("Write a Rust report".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        result.tool_calls[0].result,
+        "Rust 1.85 stabilized async closures"
+    );
+    assert_eq!(result.sub_runs.len(), 1);
+    let sub_run = &result.sub_runs[0];
+    assert_eq!(sub_run.agent_name, "Research Agent");
+    assert_eq!(sub_run.task, "What is new in Rust?");
+    // The usage of the delegated run is rolled up
+    assert!(sub_run.usage.total_tokens() > 0);
+    assert!(result.usage.total_tokens() > sub_run.usage.total_tokens());
+}
+
+#[tokio::test]
+async fn test_depth_limit() {
+    // Manager -> Lead (depth 1) -> Researcher (depth 2), which exceeds the limit
+    let lead = agent(
+        "Lead",
+        vec![delegate("research_agent", "What is new in Rust?")],
+        "Could not research",
+    )
+    .add_shared_tool(Arc::new(
+        AgentTool::new(Box::new(researcher())).max_depth(1),
+    ))
+    .build();
+    let manager = agent(
+        "Manager",
+        vec![delegate("lead", "Research Rust")],
+        "Report written",
+    )
+    .add_shared_tool(Arc::new(AgentTool::new(Box::new(lead))))
+    .build();
+
+    let result = manager
+        .run_with_result("Write a Rust report".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    let lead_run = &result.sub_runs[0];
+    assert!(lead_run.sub_runs.is_empty());
+    let call = &lead_run.tool_calls[0];
+    assert_eq!(call.error, Some(ToolCallErrorKind::ExecutionFailed));
+    assert!(call.result.contains("depth limit"), "{}", call.result);
+}
+
+#[tokio::test]
+async fn test_call_outside_of_run() {
+    let tool = AgentTool::new(Box::new(researcher()));
+
+    let answer = tool
+        .call(r#"{"task": "What is new in Rust?"}"#.to_owned())
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "Rust 1.85 stabilized async closures");
+    assert!(tool.call("{}".to_owned()).await.is_err());
+}
@@ -0,0 +1,305 @@
+//! Tests for agent artifacts
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::{ChatResponse, SwarmsAgentBuilder, ToolCallErrorKind};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::{Agent, AgentError, CancellationToken};
+use swarms_rs::structs::artifact::{ArtifactError, ArtifactStore, FsArtifactStore};
+use swarms_rs::structs::sequential_workflow::SequentialWorkflow;
+
+// Mock model that returns scripted responses, then a plain text answer
+#[derive(Clone, Default)]
+struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<Vec<AssistantContent>>>>,
+    requests: Arc<Mutex<Vec<CompletionRequest>>>,
+}
+
+impl ScriptedModel {
+    fn new(responses: Vec<Vec<AssistantContent>>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            requests: Arc::default(),
+        }
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.requests.lock().unwrap().push(request);
+        let choice = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| vec![AssistantContent::text("Report saved")]);
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice,
+                raw_response: (),
+            })
+        })
+    }
+}
+
+fn save_report() -> Vec<AssistantContent> {
+    vec![AssistantContent::tool_call(
+        "call_1",
+        "save_artifact",
+        serde_json::json!({
+            "name": "report.csv",
+            "content": "city,population\nParis,2100000\n",
+            "mime_type": "text/csv"
+        }),
+    )]
+}
+
+#[tokio::test]
+async fn test_fs_store_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsArtifactStore::new(dir.path());
+
+    let first = store
+        .save("run-1", "notes.md", "text/markdown", b"# Notes".to_vec())
+        .await
+        .unwrap();
+    store
+        .save("run-1", "data.json", "application/json", b"{}".to_vec())
+        .await
+        .unwrap();
+    // Saving the same name again replaces the artifact
+    store
+        .save(
+            "run-1",
+            "notes.md",
+            "text/markdown",
+            b"# New notes".to_vec(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(first.size, 7);
+    assert_eq!(
+        store.load("run-1", "notes.md").await.unwrap(),
+        b"# New notes"
+    );
+    let names = store
+        .list("run-1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|artifact| artifact.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["data.json", "notes.md"]);
+    assert!(store.list("run-2").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_fs_store_rejects_invalid_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = FsArtifactStore::new(dir.path());
+
+    for name in ["", "../escape.txt", "a/b.txt", ".hidden"] {
+        let result = store.save("run-1", name, "text/plain", vec![]).await;
+        assert!(
+            matches!(result, Err(ArtifactError::InvalidName(_))),
+            "{name}"
+        );
+    }
+    assert!(matches!(
+        store.load("run-1", "missing.txt").await,
+        Err(ArtifactError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_agent_saves_artifacts() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = ScriptedModel::new(vec![save_report()]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .artifact_store(FsArtifactStore::new(dir.path()))
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .build();
+
+    let result = agent
+        .run_with_result("Write a report".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(
+        model.requests.lock().unwrap()[0]
+            .tools
+            .iter()
+            .any(|tool| tool.name == "save_artifact")
+    );
+    assert_eq!(result.artifacts.len(), 1);
+    let artifact = &result.artifacts[0];
+    assert_eq!(artifact.name, "report.csv");
+    assert_eq!(artifact.mime_type, "text/csv");
+    assert_eq!(artifact.run_id, result.run_id);
+    assert_eq!(
+        std::fs::read_to_string(&artifact.location).unwrap(),
+        "city,population\nParis,2100000\n"
+    );
+
+    let store = FsArtifactStore::new(dir.path());
+    assert_eq!(store.list(&result.run_id).await.unwrap(), result.artifacts);
+}
+
+#[tokio::test]
+async fn test_agent_saves_base64_artifacts() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = ScriptedModel::new(vec![vec![AssistantContent::tool_call(
+        "call_1",
+        "save_artifact",
+        serde_json::json!({
+            "name": "pixel.png",
+            "content": "iVBORw0KGgo=",
+            "mime_type": "image/png",
+            "encoding": "base64"
+        }),
+    )]]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .artifact_store(FsArtifactStore::new(dir.path()))
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .build();
+
+    let result = agent
+        .run_with_result("Draw a pixel".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    let artifact = &result.artifacts[0];
+    assert_eq!(artifact.mime_type, "image/png");
+    assert_eq!(artifact.size, 8);
+    assert_eq!(
+        std::fs::read(&artifact.location).unwrap(),
+        b"\x89PNG\r\n\x1a\n"
+    );
+}
+
+#[tokio::test]
+async fn test_artifacts_need_a_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![save_report()]))
+        .artifact_store(FsArtifactStore::new(dir.path()))
+        .disable_task_complete_tool()
+        .build();
+
+    let ChatResponse::ToolCalls(outputs) = agent.chat("Write a report", vec![]).await.unwrap()
+    else {
+        panic!("expected tool calls");
+    };
+
+    assert_eq!(outputs[0].error, Some(ToolCallErrorKind::NotFound));
+}
+
+// Mock agent that records its input
+#[derive(Clone, Default)]
+struct RecordingAgent {
+    inputs: Arc<Mutex<Vec<String>>>,
+}
+
+impl Agent for RecordingAgent {
+    fn run(&self, task: String) -> BoxFuture<'_, Result<String, AgentError>> {
+        self.inputs.lock().unwrap().push(task);
+        Box::pin(async { Ok("Reviewed".to_owned()) })
+    }
+
+    fn run_multiple_tasks(
+        &mut self,
+        _tasks: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<String>, AgentError>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    fn plan(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query_long_term_memory(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn save_task_state(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_response_complete(&self, _response: String) -> bool {
+        true
+    }
+
+    fn id(&self) -> String {
+        "reviewer".to_owned()
+    }
+
+    fn name(&self) -> String {
+        "Reviewer".to_owned()
+    }
+
+    fn description(&self) -> String {
+        String::new()
+    }
+
+    fn clone_box(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+}
+
+#[tokio::test]
+async fn test_sequential_workflow_passes_artifacts() {
+    let artifact_dir = tempfile::tempdir().unwrap();
+    let metadata_dir = tempfile::tempdir().unwrap();
+    let writer = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![save_report()]))
+        .agent_name("Writer")
+        .artifact_store(FsArtifactStore::new(artifact_dir.path()))
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .build();
+    let reviewer = RecordingAgent::default();
+    let workflow = SequentialWorkflow::builder()
+        .metadata_output_dir(metadata_dir.path().to_str().unwrap())
+        .add_agent(Box::new(writer))
+        .add_agent(Box::new(reviewer.clone()))
+        .build();
+
+    workflow.run("Write and review a report").await.unwrap();
+
+    let inputs = reviewer.inputs.lock().unwrap();
+    // The next agent gets the conversation of the writer
+    assert!(inputs[0].starts_with("[From Agent] Writer:\n"));
+    assert!(inputs[0].contains("Write and review a report"));
+    assert!(inputs[0].contains("Report saved"));
+    assert!(inputs[0].contains("[Artifacts]\n- report.csv (text/csv, 30 bytes): "));
+
+    let metadata_file = std::fs::read_dir(metadata_dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(metadata_file.path()).unwrap()).unwrap();
+    let artifacts = &metadata["agents_output_schema"][0]["artifacts"];
+    assert_eq!(artifacts[0]["name"], "report.csv");
+    assert_eq!(
+        artifacts[0]["run_id"],
+        metadata["agents_output_schema"][0]["run_id"]
+    );
+    assert_eq!(
+        metadata["agents_output_schema"][1]["artifacts"],
+        serde_json::json!([])
+    );
+}
@@ -0,0 +1,328 @@
+//! Tests for cooperative cancellation of agent and workflow runs
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse, ToolDefinition},
+};
+use swarms_rs::structs::agent::{Agent, AgentError, CancellationToken};
+use swarms_rs::structs::concurrent_workflow::{ConcurrentWorkflow, ConcurrentWorkflowError};
+use swarms_rs::structs::graph_workflow::{DAGWorkflow, Flow, GraphWorkflowError};
+use swarms_rs::structs::tool::Tool;
+
+// Mock model that answers each call after the scripted delay, then immediately
+#[derive(Clone, Default)]
+struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<(Duration, Vec<AssistantContent>)>>>,
+    calls: Arc<AtomicU32>,
+}
+
+impl ScriptedModel {
+    fn new(responses: Vec<(Duration, Vec<AssistantContent>)>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            calls: Arc::default(),
+        }
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let (delay, choice) = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| (Duration::ZERO, vec![AssistantContent::text("Done")]));
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            Ok(CompletionResponse {
+                choice,
+                raw_response: (),
+            })
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct WaitArgs {
+    millis: u64,
+}
+
+#[derive(Serialize)]
+struct WaitOutput {
+    waited: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("wait error")]
+struct WaitError;
+
+struct WaitTool;
+
+impl Tool for WaitTool {
+    type Error = WaitError;
+    type Args = WaitArgs;
+    type Output = WaitOutput;
+
+    const NAME: &'static str = "wait";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Wait for a while".to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "millis": { "type": "integer" } },
+                "required": ["millis"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tokio::time::sleep(Duration::from_millis(args.millis)).await;
+        Ok(WaitOutput {
+            waited: args.millis,
+        })
+    }
+}
+
+fn wait_call(millis: u64) -> Vec<AssistantContent> {
+    vec![AssistantContent::tool_call(
+        "call_1",
+        "wait",
+        serde_json::json!({ "millis": millis }),
+    )]
+}
+
+fn cancel_after(millis: u64) -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(millis)).await;
+        token.cancel();
+    });
+    cancel
+}
+
+#[tokio::test]
+async fn test_cancel_in_flight_llm_call_saves_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = ScriptedModel::new(vec![
+        (Duration::ZERO, wait_call(0)),
+        (
+            Duration::from_secs(30),
+            vec![AssistantContent::text("Too late")],
+        ),
+    ]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .add_tool(WaitTool)
+        .disable_task_complete_tool()
+        .max_loops(5)
+        .save_state_dir(dir.path().to_str().unwrap())
+        .build();
+
+    let start = Instant::now();
+    let result = agent
+        .run_with_cancellation("Wait".to_owned(), cancel_after(100))
+        .await;
+
+    assert!(matches!(result, Err(AgentError::Canceled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let state = agent.load_task_state("Wait").await.unwrap();
+    assert_eq!(state.loop_count, 1);
+    assert!(!state.finished);
+
+    // The canceled run can be resumed where it stopped
+    let output = agent.resume("Wait".to_owned()).await.unwrap();
+    assert!(output.contains("Done"));
+    assert!(!output.contains("Too late"));
+    assert_eq!(model.calls.load(Ordering::SeqCst), 6);
+}
+
+#[tokio::test]
+async fn test_cancel_in_flight_tool_call() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![(
+        Duration::ZERO,
+        wait_call(30_000),
+    )]))
+    .add_tool(WaitTool)
+    .disable_task_complete_tool()
+    .build();
+
+    let start = Instant::now();
+    let result = agent
+        .run_with_cancellation("Wait".to_owned(), cancel_after(100))
+        .await;
+
+    assert!(matches!(result, Err(AgentError::Canceled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_already_canceled_run_does_nothing() {
+    let model = ScriptedModel::default();
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone()).build();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let result = agent
+        .run_with_cancellation("Hello".to_owned(), cancel)
+        .await;
+
+    assert!(matches!(result, Err(AgentError::Canceled)));
+    assert_eq!(model.calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_uncanceled_run_is_unchanged() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .disable_task_complete_tool()
+        .build();
+
+    let output = agent
+        .run_with_cancellation("Hello".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(output.contains("Done"));
+}
+
+// Mock agent that answers after a delay and only implements `run`,
+// so it relies on the default `run_with_cancellation`
+#[derive(Clone)]
+struct SleepyAgent {
+    name: String,
+    delay: Duration,
+}
+
+impl SleepyAgent {
+    fn boxed(name: &str, millis: u64) -> Box<dyn Agent> {
+        Box::new(Self {
+            name: name.to_owned(),
+            delay: Duration::from_millis(millis),
+        })
+    }
+}
+
+impl Agent for SleepyAgent {
+    fn run(&self, task: String) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move {
+            tokio::time::sleep(self.delay).await;
+            Ok(format!("{} did {}", self.name, task))
+        })
+    }
+
+    fn run_multiple_tasks(
+        &mut self,
+        _tasks: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<String>, AgentError>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    fn plan(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query_long_term_memory(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn save_task_state(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_response_complete(&self, _response: String) -> bool {
+        true
+    }
+
+    fn id(&self) -> String {
+        self.name.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> String {
+        String::new()
+    }
+
+    fn clone_box(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+}
+
+#[tokio::test]
+async fn test_cancel_concurrent_workflow() {
+    let dir = tempfile::tempdir().unwrap();
+    let workflow = ConcurrentWorkflow::builder()
+        .name("Workflow")
+        .metadata_output_dir(dir.path().to_str().unwrap())
+        .add_agent(SleepyAgent::boxed("Fast", 0))
+        .add_agent(SleepyAgent::boxed("Slow", 30_000))
+        .build();
+
+    let start = Instant::now();
+    let result = workflow
+        .run_with_cancellation("Task", cancel_after(100))
+        .await;
+
+    assert!(matches!(result, Err(ConcurrentWorkflowError::Canceled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // The output of the agent that finished is saved
+    let metadata_file = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let metadata = std::fs::read_to_string(metadata_file.path()).unwrap();
+    assert!(metadata.contains("Fast did Task"));
+    assert!(!metadata.contains("Slow did Task"));
+}
+
+#[tokio::test]
+async fn test_cancel_dag_workflow() {
+    let mut workflow = DAGWorkflow::new("Workflow", "Cancel test");
+    workflow.register_agent(SleepyAgent::boxed("First", 0));
+    workflow.register_agent(SleepyAgent::boxed("Second", 30_000));
+    workflow.register_agent(SleepyAgent::boxed("Third", 0));
+    workflow
+        .connect_agents("First", "Second", Flow::default())
+        .unwrap();
+    workflow
+        .connect_agents("Second", "Third", Flow::default())
+        .unwrap();
+
+    let start = Instant::now();
+    let results = workflow
+        .execute_workflow_with_cancellation("First", "Task", cancel_after(100))
+        .await
+        .unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(
+        results.get("First").unwrap().as_ref().unwrap(),
+        "First did Task"
+    );
+    assert!(matches!(
+        *results.get("Second").unwrap(),
+        Err(GraphWorkflowError::Canceled)
+    ));
+    assert!(results.get("Third").is_none());
+}
@@ -0,0 +1,271 @@
+//! Tests for agent handoffs
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::run_result::{TerminationReason, TranscriptEntry};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::{Agent, CancellationToken};
+use swarms_rs::structs::conversation::Role;
+use swarms_rs::structs::handoff::{
+    HandoffError, HandoffWorkflow, HistoryFilter, transfer_tool_name,
+};
+
+// What the model was asked
+#[derive(Clone, Debug)]
+struct SeenRequest {
+    system_prompt: Option<String>,
+    history: String,
+    tools: Vec<String>,
+}
+
+// Mock model that returns scripted responses, then a plain text answer
+#[derive(Clone)]
+struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<AssistantContent>>>,
+    answer: &'static str,
+    seen: Arc<Mutex<Vec<SeenRequest>>>,
+}
+
+impl ScriptedModel {
+    fn new(responses: Vec<AssistantContent>, answer: &'static str) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            answer,
+            seen: Arc::default(),
+        }
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.seen.lock().unwrap().push(SeenRequest {
+            system_prompt: request.system_prompt.clone(),
+            history: format!("{:?}", request.chat_history),
+            tools: request.tools.iter().map(|tool| tool.name.clone()).collect(),
+        });
+        let choice = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| AssistantContent::text(self.answer));
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice: vec![choice],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+fn transfer(target: &str, reason: &str) -> AssistantContent {
+    AssistantContent::tool_call(
+        "call_1",
+        transfer_tool_name(target),
+        serde_json::json!({ "reason": reason }),
+    )
+}
+
+fn agent(name: &str, model: ScriptedModel) -> SwarmsAgentBuilder<ScriptedModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .agent_name(name)
+        .system_prompt(format!("You are the {name} agent."))
+        .disable_task_complete_tool()
+        .max_loops(3)
+}
+
+#[test]
+fn test_transfer_tool_name() {
+    assert_eq!(transfer_tool_name("Billing"), "transfer_to_billing");
+    assert_eq!(
+        transfer_tool_name("Tech Support"),
+        "transfer_to_tech_support"
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_ends_run_with_handoff() {
+    let model = ScriptedModel::new(vec![transfer("Billing", "invoice question")], "unused");
+    let triage = agent("Triage", model.clone())
+        .add_handoff("Billing", "Invoices and payments")
+        .build();
+
+    let result = triage
+        .run_with_result("I was charged twice".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.termination, TerminationReason::Handoff);
+    let handoff = result.handoff.unwrap();
+    assert_eq!(handoff.target, "Billing");
+    assert_eq!(handoff.reason.as_deref(), Some("invoice question"));
+    // The turn ends with the transfer
+    assert_eq!(result.loop_count, 1);
+    assert_eq!(model.seen.lock().unwrap().len(), 1);
+    assert!(
+        model.seen.lock().unwrap()[0]
+            .tools
+            .contains(&"transfer_to_billing".to_owned())
+    );
+}
+
+#[tokio::test]
+async fn test_workflow_continues_with_target_agent() {
+    let triage_model = ScriptedModel::new(vec![transfer("Billing", "invoice question")], "unused");
+    let billing_model = ScriptedModel::new(vec![], "Refund issued for the duplicate charge");
+    let workflow = HandoffWorkflow::builder()
+        .add_agent(Box::new(
+            agent("Triage", triage_model)
+                .add_handoff("Billing", "Invoices and payments")
+                .build(),
+        ))
+        .add_agent(Box::new(agent("Billing", billing_model.clone()).build()))
+        .build();
+
+    let outcome = workflow.run("I was charged twice").await.unwrap();
+
+    assert_eq!(outcome.agent_name, "Billing");
+    assert_eq!(
+        outcome.final_answer,
+        "Refund issued for the duplicate charge"
+    );
+    assert_eq!(outcome.path(), vec!["Triage", "Billing"]);
+    assert_eq!(outcome.runs[1].termination, TerminationReason::MaxLoops);
+    assert!(outcome.runs[1].handoff.is_none());
+
+    // The billing agent answers with its own system prompt and tools, in the same conversation
+    let seen = billing_model.seen.lock().unwrap();
+    assert_eq!(
+        seen[0].system_prompt.as_deref(),
+        Some("You are the Billing agent.")
+    );
+    assert!(
+        !seen[0]
+            .tools
+            .iter()
+            .any(|tool| tool.starts_with("transfer_to_"))
+    );
+    assert!(seen[0].history.contains("I was charged twice"));
+    assert!(seen[0].history.contains("transfer_to_billing"));
+}
+
+#[tokio::test]
+async fn test_history_filters() {
+    let history = vec![
+        TranscriptEntry {
+            role: Role::User("User".to_owned()),
+            content: "Task".to_owned(),
+            timestamp: None,
+        },
+        TranscriptEntry {
+            role: Role::Assistant("Triage".to_owned()),
+            content: "[Tool name]: lookup\n[Tool args]: {}\n[Tool result]: ok".to_owned(),
+            timestamp: None,
+        },
+        TranscriptEntry {
+            role: Role::Assistant("Triage".to_owned()),
+            content: "Looking into it".to_owned(),
+            timestamp: None,
+        },
+    ];
+    let contents = |history: Vec<TranscriptEntry>| {
+        history
+            .into_iter()
+            .map(|entry| entry.content)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(HistoryFilter::Full.apply(history.clone()).len(), 3);
+    assert_eq!(
+        contents(HistoryFilter::WithoutToolCalls.apply(history.clone())),
+        vec!["Task", "Looking into it"]
+    );
+    assert_eq!(
+        contents(HistoryFilter::LastMessages(1).apply(history.clone())),
+        vec!["Task", "Looking into it"]
+    );
+    assert_eq!(
+        contents(HistoryFilter::LastMessages(0).apply(history.clone())),
+        vec!["Task"]
+    );
+    let filter = HistoryFilter::custom(|history| history.into_iter().take(1).collect());
+    assert_eq!(contents(filter.apply(history)), vec!["Task"]);
+
+    // A per-target filter is applied to the history passed to that agent
+    let triage_model = ScriptedModel::new(vec![transfer("Billing", "invoice question")], "unused");
+    let billing_model = ScriptedModel::new(vec![], "Done");
+    let workflow = HandoffWorkflow::builder()
+        .add_agent(Box::new(
+            agent("Triage", triage_model)
+                .add_handoff("Billing", "")
+                .build(),
+        ))
+        .add_agent(Box::new(agent("Billing", billing_model.clone()).build()))
+        .target_history_filter("Billing", HistoryFilter::WithoutToolCalls)
+        .build();
+    workflow.run("I was charged twice").await.unwrap();
+
+    let seen = billing_model.seen.lock().unwrap();
+    assert!(seen[0].history.contains("I was charged twice"));
+    assert!(!seen[0].history.contains("transfer_to_billing"));
+}
+
+#[tokio::test]
+async fn test_handoff_errors() {
+    let empty = HandoffWorkflow::builder().build();
+    assert!(matches!(
+        empty.run("task").await,
+        Err(HandoffError::NoAgents)
+    ));
+
+    // A handoff to an agent outside of the workflow
+    let workflow = HandoffWorkflow::builder()
+        .add_agent(Box::new(
+            agent(
+                "Triage",
+                ScriptedModel::new(vec![transfer("Legal", "contract")], "unused"),
+            )
+            .add_handoff("Legal", "Contracts")
+            .build(),
+        ))
+        .build();
+    assert!(matches!(
+        workflow.run("Review my contract").await,
+        Err(HandoffError::UnknownAgent(name)) if name == "Legal"
+    ));
+
+    // Two agents handing off to each other forever
+    let ping = ScriptedModel::new(
+        (0..5).map(|_| transfer("Pong", "your turn")).collect(),
+        "unused",
+    );
+    let pong = ScriptedModel::new(
+        (0..5).map(|_| transfer("Ping", "your turn")).collect(),
+        "unused",
+    );
+    let workflow = HandoffWorkflow::builder()
+        .add_agent(Box::new(
+            agent("Ping", ping).add_handoff("Pong", "").build(),
+        ))
+        .add_agent(Box::new(
+            agent("Pong", pong).add_handoff("Ping", "").build(),
+        ))
+        .max_handoffs(3)
+        .build();
+    assert!(matches!(
+        workflow.run("Play").await,
+        Err(HandoffError::TooManyHandoffs(3))
+    ));
+}
@@ -0,0 +1,290 @@
+//! Tests for managed MCP server connections
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Content, ListToolsResult, PaginatedRequestParam, Tool,
+};
+use rmcp::service::{RequestContext, RunningService};
+use rmcp::{Error as McpError, RoleServer, ServerHandler, ServiceExt};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::mcp::{
+    MCPClient, MCPClientHandler, MCPConnector, MCPError, MCPHealth, MCPService, ReconnectPolicy,
+    StdioConnector,
+};
+
+// MCP server with an `echo` tool
+#[derive(Clone)]
+struct EchoServer;
+
+impl ServerHandler for EchoServer {
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+        });
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: vec![Tool::new(
+                "echo",
+                "Repeat the text",
+                schema.as_object().unwrap().clone(),
+            )],
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let text = request
+            .arguments
+            .and_then(|args| args.get("text").cloned())
+            .and_then(|text| text.as_str().map(str::to_owned))
+            .unwrap_or_default();
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+}
+
+// Connects to an in-process `EchoServer`, after failing `failures` times
+#[derive(Clone, Default)]
+struct InProcessConnector {
+    connections: Arc<AtomicUsize>,
+    failures: Arc<AtomicUsize>,
+    servers: Arc<Mutex<Vec<RunningService<RoleServer, EchoServer>>>>,
+}
+
+impl InProcessConnector {
+    // Stops the running server, like a crashed server process
+    async fn crash(&self) {
+        let server = self.servers.lock().unwrap().pop().unwrap();
+        server.cancel().await.unwrap();
+    }
+}
+
+impl MCPConnector for InProcessConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            self.connections.fetch_add(1, Ordering::SeqCst);
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err("connection refused".to_owned());
+            }
+            let (client_io, server_io) = tokio::io::duplex(4096);
+            let server = tokio::spawn(EchoServer.serve(server_io));
+            let client = handler
+                .into_dyn()
+                .serve(client_io)
+                .await
+                .map_err(|e| e.to_string())?;
+            let server = server.await.unwrap().map_err(|e| e.to_string())?;
+            self.servers.lock().unwrap().push(server);
+            Ok(client)
+        })
+    }
+}
+
+// Connector that never completes the connection
+struct HangingConnector;
+
+impl MCPConnector for HangingConnector {
+    fn connect(&self, _handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(futures::future::pending())
+    }
+}
+
+fn fast_reconnect(max_attempts: u32) -> ReconnectPolicy {
+    ReconnectPolicy {
+        max_attempts: Some(max_attempts),
+        initial_backoff: Duration::from_millis(5),
+        max_backoff: Duration::from_millis(20),
+    }
+}
+
+fn echo(text: &str) -> CallToolRequestParam {
+    CallToolRequestParam {
+        name: "echo".into(),
+        arguments: serde_json::json!({ "text": text }).as_object().cloned(),
+    }
+}
+
+fn text(result: CallToolResult) -> String {
+    serde_json::to_value(&result.content).unwrap()[0]["text"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[derive(Clone)]
+struct UnusedModel;
+
+impl Model for UnusedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        unreachable!("the model is not called")
+    }
+}
+
+#[tokio::test]
+async fn test_connect_and_call() {
+    let connector = InProcessConnector::default();
+    let client = MCPClient::builder("echo", connector.clone())
+        .connect()
+        .await
+        .unwrap();
+
+    assert_eq!(client.health(), MCPHealth::Connected);
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools[0].name, "echo");
+    assert_eq!(text(client.call_tool(echo("hi")).await.unwrap()), "hi");
+    assert_eq!(client.check_health().await, MCPHealth::Connected);
+}
+
+#[tokio::test]
+async fn test_reconnects_after_crash() {
+    let connector = InProcessConnector::default();
+    let client = MCPClient::builder("echo", connector.clone())
+        .reconnect(fast_reconnect(3))
+        .connect()
+        .await
+        .unwrap();
+
+    connector.crash().await;
+    // Failing twice before the server is back
+    connector.failures.store(2, Ordering::SeqCst);
+    assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+
+    let status = client.status();
+    assert_eq!(status.health, MCPHealth::Connected);
+    assert_eq!(status.reconnects, 1);
+    assert_eq!(
+        status.last_error.as_deref().map(|e| e.contains("refused")),
+        Some(true)
+    );
+    assert_eq!(connector.connections.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_tool_call_not_retried() {
+    let connector = InProcessConnector::default();
+    let client = MCPClient::builder("echo", connector.clone())
+        .reconnect(fast_reconnect(3))
+        .connect()
+        .await
+        .unwrap();
+
+    // The server may have run the tool, so the call isn't sent again
+    connector.crash().await;
+    assert!(matches!(
+        client.call_tool(echo("hi")).await,
+        Err(MCPError::ServiceError(_))
+    ));
+    // But the connection is re-established for the next calls
+    assert_eq!(client.status().reconnects, 1);
+    assert_eq!(
+        text(client.call_tool(echo("again")).await.unwrap()),
+        "again"
+    );
+    assert_eq!(connector.connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_disconnected_after_max_attempts() {
+    let connector = InProcessConnector::default();
+    let client = MCPClient::builder("echo", connector.clone())
+        .reconnect(fast_reconnect(2))
+        .connect()
+        .await
+        .unwrap();
+
+    connector.crash().await;
+    connector.failures.store(10, Ordering::SeqCst);
+    assert!(matches!(
+        client.call_tool(echo("hi")).await,
+        Err(MCPError::Disconnected { attempts: 2, .. })
+    ));
+    assert_eq!(client.health(), MCPHealth::Disconnected);
+
+    // The next request tries again
+    connector.failures.store(0, Ordering::SeqCst);
+    assert_eq!(client.check_health().await, MCPHealth::Connected);
+
+    // Without reconnection, a lost connection fails right away
+    let client = MCPClient::builder("echo", connector.clone())
+        .reconnect(ReconnectPolicy::never())
+        .connect()
+        .await
+        .unwrap();
+    connector.crash().await;
+    assert!(matches!(
+        client.list_tools().await,
+        Err(MCPError::Disconnected { attempts: 0, .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_connection_errors() {
+    let result = MCPClient::builder("hanging", HangingConnector)
+        .connect_timeout(Duration::from_millis(20))
+        .connect()
+        .await;
+    assert!(matches!(result, Err(MCPError::Timeout { server, .. }) if server == "hanging"));
+
+    let connector = InProcessConnector::default();
+    connector.failures.store(1, Ordering::SeqCst);
+    assert!(matches!(
+        MCPClient::builder("echo", connector).connect().await,
+        Err(MCPError::Connection { .. })
+    ));
+
+    let result = MCPClient::builder(
+        "missing",
+        StdioConnector::new("swarms-rs-missing-mcp-server", []),
+    )
+    .connect()
+    .await;
+    assert!(matches!(result, Err(MCPError::Connection { .. })));
+}
+
+#[tokio::test]
+async fn test_agent_with_mcp_client() {
+    // A server that is down is an error, not a panic
+    let result = SwarmsAgentBuilder::new_with_model(UnusedModel)
+        .try_add_stdio_mcp_server("swarms-rs-missing-mcp-server", [])
+        .await;
+    assert!(result.is_err());
+
+    let client = MCPClient::builder("echo", InProcessConnector::default())
+        .connect()
+        .await
+        .unwrap();
+    let agent = SwarmsAgentBuilder::new_with_model(UnusedModel)
+        .add_mcp_client(&client)
+        .await
+        .unwrap()
+        .build();
+
+    let status = agent.mcp_server_status();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].name, "echo");
+    assert_eq!(status[0].health, MCPHealth::Connected);
+}
@@ -0,0 +1,460 @@
+//! Tests for the Streamable HTTP MCP transport
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::channel::mpsc as channel;
+use futures::future::BoxFuture;
+use futures::{SinkExt, StreamExt};
+use http_body_util::{BodyExt, Full, StreamBody, combinators::UnsyncBoxBody};
+use hyper::body::{Frame, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Content, ListToolsResult, PaginatedRequestParam,
+    ResourceUpdatedNotificationParam, SubscribeRequestParam, Tool,
+};
+use rmcp::service::{RequestContext, RxJsonRpcMessage, TxJsonRpcMessage};
+use rmcp::{Error as McpError, RoleServer, ServerHandler, ServiceExt};
+use serde_json::Value;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::mcp::{
+    MCPClient, MCPError, MCPHealth, ReconnectPolicy, StreamableHttpConnector,
+};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+// MCP server with an `echo` tool, which announces an update of every resource subscribed to
+#[derive(Clone)]
+struct EchoServer;
+
+impl ServerHandler for EchoServer {
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+        });
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: vec![Tool::new(
+                "echo",
+                "Repeat the text",
+                schema.as_object().unwrap().clone(),
+            )],
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let text = request
+            .arguments
+            .and_then(|args| args.get("text").cloned())
+            .and_then(|text| text.as_str().map(str::to_owned))
+            .unwrap_or_default();
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            // rmcp 0.1.5 reports notifications that were sent as failed, so the result is ignored
+            let _ = context
+                .peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam { uri: request.uri })
+                .await;
+        });
+        Ok(())
+    }
+}
+
+type Body = UnsyncBoxBody<Bytes, Infallible>;
+
+// Connection of one client to an `EchoServer`
+struct Session {
+    to_server: channel::UnboundedSender<RxJsonRpcMessage<RoleServer>>,
+    // Requests waiting for their response, by JSON-RPC id
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>,
+    // Messages of the server that aren't responses, until the client opens the GET stream
+    notifications: Mutex<Option<mpsc::UnboundedReceiver<Value>>>,
+}
+
+impl Session {
+    fn start() -> Arc<Self> {
+        let (to_server, from_client) = channel::unbounded::<RxJsonRpcMessage<RoleServer>>();
+        let (to_client, mut from_server) = channel::unbounded::<TxJsonRpcMessage<RoleServer>>();
+        let to_client = to_client.sink_map_err(std::io::Error::other);
+        let (notify, notifications) = mpsc::unbounded_channel();
+        let pending = Arc::new(Mutex::new(HashMap::<String, oneshot::Sender<Value>>::new()));
+
+        tokio::spawn(async move {
+            if let Ok(server) = EchoServer.serve((to_client, from_client)).await {
+                let _ = server.waiting().await;
+            }
+        });
+        let routes = pending.clone();
+        tokio::spawn(async move {
+            while let Some(message) = from_server.next().await {
+                let message = serde_json::to_value(&message).unwrap();
+                let waiting = match (message.get("method"), message.get("id")) {
+                    (None, Some(id)) => routes.lock().unwrap().remove(&id.to_string()),
+                    _ => None,
+                };
+                match waiting {
+                    Some(waiting) => {
+                        let _ = waiting.send(message);
+                    },
+                    None => {
+                        let _ = notify.send(message);
+                    },
+                }
+            }
+        });
+
+        Arc::new(Self {
+            to_server,
+            pending,
+            notifications: Mutex::new(Some(notifications)),
+        })
+    }
+}
+
+// In-process Streamable HTTP MCP server which requires a bearer token and an `X-Team` header
+#[derive(Default)]
+struct HttpServer {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    // "METHOD jsonrpc-method" of every request that was let in
+    requests: Mutex<Vec<String>>,
+    // Requests after `initialize` that came without a known session id
+    unknown_sessions: Mutex<usize>,
+}
+
+impl HttpServer {
+    async fn start() -> (Arc<Self>, String) {
+        let server = Arc::new(Self::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        let accepting = server.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server = accepting.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |request| server.clone().handle(request));
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (server, url)
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    // Forgets all sessions, like a restarted server
+    fn restart(&self) {
+        self.sessions.lock().unwrap().clear();
+    }
+
+    async fn handle(
+        self: Arc<Self>,
+        request: Request<Incoming>,
+    ) -> Result<Response<Body>, Infallible> {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        if header(AUTHORIZATION.as_str()).as_deref() != Some("Bearer secret")
+            || header("x-team").as_deref() != Some("research")
+        {
+            return Ok(status(StatusCode::UNAUTHORIZED));
+        }
+        let session_id = header(SESSION_HEADER);
+        let session = session_id
+            .as_ref()
+            .and_then(|id| self.sessions.lock().unwrap().get(id).cloned());
+
+        match request.method().clone() {
+            Method::POST => {
+                let body = request.into_body().collect().await.unwrap().to_bytes();
+                let message: Value = serde_json::from_slice(&body).unwrap();
+                let method = message["method"].as_str().map(str::to_owned);
+                self.requests
+                    .lock()
+                    .unwrap()
+                    .push(format!("POST {}", method.as_deref().unwrap_or("response")));
+
+                let (session_id, session) = if method.as_deref() == Some("initialize") {
+                    let id = uuid::Uuid::new_v4().to_string();
+                    let session = Session::start();
+                    self.sessions
+                        .lock()
+                        .unwrap()
+                        .insert(id.clone(), session.clone());
+                    (id, session)
+                } else {
+                    match (session_id, session) {
+                        (Some(id), Some(session)) => (id, session),
+                        _ => {
+                            *self.unknown_sessions.lock().unwrap() += 1;
+                            return Ok(status(StatusCode::NOT_FOUND));
+                        },
+                    }
+                };
+
+                let response = match (&method, message.get("id")) {
+                    (Some(_), Some(id)) => {
+                        let (sender, receiver) = oneshot::channel();
+                        session
+                            .pending
+                            .lock()
+                            .unwrap()
+                            .insert(id.to_string(), sender);
+                        Some(receiver)
+                    },
+                    _ => None,
+                };
+                session
+                    .to_server
+                    .unbounded_send(serde_json::from_value(message).unwrap())
+                    .unwrap();
+                let Some(response) = response else {
+                    return Ok(status(StatusCode::ACCEPTED));
+                };
+                let response = response.await.unwrap();
+
+                // Tool calls are answered with an event stream, everything else with JSON
+                let builder = Response::builder().header(SESSION_HEADER, session_id);
+                let response = if method.as_deref() == Some("tools/call") {
+                    builder
+                        .header(CONTENT_TYPE, "text/event-stream")
+                        .body(full(format!("event: message\ndata: {response}\n\n")))
+                } else {
+                    builder
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(full(response.to_string()))
+                };
+                Ok(response.unwrap())
+            },
+            Method::GET => {
+                self.requests.lock().unwrap().push("GET".to_owned());
+                let Some(session) = session else {
+                    return Ok(status(StatusCode::NOT_FOUND));
+                };
+                let Some(notifications) = session.notifications.lock().unwrap().take() else {
+                    return Ok(status(StatusCode::CONFLICT));
+                };
+                let events = futures::stream::unfold(notifications, |mut notifications| async {
+                    let message = notifications.recv().await?;
+                    let event = Frame::data(Bytes::from(format!("data: {message}\n\n")));
+                    Some((Ok(event), notifications))
+                });
+                Ok(Response::builder()
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .body(StreamBody::new(events).boxed_unsync())
+                    .unwrap())
+            },
+            Method::DELETE => {
+                self.requests.lock().unwrap().push("DELETE".to_owned());
+                if let Some(id) = session_id {
+                    self.sessions.lock().unwrap().remove(&id);
+                }
+                Ok(status(StatusCode::OK))
+            },
+            _ => Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+        }
+    }
+}
+
+fn full(body: String) -> Body {
+    Full::new(Bytes::from(body)).boxed_unsync()
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(full(String::new()))
+        .unwrap()
+}
+
+fn connector(url: &str, token: &str) -> StreamableHttpConnector {
+    StreamableHttpConnector::new(url)
+        .unwrap()
+        .bearer_auth(token)
+        .unwrap()
+        .header("X-Team", "research")
+        .unwrap()
+}
+
+async fn connect(url: &str) -> MCPClient {
+    MCPClient::builder("echo", connector(url, "secret"))
+        .reconnect(ReconnectPolicy {
+            max_attempts: Some(2),
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(5),
+        })
+        .connect()
+        .await
+        .unwrap()
+}
+
+fn echo(text: &str) -> CallToolRequestParam {
+    CallToolRequestParam {
+        name: "echo".into(),
+        arguments: serde_json::json!({ "text": text }).as_object().cloned(),
+    }
+}
+
+fn text(result: CallToolResult) -> String {
+    serde_json::to_value(&result.content).unwrap()[0]["text"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[derive(Clone)]
+struct UnusedModel;
+
+impl Model for UnusedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        unreachable!("the model is not called")
+    }
+}
+
+#[tokio::test]
+async fn test_call_tools_over_http() {
+    let (server, url) = HttpServer::start().await;
+    let client = connect(&url).await;
+
+    assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+    // Answered with an event stream
+    assert_eq!(text(client.call_tool(echo("hi")).await.unwrap()), "hi");
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[..2],
+        ["POST initialize", "POST notifications/initialized"]
+    );
+    assert!(requests.contains(&"POST tools/list".to_owned()));
+    assert!(requests.contains(&"POST tools/call".to_owned()));
+    // Every request after `initialize` carried the session id
+    assert_eq!(*server.unknown_sessions.lock().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_agent_with_http_server() {
+    let (_server, url) = HttpServer::start().await;
+
+    let result = SwarmsAgentBuilder::new_with_model(UnusedModel)
+        .try_add_streamable_http_mcp_server("echo", connector(&url, "wrong"))
+        .await;
+    assert!(matches!(result, Err(MCPError::Connection { .. })));
+
+    let agent = SwarmsAgentBuilder::new_with_model(UnusedModel)
+        .try_add_streamable_http_mcp_server("echo", connector(&url, "secret"))
+        .await
+        .unwrap()
+        .build();
+    let status = agent.mcp_server_status();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].name, "echo");
+    assert_eq!(status[0].health, MCPHealth::Connected);
+}
+
+#[tokio::test]
+async fn test_server_notifications() {
+    let (server, url) = HttpServer::start().await;
+    let client = connect(&url).await;
+    let mut updates = client.resource_updates();
+
+    client.subscribe("docs://guide").await.unwrap();
+    let uri = tokio::time::timeout(Duration::from_secs(5), updates.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(uri, "docs://guide");
+    assert!(server.requests().contains(&"GET".to_owned()));
+}
+
+#[tokio::test]
+async fn test_reconnects_after_session_expired() {
+    let (server, url) = HttpServer::start().await;
+    let client = connect(&url).await;
+
+    server.restart();
+    assert_eq!(client.list_tools().await.unwrap()[0].name, "echo");
+    assert_eq!(*server.unknown_sessions.lock().unwrap(), 1);
+    assert_eq!(
+        text(client.call_tool(echo("again")).await.unwrap()),
+        "again"
+    );
+
+    let status = client.status();
+    assert_eq!(status.health, MCPHealth::Connected);
+    assert_eq!(status.reconnects, 1);
+}
+
+#[tokio::test]
+async fn test_session_closed_on_drop() {
+    let (server, url) = HttpServer::start().await;
+    let client = connect(&url).await;
+    assert_eq!(server.sessions.lock().unwrap().len(), 1);
+
+    drop(client);
+    for _ in 0..100 {
+        if server.sessions.lock().unwrap().is_empty() {
+            assert!(server.requests().contains(&"DELETE".to_owned()));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the session wasn't closed");
+}
+
+#[tokio::test]
+async fn test_invalid_headers() {
+    let connector = StreamableHttpConnector::new("http://localhost/mcp").unwrap();
+    assert!(matches!(
+        connector.header("X Team", "research"),
+        Err(MCPError::InvalidHeader(_))
+    ));
+    assert!(matches!(
+        StreamableHttpConnector::new("http://localhost/mcp")
+            .unwrap()
+            .bearer_auth("line\nbreak"),
+        Err(MCPError::InvalidHeader(_))
+    ));
+}
@@ -0,0 +1,366 @@
+//! Tests for MCP resources and prompts
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use rmcp::model::{
+    AnnotateAble, GetPromptRequestParam, GetPromptResult, ListPromptsResult, ListResourcesResult,
+    PaginatedRequestParam, Prompt, PromptArgument, PromptMessage, PromptMessageRole, RawResource,
+    ReadResourceRequestParam, ReadResourceResult, ResourceContents,
+    ResourceUpdatedNotificationParam, SubscribeRequestParam,
+};
+use rmcp::service::{RequestContext, RunningService};
+use rmcp::{Error as McpError, Peer, RoleServer, ServerHandler, ServiceExt};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::prompts::template::PromptContext;
+use swarms_rs::structs::agent::Agent;
+use swarms_rs::structs::mcp::{
+    MCPClient, MCPClientHandler, MCPConnector, MCPError, MCPResourceContext, MCPService,
+    ReconnectPolicy,
+};
+use swarms_rs::structs::tool::{MCPResourceTool, ToolDyn};
+
+const GUIDE: &str = "docs://guide";
+
+// MCP server with a `guide` resource that can change, and a `reviewer` prompt
+#[derive(Clone, Default)]
+struct DocsServer {
+    guide: Arc<Mutex<String>>,
+    // Client that subscribed to the guide
+    subscriber: Arc<Mutex<Option<Peer<RoleServer>>>>,
+}
+
+impl DocsServer {
+    fn new(guide: &str) -> Self {
+        let server = Self::default();
+        *server.guide.lock().unwrap() = guide.to_owned();
+        server
+    }
+
+    async fn update_guide(&self, guide: &str) {
+        *self.guide.lock().unwrap() = guide.to_owned();
+        let subscriber = self.subscriber.lock().unwrap().clone();
+        // rmcp 0.1.5 reports notifications that were sent as failed, so the result is ignored
+        let _ = subscriber
+            .unwrap()
+            .notify_resource_updated(ResourceUpdatedNotificationParam { uri: GUIDE.into() })
+            .await;
+    }
+}
+
+impl ServerHandler for DocsServer {
+    async fn list_resources(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let mut guide = RawResource::new(GUIDE, "guide");
+        guide.description = Some("How to use the product".into());
+        Ok(ListResourcesResult {
+            next_cursor: None,
+            resources: vec![guide.no_annotation()],
+        })
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        if request.uri != GUIDE {
+            return Err(McpError::resource_not_found("no such resource", None));
+        }
+        let guide = self.guide.lock().unwrap().clone();
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::text(guide, GUIDE)],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        _request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        *self.subscriber.lock().unwrap() = Some(context.peer);
+        Ok(())
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        let language = PromptArgument {
+            name: "language".into(),
+            description: None,
+            required: Some(true),
+        };
+        Ok(ListPromptsResult {
+            next_cursor: None,
+            prompts: vec![Prompt::new(
+                "reviewer",
+                Some("Code reviewer"),
+                Some(vec![language]),
+            )],
+        })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        let language = request
+            .arguments
+            .and_then(|args| args.get("language").cloned())
+            .and_then(|language| language.as_str().map(str::to_owned))
+            .ok_or_else(|| McpError::invalid_params("missing language", None))?;
+        Ok(GetPromptResult {
+            description: None,
+            messages: vec![
+                PromptMessage::new_text(
+                    PromptMessageRole::User,
+                    format!("You review {language} code."),
+                ),
+                PromptMessage::new_text(PromptMessageRole::User, "Be concise."),
+            ],
+        })
+    }
+}
+
+// Connects to an in-process `DocsServer`
+#[derive(Clone)]
+struct DocsConnector {
+    server: DocsServer,
+    running: Arc<Mutex<Vec<RunningService<RoleServer, DocsServer>>>>,
+}
+
+impl DocsConnector {
+    fn new(server: DocsServer) -> Self {
+        Self {
+            server,
+            running: Arc::default(),
+        }
+    }
+
+    async fn crash(&self) {
+        let server = self.running.lock().unwrap().pop().unwrap();
+        server.cancel().await.unwrap();
+    }
+}
+
+impl MCPConnector for DocsConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            let (client_io, server_io) = tokio::io::duplex(4096);
+            let server = tokio::spawn(self.server.clone().serve(server_io));
+            let client = handler
+                .into_dyn()
+                .serve(client_io)
+                .await
+                .map_err(|e| e.to_string())?;
+            let server = server.await.unwrap().map_err(|e| e.to_string())?;
+            self.running.lock().unwrap().push(server);
+            Ok(client)
+        })
+    }
+}
+
+async fn connect(connector: DocsConnector) -> MCPClient {
+    MCPClient::builder("docs", connector)
+        .reconnect(ReconnectPolicy {
+            max_attempts: Some(2),
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(5),
+        })
+        .connect()
+        .await
+        .unwrap()
+}
+
+// Waits for the text of `context` to become `expected`
+async fn wait_for_text(context: &MCPResourceContext, expected: &str) {
+    for _ in 0..100 {
+        if context.text() == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(context.text(), expected);
+}
+
+// Mock model that records the system prompts it was sent
+#[derive(Clone, Default)]
+struct RecordingModel {
+    system_prompts: Arc<Mutex<Vec<String>>>,
+}
+
+impl Model for RecordingModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.system_prompts
+            .lock()
+            .unwrap()
+            .push(request.system_prompt.unwrap_or_default());
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("Done")],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_list_and_read_resources() {
+    let client = connect(DocsConnector::new(DocsServer::new("Press start."))).await;
+
+    let resources = client.list_resources().await.unwrap();
+    assert_eq!(resources.len(), 1);
+    assert_eq!(resources[0].raw.uri, GUIDE);
+    assert_eq!(
+        client.read_resource_text(GUIDE).await.unwrap(),
+        "Press start."
+    );
+    assert!(client.read_resource("docs://missing").await.is_err());
+}
+
+#[tokio::test]
+async fn test_resource_tools() {
+    let client = connect(DocsConnector::new(DocsServer::new("Press start."))).await;
+    let list = MCPResourceTool::list(client.clone());
+    let read = MCPResourceTool::read(client);
+
+    assert_eq!(ToolDyn::name(&list), "docs_list_resources");
+    assert_eq!(ToolDyn::name(&read), "docs_read_resource");
+    assert_eq!(read.definition().parameters["required"][0], "uri");
+
+    let listed: serde_json::Value =
+        serde_json::from_str(&list.call("{}".to_owned()).await.unwrap()).unwrap();
+    assert_eq!(listed[0]["uri"], GUIDE);
+    assert_eq!(listed[0]["description"], "How to use the product");
+    assert_eq!(
+        read.call(format!(r#"{{"uri": "{GUIDE}"}}"#)).await.unwrap(),
+        "Press start."
+    );
+    assert!(
+        read.call(r#"{"uri": "docs://missing"}"#.to_owned())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_resource_context_follows_updates() {
+    let server = DocsServer::new("Version 1");
+    let connector = DocsConnector::new(server.clone());
+    let client = connect(connector.clone()).await;
+    let mut updates = client.resource_updates();
+
+    let context = client.resource_context(GUIDE).await.unwrap();
+    assert_eq!(context.server(), "docs");
+    assert_eq!(context.text(), "Version 1");
+
+    server.update_guide("Version 2").await;
+    assert_eq!(updates.recv().await.unwrap(), GUIDE);
+    wait_for_text(&context, "Version 2").await;
+
+    // Updates missed while disconnected are caught up on after reconnecting
+    connector.crash().await;
+    *server.guide.lock().unwrap() = "Version 3".to_owned();
+    client.check_health().await;
+    wait_for_text(&context, "Version 3").await;
+
+    // The subscription was renewed on the new connection
+    server.update_guide("Version 4").await;
+    wait_for_text(&context, "Version 4").await;
+}
+
+#[tokio::test]
+async fn test_resource_context_in_system_prompt() {
+    let server = DocsServer::new("Press start.");
+    let client = connect(DocsConnector::new(server.clone())).await;
+    let model = RecordingModel::default();
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .system_prompt("You are a support agent.")
+        .add_mcp_resource_context(&client, GUIDE)
+        .await
+        .unwrap()
+        .add_mcp_resource_tools(&client)
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+    // The client is reported once
+    assert_eq!(agent.mcp_server_status().len(), 1);
+
+    agent.run("How do I start?".to_owned()).await.unwrap();
+    let prompt = model.system_prompts.lock().unwrap()[0].clone();
+    assert!(prompt.starts_with("You are a support agent."));
+    assert!(prompt.contains("<resource server=\"docs\" uri=\"docs://guide\">\nPress start.\n"));
+
+    // Each run uses the latest text of the resource
+    server.update_guide("Hold the button.").await;
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        agent.run("How do I start now?".to_owned()).await.unwrap();
+        if model
+            .system_prompts
+            .lock()
+            .unwrap()
+            .last()
+            .unwrap()
+            .contains("Hold the button.")
+        {
+            return;
+        }
+    }
+    panic!("the system prompt wasn't updated");
+}
+
+#[tokio::test]
+async fn test_prompt_template() {
+    let client = connect(DocsConnector::new(DocsServer::default())).await;
+
+    let prompts = client.list_prompts().await.unwrap();
+    assert_eq!(prompts[0].name, "reviewer");
+
+    let template = client.prompt_template("reviewer").await.unwrap();
+    assert_eq!(
+        template.source(),
+        "You review {{language}} code.\n\nBe concise."
+    );
+    assert_eq!(
+        template
+            .render(&PromptContext::new().with("language", "Rust"))
+            .unwrap(),
+        "You review Rust code.\n\nBe concise."
+    );
+    assert!(matches!(
+        client.prompt_template("writer").await,
+        Err(MCPError::UnknownPrompt { name, .. }) if name == "writer"
+    ));
+
+    // The template works as the system prompt of an agent
+    let model = RecordingModel::default();
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .system_prompt_template(template)
+        .prompt_variable("language", "Go")
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+    agent.run("Review this".to_owned()).await.unwrap();
+    assert_eq!(
+        model.system_prompts.lock().unwrap()[0],
+        "You review Go code.\n\nBe concise."
+    );
+}
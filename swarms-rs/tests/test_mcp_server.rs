@@ -0,0 +1,572 @@
+//! Tests for serving agents and workflows as an MCP server
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use rmcp::model::CallToolRequestParam;
+use serde_json::{Value, json};
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::graph_workflow::{DAGWorkflow, Flow};
+use swarms_rs::structs::mcp::{MCPClient, MCPServer, StreamableHttpConnector};
+use swarms_rs::structs::sequential_workflow::SequentialWorkflow;
+use tokio::io::{
+    AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, Lines, ReadHalf, WriteHalf,
+};
+
+#[derive(Clone)]
+enum Behavior {
+    Answer,
+    Fail,
+    // Runs until canceled
+    Hang,
+}
+
+// Agent answering "<name> done"
+#[derive(Clone)]
+struct ScriptedAgent {
+    name: String,
+    behavior: Behavior,
+    canceled: Arc<AtomicUsize>,
+}
+
+impl ScriptedAgent {
+    fn new(name: &str, behavior: Behavior) -> Self {
+        Self {
+            name: name.to_owned(),
+            behavior,
+            canceled: Arc::default(),
+        }
+    }
+}
+
+impl Agent for ScriptedAgent {
+    fn run(&self, _task: String) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move {
+            match self.behavior {
+                Behavior::Answer => Ok(format!("{} done", self.name)),
+                Behavior::Fail => Err(AgentError::InvalidConfig("no model".to_owned())),
+                Behavior::Hang => futures::future::pending().await,
+            }
+        })
+    }
+
+    fn run_with_cancellation(
+        &self,
+        task: String,
+        cancel: swarms_rs::structs::agent::CancellationToken,
+    ) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    self.canceled.fetch_add(1, Ordering::SeqCst);
+                    Err(AgentError::Canceled)
+                }
+                result = self.run(task) => result,
+            }
+        })
+    }
+
+    fn run_multiple_tasks(
+        &mut self,
+        _tasks: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<String>, AgentError>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    fn plan(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query_long_term_memory(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn save_task_state(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_response_complete(&self, _response: String) -> bool {
+        true
+    }
+
+    fn id(&self) -> String {
+        self.name.to_lowercase()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn description(&self) -> String {
+        format!("The {} agent", self.name)
+    }
+
+    fn clone_box(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+}
+
+// Client exchanging raw JSON-RPC messages with a server served over a duplex stream
+struct RawClient {
+    writer: WriteHalf<DuplexStream>,
+    lines: Lines<BufReader<ReadHalf<DuplexStream>>>,
+}
+
+impl RawClient {
+    async fn start(server: MCPServer) -> Self {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (server_reader, server_writer) = tokio::io::split(server_io);
+        tokio::spawn(server.serve(server_reader, server_writer));
+        let (reader, writer) = tokio::io::split(client_io);
+        let mut client = Self {
+            writer,
+            lines: BufReader::new(reader).lines(),
+        };
+
+        client
+            .send(json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {},
+                    "clientInfo": { "name": "test", "version": "1.0" }
+                }
+            }))
+            .await;
+        let initialized = client.receive().await;
+        assert_eq!(initialized["result"]["serverInfo"]["name"], "research");
+        client
+            .send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        client
+    }
+
+    async fn send(&mut self, message: Value) {
+        let line = format!("{message}\n");
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn receive(&mut self) -> Value {
+        let line = tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn call(&mut self, id: u32, tool: &str, token: Option<&str>) {
+        let mut params = json!({ "name": tool, "arguments": { "task": "Write a report" } });
+        if let Some(token) = token {
+            params["_meta"] = json!({ "progressToken": token });
+        }
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": params }))
+            .await;
+    }
+
+    // The response to the request `id`, with the progress notifications received before it
+    async fn response(&mut self, id: u32) -> (Value, Vec<(u64, u64)>) {
+        let mut progress = Vec::new();
+        loop {
+            let message = self.receive().await;
+            if message["id"] == id {
+                return (message, progress);
+            }
+            if message["method"] == "notifications/progress" {
+                let params = &message["params"];
+                progress.push((
+                    params["progress"].as_u64().unwrap(),
+                    params["total"].as_u64().unwrap(),
+                ));
+            }
+        }
+    }
+}
+
+fn pipeline(metadata_dir: &tempfile::TempDir) -> SequentialWorkflow {
+    SequentialWorkflow::builder()
+        .name("Report")
+        .description("Researches a topic and writes a report")
+        .metadata_output_dir(metadata_dir.path().to_str().unwrap())
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .add_agent(Box::new(ScriptedAgent::new("Writer", Behavior::Answer)))
+        .build()
+}
+
+fn output(response: &Value) -> &str {
+    response["result"]["content"][0]["text"].as_str().unwrap()
+}
+
+#[tokio::test]
+async fn test_serve_tools_with_progress() {
+    let metadata_dir = tempfile::tempdir().unwrap();
+    let server = MCPServer::new("research")
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .add_sequential_workflow(pipeline(&metadata_dir));
+    assert_eq!(server.tool_names(), ["report", "researcher"]);
+    let mut client = RawClient::start(server).await;
+
+    client
+        .send(json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+        .await;
+    let tools = client.receive().await["result"]["tools"].clone();
+    assert_eq!(tools[0]["name"], "report");
+    assert_eq!(
+        tools[0]["description"],
+        "Researches a topic and writes a report"
+    );
+    assert_eq!(tools[1]["description"], "The Researcher agent");
+    assert_eq!(tools[1]["inputSchema"]["required"], json!(["task"]));
+
+    client.call(2, "report", Some("report-1")).await;
+    let (response, progress) = client.response(2).await;
+    assert_eq!(output(&response), "Writer done");
+    assert_eq!(response["result"]["isError"], false);
+    assert_eq!(progress, [(0, 2), (1, 2), (2, 2)]);
+
+    // Without a progress token, there are no notifications
+    client.call(3, "researcher", None).await;
+    let (response, progress) = client.response(3).await;
+    assert_eq!(output(&response), "Researcher done");
+    assert!(progress.is_empty());
+}
+
+#[tokio::test]
+async fn test_failures_and_cancellation() {
+    let hanging = ScriptedAgent::new("Sleeper", Behavior::Hang);
+    let server = MCPServer::new("research")
+        .add_agent(Box::new(ScriptedAgent::new("Broken", Behavior::Fail)))
+        .add_agent(Box::new(hanging.clone()));
+    let mut client = RawClient::start(server).await;
+
+    // A failed run is a tool error
+    client.call(1, "broken", None).await;
+    let (response, _) = client.response(1).await;
+    assert_eq!(response["result"]["isError"], true);
+    assert!(output(&response).contains("no model"));
+
+    client.call(2, "missing", None).await;
+    let (response, _) = client.response(2).await;
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("missing")
+    );
+
+    client.call(3, "sleeper", Some("sleep")).await;
+    // The run started
+    assert_eq!(client.receive().await["params"]["progress"], 0);
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 3 }
+        }))
+        .await;
+    for _ in 0..100 {
+        if hanging.canceled.load(Ordering::SeqCst) == 1 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the run wasn't canceled");
+}
+
+#[tokio::test]
+async fn test_cancel_workflow() {
+    let metadata_dir = tempfile::tempdir().unwrap();
+    let hanging = ScriptedAgent::new("Sleeper", Behavior::Hang);
+    let workflow = SequentialWorkflow::builder()
+        .name("Report")
+        .metadata_output_dir(metadata_dir.path().to_str().unwrap())
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .add_agent(Box::new(hanging.clone()))
+        .build();
+    let mut client =
+        RawClient::start(MCPServer::new("research").add_sequential_workflow(workflow)).await;
+
+    client.call(1, "report", Some("report-1")).await;
+    // The second agent started
+    assert_eq!(client.receive().await["params"]["progress"], 0);
+    assert_eq!(client.receive().await["params"]["progress"], 1);
+    client
+        .send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 1 }
+        }))
+        .await;
+    for _ in 0..100 {
+        if hanging.canceled.load(Ordering::SeqCst) == 1 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the workflow wasn't canceled");
+}
+
+#[tokio::test]
+async fn test_serve_http() {
+    let mut workflow = DAGWorkflow::new("Review", "Reviews a change");
+    workflow.register_agent(Box::new(ScriptedAgent::new("Linter", Behavior::Answer)));
+    workflow.register_agent(Box::new(ScriptedAgent::new("Reviewer", Behavior::Answer)));
+    workflow
+        .connect_agents("Linter", "Reviewer", Flow::default())
+        .unwrap();
+    let server = MCPServer::new("research")
+        .instructions("Call review with the diff")
+        .add_dag_workflow(workflow, "Linter")
+        .serve_http("127.0.0.1:0")
+        .await
+        .unwrap();
+
+    let client = MCPClient::builder(
+        "research",
+        StreamableHttpConnector::new(server.url()).unwrap(),
+    )
+    .connect()
+    .await
+    .unwrap();
+    let tools = client.list_tools().await.unwrap();
+    assert_eq!(tools[0].name, "review");
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: "review".into(),
+            arguments: json!({ "task": "Review this diff" }).as_object().cloned(),
+        })
+        .await
+        .unwrap();
+    let outputs: Value = serde_json::from_str(
+        serde_json::to_value(&result.content).unwrap()[0]["text"]
+            .as_str()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        outputs,
+        json!({ "Linter": "Linter done", "Reviewer": "Reviewer done" })
+    );
+
+    server.shutdown();
+    assert!(
+        MCPClient::builder(
+            "research",
+            StreamableHttpConnector::new(server.url()).unwrap(),
+        )
+        .connect_timeout(Duration::from_secs(1))
+        .connect()
+        .await
+        .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_http_progress() {
+    let server = MCPServer::new("research")
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .serve_http("127.0.0.1:0")
+        .await
+        .unwrap();
+    let http = reqwest::Client::new();
+    let post = |body: Value, session: Option<&str>| {
+        let mut request = http
+            .post(server.url())
+            .header("Accept", "application/json, text/event-stream")
+            .json(&body);
+        if let Some(session) = session {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        request.send()
+    };
+
+    let initialized = post(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" }
+            }
+        }),
+        None,
+    )
+    .await
+    .unwrap();
+    let session = initialized.headers()["mcp-session-id"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(initialized.text().await.unwrap().contains("serverInfo"));
+    let accepted = post(
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        Some(&session),
+    )
+    .await
+    .unwrap();
+    assert_eq!(accepted.status(), 202);
+
+    let events = post(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "researcher",
+                "arguments": { "task": "Find sources" },
+                "_meta": { "progressToken": 7 }
+            }
+        }),
+        Some(&session),
+    )
+    .await
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    let messages = events
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str::<Value>(data).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(
+        messages[0]["params"],
+        json!({ "progressToken": 7, "progress": 0, "total": 1 })
+    );
+    assert_eq!(messages[1]["params"]["progress"], 1);
+    assert_eq!(output(&messages[2]), "Researcher done");
+
+    // Unknown sessions are rejected
+    let rejected = post(
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        Some("unknown"),
+    )
+    .await
+    .unwrap();
+    assert_eq!(rejected.status(), 404);
+}
+
+// Starts a session with `headers`, returning the status and the session id
+async fn initialize(url: &str, headers: &[(&str, &str)]) -> (u16, Option<String>) {
+    let mut request = reqwest::Client::new()
+        .post(url)
+        .header("Accept", "application/json, text/event-stream")
+        .json(&json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0" }
+            }
+        }));
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.send().await.unwrap();
+    let session = response
+        .headers()
+        .get("mcp-session-id")
+        .map(|id| id.to_str().unwrap().to_owned());
+    (response.status().as_u16(), session)
+}
+
+async fn list_tools(url: &str, session: &str) -> u16 {
+    reqwest::Client::new()
+        .post(url)
+        .header("Accept", "application/json, text/event-stream")
+        .header("Mcp-Session-Id", session)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn test_http_sessions_expire() {
+    let server = MCPServer::new("research")
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .http_session_ttl(Duration::from_millis(200))
+        .max_http_sessions(2)
+        .serve_http("127.0.0.1:0")
+        .await
+        .unwrap();
+    let url = server.url();
+
+    // Starting a third session closes the session idle the longest
+    let (_, first) = initialize(&url, &[]).await;
+    let (_, second) = initialize(&url, &[]).await;
+    assert_eq!(list_tools(&url, &second.clone().unwrap()).await, 200);
+    let (_, third) = initialize(&url, &[]).await;
+    assert_eq!(list_tools(&url, &first.unwrap()).await, 404);
+    assert_eq!(list_tools(&url, &second.unwrap()).await, 200);
+
+    // Idle sessions are closed
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(list_tools(&url, &third.unwrap()).await, 404);
+}
+
+#[tokio::test]
+async fn test_http_origin_and_token() {
+    let server = MCPServer::new("research")
+        .add_agent(Box::new(ScriptedAgent::new("Researcher", Behavior::Answer)))
+        .allowed_origins(["https://app.example.com"])
+        .bearer_token("secret")
+        .serve_http("127.0.0.1:0")
+        .await
+        .unwrap();
+    let url = server.url();
+    let token = ("Authorization", "Bearer secret");
+
+    assert_eq!(initialize(&url, &[]).await.0, 401);
+    assert_eq!(
+        initialize(&url, &[("Authorization", "Bearer wrong")])
+            .await
+            .0,
+        401
+    );
+    let (status, session) = initialize(&url, &[token]).await;
+    assert_eq!(status, 200);
+    assert!(session.is_some());
+
+    // Pages of other sites are rejected, whatever their token
+    for origin in ["https://evil.example.com", "null"] {
+        assert_eq!(initialize(&url, &[token, ("Origin", origin)]).await.0, 403);
+    }
+    for origin in ["https://app.example.com", "http://localhost:3000"] {
+        assert_eq!(initialize(&url, &[token, ("Origin", origin)]).await.0, 200);
+    }
+
+    // Oversized requests are rejected
+    let oversized = reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", "Bearer secret")
+        .body(vec![b' '; 5 * 1024 * 1024])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(oversized.status(), 413);
+
+    // The client sends the token
+    let client = MCPClient::builder(
+        "research",
+        StreamableHttpConnector::new(&url)
+            .unwrap()
+            .bearer_auth("secret")
+            .unwrap(),
+    )
+    .connect()
+    .await
+    .unwrap();
+    assert_eq!(client.list_tools().await.unwrap()[0].name, "researcher");
+}
@@ -0,0 +1,170 @@
+//! Tests for long-term memory and its integration into the agent loop
+
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::Agent;
+use swarms_rs::structs::memory::{InMemoryVectorStore, Memory, MemoryDocument, MetadataFilter};
+
+// Mock model that always answers with the same text
+#[derive(Clone)]
+struct MockModel;
+
+impl Model for MockModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        Box::pin(async {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("Working on it")],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+async fn create_store() -> InMemoryVectorStore {
+    let store = InMemoryVectorStore::new();
+    store
+        .add(
+            MemoryDocument::new("The capital of France is Paris")
+                .with_metadata("topic", "geography"),
+        )
+        .await
+        .unwrap();
+    store
+        .add(
+            MemoryDocument::new("Rust is a systems programming language")
+                .with_metadata("topic", "programming"),
+        )
+        .await
+        .unwrap();
+    store
+        .add(
+            MemoryDocument::new("Tokio is an async runtime for Rust")
+                .with_metadata("topic", "programming"),
+        )
+        .await
+        .unwrap();
+    store
+}
+
+#[tokio::test]
+async fn test_query_ranks_relevant_documents_first() {
+    let store = create_store().await;
+
+    let records = store
+        .query("What is the capital of France?".to_owned(), 1, None)
+        .await
+        .unwrap();
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].content, "The capital of France is Paris");
+    assert!(records[0].score > 0.0);
+}
+
+#[tokio::test]
+async fn test_query_with_metadata_filter() {
+    let store = create_store().await;
+
+    let records = store
+        .query(
+            "Rust".to_owned(),
+            10,
+            Some(MetadataFilter::new().eq("topic", "programming")),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert!(
+        records
+            .iter()
+            .all(|record| record.metadata["topic"] == "programming")
+    );
+}
+
+#[tokio::test]
+async fn test_delete_document() {
+    let store = InMemoryVectorStore::new();
+    let id = store.add(MemoryDocument::new("temporary")).await.unwrap();
+    assert_eq!(store.len(), 1);
+
+    assert!(store.delete(id.clone()).await.unwrap());
+    assert!(!store.delete(id).await.unwrap());
+    assert!(store.is_empty());
+}
+
+#[tokio::test]
+async fn test_agent_retrieves_long_term_memory_before_first_loop() {
+    let store = Arc::new(create_store().await);
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel)
+        .disable_task_complete_tool()
+        .long_term_memory(store)
+        .long_term_memory_top_k(1)
+        .build();
+
+    let result = agent
+        .run("What is the capital of France?".to_owned())
+        .await
+        .unwrap();
+
+    assert!(result.contains("LongTermMemory"));
+    assert!(result.contains("The capital of France is Paris"));
+    assert!(!result.contains("Tokio is an async runtime"));
+}
+
+#[tokio::test]
+async fn test_agent_retrieves_long_term_memory_every_loop() {
+    let store = Arc::new(create_store().await);
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel)
+        .disable_task_complete_tool()
+        .long_term_memory(store)
+        .enable_rag_every_loop()
+        .max_loops(3)
+        .build();
+
+    let result = agent.run("Tell me about Rust".to_owned()).await.unwrap();
+
+    // Later loops retrieve the same documents, which are only added once
+    assert_eq!(result.matches("LongTermMemory").count(), 1);
+    assert_eq!(result.matches("Tokio is an async runtime").count(), 1);
+}
+
+#[tokio::test]
+async fn test_agent_filters_long_term_memory() {
+    let store = Arc::new(create_store().await);
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel)
+        .disable_task_complete_tool()
+        .long_term_memory(store)
+        .long_term_memory_filter(MetadataFilter::new().eq("topic", "geography"))
+        .build();
+
+    let result = agent.run("Tell me about Rust".to_owned()).await.unwrap();
+
+    assert!(result.contains("The capital of France is Paris"));
+    assert!(!result.contains("Rust is a systems programming language"));
+}
+
+#[tokio::test]
+async fn test_agent_without_long_term_memory() {
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel)
+        .disable_task_complete_tool()
+        .build();
+
+    agent
+        .query_long_term_memory("task".to_owned())
+        .await
+        .unwrap();
+    let result = agent.run("task".to_owned()).await.unwrap();
+    assert!(!result.contains("LongTermMemory"));
+}
@@ -0,0 +1,242 @@
+//! Tests for model capability descriptors and pre-flight validation
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    capabilities::{CapabilityError, ContentKind, ModelCapabilities},
+    completion::{AssistantContent, Message, UserContent},
+    request::{CompletionRequest, CompletionResponse, ToolDefinition},
+};
+use swarms_rs::structs::agent::AgentError;
+use swarms_rs::structs::tool::Tool;
+
+// Mock model that answers with a fixed text and reports the given capabilities
+#[derive(Clone)]
+struct MockModel {
+    capabilities: ModelCapabilities,
+}
+
+impl MockModel {
+    fn new(capabilities: ModelCapabilities) -> Self {
+        Self { capabilities }
+    }
+}
+
+impl Model for MockModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        _request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        Box::pin(async {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("ok")],
+                raw_response: (),
+            })
+        })
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.capabilities.clone()
+    }
+}
+
+fn image_message() -> Message {
+    Message::User {
+        content: vec![
+            UserContent::text("What is in this image?"),
+            UserContent::image("aGVsbG8=", None, None, None),
+        ],
+    }
+}
+
+#[test]
+fn test_default_capabilities_are_permissive() {
+    let capabilities = ModelCapabilities::default();
+    assert!(capabilities.tools);
+    assert!(capabilities.vision);
+    assert!(capabilities.documents);
+    assert!(capabilities.audio);
+    assert!(capabilities.max_context_tokens.is_none());
+    assert!(capabilities.max_output_tokens.is_none());
+}
+
+#[test]
+fn test_validate_messages_rejects_unsupported_content() {
+    let capabilities = ModelCapabilities::text_only();
+    let messages = [Message::user("hello"), image_message()];
+
+    let result = capabilities.validate_messages(&messages);
+    assert_eq!(
+        result,
+        Err(CapabilityError::ContentNotSupported(ContentKind::Image))
+    );
+
+    let vision = ModelCapabilities {
+        vision: true,
+        ..ModelCapabilities::text_only()
+    };
+    assert!(vision.validate_messages(&messages).is_ok());
+}
+
+#[test]
+fn test_validate_max_tokens() {
+    let capabilities = ModelCapabilities {
+        max_output_tokens: Some(4096),
+        ..Default::default()
+    };
+    assert!(capabilities.validate_max_tokens(4096).is_ok());
+    assert_eq!(
+        capabilities.validate_max_tokens(8192),
+        Err(CapabilityError::MaxTokensExceeded {
+            requested: 8192,
+            limit: 4096
+        })
+    );
+}
+
+#[test]
+fn test_validate_request_context_window() {
+    let capabilities = ModelCapabilities {
+        max_context_tokens: Some(10),
+        ..Default::default()
+    };
+    let request = CompletionRequest {
+        prompt: Message::user("a".repeat(400)),
+        system_prompt: None,
+        chat_history: vec![],
+        tools: vec![],
+        temperature: None,
+        max_tokens: None,
+    };
+
+    assert!(matches!(
+        capabilities.validate_request(&request),
+        Err(CapabilityError::ContextWindowExceeded { limit: 10, .. })
+    ));
+}
+
+#[test]
+fn test_try_build_rejects_tools_on_text_only_model() {
+    let result = SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only()))
+        .try_build();
+
+    assert!(matches!(
+        result,
+        Err(AgentError::CapabilityError(
+            CapabilityError::ToolsNotSupported(1)
+        ))
+    ));
+}
+
+#[derive(Deserialize)]
+struct NoArgs {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("search error")]
+struct SearchError;
+
+// Tool the text-only model can't call
+struct SearchTool;
+
+impl Tool for SearchTool {
+    type Error = SearchError;
+    type Args = NoArgs;
+    type Output = String;
+
+    const NAME: &'static str = "search";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Search the web".to_owned(),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok("results".to_owned())
+    }
+}
+
+#[test]
+#[should_panic(expected = "Failed to build SwarmsAgent")]
+fn test_build_rejects_added_tools_on_text_only_model() {
+    SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only()))
+        .add_tool(SearchTool)
+        .build();
+}
+
+#[tokio::test]
+async fn test_build_drops_unsupported_tools_when_opted_in() {
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only()))
+        .add_tool(SearchTool)
+        .drop_unsupported_tools()
+        .build();
+    assert!(agent.chat("Hello", vec![]).await.is_ok());
+
+    // try_build drops them as well
+    let result = SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only()))
+        .add_tool(SearchTool)
+        .drop_unsupported_tools()
+        .try_build();
+    assert!(result.is_ok());
+}
+
+#[test]
+fn test_try_build_rejects_max_tokens_over_limit() {
+    let model = MockModel::new(ModelCapabilities {
+        max_output_tokens: Some(1024),
+        ..Default::default()
+    });
+
+    let result = SwarmsAgentBuilder::new_with_model(model)
+        .max_tokens(2048)
+        .try_build();
+
+    assert!(matches!(
+        result,
+        Err(AgentError::CapabilityError(
+            CapabilityError::MaxTokensExceeded { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_build_fits_configuration_to_model() {
+    // The default task evaluator tool is left out
+    let agent =
+        SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only())).build();
+    assert!(agent.chat("Hello", vec![]).await.is_ok());
+
+    // The default max_tokens is lowered to the limit
+    let model = MockModel::new(ModelCapabilities {
+        max_output_tokens: Some(1024),
+        ..Default::default()
+    });
+    let agent = SwarmsAgentBuilder::new_with_model(model).build();
+    assert!(agent.chat("Hello", vec![]).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_chat_rejects_unsupported_content() {
+    let agent = SwarmsAgentBuilder::new_with_model(MockModel::new(ModelCapabilities::text_only()))
+        .disable_task_complete_tool()
+        .build();
+
+    let result = agent
+        .chat("Describe the image", vec![image_message()])
+        .await;
+    assert!(matches!(
+        result,
+        Err(AgentError::CapabilityError(
+            CapabilityError::ContentNotSupported(ContentKind::Image)
+        ))
+    ));
+
+    let result = agent.chat("Hello", vec![]).await;
+    assert!(result.is_ok());
+}
@@ -0,0 +1,154 @@
+//! Tests for output processors
+
+mod common;
+
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::output_processor::{
+    ExtractFencedBlock, LlmOutputCleaner, MaxLength, OUTPUT_CLEANER_ROLE, OutputPipeline,
+    OutputProcessor, OutputProcessorError, RegexReplace, StripTimestamps,
+};
+use swarms_rs::structs::agent::{Agent, CancellationToken};
+use swarms_rs::structs::conversation::Role;
+
+use common::ScriptedModel;
+
+async fn process(processor: impl OutputProcessor, output: &str) -> String {
+    processor.process(output.to_owned()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_strip_timestamps() {
+    let output = "Agent(Assistant): Timestamp(millis): 1700000000000 \nHello\n\
+        User(User): Timestamp(millis): 1700000000001 \nHi\n";
+
+    assert_eq!(
+        process(StripTimestamps, output).await,
+        "Agent(Assistant): Hello\nUser(User): Hi\n"
+    );
+}
+
+#[tokio::test]
+async fn test_extract_fenced_block() {
+    let output = "Some code:\n```rust\nfn main() {}\n```\nand data:\n```json\n{\"a\": 1}\n```";
+
+    assert_eq!(
+        process(ExtractFencedBlock::new(), output).await,
+        "fn main() {}"
+    );
+    assert_eq!(
+        process(ExtractFencedBlock::new().language("JSON"), output).await,
+        "{\"a\": 1}"
+    );
+    // Output without a block is left unchanged
+    assert_eq!(
+        process(ExtractFencedBlock::new(), "plain text").await,
+        "plain text"
+    );
+}
+
+#[tokio::test]
+async fn test_required_fenced_block() {
+    let result = ExtractFencedBlock::new()
+        .language("json")
+        .required()
+        .process("```rust\nfn main() {}\n```".to_owned())
+        .await;
+
+    assert!(matches!(result, Err(OutputProcessorError::NoFencedBlock)));
+}
+
+#[tokio::test]
+async fn test_regex_replace() {
+    let processor = RegexReplace::new(r"(\d{3})-(\d{4})", "$1-XXXX").unwrap();
+
+    assert_eq!(
+        process(processor, "Call 555-1234 or 555-9876").await,
+        "Call 555-XXXX or 555-XXXX"
+    );
+    assert!(RegexReplace::new("(", "").is_err());
+}
+
+#[tokio::test]
+async fn test_max_length() {
+    assert_eq!(process(MaxLength::new(5), "short").await, "short");
+    assert_eq!(
+        process(MaxLength::new(8), "a long answer").await,
+        "a lon..."
+    );
+    assert_eq!(
+        process(MaxLength::new(3).ellipsis("…"), "héllo").await,
+        "hé…"
+    );
+}
+
+#[tokio::test]
+async fn test_llm_output_cleaner() {
+    let model = ScriptedModel::texts(&["The answer is 42."]);
+    let cleaner = LlmOutputCleaner::new(model.clone()).instructions("Clean it");
+
+    let output = process(cleaner, "Sure! So, the answer is 42. Hope this helps!").await;
+
+    assert_eq!(output, "The answer is 42.");
+    let requests = model.requests.lock().unwrap();
+    assert_eq!(requests[0].system_prompt.as_deref(), Some("Clean it"));
+    assert!(requests[0].tools.is_empty());
+}
+
+#[tokio::test]
+async fn test_pipeline_runs_in_order() {
+    let pipeline = OutputPipeline::new()
+        .then(ExtractFencedBlock::new())
+        .then(|output: String| output.to_uppercase())
+        .then(MaxLength::new(6));
+
+    let output = pipeline
+        .process("```\nhello world\n```".to_owned())
+        .await
+        .unwrap();
+
+    assert_eq!(output, "HEL...");
+    assert_eq!(pipeline.len(), 3);
+}
+
+#[tokio::test]
+async fn test_agent_stores_processed_output() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&[
+        "Here you go:\n```json\n{\"city\": \"Paris\"}\n```",
+    ]))
+    .agent_name("Agent")
+    .disable_task_complete_tool()
+    .max_loops(1)
+    .add_output_processor(ExtractFencedBlock::new().language("json"))
+    .build();
+
+    let result = agent
+        .run_with_result("Capital?".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.final_answer, "{\"city\": \"Paris\"}");
+    let last = result.transcript.last().unwrap();
+    assert_eq!(last.role, Role::Assistant(OUTPUT_CLEANER_ROLE.to_owned()));
+    // The unprocessed answer stays in the transcript
+    assert!(result.transcript[1].content.starts_with("Here you go"));
+
+    let output = agent.run("Capital again?".to_owned()).await.unwrap();
+    assert!(output.contains("Output Cleaner(Assistant): "));
+}
+
+#[tokio::test]
+async fn test_failed_processor_keeps_answer() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&["plain answer"]))
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .add_output_processor(ExtractFencedBlock::new().required())
+        .build();
+
+    let result = agent
+        .run_with_result("Question".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.final_answer, "plain answer");
+    assert_eq!(result.transcript.len(), 2);
+}
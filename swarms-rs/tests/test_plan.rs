@@ -0,0 +1,270 @@
+//! Tests for executable plans
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::plan::{Plan, PlanError, StepStatus, StepUpdate};
+use swarms_rs::agent::run_result::TerminationReason;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::{AssistantContent, Message, UserContent},
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::{Agent, CancellationToken};
+use swarms_rs::structs::budget::RunBudget;
+
+// Mock model that returns scripted responses, then "Done", and records the prompts
+#[derive(Clone, Default)]
+struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<AssistantContent>>>,
+    prompts: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedModel {
+    fn new(responses: Vec<AssistantContent>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into())),
+            prompts: Arc::default(),
+        }
+    }
+
+    fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+impl Model for ScriptedModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        let prompt = match request.prompt {
+            Message::User { content } => content
+                .into_iter()
+                .filter_map(|c| match c {
+                    UserContent::Text(text) => Some(text.text),
+                    _ => None,
+                })
+                .collect::<String>(),
+            Message::Assistant { .. } => String::new(),
+        };
+        self.prompts.lock().unwrap().push(prompt);
+        let choice = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| AssistantContent::text("Done"));
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice: vec![choice],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+fn update_plan(step: u32, status: &str, note: &str) -> AssistantContent {
+    AssistantContent::tool_call(
+        format!("call_{step}"),
+        "update_plan",
+        serde_json::json!({ "step": step, "status": status, "note": note }),
+    )
+}
+
+#[tokio::test]
+async fn test_works_through_plan_and_replans() {
+    let model = ScriptedModel::new(vec![
+        AssistantContent::text(r#"{"steps": ["Find sources", "Read the sources"]}"#),
+        update_plan(1, "done", "Found 2 sources"),
+        update_plan(2, "failed", "Sources are paywalled"),
+        AssistantContent::text(r#"["Summarize from the abstracts"]"#),
+        update_plan(3, "done", "Summarized"),
+        AssistantContent::text("Final summary"),
+    ]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .enable_plan("Plan the research for:".to_owned())
+        .disable_task_complete_tool()
+        .max_loops(4)
+        .build();
+
+    let result = agent
+        .run_with_result("Summarize the papers".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    let plan = result.plan.unwrap();
+    assert_eq!(plan.replans, 1);
+    assert_eq!(
+        plan.steps
+            .iter()
+            .map(|step| (step.id, step.status))
+            .collect::<Vec<_>>(),
+        [
+            (1, StepStatus::Done),
+            (2, StepStatus::Failed),
+            (3, StepStatus::Done)
+        ]
+    );
+    assert_eq!(plan.steps[1].note.as_deref(), Some("Sources are paywalled"));
+    assert_eq!(result.final_answer, "Final summary");
+
+    let prompts = model.prompts();
+    assert!(prompts[0].starts_with("Plan the research for: Summarize the papers"));
+    assert!(prompts[1].contains("Current step 1: Find sources"));
+    assert!(prompts[2].contains("Current step 2: Read the sources"));
+    assert!(prompts[3].contains("Step 2 failed: Sources are paywalled"));
+    assert!(prompts[4].contains("Current step 3: Summarize from the abstracts"));
+    assert!(prompts[5].contains("All steps of the plan are finished"));
+}
+
+#[tokio::test]
+async fn test_max_replans() {
+    let model = ScriptedModel::new(vec![
+        AssistantContent::text("1. Find sources\n2. Write the summary"),
+        update_plan(1, "failed", "No sources"),
+    ]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .enable_plan(None)
+        .max_replans(0)
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .build();
+
+    let result = agent
+        .run_with_result("Summarize the papers".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    let plan = result.plan.unwrap();
+    assert_eq!(plan.replans, 0);
+    assert_eq!(
+        plan.current_step().unwrap().description,
+        "Write the summary"
+    );
+    // No re-planning request between the two loops
+    assert!(model.prompts()[2].contains("Current step 2: Write the summary"));
+}
+
+#[tokio::test]
+async fn test_plan_is_autosaved() {
+    let dir = tempfile::tempdir().unwrap();
+    let model = ScriptedModel::new(vec![
+        AssistantContent::text(r#"{"steps": ["Outline", "Draft"]}"#),
+        update_plan(1, "done", "Outlined"),
+    ]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .enable_plan(None)
+        .enable_autosave()
+        .save_state_dir(dir.path().to_string_lossy())
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+
+    agent.run("Write a poem".to_owned()).await.unwrap();
+
+    let state = agent.load_task_state("Write a poem").await.unwrap();
+    let plan = state.plan.unwrap();
+    assert_eq!(plan.steps[0].status, StepStatus::Done);
+    assert_eq!(plan.current_step().unwrap().description, "Draft");
+}
+
+#[tokio::test]
+async fn test_plan_without_steps_is_kept_as_text() {
+    let model = ScriptedModel::new(vec![AssistantContent::text("Just write it.")]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .enable_plan(None)
+        .disable_task_complete_tool()
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(result.plan.is_none());
+    assert!(
+        result
+            .transcript
+            .iter()
+            .any(|entry| entry.content == "Just write it.")
+    );
+    assert!(!model.prompts()[1].contains("Plan:"));
+}
+
+#[tokio::test]
+async fn test_plan_counts_toward_budget() {
+    let model = ScriptedModel::new(vec![AssistantContent::text(
+        r#"{"steps": ["Outline", "Draft"]}"#,
+    )]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .enable_plan(None)
+        .budget(RunBudget::new().max_total_tokens(1).final_summary(false))
+        .disable_task_complete_tool()
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    // The plan used up the budget before the first loop
+    assert_eq!(model.prompts().len(), 1);
+    assert_eq!(result.loop_count, 0);
+    assert_eq!(result.termination, TerminationReason::Budget);
+    assert!(result.usage.total_tokens() > 0);
+}
+
+#[test]
+fn test_parse_plan() {
+    let plan = Plan::parse("Here you go:\n- Outline\n* Draft\n3) Edit\nThanks").unwrap();
+    assert_eq!(
+        plan.steps
+            .iter()
+            .map(|step| step.description.as_str())
+            .collect::<Vec<_>>(),
+        ["Outline", "Draft", "Edit"]
+    );
+    assert_eq!(plan.steps[2].id, 3);
+
+    let plan = Plan::parse(r#"{"steps": [{"description": "Outline"}]}"#).unwrap();
+    assert_eq!(plan.steps[0].description, "Outline");
+
+    assert!(Plan::parse("No plan needed").is_none());
+}
+
+#[test]
+fn test_update_and_replan() {
+    let mut plan = Plan::new(["Outline", "Draft", "Edit"]);
+    let update = |step, status| StepUpdate {
+        step,
+        status,
+        note: None,
+    };
+
+    plan.update(&update(1, StepStatus::Done)).unwrap();
+    assert!(matches!(
+        plan.update(&update(1, StepStatus::Failed)),
+        Err(PlanError::StepFinished(1))
+    ));
+    assert!(matches!(
+        plan.update(&update(9, StepStatus::Done)),
+        Err(PlanError::UnknownStep(9))
+    ));
+    assert!(matches!(
+        plan.update(&update(2, StepStatus::Pending)),
+        Err(PlanError::InvalidStatus)
+    ));
+
+    plan.update(&update(2, StepStatus::Failed)).unwrap();
+    plan.replan(["Draft shorter"]);
+    assert_eq!(
+        plan.to_string(),
+        "1. [x] Outline\n2. [!] Draft\n3. [ ] Draft shorter"
+    );
+    assert_eq!(plan.replans, 1);
+}
@@ -0,0 +1,266 @@
+//! Tests for system prompt templates
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::request::ToolDefinition;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::prompts::{
+    self,
+    multi_agent_collab_prompt::MULTI_AGENT_COLLAB_PROMPT,
+    template::{PromptContext, PromptTemplate, TemplateError},
+};
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::tool::{ToolDyn, ToolError};
+
+// Mock model that records the system prompts it is sent
+#[derive(Clone, Default)]
+struct RecordingModel {
+    system_prompts: Arc<Mutex<Vec<Option<String>>>>,
+}
+
+impl Model for RecordingModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        self.system_prompts
+            .lock()
+            .unwrap()
+            .push(request.system_prompt);
+        Box::pin(async move {
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text("Done")],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+struct Weather;
+
+impl ToolDyn for Weather {
+    fn name(&self) -> String {
+        "weather".to_owned()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "weather".to_owned(),
+            description: "Look up the weather of a city".to_owned(),
+            parameters: serde_json::json!({"type": "object", "properties": {}}),
+        }
+    }
+
+    fn call(&self, _args: String) -> BoxFuture<'_, Result<String, ToolError>> {
+        Box::pin(async { Ok("Sunny".to_owned()) })
+    }
+}
+
+#[test]
+fn test_variables_and_conditionals() {
+    let template = PromptTemplate::parse(
+        "Hello {{ user }}!{{#if vip}} Welcome back.{{else}} Nice to meet you.{{/if}}",
+    )
+    .unwrap();
+    assert_eq!(
+        template.variables().into_iter().collect::<Vec<_>>(),
+        vec!["user"]
+    );
+
+    let context = PromptContext::new().with("user", "Ada");
+    assert_eq!(
+        template.render(&context).unwrap(),
+        "Hello Ada! Nice to meet you."
+    );
+    let context = context.with("vip", "yes");
+    assert_eq!(
+        template.render(&context).unwrap(),
+        "Hello Ada! Welcome back."
+    );
+    // An empty variable is false
+    let context = PromptContext::new().with("user", "Ada").with("vip", " ");
+    assert_eq!(
+        template.render(&context).unwrap(),
+        "Hello Ada! Nice to meet you."
+    );
+
+    assert_eq!(
+        template.render(&PromptContext::new()),
+        Err(TemplateError::MissingVariables(vec!["user".to_owned()]))
+    );
+}
+
+#[test]
+fn test_guarded_variables_are_optional() {
+    let template = PromptTemplate::parse(
+        "{{#if agents}}Team:\n{{agents}}{{else}}Work alone, {{name}}.{{/if}}",
+    )
+    .unwrap();
+    // `agents` is only used when it is set, `name` only when `agents` isn't
+    assert_eq!(
+        template.variables().into_iter().collect::<Vec<_>>(),
+        vec!["name"]
+    );
+    assert!(template.validate(["name"]).is_ok());
+    assert_eq!(
+        template
+            .render(&PromptContext::new().with("agents", "- Writer"))
+            .unwrap_err(),
+        TemplateError::MissingVariables(vec!["name".to_owned()])
+    );
+}
+
+#[test]
+fn test_partials() {
+    let template = PromptTemplate::parse("You are an analyst.\n{{> multi_agent_collab}}").unwrap();
+    assert_eq!(
+        template.render(&PromptContext::new()).unwrap(),
+        format!("You are an analyst.\n{MULTI_AGENT_COLLAB_PROMPT}")
+    );
+
+    // The swarm rules partial uses the `rules` variable
+    let template = PromptTemplate::parse("Base{{> swarm_rules}}").unwrap();
+    assert_eq!(
+        template
+            .render(&PromptContext::new().with("rules", "Be brief"))
+            .unwrap(),
+        "Base\n### SWARM RULES ###\nBe brief"
+    );
+    assert!(prompts::partial("swarm_rules").is_some());
+
+    let partials = HashMap::from([
+        ("signature".to_owned(), "-- {{team}}".to_owned()),
+        ("loop".to_owned(), "{{> loop}}".to_owned()),
+    ]);
+    let template =
+        PromptTemplate::parse_with_partials("Reply.\n{{> signature}}", &partials).unwrap();
+    assert_eq!(
+        template
+            .render(&PromptContext::new().with("team", "Support"))
+            .unwrap(),
+        "Reply.\n-- Support"
+    );
+
+    assert_eq!(
+        PromptTemplate::parse("{{> unknown}}"),
+        Err(TemplateError::UnknownPartial("unknown".to_owned()))
+    );
+    assert_eq!(
+        PromptTemplate::parse_with_partials("{{> loop}}", &partials),
+        Err(TemplateError::RecursivePartial("loop".to_owned()))
+    );
+}
+
+#[test]
+fn test_syntax_errors() {
+    for source in [
+        "Hello {{name",
+        "{{#if x}}unclosed",
+        "{{else}}",
+        "{{/if}}",
+        "{{#if x}}a{{else}}b{{else}}c{{/if}}",
+        "{{two words}}",
+        "{{}}",
+    ] {
+        assert!(
+            matches!(
+                PromptTemplate::parse(source),
+                Err(TemplateError::Syntax { .. })
+            ),
+            "{source} should not parse"
+        );
+    }
+    // Single braces are text
+    let template = PromptTemplate::parse(r#"Reply with {"answer": "..."}"#).unwrap();
+    assert_eq!(
+        template.render(&PromptContext::new()).unwrap(),
+        r#"Reply with {"answer": "..."}"#
+    );
+}
+
+#[test]
+fn test_literal_and_append() {
+    let template = PromptTemplate::literal("Literal {{not_a_variable}}")
+        .append(PromptTemplate::parse(" and {{x}}").unwrap());
+    assert_eq!(template.source(), "Literal {{not_a_variable}} and {{x}}");
+    assert_eq!(
+        template
+            .render(&PromptContext::new().with("x", "y"))
+            .unwrap(),
+        "Literal {{not_a_variable}} and y"
+    );
+}
+
+#[test]
+fn test_agent_build_validates_template() {
+    let template = PromptTemplate::parse("You work for {{company}} with {{tools}}.").unwrap();
+
+    let result = SwarmsAgentBuilder::new_with_model(RecordingModel::default())
+        .system_prompt_template(template.clone())
+        .try_build();
+    assert!(matches!(
+        result,
+        Err(AgentError::TemplateError(TemplateError::MissingVariables(missing)))
+            if missing == vec!["company".to_owned()]
+    ));
+
+    let agent = SwarmsAgentBuilder::new_with_model(RecordingModel::default())
+        .system_prompt_template(template)
+        .prompt_variable("company", "ACME")
+        .try_build()
+        .unwrap();
+    assert_eq!(
+        agent.get_system_prompt(),
+        Some("You work for {{company}} with {{tools}}.")
+    );
+}
+
+#[tokio::test]
+async fn test_template_rendered_per_run() {
+    let model = RecordingModel::default();
+    let template = PromptTemplate::parse(
+        "You are {{agent_name}} helping {{user_name}} on {{date}} for {{company}}.\nTools:\n{{tools}}",
+    )
+    .unwrap();
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .agent_name("Concierge")
+        .user_name("Ada")
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .add_shared_tool(Arc::new(Weather))
+        .system_prompt_template(template)
+        .prompt_variable("company", "ACME")
+        .build();
+
+    agent.run("What's the weather?".to_owned()).await.unwrap();
+
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    assert_eq!(
+        model.system_prompts.lock().unwrap()[0].as_deref(),
+        Some(
+            format!(
+                "You are Concierge helping Ada on {today} for ACME.\nTools:\n\
+                - weather: Look up the weather of a city"
+            )
+            .as_str()
+        )
+    );
+
+    // A fixed system prompt replaces the template
+    let agent = agent.system_prompt("Fixed");
+    assert!(agent.get_system_prompt_template().is_none());
+    agent.run("Again".to_owned()).await.unwrap();
+    assert_eq!(
+        model.system_prompts.lock().unwrap()[1].as_deref(),
+        Some("Fixed")
+    );
+}
@@ -0,0 +1,217 @@
+//! Tests for reflexion mode
+
+mod common;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::reflexion::{
+    AgentCritic, CRITIC_ROLE, Critic, Critique, CritiqueError, Reflexion,
+};
+use swarms_rs::agent::run_result::TerminationReason;
+use swarms_rs::structs::agent::{Agent, AgentError, CancellationToken};
+use swarms_rs::structs::conversation::Role;
+
+use common::ScriptedModel;
+
+// Mock critic that returns scripted scores and records the drafts
+#[derive(Clone, Default)]
+struct ScriptedCritic {
+    scores: Arc<Mutex<VecDeque<f64>>>,
+    drafts: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedCritic {
+    fn new(scores: &[f64]) -> Self {
+        Self {
+            scores: Arc::new(Mutex::new(scores.iter().copied().collect())),
+            drafts: Arc::default(),
+        }
+    }
+}
+
+impl Critic for ScriptedCritic {
+    fn critique<'a>(
+        &'a self,
+        _task: &'a str,
+        _rubric: Option<&'a str>,
+        draft: &'a str,
+    ) -> BoxFuture<'a, Result<Critique, CritiqueError>> {
+        self.drafts.lock().unwrap().push(draft.to_owned());
+        let score = self.scores.lock().unwrap().pop_front().unwrap_or(0.0);
+        Box::pin(async move {
+            Ok(Critique {
+                score,
+                feedback: "Add more detail".to_owned(),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_revises_until_threshold_with_own_model() {
+    let model = ScriptedModel::texts(&[
+        "Draft one",
+        r#"{"score": 4, "feedback": "Mention the capital"}"#,
+        "Draft two",
+        r#"Sure: {"score": 9, "feedback": ""}"#,
+    ]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .disable_task_complete_tool()
+        .max_loops(5)
+        .enable_reflexion(Reflexion::new().rubric("Mentions the capital"))
+        .build();
+
+    let result = agent
+        .run_with_result("Describe France".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.termination, TerminationReason::CritiquePassed);
+    assert_eq!(result.final_answer, "Draft two");
+    assert_eq!(result.loop_count, 2);
+    assert_eq!(
+        result
+            .critiques
+            .iter()
+            .map(|critique| critique.score)
+            .collect::<Vec<_>>(),
+        [4.0, 9.0]
+    );
+
+    let requests = model.requests.lock().unwrap();
+    assert_eq!(requests.len(), 4);
+    // The critic sees the task, rubric and draft, without tools
+    let critic_prompt = format!("{:?}", requests[1].prompt);
+    assert!(critic_prompt.contains("Mentions the capital"));
+    assert!(critic_prompt.contains("Draft one"));
+    assert!(requests[1].tools.is_empty());
+    // The revision sees the feedback
+    assert!(format!("{:?}", requests[2].chat_history).contains("Mention the capital"));
+    drop(requests);
+
+    let critic_messages = result
+        .transcript
+        .iter()
+        .filter(|entry| entry.role == Role::User(CRITIC_ROLE.to_owned()))
+        .collect::<Vec<_>>();
+    assert_eq!(critic_messages.len(), 2);
+    assert!(critic_messages[0].content.starts_with("Score: 4/10."));
+    assert!(critic_messages[0].content.contains("Revise your answer"));
+    assert!(!critic_messages[1].content.contains("Revise your answer"));
+}
+
+#[tokio::test]
+async fn test_stops_after_max_revisions() {
+    let critic = ScriptedCritic::new(&[2.0, 3.0, 4.0]);
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&["v1", "v2", "v3"]))
+        .disable_task_complete_tool()
+        .max_loops(10)
+        .enable_reflexion(Reflexion::new().max_revisions(2).critic(critic.clone()))
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.termination, TerminationReason::MaxRevisions);
+    assert_eq!(result.final_answer, "v3");
+    assert_eq!(result.critiques.len(), 3);
+    assert_eq!(*critic.drafts.lock().unwrap(), ["v1", "v2", "v3"]);
+}
+
+#[tokio::test]
+async fn test_stops_at_max_loops() {
+    let critic = ScriptedCritic::new(&[1.0, 1.0]);
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&["v1", "v2"]))
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .enable_reflexion(Reflexion::new().critic(critic))
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.termination, TerminationReason::MaxLoops);
+    assert_eq!(result.critiques.len(), 2);
+}
+
+#[tokio::test]
+async fn test_invalid_critique_keeps_draft() {
+    let model = ScriptedModel::texts(&["Draft", "I like it"]);
+    let agent = SwarmsAgentBuilder::new_with_model(model.clone())
+        .disable_task_complete_tool()
+        .max_loops(3)
+        .enable_reflexion(Reflexion::new())
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(result.critiques.is_empty());
+    assert_eq!(result.final_answer, "Done");
+    assert_eq!(result.termination, TerminationReason::MaxLoops);
+}
+
+#[tokio::test]
+async fn test_agent_critic() {
+    let reviewer = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&[
+        r#"{"score": 8.5, "feedback": "Good"}"#,
+    ]))
+    .agent_name("Reviewer")
+    .disable_task_complete_tool()
+    .build();
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::texts(&["Draft"]))
+        .disable_task_complete_tool()
+        .max_loops(3)
+        .enable_reflexion(Reflexion::new().critic(AgentCritic::new(Box::new(reviewer))))
+        .build();
+
+    let result = agent
+        .run_with_result("Write a poem".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert_eq!(result.termination, TerminationReason::CritiquePassed);
+    assert_eq!(result.critiques[0].score, 8.5);
+    assert_eq!(result.final_answer, "Draft");
+}
+
+#[test]
+fn test_invalid_threshold() {
+    let result = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .enable_reflexion(Reflexion::new().threshold(11.0))
+        .try_build();
+
+    assert!(matches!(result, Err(AgentError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_parse_critique() {
+    let critique =
+        Critique::parse("```json\n{\"score\": 6, \"feedback\": \"Shorter\"}\n```").unwrap();
+    assert_eq!(critique.score, 6.0);
+    assert_eq!(critique.feedback, "Shorter");
+
+    assert!(
+        Critique::parse(r#"{"score": 7}"#)
+            .unwrap()
+            .feedback
+            .is_empty()
+    );
+    assert!(matches!(
+        Critique::parse("Looks great"),
+        Err(CritiqueError::InvalidCritique(_))
+    ));
+    assert!(matches!(
+        Critique::parse(r#"{"score": 42}"#),
+        Err(CritiqueError::InvalidCritique(_))
+    ));
+}
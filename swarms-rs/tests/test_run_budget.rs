@@ -0,0 +1,354 @@
+//! Tests for run budgets
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::hooks::{AgentHook, HookContext};
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse, ToolDefinition, Usage},
+};
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::budget::{BudgetExceeded, BudgetTracker, ModelPricing, RunBudget};
+use swarms_rs::structs::tool::Tool;
+use tokio_util::sync::CancellationToken;
+
+// Mock model that keeps calling the `count` tool and reports a fixed usage per call.
+// Requests without tools (the final summary) get a text answer.
+#[derive(Clone, Default)]
+struct LoopingModel {
+    usage: Usage,
+    delay: Duration,
+    calls: Arc<AtomicU32>,
+}
+
+impl Model for LoopingModel {
+    type RawCompletionResponse = Usage;
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<Usage>, CompletionError>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let choice = if request.tools.is_empty() {
+            vec![AssistantContent::text("Final summary")]
+        } else {
+            vec![AssistantContent::tool_call(
+                "call_1",
+                "count",
+                serde_json::json!({}),
+            )]
+        };
+        Box::pin(async move {
+            tokio::time::sleep(self.delay).await;
+            Ok(CompletionResponse {
+                choice,
+                raw_response: self.usage,
+            })
+        })
+    }
+
+    fn usage(&self, response: &Usage) -> Option<Usage> {
+        Some(*response)
+    }
+}
+
+#[derive(Deserialize)]
+struct CountArgs {}
+
+#[derive(Serialize)]
+struct CountOutput {
+    count: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("count error")]
+struct CountError;
+
+#[derive(Clone, Default)]
+struct CountTool {
+    calls: Arc<AtomicU32>,
+}
+
+impl Tool for CountTool {
+    type Error = CountError;
+    type Args = CountArgs;
+    type Output = CountOutput;
+
+    const NAME: &'static str = "count";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Count calls".to_owned(),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let count = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(CountOutput { count })
+    }
+}
+
+fn builder(model: LoopingModel, tool: CountTool) -> SwarmsAgentBuilder<LoopingModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .add_tool(tool)
+        .disable_task_complete_tool()
+        .max_loops(10)
+}
+
+#[tokio::test]
+async fn test_tool_call_budget() {
+    let model = LoopingModel::default();
+    let tool = CountTool::default();
+    let agent = builder(model.clone(), tool.clone())
+        .budget(RunBudget::new().max_tool_calls(2))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert_eq!(tool.calls.load(Ordering::SeqCst), 2);
+    // two loops with tool calls, then the final summary
+    assert_eq!(model.calls.load(Ordering::SeqCst), 3);
+    assert!(output.contains("Tool call budget exhausted: made 2 of 2 tool calls"));
+    assert!(output.contains("Final summary"));
+}
+
+#[tokio::test]
+async fn test_token_budget() {
+    let model = LoopingModel {
+        usage: Usage::new(80, 20),
+        ..Default::default()
+    };
+    let agent = builder(model.clone(), CountTool::default())
+        .budget(RunBudget::new().max_total_tokens(250))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert_eq!(model.calls.load(Ordering::SeqCst), 4);
+    assert!(output.contains("Token budget exhausted: used 300 of 250 tokens"));
+}
+
+#[tokio::test]
+async fn test_token_budget_is_estimated_without_reported_usage() {
+    #[derive(Clone)]
+    struct SilentModel(LoopingModel);
+
+    impl Model for SilentModel {
+        type RawCompletionResponse = Usage;
+
+        fn completion(
+            &self,
+            request: CompletionRequest,
+        ) -> BoxFuture<'_, Result<CompletionResponse<Usage>, CompletionError>> {
+            self.0.completion(request)
+        }
+    }
+
+    let agent = SwarmsAgentBuilder::new_with_model(SilentModel(LoopingModel::default()))
+        .system_prompt("x".repeat(400))
+        .add_tool(CountTool::default())
+        .disable_task_complete_tool()
+        .max_loops(10)
+        .budget(RunBudget::new().max_total_tokens(150))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert!(output.contains("Token budget exhausted"));
+}
+
+#[tokio::test]
+async fn test_cost_budget() {
+    let model = LoopingModel {
+        usage: Usage::new(100, 0),
+        ..Default::default()
+    };
+    let agent = builder(model.clone(), CountTool::default())
+        .pricing(ModelPricing::new(10_000.0, 0.0))
+        .budget(RunBudget::new().max_cost(2.5))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert_eq!(model.calls.load(Ordering::SeqCst), 4);
+    assert!(output.contains("Cost budget exhausted"));
+}
+
+#[test]
+fn test_cost_budget_requires_pricing() {
+    let result = builder(LoopingModel::default(), CountTool::default())
+        .budget(RunBudget::new().max_cost(1.0))
+        .try_build();
+
+    assert!(matches!(result, Err(AgentError::InvalidConfig(_))));
+}
+
+#[tokio::test]
+async fn test_time_budget() {
+    let model = LoopingModel {
+        delay: Duration::from_millis(30),
+        ..Default::default()
+    };
+    let agent = builder(model.clone(), CountTool::default())
+        .budget(RunBudget::new().max_duration(Duration::from_millis(50)))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert!(model.calls.load(Ordering::SeqCst) < 10);
+    assert!(output.contains("Time budget exhausted"));
+}
+
+#[tokio::test]
+async fn test_time_budget_interrupts_completion() {
+    let model = LoopingModel {
+        delay: Duration::from_secs(10),
+        ..Default::default()
+    };
+    let agent = builder(model.clone(), CountTool::default())
+        .budget(
+            RunBudget::new()
+                .max_duration(Duration::from_millis(50))
+                .final_summary(false),
+        )
+        .build();
+
+    let started = std::time::Instant::now();
+    let result = agent
+        .run_with_result("Count".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(matches!(
+        result.budget_exceeded,
+        Some(BudgetExceeded::Duration { .. })
+    ));
+}
+
+#[derive(Deserialize)]
+struct SleepArgs {}
+
+// Tool that never finishes in time
+struct SleepTool;
+
+impl Tool for SleepTool {
+    type Error = CountError;
+    type Args = SleepArgs;
+    type Output = String;
+
+    const NAME: &'static str = "count";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Sleep".to_owned(),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        Ok("Woke up".to_owned())
+    }
+}
+
+#[tokio::test]
+async fn test_time_budget_interrupts_tool_call() {
+    let agent = SwarmsAgentBuilder::new_with_model(LoopingModel::default())
+        .add_tool(SleepTool)
+        .disable_task_complete_tool()
+        .max_loops(10)
+        .budget(RunBudget::new().max_duration(Duration::from_millis(50)))
+        .build();
+
+    let started = std::time::Instant::now();
+    let result = agent
+        .run_with_result("Sleep".to_owned(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(matches!(
+        result.budget_exceeded,
+        Some(BudgetExceeded::Duration { .. })
+    ));
+    // The final summary still runs
+    assert!(result.final_answer.contains("Final summary"));
+}
+
+#[tokio::test]
+async fn test_final_summary_can_be_disabled() {
+    let model = LoopingModel::default();
+    let agent = builder(model.clone(), CountTool::default())
+        .budget(RunBudget::new().max_tool_calls(1).final_summary(false))
+        .build();
+
+    let output = agent.run("Count".to_owned()).await.unwrap();
+
+    assert_eq!(model.calls.load(Ordering::SeqCst), 1);
+    assert!(!output.contains("Final summary"));
+}
+
+#[derive(Clone, Default)]
+struct BudgetHook {
+    exhausted: Arc<Mutex<Vec<BudgetExceeded>>>,
+}
+
+impl AgentHook for BudgetHook {
+    fn on_budget_exhausted(&self, _ctx: &HookContext, exceeded: &BudgetExceeded) {
+        self.exhausted.lock().unwrap().push(exceeded.clone());
+    }
+}
+
+#[tokio::test]
+async fn test_exhausted_budget_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let hook = BudgetHook::default();
+    let agent = builder(LoopingModel::default(), CountTool::default())
+        .budget(RunBudget::new().max_tool_calls(1))
+        .add_hook(hook.clone())
+        .enable_autosave()
+        .save_state_dir(dir.path().to_str().unwrap())
+        .build();
+
+    agent.run("Count".to_owned()).await.unwrap();
+
+    let expected = BudgetExceeded::ToolCalls { used: 1, limit: 1 };
+    assert_eq!(*hook.exhausted.lock().unwrap(), vec![expected.clone()]);
+    let state = agent.load_task_state("Count").await.unwrap();
+    assert!(state.finished);
+    assert_eq!(state.budget_exceeded, Some(expected));
+}
+
+#[tokio::test]
+async fn test_concurrent_tool_calls_respect_budget() {
+    let tracker = Arc::new(BudgetTracker::new(RunBudget::new().max_tool_calls(3), None));
+
+    let handles = (0..10)
+        .map(|_| {
+            let tracker = Arc::clone(&tracker);
+            tokio::spawn(async move { tracker.start_tool_call().is_ok() })
+        })
+        .collect::<Vec<_>>();
+    let mut started = 0;
+    for handle in handles {
+        if handle.await.unwrap() {
+            started += 1;
+        }
+    }
+
+    assert_eq!(started, 3);
+    assert_eq!(
+        tracker.check(),
+        Some(BudgetExceeded::ToolCalls { used: 3, limit: 3 })
+    );
+}
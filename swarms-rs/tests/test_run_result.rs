@@ -0,0 +1,319 @@
+//! Tests for structured run results
+
+mod common;
+
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::agent::hooks::{AgentHook, HookContext};
+use swarms_rs::agent::run_result::{RunResult, TerminationReason};
+use swarms_rs::llm::{completion::AssistantContent, request::ToolDefinition};
+use swarms_rs::structs::agent::{Agent, AgentError, CancellationToken};
+use swarms_rs::structs::budget::RunBudget;
+use swarms_rs::structs::conversation::Role;
+use swarms_rs::structs::tool::Tool;
+
+use common::ScriptedModel;
+
+#[derive(Deserialize)]
+struct SleepArgs {
+    millis: u64,
+}
+
+#[derive(Serialize)]
+struct SleepOutput {
+    slept: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("sleep error")]
+struct SleepError;
+
+struct SleepTool;
+
+impl Tool for SleepTool {
+    type Error = SleepError;
+    type Args = SleepArgs;
+    type Output = SleepOutput;
+
+    const NAME: &'static str = "sleep";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Sleep for a while".to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "millis": { "type": "integer" } },
+                "required": ["millis"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        tokio::time::sleep(Duration::from_millis(args.millis)).await;
+        Ok(SleepOutput { slept: args.millis })
+    }
+}
+
+fn sleep_call(millis: u64) -> Vec<AssistantContent> {
+    vec![AssistantContent::tool_call(
+        "call_1",
+        "sleep",
+        serde_json::json!({ "millis": millis }),
+    )]
+}
+
+fn task_complete() -> Vec<AssistantContent> {
+    vec![AssistantContent::tool_call(
+        "call_2",
+        "task_evaluator",
+        serde_json::json!({ "status": "Complete" }),
+    )]
+}
+
+async fn run(agent: &dyn Agent, task: &str) -> RunResult {
+    agent
+        .run_with_result(task.to_owned(), CancellationToken::new())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_result_of_text_answer() {
+    let agent =
+        SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![AssistantContent::text(
+            "Paris",
+        )]]))
+        .agent_name("Geographer")
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+
+    let result = run(&agent, "Capital of France?").await;
+
+    assert_eq!(result.final_answer, "Paris");
+    assert_eq!(result.agent_name, "Geographer");
+    assert_eq!(result.task, "Capital of France?");
+    assert_eq!(result.loop_count, 1);
+    assert_eq!(result.termination, TerminationReason::MaxLoops);
+    assert!(result.tool_calls.is_empty());
+    assert!(result.usage.total_tokens() > 0);
+
+    assert_eq!(result.transcript.len(), 2);
+    assert_eq!(result.transcript[0].role, Role::User("User".to_owned()));
+    assert_eq!(result.transcript[0].content, "Capital of France?");
+    assert_eq!(
+        result.transcript[1].role,
+        Role::Assistant("Geographer".to_owned())
+    );
+    assert_eq!(result.transcript[1].content, "Paris");
+    assert!(
+        result
+            .transcript
+            .iter()
+            .all(|entry| entry.timestamp.is_some())
+    );
+}
+
+#[tokio::test]
+async fn test_result_records_tool_calls_and_timings() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![sleep_call(20)]))
+        .add_tool(SleepTool)
+        .disable_task_complete_tool()
+        .max_loops(2)
+        .build();
+
+    let result = run(&agent, "Sleep").await;
+
+    assert_eq!(result.final_answer, "Done");
+    assert_eq!(result.loop_count, 2);
+    assert_eq!(result.tool_calls.len(), 1);
+    assert_eq!(result.tool_calls[0].name, "sleep");
+    assert_eq!(result.tool_calls[0].result, r#"{"slept":20}"#);
+    assert!(result.timings.tool_duration >= Duration::from_millis(20));
+    assert!(result.timings.duration >= result.timings.tool_duration);
+    assert!(result.timings.finished_at >= result.timings.started_at);
+}
+
+#[tokio::test]
+async fn test_task_evaluator_termination() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![
+        vec![AssistantContent::text("The answer is 4")],
+        task_complete(),
+    ]))
+    .max_loops(5)
+    .build();
+
+    let result = run(&agent, "2 + 2?").await;
+
+    assert_eq!(result.termination, TerminationReason::TaskComplete);
+    assert_eq!(result.loop_count, 2);
+    // The task_evaluator call is not the answer
+    assert_eq!(result.final_answer, "The answer is 4");
+}
+
+#[tokio::test]
+async fn test_stop_word_termination() {
+    let agent =
+        SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![AssistantContent::text(
+            "All good. FINISHED",
+        )]]))
+        .disable_task_complete_tool()
+        .add_stop_word("FINISHED")
+        .max_loops(5)
+        .build();
+
+    let result = run(&agent, "Check").await;
+
+    assert_eq!(result.termination, TerminationReason::StopWord);
+    assert_eq!(result.loop_count, 1);
+}
+
+#[tokio::test]
+async fn test_budget_termination() {
+    let agent =
+        SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![sleep_call(0), sleep_call(0)]))
+            .add_tool(SleepTool)
+            .disable_task_complete_tool()
+            .max_loops(5)
+            .budget(RunBudget::new().max_tool_calls(1).final_summary(false))
+            .build();
+
+    let result = run(&agent, "Sleep").await;
+
+    assert_eq!(result.termination, TerminationReason::Budget);
+    assert!(result.budget_exceeded.is_some());
+    assert_eq!(result.tool_calls.len(), 1);
+}
+
+#[tokio::test]
+async fn test_result_string_accessors() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+
+    let result = run(&agent, "Hello").await;
+
+    assert_eq!(result.as_str(), "Done");
+    assert_eq!(result.to_string(), "Done");
+    assert_eq!(String::from(result), "Done");
+}
+
+#[tokio::test]
+async fn test_run_output_is_unchanged() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .agent_name("Agent")
+        .disable_task_complete_tool()
+        .max_loops(1)
+        .build();
+
+    let output = agent.run("Hello".to_owned()).await.unwrap();
+
+    assert!(output.contains("Timestamp(millis): "));
+    assert!(output.contains("Agent(Assistant): "));
+    assert!(output.contains("Done"));
+}
+
+#[tokio::test]
+async fn test_resumed_finished_run_keeps_termination() {
+    let dir = tempfile::tempdir().unwrap();
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![
+        vec![AssistantContent::text("Answer")],
+        task_complete(),
+    ]))
+    .max_loops(5)
+    .enable_autosave()
+    .save_state_dir(dir.path().to_str().unwrap())
+    .build();
+    agent.run("Question".to_owned()).await.unwrap();
+
+    let state = agent.load_task_state("Question").await.unwrap();
+
+    assert!(state.finished);
+    assert_eq!(state.termination, Some(TerminationReason::TaskComplete));
+}
+
+#[derive(Clone, Default)]
+struct UppercaseHook;
+
+impl AgentHook for UppercaseHook {
+    fn on_complete(&self, _ctx: &HookContext, output: &mut String) {
+        *output = output.to_uppercase();
+    }
+}
+
+#[tokio::test]
+async fn test_complete_hook_receives_final_answer() {
+    let agent = SwarmsAgentBuilder::new_with_model(ScriptedModel::default())
+        .disable_task_complete_tool()
+        .add_hook(UppercaseHook)
+        .max_loops(1)
+        .build();
+
+    let result = run(&agent, "Hello").await;
+
+    assert_eq!(result.final_answer, "DONE");
+}
+
+// Mock agent that only implements `run`, so it relies on the default `run_with_result`
+#[derive(Clone)]
+struct EchoAgent;
+
+impl Agent for EchoAgent {
+    fn run(&self, task: String) -> BoxFuture<'_, Result<String, AgentError>> {
+        Box::pin(async move { Ok(format!("echo: {task}")) })
+    }
+
+    fn run_multiple_tasks(
+        &mut self,
+        _tasks: Vec<String>,
+    ) -> BoxFuture<'_, Result<Vec<String>, AgentError>> {
+        Box::pin(async { Ok(vec![]) })
+    }
+
+    fn plan(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn query_long_term_memory(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn save_task_state(&self, _task: String) -> BoxFuture<'_, Result<(), AgentError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn is_response_complete(&self, _response: String) -> bool {
+        true
+    }
+
+    fn id(&self) -> String {
+        "echo".to_owned()
+    }
+
+    fn name(&self) -> String {
+        "Echo".to_owned()
+    }
+
+    fn description(&self) -> String {
+        String::new()
+    }
+
+    fn clone_box(&self) -> Box<dyn Agent> {
+        Box::new(self.clone())
+    }
+}
+
+#[tokio::test]
+async fn test_default_run_with_result() {
+    let result = run(&EchoAgent, "hi").await;
+
+    assert_eq!(result.final_answer, "echo: hi");
+    assert_eq!(result.agent_name, "Echo");
+    assert_eq!(result.termination, TerminationReason::Unspecified);
+    assert!(!result.run_id.is_empty());
+}
@@ -0,0 +1,205 @@
+//! Tests for session-scoped conversations
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{
+    CompletionError, Model,
+    completion::AssistantContent,
+    request::{CompletionRequest, CompletionResponse},
+};
+use swarms_rs::structs::agent::{Agent, CancellationToken};
+
+// Mock model that answers with the number of messages it was sent after `delay`, and
+// records them
+#[derive(Clone, Default)]
+struct CountingModel {
+    histories: Arc<Mutex<Vec<String>>>,
+    delay: Duration,
+}
+
+impl Model for CountingModel {
+    type RawCompletionResponse = ();
+
+    fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> BoxFuture<'_, Result<CompletionResponse<()>, CompletionError>> {
+        let history = format!("{:?}", request.chat_history);
+        let answer = format!("Seen {} messages", request.chat_history.len());
+        self.histories.lock().unwrap().push(history);
+        let delay = self.delay;
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            Ok(CompletionResponse {
+                choice: vec![AssistantContent::text(answer)],
+                raw_response: (),
+            })
+        })
+    }
+}
+
+impl CountingModel {
+    fn last_history(&self) -> String {
+        self.histories.lock().unwrap().last().cloned().unwrap()
+    }
+}
+
+fn agent(model: CountingModel) -> SwarmsAgentBuilder<CountingModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .agent_name("Assistant")
+        .disable_task_complete_tool()
+        .max_loops(1)
+}
+
+#[tokio::test]
+async fn test_session_keeps_previous_turns() {
+    let model = CountingModel::default();
+    let agent = agent(model.clone()).build();
+    let session = agent.session("ada");
+
+    assert_eq!(
+        session.run("My name is Ada").await.unwrap(),
+        "Seen 1 messages"
+    );
+    // The second turn sees the first task, its answer and the new task
+    assert_eq!(
+        session.run("What is my name?").await.unwrap(),
+        "Seen 3 messages"
+    );
+    assert!(model.last_history().contains("My name is Ada"));
+
+    let history = session.history();
+    assert_eq!(history.len(), 4);
+    assert!(history[2].content.contains("What is my name?"));
+
+    let result = session
+        .run_with_result("Thanks", CancellationToken::new())
+        .await
+        .unwrap();
+    assert_eq!(result.session_id.as_deref(), Some("ada"));
+    assert_eq!(result.task, "Thanks");
+    assert_eq!(result.transcript.len(), 6);
+}
+
+#[tokio::test]
+async fn test_sessions_are_isolated() {
+    let model = CountingModel::default();
+    let agent = agent(model.clone()).build();
+
+    agent.session("ada").run("Secret: 1234").await.unwrap();
+    // The same task in another session starts a new conversation
+    assert_eq!(
+        agent.session("bob").run("Secret: 1234").await.unwrap(),
+        "Seen 1 messages"
+    );
+    assert_eq!(
+        agent
+            .session("bob")
+            .run("What is the secret?")
+            .await
+            .unwrap(),
+        "Seen 3 messages"
+    );
+
+    // Runs without a session don't see the sessions, nor each other's tasks
+    let output = agent.run("What is the secret?".to_owned()).await.unwrap();
+    assert!(output.contains("Seen 1 messages"));
+
+    // Concurrent sessions with the same task
+    let (carol, dave) = (agent.session("carol"), agent.session("dave"));
+    let (first, second) = tokio::join!(carol.run("Hello"), dave.run("Hello"));
+    assert_eq!(first.unwrap(), "Seen 1 messages");
+    assert_eq!(second.unwrap(), "Seen 1 messages");
+
+    assert_eq!(agent.session_ids(), vec!["ada", "bob", "carol", "dave"]);
+}
+
+// On a single thread, a session waiting for the model must not keep the others from
+// updating their conversations
+#[tokio::test(flavor = "current_thread")]
+async fn test_concurrent_sessions_on_one_thread() {
+    let model = CountingModel {
+        delay: Duration::from_millis(50),
+        ..CountingModel::default()
+    };
+    let agent = agent(model).build();
+
+    let ids = (0..32).map(|i| format!("user-{i}")).collect::<Vec<_>>();
+    let sessions = ids.iter().map(|id| agent.session(id)).collect::<Vec<_>>();
+    let outputs =
+        futures::future::join_all(sessions.iter().map(|session| session.run("Hello"))).await;
+    for output in outputs {
+        assert_eq!(output.unwrap(), "Seen 1 messages");
+    }
+    assert_eq!(agent.session_ids().len(), 32);
+}
+
+#[tokio::test]
+async fn test_task_named_like_a_session_key() {
+    let model = CountingModel::default();
+    let agent = agent(model.clone()).build();
+    agent.session("alice").run("Secret: 1234").await.unwrap();
+
+    // A task can't read or continue a session's conversation, whatever it is named
+    for task in ["alice", "session:alice"] {
+        let output = agent.run(task.to_owned()).await.unwrap();
+        assert!(output.contains("Seen 1 messages"));
+        assert!(!model.last_history().contains("Secret: 1234"));
+    }
+    assert_eq!(agent.session("alice").history().len(), 2);
+}
+
+#[tokio::test]
+async fn test_end_session() {
+    let agent = agent(CountingModel::default()).build();
+    let session = agent.session("ada");
+    session.run("Hello").await.unwrap();
+    assert!(session.end());
+
+    assert!(agent.session("ada").history().is_empty());
+    assert!(!agent.end_session("ada"));
+    assert_eq!(
+        agent.session("ada").run("Hello again").await.unwrap(),
+        "Seen 1 messages"
+    );
+}
+
+#[tokio::test]
+async fn test_session_ttl() {
+    let agent = agent(CountingModel::default())
+        .session_ttl(Duration::from_millis(50))
+        .build();
+
+    agent.session("ada").run("Hello").await.unwrap();
+    assert!(agent.evict_sessions().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(agent.evict_sessions(), vec!["ada"]);
+    assert!(agent.session("ada").history().is_empty());
+
+    // An expired session starts over when it is used again
+    agent.session("bob").run("Hello").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(80)).await;
+    assert_eq!(
+        agent.session("bob").run("Hello again").await.unwrap(),
+        "Seen 1 messages"
+    );
+}
+
+#[tokio::test]
+async fn test_max_sessions() {
+    let agent = agent(CountingModel::default()).max_sessions(2).build();
+
+    agent.session("ada").run("Hello").await.unwrap();
+    agent.session("bob").run("Hello").await.unwrap();
+    // Using ada makes bob the least recently used session
+    agent.session("ada").run("Hello again").await.unwrap();
+    agent.session("carol").run("Hello").await.unwrap();
+
+    assert_eq!(agent.session_ids(), vec!["ada", "carol"]);
+    assert!(agent.session("bob").history().is_empty());
+    assert_eq!(agent.session("ada").history().len(), 4);
+}
@@ -0,0 +1,367 @@
+//! Tests for human approval of tool calls
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::approval::{
+    ApprovalDecision, ApprovalHandler, ApprovalPolicy, ApprovalRequest, ChannelApprovalHandler,
+    HttpApprovalHandler,
+};
+use swarms_rs::agent::{ChatResponse, SwarmsAgentBuilder, ToolCallOutput};
+use swarms_rs::llm::{completion::AssistantContent, request::ToolDefinition};
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::tool::Tool;
+use swarms_rs::structs::tool_registry::{SideEffects, ToolMetadata, ToolRegistry};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+use common::ScriptedModel;
+
+#[derive(Deserialize)]
+struct DeleteArgs {
+    path: String,
+}
+
+#[derive(Serialize)]
+struct DeleteOutput {
+    deleted: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("delete error")]
+struct DeleteError;
+
+// Mock tool that records the paths it was called with
+#[derive(Clone, Default)]
+struct DeleteFileTool {
+    deleted: Arc<Mutex<Vec<String>>>,
+}
+
+impl Tool for DeleteFileTool {
+    type Error = DeleteError;
+    type Args = DeleteArgs;
+    type Output = DeleteOutput;
+
+    const NAME: &'static str = "delete_file";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Delete a file".to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.deleted.lock().unwrap().push(args.path.clone());
+        Ok(DeleteOutput { deleted: args.path })
+    }
+}
+
+fn delete_call(path: &str) -> AssistantContent {
+    AssistantContent::tool_call("call_1", "delete_file", serde_json::json!({ "path": path }))
+}
+
+// Handler that always gives the same decision and records requests
+#[derive(Clone)]
+struct FixedHandler {
+    decision: ApprovalDecision,
+    requests: Arc<Mutex<Vec<ApprovalRequest>>>,
+}
+
+impl FixedHandler {
+    fn new(decision: ApprovalDecision) -> Self {
+        Self {
+            decision,
+            requests: Arc::default(),
+        }
+    }
+}
+
+impl ApprovalHandler for FixedHandler {
+    fn request_approval(
+        &self,
+        request: ApprovalRequest,
+    ) -> BoxFuture<'_, Result<ApprovalDecision, swarms_rs::agent::approval::ApprovalError>> {
+        self.requests.lock().unwrap().push(request);
+        let decision = self.decision.clone();
+        Box::pin(async move { Ok(decision) })
+    }
+}
+
+async fn chat_once(
+    tool: DeleteFileTool,
+    path: &str,
+    policy: ApprovalPolicy,
+    handler: Option<FixedHandler>,
+) -> ToolCallOutput {
+    let mut builder =
+        SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![delete_call(path)]]))
+            .add_tool(tool)
+            .disable_task_complete_tool()
+            .require_approval("delete_file", policy);
+    if let Some(handler) = handler {
+        builder = builder.approval_handler(handler);
+    }
+    let agent = builder.build();
+
+    match agent.chat("Clean up", vec![]).await.unwrap() {
+        ChatResponse::ToolCalls(mut outputs) => outputs.remove(0),
+        ChatResponse::Text(text) => panic!("expected tool call, got {text}"),
+    }
+}
+
+#[tokio::test]
+async fn test_approved_call_runs() {
+    let tool = DeleteFileTool::default();
+    let handler = FixedHandler::new(ApprovalDecision::Approve);
+
+    let output = chat_once(
+        tool.clone(),
+        "/tmp/a",
+        ApprovalPolicy::Always,
+        Some(handler.clone()),
+    )
+    .await;
+
+    assert_eq!(output.result, r#"{"deleted":"/tmp/a"}"#);
+    assert_eq!(*tool.deleted.lock().unwrap(), vec!["/tmp/a"]);
+    let requests = handler.requests.lock().unwrap();
+    assert_eq!(requests[0].tool_name, "delete_file");
+    assert_eq!(requests[0].args["path"], "/tmp/a");
+}
+
+#[tokio::test]
+async fn test_denied_call_returns_reason() {
+    let tool = DeleteFileTool::default();
+    let handler = FixedHandler::new(ApprovalDecision::Deny {
+        reason: "keep that file".to_owned(),
+    });
+
+    let output = chat_once(
+        tool.clone(),
+        "/tmp/a",
+        ApprovalPolicy::Always,
+        Some(handler),
+    )
+    .await;
+
+    assert_eq!(output.result, "Tool call denied: keep that file");
+    assert!(tool.deleted.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_edited_arguments_are_used() {
+    let tool = DeleteFileTool::default();
+    let handler = FixedHandler::new(ApprovalDecision::Edit {
+        args: serde_json::json!({ "path": "/tmp/safe" }),
+    });
+
+    let output = chat_once(
+        tool.clone(),
+        "/etc/passwd",
+        ApprovalPolicy::Always,
+        Some(handler),
+    )
+    .await;
+
+    assert_eq!(output.args, r#"{"path":"/tmp/safe"}"#);
+    assert_eq!(*tool.deleted.lock().unwrap(), vec!["/tmp/safe"]);
+}
+
+#[tokio::test]
+async fn test_predicate_policy() {
+    let policy = ApprovalPolicy::when(|args| {
+        args["path"]
+            .as_str()
+            .is_some_and(|path| !path.starts_with("/tmp/"))
+    });
+    let handler = FixedHandler::new(ApprovalDecision::Deny {
+        reason: "no".to_owned(),
+    });
+
+    let tool = DeleteFileTool::default();
+    chat_once(
+        tool.clone(),
+        "/tmp/a",
+        policy.clone(),
+        Some(handler.clone()),
+    )
+    .await;
+    assert_eq!(*tool.deleted.lock().unwrap(), vec!["/tmp/a"]);
+    assert!(handler.requests.lock().unwrap().is_empty());
+
+    let output = chat_once(tool.clone(), "/etc/hosts", policy, Some(handler.clone())).await;
+    assert_eq!(output.result, "Tool call denied: no");
+    assert_eq!(handler.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_request_carries_registry_metadata() {
+    let metadata = ToolMetadata::new()
+        .tag("files")
+        .side_effects(SideEffects::Destructive);
+    let registry =
+        ToolRegistry::new().with_tool_metadata(DeleteFileTool::default(), metadata.clone());
+
+    for (own_tool, expected) in [(false, Some(metadata)), (true, None)] {
+        let handler = FixedHandler::new(ApprovalDecision::Approve);
+        let mut builder =
+            SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![delete_call(
+                "/tmp/a",
+            )]]))
+            .tool_registry(registry.clone())
+            .disable_task_complete_tool()
+            .require_approval("delete_file", ApprovalPolicy::Always)
+            .approval_handler(handler.clone());
+        // A tool of the agent shadows the registry tool, and has no metadata
+        if own_tool {
+            builder = builder.add_tool(DeleteFileTool::default());
+        }
+        builder.build().chat("Clean up", vec![]).await.unwrap();
+
+        assert_eq!(handler.requests.lock().unwrap()[0].metadata, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_missing_handler_denies() {
+    let tool = DeleteFileTool::default();
+
+    let output = chat_once(tool.clone(), "/tmp/a", ApprovalPolicy::Always, None).await;
+
+    assert!(output.result.starts_with("Tool call denied"));
+    assert!(tool.deleted.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_channel_approval_handler() {
+    let (handler, mut receiver) = ChannelApprovalHandler::new(8);
+    tokio::spawn(async move {
+        while let Some(pending) = receiver.recv().await {
+            if pending.request.args["path"] == "/tmp/a" {
+                pending.approve();
+            } else {
+                pending.deny("not allowed");
+            }
+        }
+    });
+
+    let request = |path: &str| ApprovalRequest {
+        agent_name: "Agent".to_owned(),
+        task: None,
+        tool_name: "delete_file".to_owned(),
+        args: serde_json::json!({ "path": path }),
+        metadata: None,
+    };
+    assert_eq!(
+        handler.request_approval(request("/tmp/a")).await.unwrap(),
+        ApprovalDecision::Approve
+    );
+    assert_eq!(
+        handler.request_approval(request("/etc")).await.unwrap(),
+        ApprovalDecision::Deny {
+            reason: "not allowed".to_owned()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_while_waiting_for_approval() {
+    // Nobody answers the approval requests
+    let (handler, _receiver) = ChannelApprovalHandler::new(8);
+    let tool = DeleteFileTool::default();
+    let agent =
+        SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![delete_call("/tmp/a")]]))
+            .add_tool(tool.clone())
+            .disable_task_complete_tool()
+            .require_approval("delete_file", ApprovalPolicy::Always)
+            .approval_handler(handler)
+            .build();
+
+    let cancel = CancellationToken::new();
+    let canceler = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        canceler.cancel();
+    });
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        agent.run_with_result("Clean up".to_owned(), cancel),
+    )
+    .await
+    .expect("the run should stop when canceled");
+
+    assert!(matches!(result, Err(AgentError::Canceled)));
+    assert!(tool.deleted.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_http_approval_handler() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0; 4096];
+        let n = socket.read(&mut buf).await.unwrap();
+        let request = String::from_utf8_lossy(&buf[..n]);
+        assert!(request.starts_with("POST /approve"));
+        assert!(request.contains("authorization: Bearer secret"));
+
+        let body = r#"{"decision":"deny","reason":"market closed"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let handler = HttpApprovalHandler::new(format!("http://{addr}/approve")).bearer_auth("secret");
+    let decision = handler
+        .request_approval(ApprovalRequest {
+            agent_name: "Trader".to_owned(),
+            task: Some("Buy BTC".to_owned()),
+            tool_name: "place_order".to_owned(),
+            args: serde_json::json!({ "symbol": "BTCUSDT" }),
+            metadata: None,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        decision,
+        ApprovalDecision::Deny {
+            reason: "market closed".to_owned()
+        }
+    );
+}
+
+#[test]
+fn test_parse_terminal_answers() {
+    assert_eq!(
+        "y".parse::<ApprovalDecision>().unwrap(),
+        ApprovalDecision::Approve
+    );
+    assert_eq!(
+        "no too risky".parse::<ApprovalDecision>().unwrap(),
+        ApprovalDecision::Deny {
+            reason: "too risky".to_owned()
+        }
+    );
+    assert_eq!(
+        r#"edit {"path": "/tmp/b"}"#.parse::<ApprovalDecision>().unwrap(),
+        ApprovalDecision::Edit {
+            args: serde_json::json!({ "path": "/tmp/b" })
+        }
+    );
+    assert!("maybe".parse::<ApprovalDecision>().is_err());
+}
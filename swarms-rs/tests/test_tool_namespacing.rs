@@ -0,0 +1,280 @@
+//! Tests for MCP tool prefixes, aliases, filters and duplicate tool names
+
+mod common;
+
+use std::sync::Mutex;
+
+use futures::future::BoxFuture;
+use rmcp::model::{
+    CallToolRequestParam, CallToolResult, Content, ListToolsResult, PaginatedRequestParam, Tool,
+};
+use rmcp::service::{RequestContext, RunningService};
+use rmcp::{Error as McpError, RoleServer, ServerHandler, ServiceExt};
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::completion::AssistantContent;
+use swarms_rs::structs::agent::{Agent, AgentError};
+use swarms_rs::structs::mcp::{
+    MCPClient, MCPClientHandler, MCPConnector, MCPService, MCPToolOptions,
+};
+use swarms_rs::structs::tool::{DuplicateToolPolicy, ToolDyn};
+
+use common::ScriptedModel;
+
+// MCP server with `search` and `delete` tools, which answer with the name of the server
+#[derive(Clone)]
+struct SearchServer {
+    label: &'static str,
+}
+
+impl ServerHandler for SearchServer {
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": { "query": { "type": "string" } },
+        });
+        let schema = schema.as_object().unwrap().clone();
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools: vec![
+                Tool::new("search", "Search", schema.clone()),
+                Tool::new("delete", "Delete", schema),
+            ],
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let query = request
+            .arguments
+            .and_then(|args| args.get("query").cloned())
+            .and_then(|query| query.as_str().map(str::to_owned))
+            .unwrap_or_default();
+        Ok(CallToolResult::success(vec![Content::text(format!(
+            "{} {}: {query}",
+            self.label, request.name
+        ))]))
+    }
+}
+
+// Connects to an in-process `SearchServer`
+struct InProcessConnector {
+    server: SearchServer,
+    running: Mutex<Vec<RunningService<RoleServer, SearchServer>>>,
+}
+
+impl MCPConnector for InProcessConnector {
+    fn connect(&self, handler: MCPClientHandler) -> BoxFuture<'_, Result<MCPService, String>> {
+        Box::pin(async move {
+            let (client_io, server_io) = tokio::io::duplex(4096);
+            let server = tokio::spawn(self.server.clone().serve(server_io));
+            let client = handler
+                .into_dyn()
+                .serve(client_io)
+                .await
+                .map_err(|e| e.to_string())?;
+            let server = server.await.unwrap().map_err(|e| e.to_string())?;
+            self.running.lock().unwrap().push(server);
+            Ok(client)
+        })
+    }
+}
+
+async fn connect(name: &str, label: &'static str) -> MCPClient {
+    let connector = InProcessConnector {
+        server: SearchServer { label },
+        running: Mutex::default(),
+    };
+    MCPClient::builder(name, connector).connect().await.unwrap()
+}
+
+fn search(tool: &str, query: &str) -> AssistantContent {
+    AssistantContent::tool_call("call_1", tool, serde_json::json!({ "query": query }))
+}
+
+fn agent(model: ScriptedModel) -> SwarmsAgentBuilder<ScriptedModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .disable_task_complete_tool()
+        .max_loops(2)
+}
+
+#[test]
+fn test_tool_name() {
+    let options = MCPToolOptions::new();
+    assert_eq!(
+        options.tool_name("web", "search").as_deref(),
+        Some("search")
+    );
+
+    let options = MCPToolOptions::new()
+        .server_prefix()
+        .alias("delete", "remove");
+    assert_eq!(
+        options.tool_name("web search", "search").as_deref(),
+        Some("web_search_search")
+    );
+    assert_eq!(
+        options.tool_name("web search", "delete").as_deref(),
+        Some("remove")
+    );
+
+    let options = MCPToolOptions::new()
+        .prefix("docs")
+        .include(["search", "delete"])
+        .exclude(["delete"]);
+    assert_eq!(
+        options.tool_name("web", "search").as_deref(),
+        Some("docs_search")
+    );
+    assert_eq!(options.tool_name("web", "delete"), None);
+    assert_eq!(options.tool_name("web", "list"), None);
+}
+
+#[tokio::test]
+async fn test_duplicate_names_are_an_error() {
+    let web = connect("web", "web").await;
+    let docs = connect("docs", "docs").await;
+
+    let result = agent(ScriptedModel::default())
+        .add_mcp_client(&web)
+        .await
+        .unwrap()
+        .add_mcp_client(&docs)
+        .await
+        .unwrap()
+        .try_build();
+    assert!(matches!(result, Err(AgentError::DuplicateTool(name)) if name == "search"));
+}
+
+#[tokio::test]
+async fn test_prefixed_tools_reach_their_server() {
+    let web = connect("web", "web").await;
+    let docs = connect("docs", "docs").await;
+    let model = ScriptedModel::new(vec![vec![search("find_docs", "rust")]]);
+
+    let agent = agent(model.clone())
+        .add_mcp_client_with(&web, &MCPToolOptions::new().server_prefix())
+        .await
+        .unwrap()
+        .add_mcp_client_with(
+            &docs,
+            &MCPToolOptions::new()
+                .server_prefix()
+                .alias("search", "find_docs"),
+        )
+        .await
+        .unwrap()
+        .try_build()
+        .unwrap();
+    agent.run("Find rust".to_owned()).await.unwrap();
+
+    assert_eq!(
+        model.first_tools(),
+        ["web_search", "web_delete", "find_docs", "docs_delete"]
+    );
+    // The server is called with the original name of the tool
+    assert!(model.last_history().contains("docs search: rust"));
+}
+
+#[tokio::test]
+async fn test_include_and_exclude() {
+    let web = connect("web", "web").await;
+    let docs = connect("docs", "docs").await;
+
+    let tools = web
+        .tools(&MCPToolOptions::new().exclude(["delete"]))
+        .await
+        .unwrap();
+    assert_eq!(
+        tools.iter().map(ToolDyn::name).collect::<Vec<_>>(),
+        ["search"]
+    );
+
+    let model = ScriptedModel::new(vec![vec![search("delete", "rust")]]);
+    let agent = agent(model.clone())
+        .add_mcp_client_with(&web, &MCPToolOptions::new().include(["search"]))
+        .await
+        .unwrap()
+        .add_mcp_client_with(&docs, &MCPToolOptions::new().exclude(["search"]))
+        .await
+        .unwrap()
+        .try_build()
+        .unwrap();
+    agent.run("Delete rust".to_owned()).await.unwrap();
+
+    assert_eq!(model.first_tools(), ["search", "delete"]);
+    assert!(model.last_history().contains("docs delete: rust"));
+}
+
+#[tokio::test]
+async fn test_duplicate_tool_policies() {
+    for (policy, expected) in [
+        (DuplicateToolPolicy::KeepFirst, "web search: rust"),
+        (DuplicateToolPolicy::KeepLast, "docs search: rust"),
+    ] {
+        let web = connect("web", "web").await;
+        let docs = connect("docs", "docs").await;
+        let model = ScriptedModel::new(vec![vec![search("search", "rust")]]);
+
+        let agent = agent(model.clone())
+            .duplicate_tool_policy(policy)
+            .add_mcp_client(&web)
+            .await
+            .unwrap()
+            .add_mcp_client(&docs)
+            .await
+            .unwrap()
+            .try_build()
+            .unwrap();
+        agent.run("Find rust".to_owned()).await.unwrap();
+
+        // Each name is sent to the model once, in the order it was first added
+        assert_eq!(model.first_tools(), ["search", "delete"]);
+        assert!(model.last_history().contains(expected), "{policy:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_tool_added_to_built_agent() {
+    let web = connect("web", "web").await;
+    let docs = connect("docs", "docs").await;
+    let docs_search = || async {
+        docs.tools(&MCPToolOptions::new().include(["search"]))
+            .await
+            .unwrap()
+            .remove(0)
+    };
+
+    let result = agent(ScriptedModel::default())
+        .add_mcp_client(&web)
+        .await
+        .unwrap()
+        .build()
+        .tool(docs_search().await);
+    assert!(matches!(result, Err(AgentError::DuplicateTool(name)) if name == "search"));
+
+    for (policy, expected) in [
+        (DuplicateToolPolicy::KeepFirst, "web search: rust"),
+        (DuplicateToolPolicy::KeepLast, "docs search: rust"),
+    ] {
+        let model = ScriptedModel::new(vec![vec![search("search", "rust")]]);
+        let agent = agent(model.clone())
+            .duplicate_tool_policy(policy)
+            .add_mcp_client(&web)
+            .await
+            .unwrap()
+            .build()
+            .tool(docs_search().await)
+            .unwrap();
+        agent.run("Find rust".to_owned()).await.unwrap();
+
+        assert_eq!(model.first_tools(), ["search", "delete"]);
+        assert!(model.last_history().contains(expected), "{policy:?}");
+    }
+}
@@ -0,0 +1,256 @@
+//! Tests for tool timeouts, retries and error feedback
+
+mod common;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use swarms_rs::agent::{
+    ChatResponse, SwarmsAgentBuilder, ToolCallErrorKind, ToolCallOutput, ToolPolicy,
+};
+use swarms_rs::llm::{completion::AssistantContent, request::ToolDefinition};
+use swarms_rs::structs::tool::Tool;
+
+use common::ScriptedModel;
+
+#[derive(Deserialize)]
+struct FetchArgs {
+    url: String,
+}
+
+#[derive(Serialize)]
+struct FetchOutput {
+    body: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("connection reset")]
+struct FetchError;
+
+// Mock tool that sleeps, then fails until it has been called `fail_times` times
+#[derive(Clone, Default)]
+struct FetchTool {
+    delay: Duration,
+    fail_times: u32,
+    calls: Arc<AtomicU32>,
+}
+
+impl Tool for FetchTool {
+    type Error = FetchError;
+    type Args = FetchArgs;
+    type Output = FetchOutput;
+
+    const NAME: &'static str = "fetch";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Fetch a URL".to_owned(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": { "url": { "type": "string" } },
+                "required": ["url"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        tokio::time::sleep(self.delay).await;
+        if call <= self.fail_times {
+            return Err(FetchError);
+        }
+        Ok(FetchOutput {
+            body: format!("content of {}", args.url),
+        })
+    }
+}
+
+fn fetch_call(args: serde_json::Value) -> AssistantContent {
+    AssistantContent::tool_call("call_1", "fetch", args)
+}
+
+async fn chat_once(
+    tool: FetchTool,
+    call: AssistantContent,
+    policy: ToolPolicy,
+    concurrent: bool,
+) -> ToolCallOutput {
+    let mut builder = SwarmsAgentBuilder::new_with_model(ScriptedModel::new(vec![vec![call]]))
+        .add_tool(tool)
+        .disable_task_complete_tool()
+        .tool_policy("fetch", policy);
+    if !concurrent {
+        builder = builder.disable_concurrent_tool_call();
+    }
+    let agent = builder.build();
+
+    match agent.chat("Fetch it", vec![]).await.unwrap() {
+        ChatResponse::ToolCalls(mut outputs) => outputs.remove(0),
+        ChatResponse::Text(text) => panic!("expected tool call, got {text}"),
+    }
+}
+
+#[test]
+fn test_default_policy_waits_indefinitely() {
+    let policy = ToolPolicy::default();
+    assert_eq!(policy.timeout, None);
+    assert_eq!(policy.max_retries, 0);
+    assert_eq!(
+        ToolPolicy::default()
+            .timeout(Duration::from_secs(1))
+            .no_timeout(),
+        policy
+    );
+}
+
+#[tokio::test]
+async fn test_slow_tool_times_out() {
+    let tool = FetchTool {
+        delay: Duration::from_secs(10),
+        ..Default::default()
+    };
+    let policy = ToolPolicy::default().timeout(Duration::from_millis(20));
+
+    let output = chat_once(
+        tool,
+        fetch_call(serde_json::json!({ "url": "a" })),
+        policy,
+        true,
+    )
+    .await;
+
+    assert_eq!(output.error, Some(ToolCallErrorKind::Timeout));
+    assert!(output.result.contains("timeout"));
+}
+
+#[tokio::test]
+async fn test_failed_call_is_retried() {
+    let tool = FetchTool {
+        fail_times: 2,
+        ..Default::default()
+    };
+    let policy = ToolPolicy::default()
+        .max_retries(2)
+        .retry_backoff(Duration::from_millis(1));
+
+    let output = chat_once(
+        tool.clone(),
+        fetch_call(serde_json::json!({ "url": "a" })),
+        policy,
+        true,
+    )
+    .await;
+
+    assert!(!output.is_error());
+    assert_eq!(output.result, r#"{"body":"content of a"}"#);
+    assert_eq!(tool.calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retries_are_limited() {
+    let tool = FetchTool {
+        fail_times: 5,
+        ..Default::default()
+    };
+    let policy = ToolPolicy::default()
+        .max_retries(1)
+        .retry_backoff(Duration::from_millis(1));
+
+    let output = chat_once(
+        tool.clone(),
+        fetch_call(serde_json::json!({ "url": "a" })),
+        policy,
+        true,
+    )
+    .await;
+
+    assert_eq!(output.error, Some(ToolCallErrorKind::ExecutionFailed));
+    assert!(output.result.contains("connection reset"));
+    assert_eq!(tool.calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_invalid_arguments_are_not_retried() {
+    let tool = FetchTool::default();
+    let policy = ToolPolicy::default().max_retries(3);
+
+    let output = chat_once(
+        tool.clone(),
+        fetch_call(serde_json::json!({ "link": "a" })),
+        policy,
+        true,
+    )
+    .await;
+
+    assert_eq!(output.error, Some(ToolCallErrorKind::InvalidArguments));
+    assert_eq!(tool.calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_sequential_errors_are_returned_to_the_model() {
+    let tool = FetchTool {
+        fail_times: 1,
+        ..Default::default()
+    };
+
+    let output = chat_once(
+        tool,
+        fetch_call(serde_json::json!({ "url": "a" })),
+        ToolPolicy::default(),
+        false,
+    )
+    .await;
+
+    assert_eq!(output.error, Some(ToolCallErrorKind::ExecutionFailed));
+    assert!(output.result.contains("connection reset"));
+}
+
+#[tokio::test]
+async fn test_unknown_tool_lists_available_tools() {
+    for concurrent in [true, false] {
+        let output = chat_once(
+            FetchTool::default(),
+            AssistantContent::tool_call("call_1", "fetch_url", serde_json::json!({})),
+            ToolPolicy::default(),
+            concurrent,
+        )
+        .await;
+
+        assert_eq!(output.error, Some(ToolCallErrorKind::NotFound));
+        assert!(output.result.contains("Available tools: fetch"));
+    }
+}
+
+#[tokio::test]
+async fn test_failed_call_feedback_reaches_the_next_loop() {
+    let model = ScriptedModel::new(vec![vec![fetch_call(serde_json::json!({ "url": "a" }))]]);
+    let agent = SwarmsAgentBuilder::new_with_model(model)
+        .add_tool(FetchTool {
+            fail_times: 1,
+            ..Default::default()
+        })
+        .disable_task_complete_tool()
+        .disable_concurrent_tool_call()
+        .max_loops(2)
+        .build();
+
+    let output = swarms_rs::structs::agent::Agent::run(&agent, "Fetch a".to_owned())
+        .await
+        .unwrap();
+
+    assert!(output.contains("connection reset"));
+    assert!(output.contains("Done"));
+}
+
+#[test]
+fn test_error_kind_serialization() {
+    let output: ToolCallOutput =
+        serde_json::from_str(r#"{"name":"fetch","args":"{}","result":"ok"}"#).unwrap();
+    assert_eq!(output.error, None);
+
+    let json = serde_json::to_value(ToolCallErrorKind::Timeout).unwrap();
+    assert_eq!(json, "timeout");
+}
@@ -0,0 +1,276 @@
+//! Tests for the runtime tool registry
+
+mod common;
+
+use serde::Deserialize;
+use swarms_rs::agent::SwarmsAgentBuilder;
+use swarms_rs::llm::{completion::AssistantContent, request::ToolDefinition};
+use swarms_rs::structs::agent::Agent;
+use swarms_rs::structs::tool::Tool;
+use swarms_rs::structs::tool_registry::{SideEffects, ToolFilter, ToolMetadata, ToolRegistry};
+
+use common::ScriptedModel;
+
+#[derive(Deserialize)]
+struct NoArgs {}
+
+#[derive(Debug, thiserror::Error)]
+#[error("named tool error")]
+struct NamedToolError;
+
+// Tool that answers with its name and a label
+struct NamedTool {
+    name: &'static str,
+    label: &'static str,
+}
+
+impl Tool for NamedTool {
+    type Error = NamedToolError;
+    type Args = NoArgs;
+    type Output = String;
+
+    const NAME: &'static str = "";
+
+    fn name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name.to_owned(),
+            description: format!("The {} tool", self.name),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        Ok(format!("{} called ({})", self.name, self.label))
+    }
+}
+
+// Tool that registers a `deploy` tool in the registry when called
+struct InstallTool {
+    registry: ToolRegistry,
+}
+
+impl Tool for InstallTool {
+    type Error = NamedToolError;
+    type Args = NoArgs;
+    type Output = String;
+
+    const NAME: &'static str = "install";
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_owned(),
+            description: "Install the deploy tool".to_owned(),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        self.registry.register(named("deploy", "registry"));
+        Ok("installed".to_owned())
+    }
+}
+
+fn named(name: &'static str, label: &'static str) -> NamedTool {
+    NamedTool { name, label }
+}
+
+fn call(tool: &str) -> AssistantContent {
+    AssistantContent::tool_call("call_1", tool, serde_json::json!({}))
+}
+
+fn agent(model: ScriptedModel) -> SwarmsAgentBuilder<ScriptedModel> {
+    SwarmsAgentBuilder::new_with_model(model)
+        .disable_task_complete_tool()
+        .max_loops(3)
+}
+
+#[test]
+fn test_registry_operations() {
+    let registry = ToolRegistry::new()
+        .with_tool(named("search", "first"))
+        .with_tool_metadata(
+            named("delete", "first"),
+            ToolMetadata::new()
+                .tag("files")
+                .side_effects(SideEffects::Destructive),
+        );
+    assert_eq!(registry.names(), ["delete", "search"]);
+    assert_eq!(
+        registry.metadata("delete").unwrap().side_effects,
+        SideEffects::Destructive
+    );
+    assert_eq!(
+        registry.metadata("search").unwrap().side_effects,
+        SideEffects::Write
+    );
+
+    assert!(registry.disable("search"));
+    assert!(!registry.is_enabled("search"));
+    assert!(registry.get("search").is_some());
+    assert!(registry.get_allowed("search", &ToolFilter::new()).is_none());
+    assert_eq!(
+        registry
+            .definitions(&ToolFilter::new())
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect::<Vec<_>>(),
+        ["delete"]
+    );
+    assert!(registry.enable("search"));
+    assert!(!registry.enable("missing"));
+
+    assert!(registry.set_metadata("search", ToolMetadata::new().cost(2.0)));
+    assert_eq!(registry.metadata("search").unwrap().cost, Some(2.0));
+
+    assert!(registry.remove("delete").is_some());
+    assert!(registry.remove("delete").is_none());
+    assert_eq!(registry.names(), ["search"]);
+}
+
+#[test]
+fn test_tool_filter() {
+    let read = ToolMetadata::new()
+        .tag("files")
+        .side_effects(SideEffects::ReadOnly);
+    let delete = ToolMetadata::new()
+        .tag("files")
+        .side_effects(SideEffects::Destructive);
+    let expensive = ToolMetadata::new().cost(5.0);
+
+    let all = ToolFilter::new();
+    assert!(all.allows("read_file", &read));
+    assert!(all.allows("delete_file", &delete));
+
+    let files = ToolFilter::new().tag("files").deny("delete_file");
+    assert!(files.allows("read_file", &read));
+    assert!(!files.allows("delete_file", &delete));
+    assert!(!files.allows("search", &expensive));
+
+    let named = ToolFilter::new().names(["search", "delete_file"]);
+    assert!(named.allows("search", &expensive));
+    assert!(!named.allows("read_file", &read));
+
+    let cautious = ToolFilter::new()
+        .max_side_effects(SideEffects::Write)
+        .max_cost(1.0);
+    assert!(cautious.allows("read_file", &read));
+    assert!(!cautious.allows("delete_file", &delete));
+    assert!(!cautious.allows("search", &expensive));
+}
+
+#[tokio::test]
+async fn test_tools_change_between_runs() {
+    let registry = ToolRegistry::new().with_tool(named("search", "registry"));
+    let model = ScriptedModel::new(vec![vec![call("search")]]);
+    let agent = agent(model.clone()).tool_registry(registry.clone()).build();
+
+    agent.run("Search".to_owned()).await.unwrap();
+    assert_eq!(model.tools()[0], ["search"]);
+    assert!(model.last_history().contains("search called (registry)"));
+
+    registry.register(named("fetch", "registry"));
+    registry.disable("search");
+    model
+        .responses
+        .lock()
+        .unwrap()
+        .push_back(vec![call("search")]);
+    agent.run("Search again".to_owned()).await.unwrap();
+    assert_eq!(model.tools().last().unwrap(), &["fetch"]);
+    // Disabled tools can't be called either
+    assert!(model.last_history().contains("Tool not found: search"));
+    assert!(model.last_history().contains("Available tools: fetch"));
+
+    registry.remove("fetch");
+    agent.run("Nothing".to_owned()).await.unwrap();
+    assert!(model.tools().last().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_tool_added_during_run() {
+    let registry = ToolRegistry::new();
+    let model = ScriptedModel::new(vec![vec![call("install")], vec![call("deploy")]]);
+    let agent = agent(model.clone())
+        .add_tool(InstallTool {
+            registry: registry.clone(),
+        })
+        .tool_registry(registry.clone())
+        .build();
+
+    agent.run("Deploy".to_owned()).await.unwrap();
+    let tools = model.tools();
+    assert_eq!(tools[0], ["install"]);
+    assert_eq!(tools[1], ["install", "deploy"]);
+    assert!(model.last_history().contains("deploy called (registry)"));
+}
+
+#[tokio::test]
+async fn test_shared_registry_with_allow_lists() {
+    let registry = ToolRegistry::new()
+        .with_tool_metadata(
+            named("read_file", "registry"),
+            ToolMetadata::new()
+                .tag("files")
+                .side_effects(SideEffects::ReadOnly),
+        )
+        .with_tool_metadata(
+            named("delete_file", "registry"),
+            ToolMetadata::new()
+                .tag("files")
+                .side_effects(SideEffects::Destructive),
+        )
+        .with_tool(named("search", "registry"));
+
+    let reader_model = ScriptedModel::new(vec![vec![call("delete_file")]]);
+    let reader = agent(reader_model.clone())
+        .tool_registry_with(
+            registry.clone(),
+            ToolFilter::new()
+                .tag("files")
+                .max_side_effects(SideEffects::ReadOnly),
+        )
+        .build();
+    let admin_model = ScriptedModel::new(vec![vec![call("delete_file")]]);
+    let admin = agent(admin_model.clone())
+        .tool_registry(registry.clone())
+        .build();
+
+    reader.run("Clean up".to_owned()).await.unwrap();
+    admin.run("Clean up".to_owned()).await.unwrap();
+
+    assert_eq!(reader_model.tools()[0], ["read_file"]);
+    assert!(
+        reader_model
+            .last_history()
+            .contains("Tool not found: delete_file")
+    );
+    assert_eq!(
+        admin_model.tools()[0],
+        ["delete_file", "read_file", "search"]
+    );
+    assert!(
+        admin_model
+            .last_history()
+            .contains("delete_file called (registry)")
+    );
+}
+
+#[tokio::test]
+async fn test_agent_tools_take_precedence() {
+    let registry = ToolRegistry::new()
+        .with_tool(named("search", "registry"))
+        .with_tool(named("fetch", "registry"));
+    let model = ScriptedModel::new(vec![vec![call("search")]]);
+    let agent = agent(model.clone())
+        .add_tool(named("search", "agent"))
+        .tool_registry(registry)
+        .build();
+
+    agent.run("Search".to_owned()).await.unwrap();
+    assert_eq!(model.tools()[0], ["search", "fetch"]);
+    assert!(model.last_history().contains("search called (agent)"));
+}